@@ -1,7 +1,10 @@
 use criterion::*;
 use densmap::{
-    average::get_system_bin_neighbours,
-    densmap::{Shape, Vec3},
+    average::{
+        get_system_bin_neighbours, smoothen_data_of_bins_within_radius, EdgeMode, Kernel,
+        SmoothingOptions,
+    },
+    densmap::{DensMap, Shape, Vec3},
 };
 
 fn bin_neighbour_lists(c: &mut Criterion) {
@@ -9,9 +12,40 @@ fn bin_neighbour_lists(c: &mut Criterion) {
         let shape: Shape = [100, 200];
         let bin_size: Vec3 = [0.1, 0.1, 0.0];
         let radius = 0.3_f64;
-        b.iter(|| get_system_bin_neighbours(radius, bin_size, shape))
+        b.iter(|| {
+            get_system_bin_neighbours(
+                radius,
+                bin_size,
+                shape,
+                Kernel::Disc,
+                [EdgeMode::Truncate; 2],
+            )
+        })
     });
 }
 
-criterion_group!(benches, bin_neighbour_lists);
+fn smoothing_kernels(c: &mut Criterion) {
+    let densmap = DensMap {
+        bin_size: [0.1, 0.1, 0.0],
+        origin: [0.0, 0.0],
+        shape: [100, 200],
+        center: [5.0, 10.0],
+        data: (0..20000).map(|i| (i % 17) as f64).collect(),
+    };
+
+    for &kernel in [Kernel::Disc, Kernel::Box].iter() {
+        let options = SmoothingOptions {
+            kernel,
+            ..SmoothingOptions::default()
+        };
+
+        let densmap = densmap.clone();
+
+        c.bench_function(&format!("smoothing {:?} 100 200", kernel), move |b| {
+            b.iter(|| smoothen_data_of_bins_within_radius(densmap.clone(), 1.0, options))
+        });
+    }
+}
+
+criterion_group!(benches, bin_neighbour_lists, smoothing_kernels);
 criterion_main!(benches);
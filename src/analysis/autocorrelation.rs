@@ -1,4 +1,11 @@
-use crate::graphdata::XYData;
+use crate::{
+    analysis::kymograph::Kymograph,
+    densmap::DensMap,
+    graphdata::{Graph, XYData},
+    parallel::*,
+};
+
+use std::collections::VecDeque;
 
 pub fn calc_autocorrelation<T: XYData>(data: &[T]) -> Vec<f64> {
     let mut values = vec![0.0; data.len()];
@@ -26,3 +33,326 @@ pub fn calc_autocorrelation<T: XYData>(data: &[T]) -> Vec<f64> {
     let max = rescaled_values[0];
     rescaled_values.into_iter().map(|v| v / max).collect()
 }
+
+/// Resample contact lines onto a common set of angles. We use the largest number
+/// of sample points as the base.
+pub fn resample_onto_common_angles(contact_lines: &[Graph]) -> Vec<Graph> {
+    match contact_lines
+        .iter()
+        .max_by(|&a, &b| a.x().len().cmp(&b.x().len()))
+    {
+        Some(base) => contact_lines
+            .iter()
+            .map(|contact_line| contact_line.resample(base.x()))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Correlation of contact line fluctuations as a function of angular and time separation.
+pub struct SpaceTimeCorrelation {
+    /// Angular separations in degrees.
+    pub angles: Vec<f64>,
+    /// Time separations.
+    pub times: Vec<f64>,
+    /// Correlation for every time separation (outer) and angular separation (inner),
+    /// normalized by the value at zero separation.
+    pub values: Vec<Vec<f64>>,
+}
+
+/// Calculate the space-time correlation C(da, dt) of the contact line fluctuations in
+/// a kymograph, for angular separations up to 180 degrees and time separations up to
+/// `max_lag` frames.
+///
+/// The fluctuations are taken relative to the mean radius of each contact line and the
+/// correlation is averaged over all angles (which are periodic) and pairs of times.
+/// Time separations are given relative to the first time of the kymograph.
+pub fn calc_space_time_correlation(kymograph: &Kymograph, max_lag: usize) -> SpaceTimeCorrelation {
+    let fluctuations = kymograph.fluctuations();
+
+    let num_times = fluctuations.len();
+    let num_angles = kymograph.angles.len();
+    let num_lags = (max_lag + 1).min(num_times);
+    let num_angle_lags = if num_angles > 0 {
+        num_angles / 2 + 1
+    } else {
+        0
+    };
+
+    let mut values = (0..num_lags)
+        .into_par_iter()
+        .map(|k| {
+            let mut sums = vec![0.0; num_angle_lags];
+
+            for (r0, r1) in fluctuations.iter().zip(fluctuations[k..].iter()) {
+                for (j, sum) in sums.iter_mut().enumerate() {
+                    *sum += (0..num_angles)
+                        .map(|i| r0[i] * r1[(i + j) % num_angles])
+                        .sum::<f64>();
+                }
+            }
+
+            let num_samples = ((num_times - k) * num_angles) as f64;
+            sums.into_iter()
+                .map(|v| v / num_samples)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let norm = values
+        .first()
+        .and_then(|v| v.first())
+        .cloned()
+        .unwrap_or(0.0);
+    if norm != 0.0 {
+        values
+            .iter_mut()
+            .for_each(|row| row.iter_mut().for_each(|v| *v /= norm));
+    }
+
+    let t0 = kymograph.times.first().cloned().unwrap_or(0.0);
+
+    SpaceTimeCorrelation {
+        angles: kymograph.angles[..num_angle_lags]
+            .iter()
+            .map(|a| a - kymograph.angles[0])
+            .collect(),
+        times: kymograph.times[..num_lags].iter().map(|t| t - t0).collect(),
+        values,
+    }
+}
+
+/// Online accumulation of the temporal autocorrelation of the density in every bin
+/// of a series of density maps, for a set of time lags given in number of frames.
+///
+/// The autocorrelation at a lag k is the covariance of densities separated by k frames,
+/// normalized by the variance of the density in the bin. Only the last maps up to the
+/// largest lag are kept in memory.
+pub struct AutocorrelationMap {
+    lags: Vec<usize>,
+    grid: Option<DensMap>,
+    history: VecDeque<Vec<f64>>,
+    lagged_sums: Vec<LaggedSums>,
+    sum: Vec<f64>,
+    sum_squares: Vec<f64>,
+    count: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+/// Sums of the densities and their products for pairs of maps separated by a lag.
+struct LaggedSums {
+    products: Vec<f64>,
+    first: Vec<f64>,
+    second: Vec<f64>,
+    count: u64,
+}
+
+impl AutocorrelationMap {
+    pub fn new(lags: &[usize]) -> Self {
+        AutocorrelationMap {
+            lags: lags.to_vec(),
+            grid: None,
+            history: VecDeque::new(),
+            lagged_sums: vec![LaggedSums::default(); lags.len()],
+            sum: Vec::new(),
+            sum_squares: Vec::new(),
+            count: 0,
+        }
+    }
+
+    /// Add the next map in the series.
+    ///
+    /// # Error
+    /// Returns an error if the map does not have the same shape as previously added maps.
+    pub fn add(&mut self, densmap: &DensMap) -> Result<(), String> {
+        let num_bins = densmap.data.len();
+
+        match &self.grid {
+            Some(grid) if grid.shape != densmap.shape => {
+                return Err(format!(
+                    "cannot accumulate autocorrelation of map with shape {:?} onto shape {:?}",
+                    densmap.shape, grid.shape
+                ));
+            }
+            Some(_) => (),
+            None => {
+                self.grid = Some(DensMap {
+                    data: Vec::new(),
+                    ..densmap.clone()
+                });
+                self.sum = vec![0.0; num_bins];
+                self.sum_squares = vec![0.0; num_bins];
+
+                for sums in self.lagged_sums.iter_mut() {
+                    sums.products = vec![0.0; num_bins];
+                    sums.first = vec![0.0; num_bins];
+                    sums.second = vec![0.0; num_bins];
+                }
+            }
+        }
+
+        let data = &densmap.data;
+
+        for (i, &v) in data.iter().enumerate() {
+            self.sum[i] += v;
+            self.sum_squares[i] += v * v;
+        }
+        self.count += 1;
+
+        // The history is ordered with the most recent map first.
+        for (&lag, sums) in self.lags.iter().zip(self.lagged_sums.iter_mut()) {
+            let previous = if lag == 0 {
+                Some(data)
+            } else {
+                self.history.get(lag - 1)
+            };
+
+            if let Some(previous) = previous {
+                for (i, (&v0, &v1)) in previous.iter().zip(data.iter()).enumerate() {
+                    sums.products[i] += v0 * v1;
+                    sums.first[i] += v0;
+                    sums.second[i] += v1;
+                }
+                sums.count += 1;
+            }
+        }
+
+        let max_lag = self.lags.iter().cloned().max().unwrap_or(0);
+        if max_lag > 0 {
+            self.history.push_front(data.clone());
+            self.history.truncate(max_lag);
+        }
+
+        Ok(())
+    }
+
+    /// Get the autocorrelation map for every lag, in the order of the lags given
+    /// at construction. Lags for which no pairs of maps have been added are skipped.
+    ///
+    /// Bins with no density variance have an autocorrelation of zero.
+    pub fn get_maps(&self) -> Vec<(usize, DensMap)> {
+        let grid = match &self.grid {
+            Some(grid) => grid,
+            None => return Vec::new(),
+        };
+
+        let n = self.count as f64;
+
+        self.lags
+            .iter()
+            .zip(self.lagged_sums.iter())
+            .filter(|(_, sums)| sums.count > 0)
+            .map(|(&lag, sums)| {
+                let m = sums.count as f64;
+
+                let data = (0..self.sum.len())
+                    .map(|i| {
+                        let mean = self.sum[i] / n;
+                        let variance = self.sum_squares[i] / n - mean.powi(2);
+
+                        let covariance =
+                            sums.products[i] / m - (sums.first[i] / m) * (sums.second[i] / m);
+
+                        if variance > 0.0 {
+                            covariance / variance
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect();
+
+                (
+                    lag,
+                    DensMap {
+                        data,
+                        ..grid.clone()
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_densmap(data: Vec<f64>) -> DensMap {
+        DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [2, 1],
+            center: [1.0, 0.5],
+            data,
+        }
+    }
+
+    #[test]
+    fn test_space_time_correlation_of_propagating_wave() {
+        // A wave which travels 90 degrees per time step around the contact line.
+        let angles = vec![0.0, 90.0, 180.0, 270.0];
+        let radius = (0..4)
+            .map(|t| {
+                (0..4)
+                    .map(|i| if (i + 4 - t) % 4 == 0 { 3.0 } else { -1.0 })
+                    .collect()
+            })
+            .collect();
+
+        let kymograph = Kymograph {
+            times: vec![0.0, 1.0, 2.0, 3.0],
+            angles,
+            radius,
+        };
+
+        let correlation = calc_space_time_correlation(&kymograph, 2);
+
+        assert_eq!(vec![0.0, 90.0, 180.0], correlation.angles);
+        assert_eq!(vec![0.0, 1.0, 2.0], correlation.times);
+
+        // The correlation is maximal when following the wave.
+        assert_eq!(1.0, correlation.values[0][0]);
+        assert_eq!(1.0, correlation.values[1][1]);
+        assert_eq!(1.0, correlation.values[2][2]);
+        assert_eq!(-1.0 / 3.0, correlation.values[1][0]);
+    }
+
+    #[test]
+    fn test_autocorrelation_map_of_alternating_bin() {
+        let mut autocorrelation = AutocorrelationMap::new(&[0, 1, 2]);
+
+        // The first bin alternates between two values, the second is constant.
+        for n in 0..10 {
+            let v = if n % 2 == 0 { 1.0 } else { -1.0 };
+            autocorrelation.add(&get_densmap(vec![v, 3.0])).unwrap();
+        }
+
+        let maps = autocorrelation.get_maps();
+
+        assert_eq!(
+            vec![0, 1, 2],
+            maps.iter().map(|(lag, _)| *lag).collect::<Vec<_>>()
+        );
+
+        // With an odd number of pairs the means of the first and second values of
+        // the pairs differ slightly, so the correlation is not exactly -1.
+        assert!((maps[0].1.data[0] - 1.0).abs() < 1e-12);
+        assert!((maps[1].1.data[0] + 1.0).abs() < 0.02);
+        assert!((maps[2].1.data[0] - 1.0).abs() < 1e-12);
+        assert!(maps.iter().all(|(_, densmap)| densmap.data[1] == 0.0));
+    }
+
+    #[test]
+    fn test_autocorrelation_map_skips_lags_longer_than_series() {
+        let mut autocorrelation = AutocorrelationMap::new(&[1, 5]);
+
+        for v in &[1.0, 2.0, 3.0] {
+            autocorrelation.add(&get_densmap(vec![*v, 0.0])).unwrap();
+        }
+
+        let maps = autocorrelation.get_maps();
+        assert_eq!(1, maps.len());
+        assert_eq!(1, maps[0].0);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::densmap::DensMap;
+
+/// Bins which lie further than this factor times the droplet radius from its center
+/// are used to estimate the vapor density.
+const BACKGROUND_RADIUS_FACTOR: f64 = 1.5;
+
+/// Estimate the density of the vapor phase as the median density of bins far outside
+/// of the droplet, ie. further away from the center than 1.5 times the input radius.
+///
+/// # Error
+/// Returns an error if no bins lie that far outside of the droplet.
+pub fn estimate_background(densmap: &DensMap, radius: f64) -> Result<f64, String> {
+    let [dx, dy, _] = densmap.bin_size;
+    let [xmin, ymin] = densmap.origin;
+    let x0 = densmap.center[0] - xmin;
+    let y0 = densmap.center[1] - ymin;
+
+    let rmin2 = (BACKGROUND_RADIUS_FACTOR * radius).powi(2);
+
+    let mut values = densmap
+        .iter_bins()
+        .map(|(ix, iy, _, _, v)| ((dx * ix as f64, dy * iy as f64), v))
+        .filter(|((x, y), _)| (x - x0).powi(2) + (y - y0).powi(2) > rmin2)
+        .map(|(_, v)| v)
+        .filter(|v| v.is_finite())
+        .collect::<Vec<_>>();
+
+    if values.is_empty() {
+        return Err(format!(
+            "no bins lie outside of {} times the droplet radius to estimate the background from",
+            BACKGROUND_RADIUS_FACTOR
+        ));
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let n = values.len();
+    let median = if n % 2 == 0 {
+        0.5 * (values[n / 2 - 1] + values[n / 2])
+    } else {
+        values[n / 2]
+    };
+
+    Ok(median)
+}
+
+/// Subtract a background density from all bins of the map. Bins which would become
+/// negative are set to zero.
+pub fn subtract_background(densmap: DensMap, background: f64) -> DensMap {
+    DensMap {
+        data: densmap
+            .data
+            .into_iter()
+            .map(|v| (v - background).max(0.0))
+            .collect(),
+        ..densmap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_droplet_in_vapor(vapor: f64) -> DensMap {
+        let data = (0..100)
+            .map(|i| {
+                let x = (i % 10) as f64 - 4.5;
+                let y = (i / 10) as f64 - 4.5;
+
+                if x.powi(2) + y.powi(2) <= 4.0 {
+                    1.0
+                } else {
+                    vapor
+                }
+            })
+            .collect();
+
+        DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [10, 10],
+            center: [4.5, 4.5],
+            data,
+        }
+    }
+
+    #[test]
+    fn test_background_is_estimated_from_bins_outside_of_droplet() {
+        let densmap = get_droplet_in_vapor(0.2);
+        assert_eq!(Ok(0.2), estimate_background(&densmap, 2.0));
+    }
+
+    #[test]
+    fn test_background_cannot_be_estimated_if_droplet_fills_system() {
+        let densmap = get_droplet_in_vapor(0.2);
+        assert!(estimate_background(&densmap, 10.0).is_err());
+    }
+
+    #[test]
+    fn test_subtracting_background_clamps_values_at_zero() {
+        let densmap = subtract_background(get_droplet_in_vapor(0.2), 0.5);
+
+        assert_eq!(0.0, densmap.data[0]);
+        assert_eq!(0.5, densmap.data[44]);
+    }
+}
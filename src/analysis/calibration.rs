@@ -0,0 +1,251 @@
+use crate::{
+    analysis::{
+        radial_density::{get_radius_from_distribution, RadialBinning},
+        sample_interface::sample_interface,
+    },
+    average::{Smoother, SmoothingOptions},
+    densmap::DensMap,
+    graphdata::{Histogram, XYData},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Result of analyzing a set of density maps with a single smoothing radius.
+pub struct SmoothingCalibration {
+    /// Smoothing radius which was used, which is 0 for the maps without smoothing.
+    pub smoothing_radius: f64,
+    /// Mean droplet radius of the maps.
+    pub droplet_radius: f64,
+    /// Mean root-mean-square deviation of the contact line from the droplet radius.
+    pub noise: f64,
+    /// Absolute difference of the droplet radius to that of the maps without smoothing.
+    pub bias: f64,
+    /// Mean width of the interface, see `get_interface_width`.
+    pub interface_width: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Calibration of the smoothing radius from the analysis of a set of density maps.
+pub struct Calibration {
+    /// Result of the maps without smoothing, which the bias of the candidates is
+    /// measured relative to. It is `None` if no droplet was found in any of them.
+    pub reference: Option<SmoothingCalibration>,
+    /// Results of the candidate smoothing radii, sorted by the radius.
+    pub candidates: Vec<SmoothingCalibration>,
+}
+
+/// Analyze the input density maps without smoothing and with each of the candidate
+/// smoothing radii, and measure the droplet radius, contact line noise and interface
+/// width for them.
+///
+/// Smoothing reduces the noise of the contact line, but also shifts the droplet radius
+/// and broadens the interface. The bias of each candidate is measured relative to the
+/// maps without smoothing, which is the reference. A map is prepared for the analysis
+/// by `preprocess` with a smoother of the reference or a candidate, which should treat
+/// it like the analysis of the full trajectory does, eg. by subtracting its background
+/// after smoothing it. The radius is found from the radial density distribution of the
+/// binning. Maps without a droplet are left out of the means, and candidates for which
+/// no droplet was found in any map, or which are not finite, are skipped.
+pub fn calibrate_smoothing_radius<F>(
+    densmaps: &[DensMap],
+    candidates: &[f64],
+    options: SmoothingOptions,
+    mut radial_binning: RadialBinning,
+    mut preprocess: F,
+) -> Calibration
+where
+    F: FnMut(DensMap, &mut Smoother) -> DensMap,
+{
+    let mut sorted_candidates = candidates
+        .iter()
+        .cloned()
+        .filter(|r| r.is_finite())
+        .collect::<Vec<_>>();
+    sorted_candidates.sort_by(f64::total_cmp);
+
+    let mut analyze = |smoothing_radius, mut smoother: Smoother| {
+        let (radius_sum, noise_sum, width_sum, num_frames) = densmaps
+            .iter()
+            .filter_map(|densmap| {
+                let smoothed = preprocess(densmap.clone(), &mut smoother);
+                let distribution = radial_binning.get_radial_density_distribution(&smoothed);
+                let width = get_interface_width(&distribution);
+                let radius = get_radius_from_distribution(distribution).ok()?;
+                let contact_line =
+                    sample_interface(&smoothed, radius, radial_binning.options().length_unit);
+
+                Some((radius, calc_rms_deviation(contact_line.y(), radius), width))
+            })
+            .fold(
+                (0.0, 0.0, 0.0, 0),
+                |(rsum, nsum, wsum, n), (r, noise, w)| (rsum + r, nsum + noise, wsum + w, n + 1),
+            );
+
+        if num_frames > 0 {
+            Some(SmoothingCalibration {
+                smoothing_radius,
+                droplet_radius: radius_sum / num_frames as f64,
+                noise: noise_sum / num_frames as f64,
+                bias: 0.0,
+                interface_width: width_sum / num_frames as f64,
+            })
+        } else {
+            None
+        }
+    };
+
+    let reference = analyze(0.0, Smoother::disabled(options));
+
+    let candidates = sorted_candidates
+        .into_iter()
+        .filter_map(|smoothing_radius| {
+            analyze(smoothing_radius, Smoother::new(smoothing_radius, options))
+        })
+        .map(|c| SmoothingCalibration {
+            bias: reference
+                .map(|reference| (c.droplet_radius - reference.droplet_radius).abs())
+                .unwrap_or(f64::NAN),
+            ..c
+        })
+        .collect();
+
+    Calibration {
+        reference,
+        candidates,
+    }
+}
+
+/// Select the smoothing radius which gives the best trade-off between radius bias and
+/// contact line noise, by minimizing the sum of the noise and the weighted bias.
+///
+/// Only candidates with radii up to the interface width of the reference are selected,
+/// since smoothing over a longer distance smears out the interface that is located.
+/// No radius is selected if there is no reference or no candidate within the width.
+pub fn select_smoothing_radius(calibration: &Calibration, bias_weight: f64) -> Option<f64> {
+    let max_radius = calibration.reference?.interface_width;
+
+    calibration
+        .candidates
+        .iter()
+        .filter(|c| c.smoothing_radius <= max_radius)
+        .map(|c| (c.noise + bias_weight * c.bias, c.smoothing_radius))
+        .filter(|(score, _)| score.is_finite())
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, smoothing_radius)| smoothing_radius)
+}
+
+/// Get the width of the interface of a droplet from its radial density distribution,
+/// as the distance over which the density falls from 90 to 10 percent of its maximum.
+///
+/// The crossings are taken as the outermost radii at which the density is above the
+/// levels, interpolated between the bins. The width is 0 for empty distributions.
+pub fn get_interface_width(distribution: &Histogram) -> f64 {
+    let max = distribution
+        .y
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold(0.0, |acc: f64, &v| acc.max(v));
+
+    let get_crossing = |level: f64| {
+        let points = distribution
+            .x
+            .iter()
+            .zip(&distribution.y)
+            .collect::<Vec<_>>();
+
+        points
+            .windows(2)
+            .rev()
+            .find(|w| *w[0].1 >= level)
+            .map(|w| {
+                let ((&r0, &v0), (&r1, &v1)) = (w[0], w[1]);
+
+                if v1 >= level || v0 == v1 {
+                    r1
+                } else {
+                    r0 + (r1 - r0) * (v0 - level) / (v0 - v1)
+                }
+            })
+            .or_else(|| points.first().map(|(&r, _)| r))
+    };
+
+    match (get_crossing(0.9 * max), get_crossing(0.1 * max)) {
+        (Some(inner), Some(outer)) if max > 0.0 => (outer - inner).max(0.0),
+        _ => 0.0,
+    }
+}
+
+fn calc_rms_deviation(values: &[f64], reference: f64) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        (values.iter().map(|v| (v - reference).powi(2)).sum::<f64>() / values.len() as f64).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_calibration(candidates: &[(f64, f64, f64)]) -> Vec<SmoothingCalibration> {
+        candidates
+            .iter()
+            .map(|&(smoothing_radius, noise, bias)| SmoothingCalibration {
+                smoothing_radius,
+                droplet_radius: 2.0,
+                noise,
+                bias,
+                interface_width: 1.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_select_smoothing_radius_minimizes_sum_of_noise_and_weighted_bias() {
+        let calibration = Calibration {
+            reference: get_calibration(&[(0.0, 1.0, 0.0)]).pop(),
+            candidates: get_calibration(&[
+                (0.25, 0.5, 0.0),
+                (0.5, 0.2, 0.1),
+                (1.0, 0.1, 0.4),
+                (1.5, 0.0, 0.0),
+                (0.75, f64::NAN, 0.0),
+            ]),
+        };
+
+        // The last candidate is wider than the interface.
+        assert_eq!(Some(0.5), select_smoothing_radius(&calibration, 1.0));
+        assert_eq!(Some(0.25), select_smoothing_radius(&calibration, 4.0));
+        assert_eq!(Some(1.0), select_smoothing_radius(&calibration, 0.0));
+
+        let without_reference = Calibration {
+            reference: None,
+            ..calibration
+        };
+        assert_eq!(None, select_smoothing_radius(&without_reference, 1.0));
+    }
+
+    #[test]
+    fn test_interface_width_is_distance_between_90_and_10_percent_of_maximum() {
+        let distribution = Histogram {
+            x: vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0],
+            y: vec![10.0, 10.0, 10.0, 5.0, 0.0, 0.0],
+            yerr: None,
+        };
+
+        // The density crosses 9 at r = 2.2 and 1 at r = 3.8.
+        assert!((get_interface_width(&distribution) - 1.6).abs() < 1e-12);
+
+        let empty = Histogram {
+            x: vec![],
+            y: vec![],
+            yerr: None,
+        };
+        assert_eq!(0.0, get_interface_width(&empty));
+    }
+
+    #[test]
+    fn test_rms_deviation_from_reference() {
+        assert_eq!(0.0, calc_rms_deviation(&[], 1.0));
+        assert_eq!(1.0, calc_rms_deviation(&[0.0, 2.0], 1.0));
+    }
+}
@@ -0,0 +1,104 @@
+use crate::graphdata::{Histogram, XYData};
+
+/// Difference between two time series, like the droplet radius of two analysis runs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    /// Difference of the first minus the second series at their common times.
+    pub difference: Histogram,
+    /// Root mean square deviation between the series.
+    pub rmsd: f64,
+    /// Mean difference of the first minus the second series.
+    pub mean_difference: f64,
+    /// Largest absolute deviation between the series, with its time.
+    pub max_deviation: (f64, f64),
+}
+
+/// Compare two time series at common times.
+///
+/// The second series is resampled onto the times of the first which are within the
+/// range of both, like for ensemble averages, so that neither is extrapolated. Times
+/// at which either value is not finite are left out of the statistics. Returns `None`
+/// if the series have no such time in common.
+pub fn compare_series<T: XYData>(a: &T, b: &T) -> Option<Comparison> {
+    let (b0, b1) = match (b.x().first(), b.x().last()) {
+        (Some(&b0), Some(&b1)) => (b0, b1),
+        _ => return None,
+    };
+
+    let (xs, ys): (Vec<f64>, Vec<f64>) = a
+        .x()
+        .iter()
+        .zip(a.y())
+        .filter(|(&x, _)| x >= b0 && x <= b1)
+        .unzip();
+
+    let resampled = b.resample(&xs);
+
+    let (x, y): (Vec<f64>, Vec<f64>) = xs
+        .iter()
+        .zip(ys.iter().zip(resampled.y()))
+        .map(|(&x, (ya, yb))| (x, ya - yb))
+        .filter(|(_, dy)| dy.is_finite())
+        .unzip();
+
+    if x.is_empty() {
+        return None;
+    }
+
+    let n = y.len() as f64;
+    let rmsd = (y.iter().map(|dy| dy * dy).sum::<f64>() / n).sqrt();
+    let mean_difference = y.iter().sum::<f64>() / n;
+
+    let max_deviation = x.iter().zip(&y).fold((x[0], 0.0), |max, (&x, dy)| {
+        if dy.abs() > max.1 {
+            (x, dy.abs())
+        } else {
+            max
+        }
+    });
+
+    Some(Comparison {
+        difference: Histogram { x, y, yerr: None },
+        rmsd,
+        mean_difference,
+        max_deviation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphdata::Graph;
+
+    #[test]
+    fn test_compare_series_at_common_times_of_first() {
+        let a = Graph::Carthesian {
+            x: vec![0.0, 10.0, 20.0, 30.0],
+            y: vec![1.0, 2.0, 3.0, 4.0],
+            yerr: None,
+        };
+
+        // The second series has other times and ends before the first.
+        let b = Graph::Carthesian {
+            x: vec![5.0, 15.0, 25.0],
+            y: vec![1.5, 2.5, 2.5],
+            yerr: None,
+        };
+
+        let comparison = compare_series(&a, &b).unwrap();
+
+        assert_eq!(vec![10.0, 20.0], comparison.difference.x);
+        assert_eq!(vec![0.0, 0.5], comparison.difference.y);
+        assert!((comparison.rmsd - (0.125f64).sqrt()).abs() < 1e-12);
+        assert!((comparison.mean_difference - 0.25).abs() < 1e-12);
+        assert_eq!((20.0, 0.5), comparison.max_deviation);
+
+        let disjoint = Graph::Carthesian {
+            x: vec![40.0, 50.0],
+            y: vec![1.0, 1.0],
+            yerr: None,
+        };
+
+        assert_eq!(None, compare_series(&a, &disjoint));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{densmap::DensMap, graphdata::Histogram};
+
+#[derive(Clone, Debug)]
+/// Accumulation of the probability distribution of the densities of the bins of a series
+/// of density maps.
+///
+/// For a droplet the distribution has one peak for the vapor and one for the liquid,
+/// between which a density cutoff of the interface can be picked, while a phase change
+/// moves weight from one peak to the other.
+///
+/// The histogram bins have a fixed width, which is the maximum density of the first map
+/// with any density divided by the number of bins. They are extended for higher densities
+/// in later maps, so that the distributions of all maps share their bins and can be
+/// averaged. Maps before the first with any density, negative densities, which
+/// subtracting the background can give, and values which are not finite are left out.
+pub struct DensityDistribution {
+    num_bins: usize,
+    /// Width of the bins and the maximum density of the first map, which is included
+    /// in the last of the initial bins.
+    bins: Option<(f64, f64)>,
+    counts: Vec<u64>,
+    num_values: u64,
+}
+
+impl DensityDistribution {
+    pub fn new(num_bins: usize) -> Self {
+        DensityDistribution {
+            num_bins: num_bins.max(1),
+            bins: None,
+            counts: Vec::new(),
+            num_values: 0,
+        }
+    }
+
+    /// Add the densities of a map to the accumulated distribution, returning the
+    /// distribution of the map alone.
+    pub fn add(&mut self, densmap: &DensMap) -> Histogram {
+        let values = densmap
+            .data
+            .iter()
+            .cloned()
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .collect::<Vec<_>>();
+
+        if self.bins.is_none() {
+            let max = values.iter().fold(0.0, |acc: f64, &v| acc.max(v));
+
+            if max > 0.0 {
+                self.bins = Some((max / self.num_bins as f64, max));
+            }
+        }
+
+        let (width, max) = match self.bins {
+            Some(bins) => bins,
+            None => return get_distribution(&[], 0, 0.0),
+        };
+
+        let mut counts = vec![0; self.counts.len().max(self.num_bins)];
+
+        for &v in &values {
+            let n = if v <= max {
+                ((v / width) as usize).min(self.num_bins - 1)
+            } else {
+                (v / width) as usize
+            };
+
+            if n >= counts.len() {
+                counts.resize(n + 1, 0);
+            }
+
+            counts[n] += 1;
+        }
+
+        if counts.len() > self.counts.len() {
+            self.counts.resize(counts.len(), 0);
+        }
+
+        self.counts
+            .iter_mut()
+            .zip(&counts)
+            .for_each(|(total, count)| *total += count);
+        self.num_values += values.len() as u64;
+
+        get_distribution(&counts, values.len() as u64, width)
+    }
+
+    /// Get the distribution averaged over all added maps.
+    pub fn get_average(&self) -> Histogram {
+        let width = self.bins.map(|(width, _)| width).unwrap_or(0.0);
+        get_distribution(&self.counts, self.num_values, width)
+    }
+}
+
+/// Get the probability density of the counts of bins of a width, at their centers.
+fn get_distribution(counts: &[u64], num_values: u64, width: f64) -> Histogram {
+    let (x, y) = counts
+        .iter()
+        .enumerate()
+        .map(|(n, &count)| {
+            let p = if num_values > 0 {
+                count as f64 / (num_values as f64 * width)
+            } else {
+                0.0
+            };
+
+            (width * (n as f64 + 0.5), p)
+        })
+        .unzip();
+
+    Histogram { x, y, yerr: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::DensMapBuilder;
+
+    fn get_map(data: Vec<f64>) -> DensMap {
+        DensMapBuilder::new()
+            .bin_size([1.0, 1.0, 0.0])
+            .shape([data.len() as u64, 1])
+            .from_data(data)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_distribution_of_liquid_and_vapor_is_bimodal() {
+        let mut distribution = DensityDistribution::new(4);
+
+        // A quarter of the bins are vapor and the rest are liquid, with the maximum
+        // on the upper edge of the last histogram bin.
+        let histogram = distribution.add(&get_map(vec![0.5, 7.0, 8.0, 7.5]));
+
+        assert_eq!(vec![1.0, 3.0, 5.0, 7.0], histogram.x);
+        assert_eq!(vec![0.125, 0.0, 0.0, 0.375], histogram.y);
+
+        let integral = histogram.y.iter().map(|p| p * 2.0).sum::<f64>();
+        assert!((integral - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_bins_are_extended_for_later_maps_and_averaged() {
+        let mut distribution = DensityDistribution::new(2);
+
+        // The first map has no densities and is left out, after which the next sets
+        // the bin width to 1.
+        assert!(distribution.add(&get_map(vec![0.0, 0.0])).x.is_empty());
+        distribution.add(&get_map(vec![0.5, 2.0, f64::NAN, -1.0]));
+
+        let histogram = distribution.add(&get_map(vec![0.5, 3.5]));
+        assert_eq!(vec![0.5, 1.5, 2.5, 3.5], histogram.x);
+        assert_eq!(vec![0.5, 0.0, 0.0, 0.5], histogram.y);
+
+        assert_eq!(vec![0.5, 0.25, 0.0, 0.25], distribution.get_average().y);
+    }
+}
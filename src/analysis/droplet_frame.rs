@@ -0,0 +1,181 @@
+use crate::densmap::{DensMap, Vec2};
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Affine transform which maps lab coordinates into the droplet frame.
+///
+/// A lab coordinate `r` is transformed by first adding the translation, which moves
+/// the droplet center to the origin, and then rotating by the negative angle, which
+/// aligns the principal axis of the droplet with the x axis.
+pub struct DropletFrame {
+    /// Translation to apply to lab coordinates, ie. the negative droplet center.
+    pub translation: Vec2,
+    /// Angle of the droplet principal axis relative to the lab x axis, in degrees.
+    pub angle: f64,
+}
+
+impl DropletFrame {
+    /// Transform a lab coordinate into the droplet frame.
+    pub fn transform(&self, [x, y]: Vec2) -> Vec2 {
+        let (sin, cos) = (-self.angle).to_radians().sin_cos();
+
+        let x1 = x + self.translation[0];
+        let y1 = y + self.translation[1];
+
+        [cos * x1 - sin * y1, sin * x1 + cos * y1]
+    }
+}
+
+/// Get the transform from lab coordinates into the frame of the droplet.
+///
+/// The translation moves the droplet center to the origin. If `with_rotation` is set
+/// the principal axis is determined from the second moments of the density around
+/// the center, otherwise the angle is set to 0.
+pub fn get_droplet_frame(densmap: &DensMap, with_rotation: bool) -> DropletFrame {
+    let [x0, y0] = densmap.center;
+
+    let angle = if with_rotation {
+        get_principal_axis_angle(densmap)
+    } else {
+        0.0
+    };
+
+    DropletFrame {
+        translation: [-x0, -y0],
+        angle,
+    }
+}
+
+/// Calculate the angle in degrees of the principal axis of the density distribution
+/// around the droplet center point.
+///
+/// The angle is returned in the range (-90, 90].
+fn get_principal_axis_angle(densmap: &DensMap) -> f64 {
+    let [dx, dy, _] = densmap.bin_size;
+    let [xmin, ymin] = densmap.origin;
+    let x0 = densmap.center[0] - xmin;
+    let y0 = densmap.center[1] - ymin;
+
+    let (ixx, iyy, ixy) = densmap
+        .iter_bins()
+        .map(|(ix, iy, _, _, v)| ((dx * ix as f64 - x0, dy * iy as f64 - y0), v))
+        .fold((0.0, 0.0, 0.0), |(ixx, iyy, ixy), ((x, y), v)| {
+            (ixx + v * x * x, iyy + v * y * y, ixy + v * x * y)
+        });
+
+    let angle = 0.5 * (2.0 * ixy).atan2(ixx - iyy).to_degrees();
+
+    if angle <= -90.0 {
+        angle + 180.0
+    } else {
+        angle
+    }
+}
+
+/// Write the droplet frame transforms of a trajectory to disk.
+///
+/// Every line contains the time, the translation along x and y and the rotation angle
+/// of the transform. The columns are described in a commented header.
+pub fn write_droplet_frames(
+    path: &Path,
+    times: &[f64],
+    frames: &[DropletFrame],
+) -> Result<(), io::Error> {
+    let fp = File::create(path)?;
+    let mut writer = BufWriter::new(fp);
+
+    writeln!(
+        writer,
+        "# Transform from lab coordinates r to the droplet frame:"
+    )?;
+    writeln!(writer, "#   r' = R(-angle) (r + translation)")?;
+    writeln!(writer, "# time translation_x translation_y angle (degrees)")?;
+    write_droplet_frame_rows(&mut writer, times, frames)
+}
+
+/// Append droplet frame transforms to an existing file, or create it without a header
+/// if it does not exist.
+pub fn append_droplet_frames(
+    path: &Path,
+    times: &[f64],
+    frames: &[DropletFrame],
+) -> Result<(), io::Error> {
+    let fp = OpenOptions::new().append(true).create(true).open(path)?;
+    write_droplet_frame_rows(&mut BufWriter::new(fp), times, frames)
+}
+
+fn write_droplet_frame_rows<W: Write>(
+    writer: &mut W,
+    times: &[f64],
+    frames: &[DropletFrame],
+) -> Result<(), io::Error> {
+    for (t, frame) in times.iter().zip(frames.iter()) {
+        let [tx, ty] = frame.translation;
+        writeln!(
+            writer,
+            "{:12.5} {:12.5} {:12.5} {:12.5}",
+            t, tx, ty, frame.angle
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_elongated_densmap(along_x: bool) -> DensMap {
+        let shape = [5, 5];
+        let mut data = vec![0.0; 25];
+
+        for n in 0..5 {
+            let i = if along_x { 2 * 5 + n } else { n * 5 + 2 };
+            data[i] = 1.0;
+        }
+
+        DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [10.0, 20.0],
+            shape,
+            center: [12.0, 22.0],
+            data,
+        }
+    }
+
+    #[test]
+    fn test_principal_axis_of_droplet_elongated_along_x_or_y() {
+        let frame = get_droplet_frame(&get_elongated_densmap(true), true);
+        assert!(frame.angle.abs() < 1e-9);
+
+        let frame = get_droplet_frame(&get_elongated_densmap(false), true);
+        assert!((frame.angle - 90.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_droplet_frame_without_rotation_has_zero_angle() {
+        let frame = get_droplet_frame(&get_elongated_densmap(false), false);
+        assert_eq!([-12.0, -22.0], frame.translation);
+        assert_eq!(0.0, frame.angle);
+    }
+
+    #[test]
+    fn test_transform_moves_center_to_origin_and_aligns_axis() {
+        let frame = DropletFrame {
+            translation: [-1.0, -2.0],
+            angle: 90.0,
+        };
+
+        let [x, y] = frame.transform([1.0, 2.0]);
+        assert!(x.abs() < 1e-9 && y.abs() < 1e-9);
+
+        // A point along the principal axis ends up on the positive x axis.
+        let [x, y] = frame.transform([1.0, 3.0]);
+        assert!((x - 1.0).abs() < 1e-9 && y.abs() < 1e-9);
+    }
+}
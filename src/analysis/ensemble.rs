@@ -0,0 +1,103 @@
+use crate::graphdata::{Histogram, XYData};
+
+/// Average data of replicate trajectories, like the droplet radius over time, with
+/// the standard error of the mean across the replicas as errors.
+///
+/// All data is resampled onto the x values of the first data which are within the
+/// range of all data, so that replicas with other or fewer times are not extrapolated.
+/// The errors are only set for two or more replicas. Returns `None` if there is no data.
+pub fn calc_ensemble_average<T: XYData>(replicas: &[T]) -> Option<Histogram> {
+    let first = replicas.first()?;
+
+    let (min, max) =
+        replicas
+            .iter()
+            .fold((f64::NEG_INFINITY, f64::INFINITY), |(min, max), replica| {
+                let x = replica.x();
+                let (x0, x1) = match (x.first(), x.last()) {
+                    (Some(&x0), Some(&x1)) => (x0, x1),
+                    _ => (f64::INFINITY, f64::NEG_INFINITY),
+                };
+
+                (min.max(x0), max.min(x1))
+            });
+
+    let xs = first
+        .x()
+        .iter()
+        .cloned()
+        .filter(|&x| x >= min && x <= max)
+        .collect::<Vec<_>>();
+
+    let values = replicas
+        .iter()
+        .map(|replica| replica.resample(&xs).y().to_vec())
+        .collect::<Vec<_>>();
+
+    let n = replicas.len() as f64;
+
+    let mean = (0..xs.len())
+        .map(|i| values.iter().map(|y| y[i]).sum::<f64>() / n)
+        .collect::<Vec<_>>();
+
+    let yerr = if replicas.len() > 1 {
+        let stderr = mean
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let variance = values.iter().map(|y| (y[i] - m).powi(2)).sum::<f64>() / (n - 1.0);
+
+                (variance / n).sqrt()
+            })
+            .collect();
+
+        Some(stderr)
+    } else {
+        None
+    };
+
+    Some(Histogram {
+        x: xs,
+        y: mean,
+        yerr,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphdata::Graph;
+
+    #[test]
+    fn test_ensemble_average_has_standard_errors_within_common_range() {
+        let replicas = vec![
+            Graph::Carthesian {
+                x: vec![0.0, 10.0, 20.0, 30.0],
+                y: vec![1.0, 2.0, 3.0, 4.0],
+                yerr: None,
+            },
+            Graph::Carthesian {
+                x: vec![0.0, 5.0, 10.0, 15.0, 20.0],
+                y: vec![3.0, 3.5, 4.0, 4.5, 5.0],
+                yerr: None,
+            },
+        ];
+
+        let average = calc_ensemble_average(&replicas).unwrap();
+
+        // The second replica ends at 20, after which values would be extrapolated.
+        assert_eq!(vec![0.0, 10.0, 20.0], average.x);
+        assert_eq!(vec![2.0, 3.0, 4.0], average.y);
+
+        // Two values which differ by 2 have a standard error of 1.
+        for e in average.yerr.unwrap() {
+            assert!((e - 1.0).abs() < 1e-12);
+        }
+
+        let single = calc_ensemble_average(&replicas[..1]).unwrap();
+        assert_eq!(vec![1.0, 2.0, 3.0, 4.0], single.y);
+        assert_eq!(None, single.yerr);
+
+        assert_eq!(None, calc_ensemble_average::<Graph>(&[]));
+    }
+}
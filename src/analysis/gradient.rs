@@ -0,0 +1,82 @@
+use crate::densmap::DensMap;
+
+/// Compute the magnitude of the density gradient in every bin.
+///
+/// The gradient is calculated with central differences, except at the edges of the map
+/// where one-sided differences are used. Along an axis with a single bin the gradient
+/// component is zero.
+pub fn get_gradient_magnitude_map(densmap: &DensMap) -> DensMap {
+    let [dx, dy, _] = densmap.bin_size;
+    let [nx, ny] = densmap.shape;
+    let (nx, ny) = (nx as usize, ny as usize);
+
+    let value = |ix: usize, iy: usize| densmap.data[iy * nx + ix];
+
+    // Get the differentiation stencil (lower index, upper index) for an index along an axis.
+    let get_stencil = |i: usize, n: usize| {
+        if n < 2 {
+            None
+        } else if i == 0 {
+            Some((0, 1))
+        } else if i == n - 1 {
+            Some((n - 2, n - 1))
+        } else {
+            Some((i - 1, i + 1))
+        }
+    };
+
+    let data = densmap
+        .iter_bins()
+        .map(|(ix, iy, _, _, _)| {
+            let grad_x = get_stencil(ix, nx)
+                .map(|(i0, i1)| (value(i1, iy) - value(i0, iy)) / (dx * (i1 - i0) as f64))
+                .unwrap_or(0.0);
+            let grad_y = get_stencil(iy, ny)
+                .map(|(i0, i1)| (value(ix, i1) - value(ix, i0)) / (dy * (i1 - i0) as f64))
+                .unwrap_or(0.0);
+
+            (grad_x.powi(2) + grad_y.powi(2)).sqrt()
+        })
+        .collect();
+
+    DensMap {
+        data,
+        ..densmap.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gradient_of_linear_map_is_constant() {
+        // Density increases by 3 per unit length along x and 4 along y.
+        let densmap = DensMap {
+            bin_size: [0.5, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [3, 3],
+            center: [0.75, 1.5],
+            data: (0..9)
+                .map(|i| 1.5 * (i % 3) as f64 + 4.0 * (i / 3) as f64)
+                .collect(),
+        };
+
+        let gradient = get_gradient_magnitude_map(&densmap);
+
+        assert!(gradient.data.iter().all(|v| (v - 5.0).abs() < 1e-12));
+    }
+
+    #[test]
+    fn test_gradient_along_axis_with_single_bin_is_zero() {
+        let densmap = DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [1, 2],
+            center: [0.5, 1.0],
+            data: vec![1.0, 3.0],
+        };
+
+        assert_eq!(vec![2.0, 2.0], get_gradient_magnitude_map(&densmap).data);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::graphdata::{Graph, XYData};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// Magic string which begins every binary kymograph file.
+pub const KYMOGRAPH_MAGIC: &[u8; 4] = b"DMCL";
+
+/// Version of the binary kymograph format.
+const KYMOGRAPH_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+/// Contact line radius as a function of angle and time.
+pub struct Kymograph {
+    /// Times of the contact lines.
+    pub times: Vec<f64>,
+    /// Angles in degrees at which all contact lines are sampled.
+    pub angles: Vec<f64>,
+    /// Radius of the contact line for every time (outer) and angle (inner).
+    pub radius: Vec<Vec<f64>>,
+}
+
+impl Kymograph {
+    /// Construct a kymograph from contact lines in polar coordinates and their times.
+    ///
+    /// # Error
+    /// Returns an error if the number of times and contact lines differ, or if the
+    /// contact lines are not sampled at identical angles. Resample them onto a common
+    /// set of angles before constructing the kymograph.
+    pub fn from_contact_lines<T: XYData>(
+        times: &[f64],
+        contact_lines: &[T],
+    ) -> Result<Kymograph, String> {
+        if times.len() != contact_lines.len() {
+            return Err(format!(
+                "got {} times for {} contact lines",
+                times.len(),
+                contact_lines.len()
+            ));
+        }
+
+        let angles = contact_lines
+            .first()
+            .map(|contact_line| contact_line.x().to_vec())
+            .unwrap_or_default();
+
+        if contact_lines.iter().any(|c| c.x() != angles.as_slice()) {
+            return Err(String::from(
+                "all contact lines must be sampled at the same angles",
+            ));
+        }
+
+        Ok(Kymograph {
+            times: times.to_vec(),
+            angles,
+            radius: contact_lines.iter().map(|c| c.y().to_vec()).collect(),
+        })
+    }
+
+    /// Get the fluctuations of the contact line, ie. the radius at every angle relative
+    /// to the mean radius of the contact line at that time.
+    pub fn fluctuations(&self) -> Vec<Vec<f64>> {
+        self.radius
+            .iter()
+            .map(|radius| {
+                let mean = radius.iter().sum::<f64>() / radius.len().max(1) as f64;
+                radius.iter().map(|r| r - mean).collect()
+            })
+            .collect()
+    }
+}
+
+impl Kymograph {
+    /// Get the contact line of every time in polar coordinates.
+    pub fn contact_lines(&self) -> Vec<Graph> {
+        self.radius
+            .iter()
+            .map(|radius| Graph::Polar {
+                angles: self.angles.clone(),
+                radius: radius.clone(),
+                yerr: None,
+            })
+            .collect()
+    }
+}
+
+/// Write a kymograph to a binary file, which keeps the values exactly.
+///
+/// The file begins with the magic string 'DMCL' and the format version as a 32 bit
+/// unsigned integer, followed by the number of times and angles as 64 bit unsigned
+/// integers. After them come the angles, the times and the radius of every time (outer)
+/// and angle (inner) as 64 bit floats. All values are little endian.
+pub fn write_kymograph(path: &Path, kymograph: &Kymograph) -> Result<(), io::Error> {
+    let fp = File::create(path)?;
+    let mut writer = BufWriter::new(fp);
+
+    writer.write_all(KYMOGRAPH_MAGIC)?;
+    writer.write_u32::<LittleEndian>(KYMOGRAPH_VERSION)?;
+    writer.write_u64::<LittleEndian>(kymograph.times.len() as u64)?;
+    writer.write_u64::<LittleEndian>(kymograph.angles.len() as u64)?;
+
+    let values = kymograph
+        .angles
+        .iter()
+        .chain(kymograph.times.iter())
+        .chain(kymograph.radius.iter().flatten());
+
+    for &v in values {
+        writer.write_f64::<LittleEndian>(v)?;
+    }
+
+    writer.flush()
+}
+
+/// Read a kymograph from a binary file, see `write_kymograph`.
+///
+/// # Errors
+/// Returns an error if the file does not begin with the magic string, has an unknown
+/// version or is truncated.
+pub fn read_kymograph(path: &Path) -> Result<Kymograph, io::Error> {
+    let fp = File::open(path)?;
+    let mut reader = BufReader::new(fp);
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+
+    if &magic != KYMOGRAPH_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a binary kymograph file",
+        ));
+    }
+
+    let version = reader.read_u32::<LittleEndian>()?;
+    if version != KYMOGRAPH_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported kymograph format version {}", version),
+        ));
+    }
+
+    let num_times = reader.read_u64::<LittleEndian>()? as usize;
+    let num_angles = reader.read_u64::<LittleEndian>()? as usize;
+
+    let mut read_values = |n: usize| -> Result<Vec<f64>, io::Error> {
+        let mut values = vec![0.0; n];
+        reader.read_f64_into::<LittleEndian>(&mut values)?;
+        Ok(values)
+    };
+
+    let angles = read_values(num_angles)?;
+    let times = read_values(num_times)?;
+    let radius = (0..num_times)
+        .map(|_| read_values(num_angles))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Kymograph {
+        times,
+        angles,
+        radius,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphdata::Graph;
+
+    #[test]
+    fn test_kymograph_requires_contact_lines_at_identical_angles() {
+        let a = Graph::Polar {
+            angles: vec![0.0, 180.0],
+            radius: vec![1.0, 3.0],
+            yerr: None,
+        };
+        let b = Graph::Polar {
+            angles: vec![0.0, 90.0],
+            radius: vec![1.0, 3.0],
+            yerr: None,
+        };
+
+        let kymograph =
+            Kymograph::from_contact_lines(&[0.0, 1.0], &[a.clone(), a.clone()]).unwrap();
+        assert_eq!(vec![0.0, 180.0], kymograph.angles);
+        assert_eq!(
+            vec![vec![-1.0, 1.0], vec![-1.0, 1.0]],
+            kymograph.fluctuations()
+        );
+
+        assert!(Kymograph::from_contact_lines(&[0.0, 1.0], &[a.clone(), b]).is_err());
+        assert!(Kymograph::from_contact_lines(&[0.0], &[a.clone(), a]).is_err());
+    }
+
+    #[test]
+    fn test_kymograph_is_read_back_exactly_from_a_binary_file() {
+        let path = std::env::temp_dir().join("densmap_test_kymograph.dat");
+
+        let kymograph = Kymograph {
+            times: vec![0.0, 10.0],
+            angles: vec![0.0, 120.0, 240.0],
+            radius: vec![vec![0.1, -0.2, 1.0 / 3.0], vec![0.0, 0.5, -0.5]],
+        };
+
+        write_kymograph(&path, &kymograph).unwrap();
+        let read = read_kymograph(&path);
+
+        std::fs::write(&path, b"DMAP").unwrap();
+        let invalid = read_kymograph(&path);
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(kymograph, read.unwrap());
+        assert_eq!(io::ErrorKind::InvalidData, invalid.unwrap_err().kind());
+
+        let contact_lines = kymograph.contact_lines();
+        assert_eq!(2, contact_lines.len());
+        assert_eq!(&[0.0, 0.5, -0.5], contact_lines[1].y());
+    }
+}
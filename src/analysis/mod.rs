@@ -1,3 +1,20 @@
 pub mod autocorrelation;
+pub mod background;
+pub mod calibration;
+pub mod comparison;
+pub mod density_distribution;
+pub mod droplet_frame;
+pub mod ensemble;
+pub mod gradient;
+pub mod kymograph;
+pub mod pipeline;
+pub mod polygon;
+pub mod precursor;
 pub mod radial_density;
+pub mod radial_velocity;
+pub mod registration;
 pub mod sample_interface;
+pub mod spectrum;
+pub mod statistics;
+pub mod variance;
+pub mod velocity;
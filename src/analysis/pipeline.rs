@@ -0,0 +1,714 @@
+use crate::{
+    analysis::{
+        autocorrelation::{calc_autocorrelation, resample_onto_common_angles},
+        background::{estimate_background, subtract_background},
+        density_distribution::DensityDistribution,
+        polygon::get_contact_line_polygon,
+        radial_density::{get_radius_from_distribution, DensityProfileOptions, RadialBinning},
+        sample_interface::{get_density_cutoff, sample_interface},
+    },
+    average::{Smoother, SmoothingOptions},
+    densmap::DensMap,
+    graphdata::{Graph, Histogram, XYData},
+    mask::MaskRegion,
+    series::DensMapSeries,
+};
+
+use std::{io, path::Path};
+
+/// Name of the radial density distribution output of `RadialDensityAnalysis`.
+pub const RADIAL_DENSITY: &str = "radial_density";
+/// Name of the radial bin edges output of `RadialDensityAnalysis`.
+pub const RADIAL_BIN_EDGES: &str = "radial_bin_edges";
+/// Name of the radial occupancy output of `RadialOccupancyAnalysis`.
+pub const RADIAL_OCCUPANCY: &str = "radial_occupancy";
+/// Name of the radial mean density output of `RadialMeanDensityAnalysis`.
+pub const RADIAL_MEAN_DENSITY: &str = "radial_mean_density";
+/// Name of the density distribution output of every frame of `DensityDistributionAnalysis`.
+pub const DENSITY_DISTRIBUTION: &str = "density_distribution";
+/// Name of the density distribution output of all frames of `DensityDistributionAnalysis`.
+pub const DENSITY_DISTRIBUTION_AVERAGE: &str = "density_distribution_average";
+/// Name of the interface output of `InterfaceAnalysis`.
+pub const INTERFACE: &str = "interface";
+/// Name of the contact line output of `InterfaceAnalysis`.
+pub const CONTACT_LINE: &str = "contact_line";
+/// Name of the contact line polygon output of `ContactLinePolygonAnalysis`.
+pub const CONTACT_LINE_POLYGON: &str = "contact_line_polygon";
+/// Name of the radius output of `RadiusAnalysis`.
+pub const RADIUS: &str = "radius";
+/// Name of the autocorrelation output of `ContactLineAutocorrelation`.
+pub const AUTOCORRELATION: &str = "autocorrelation";
+
+/// A frame of a trajectory, along with the results of the droplet analysis which are
+/// shared by all analyses of the frame.
+pub struct Frame<'a> {
+    /// The (smoothed) density map of the frame.
+    pub densmap: &'a DensMap,
+    /// Time of the frame.
+    pub time: f64,
+    /// Radial density distribution around the droplet center.
+    pub radial_density: &'a Histogram,
+    /// Radius of the droplet, if it could be found.
+    pub radius: Option<f64>,
+    /// Contact line of the droplet with absolute radii, as returned by `sample_interface`,
+    /// if the radius could be found.
+    pub contact_line: Option<&'a Graph>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Data which is output by an analysis.
+pub enum Output {
+    Graph(Graph),
+    Histogram(Histogram),
+}
+
+/// Outputs of an analysis, which are named to let the caller decide where to write them.
+pub type Outputs = Vec<(String, Output)>;
+
+/// Analysis which is applied to every frame of a trajectory.
+///
+/// Analyses are run for all frames by an `AnalysisRegistry`, to which new analyses
+/// can be added without changing how the frames are processed.
+pub trait Analysis {
+    /// Analyze a frame, returning the outputs which belong to it.
+    ///
+    /// # Errors
+    /// Returns an error if the frame cannot be analyzed, which stops the analysis.
+    fn process_frame(&mut self, frame: &Frame) -> Result<Outputs, String>;
+
+    /// Finish the analysis of the frames which have been processed since the last call,
+    /// returning the outputs which belong to all of them.
+    fn finish(&mut self) -> Outputs {
+        Outputs::new()
+    }
+}
+
+/// Results of the droplet analysis of a frame, which are shared by all analyses.
+pub struct FrameResults {
+    pub radial_density: Histogram,
+    pub radius: Option<f64>,
+    pub contact_line: Option<Graph>,
+}
+
+#[derive(Default)]
+/// Registry of analyses which are run for every frame.
+///
+/// The radial density distribution, radius and contact line of every frame are found
+/// once and shared by all registered analyses through the `Frame`.
+pub struct AnalysisRegistry {
+    radial_binning: RadialBinning,
+    analyses: Vec<Box<dyn Analysis>>,
+    skip_interface: bool,
+}
+
+impl AnalysisRegistry {
+    pub fn new() -> Self {
+        AnalysisRegistry::default()
+    }
+
+    /// Add an analysis which is run after those which are already registered.
+    pub fn register<A: Analysis + 'static>(&mut self, analysis: A) {
+        self.analyses.push(Box::new(analysis));
+    }
+
+    /// Set whether the interface of every frame is sampled for its contact line, which
+    /// it is by default. Sampling the interface takes most of the time of the analysis
+    /// of a frame, so it can be skipped when only the radius is needed, after which
+    /// the frames have no contact line.
+    pub fn set_sample_interface(&mut self, sample: bool) {
+        self.skip_interface = !sample;
+    }
+
+    /// Set the options of the radial density distribution of every frame, which the
+    /// radius is found from, and the unit of the lengths of the maps, which the
+    /// resolution of the sampled interface is set by. By default the radial bins are
+    /// of fixed width and the lengths are in nm.
+    pub fn set_density_profile_options(&mut self, options: DensityProfileOptions) {
+        self.radial_binning = RadialBinning::with_options(options);
+    }
+
+    pub fn len(&self) -> usize {
+        self.analyses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.analyses.is_empty()
+    }
+
+    /// Run all analyses for the map of a frame, returning the shared results of the
+    /// frame along with the outputs of the analyses, in order of registration.
+    ///
+    /// # Errors
+    /// Returns the error of the first analysis which fails.
+    pub fn process_frame(
+        &mut self,
+        densmap: &DensMap,
+        time: f64,
+    ) -> Result<(FrameResults, Outputs), String> {
+        let radial_density = self.radial_binning.get_radial_density_distribution(densmap);
+        let radius = get_radius_from_distribution(radial_density.clone()).ok();
+        let length_unit = self.radial_binning.options().length_unit;
+        let contact_line = radius
+            .filter(|_| !self.skip_interface)
+            .map(|radius| sample_interface(densmap, radius, length_unit));
+
+        let frame = Frame {
+            densmap,
+            time,
+            radial_density: &radial_density,
+            radius,
+            contact_line: contact_line.as_ref(),
+        };
+
+        let mut outputs = Outputs::new();
+        for analysis in self.analyses.iter_mut() {
+            outputs.extend(analysis.process_frame(&frame)?);
+        }
+
+        let results = FrameResults {
+            radial_density,
+            radius,
+            contact_line,
+        };
+
+        Ok((results, outputs))
+    }
+
+    /// Finish all analyses, returning their outputs in order of registration.
+    pub fn finish(&mut self) -> Outputs {
+        self.analyses
+            .iter_mut()
+            .flat_map(|analysis| analysis.finish())
+            .collect()
+    }
+}
+
+#[derive(Default)]
+/// Output the radial density distribution of every frame.
+///
+/// When the radial bins are widened to a minimum number of map bins their edges are
+/// also output, with the inner edge of every bin as x and its outer edge as y.
+pub struct RadialDensityAnalysis {
+    radial_binning: RadialBinning,
+}
+
+impl RadialDensityAnalysis {
+    /// Construct the analysis with the options of the radial density profiles, which
+    /// should be the same as those of the `AnalysisRegistry`.
+    pub fn new(options: DensityProfileOptions) -> Self {
+        RadialDensityAnalysis {
+            radial_binning: RadialBinning::with_options(options),
+        }
+    }
+}
+
+impl Analysis for RadialDensityAnalysis {
+    fn process_frame(&mut self, frame: &Frame) -> Result<Outputs, String> {
+        let output = Output::Histogram(frame.radial_density.clone());
+        let mut outputs = vec![(RADIAL_DENSITY.to_string(), output)];
+
+        if self.radial_binning.options().min_samples.is_some() {
+            let edges = self
+                .radial_binning
+                .get_radial_density_bin_edges(frame.densmap);
+
+            let output = Output::Histogram(Histogram {
+                x: edges[..edges.len() - 1].to_vec(),
+                y: edges[1..].to_vec(),
+                yerr: None,
+            });
+            outputs.push((RADIAL_BIN_EDGES.to_string(), output));
+        }
+
+        Ok(outputs)
+    }
+}
+
+#[derive(Default)]
+/// Output the fraction of bins above the density cutoff of the contact line at every
+/// radius of every frame.
+pub struct RadialOccupancyAnalysis {
+    radial_binning: RadialBinning,
+}
+
+impl RadialOccupancyAnalysis {
+    /// Construct the analysis with the options of the radial density profiles.
+    pub fn new(options: DensityProfileOptions) -> Self {
+        RadialOccupancyAnalysis {
+            radial_binning: RadialBinning::with_options(options),
+        }
+    }
+}
+
+impl Analysis for RadialOccupancyAnalysis {
+    fn process_frame(&mut self, frame: &Frame) -> Result<Outputs, String> {
+        let cutoff = get_density_cutoff(frame.densmap);
+        let occupancy = self
+            .radial_binning
+            .get_radial_occupancy(frame.densmap, cutoff);
+        Ok(vec![(
+            RADIAL_OCCUPANCY.to_string(),
+            Output::Histogram(occupancy),
+        )])
+    }
+}
+
+#[derive(Default)]
+/// Output the mean density of the bins at every radius of every frame.
+pub struct RadialMeanDensityAnalysis {
+    radial_binning: RadialBinning,
+}
+
+impl RadialMeanDensityAnalysis {
+    /// Construct the analysis with the options of the radial density profiles.
+    pub fn new(options: DensityProfileOptions) -> Self {
+        RadialMeanDensityAnalysis {
+            radial_binning: RadialBinning::with_options(options),
+        }
+    }
+}
+
+impl Analysis for RadialMeanDensityAnalysis {
+    fn process_frame(&mut self, frame: &Frame) -> Result<Outputs, String> {
+        let density = self.radial_binning.get_radial_mean_density(frame.densmap);
+        Ok(vec![(
+            RADIAL_MEAN_DENSITY.to_string(),
+            Output::Histogram(density),
+        )])
+    }
+}
+
+/// Output the probability distribution of the densities of the bins of every frame,
+/// along with the distribution averaged over all frames, see `DensityDistribution`.
+pub struct DensityDistributionAnalysis {
+    distribution: DensityDistribution,
+}
+
+impl DensityDistributionAnalysis {
+    /// Construct the analysis with the number of histogram bins up to the maximum
+    /// density of the first frame.
+    pub fn new(num_bins: usize) -> Self {
+        DensityDistributionAnalysis {
+            distribution: DensityDistribution::new(num_bins),
+        }
+    }
+}
+
+impl Analysis for DensityDistributionAnalysis {
+    fn process_frame(&mut self, frame: &Frame) -> Result<Outputs, String> {
+        let distribution = self.distribution.add(frame.densmap);
+        Ok(vec![(
+            DENSITY_DISTRIBUTION.to_string(),
+            Output::Histogram(distribution),
+        )])
+    }
+
+    fn finish(&mut self) -> Outputs {
+        vec![(
+            DENSITY_DISTRIBUTION_AVERAGE.to_string(),
+            Output::Histogram(self.distribution.get_average()),
+        )]
+    }
+}
+
+/// Output the interface of every frame in Carthesian coordinates, along with the contact
+/// line in polar coordinates relative to the droplet radius.
+pub struct InterfaceAnalysis;
+
+impl Analysis for InterfaceAnalysis {
+    fn process_frame(&mut self, frame: &Frame) -> Result<Outputs, String> {
+        match (frame.radius, frame.contact_line) {
+            (Some(radius), Some(contact_line)) => Ok(vec![
+                (
+                    INTERFACE.to_string(),
+                    Output::Graph(contact_line.to_carthesian()),
+                ),
+                (
+                    CONTACT_LINE.to_string(),
+                    Output::Graph(get_relative_contact_line(contact_line, radius)),
+                ),
+            ]),
+            _ => Ok(Outputs::new()),
+        }
+    }
+}
+
+/// Output the contact line of every frame as a closed polygon in Carthesian coordinates
+/// around the droplet center, see `get_contact_line_polygon`.
+pub struct ContactLinePolygonAnalysis;
+
+impl Analysis for ContactLinePolygonAnalysis {
+    fn process_frame(&mut self, frame: &Frame) -> Result<Outputs, String> {
+        match frame.contact_line {
+            Some(contact_line) => Ok(vec![(
+                CONTACT_LINE_POLYGON.to_string(),
+                Output::Graph(get_contact_line_polygon(contact_line).polygon),
+            )]),
+            None => Ok(Outputs::new()),
+        }
+    }
+}
+
+#[derive(Default)]
+/// Output the droplet radius as a function of time.
+pub struct RadiusAnalysis {
+    times: Vec<f64>,
+    radius: Vec<f64>,
+}
+
+impl Analysis for RadiusAnalysis {
+    fn process_frame(&mut self, frame: &Frame) -> Result<Outputs, String> {
+        if let Some(radius) = frame.radius {
+            self.times.push(frame.time);
+            self.radius.push(radius);
+        }
+
+        Ok(Outputs::new())
+    }
+
+    fn finish(&mut self) -> Outputs {
+        let radius_per_time = Graph::Carthesian {
+            x: self.times.split_off(0),
+            y: self.radius.split_off(0),
+            yerr: None,
+        };
+
+        vec![(RADIUS.to_string(), Output::Graph(radius_per_time))]
+    }
+}
+
+#[derive(Default)]
+/// Output the autocorrelation of the contact line fluctuations as a function of time.
+///
+/// The contact line of every frame is kept until the analysis is finished, after which
+/// they are resampled onto common angles.
+pub struct ContactLineAutocorrelation {
+    times: Vec<f64>,
+    contact_lines: Vec<Graph>,
+}
+
+impl Analysis for ContactLineAutocorrelation {
+    fn process_frame(&mut self, frame: &Frame) -> Result<Outputs, String> {
+        if let (Some(radius), Some(contact_line)) = (frame.radius, frame.contact_line) {
+            self.times.push(frame.time);
+            self.contact_lines
+                .push(get_relative_contact_line(contact_line, radius));
+        }
+
+        Ok(Outputs::new())
+    }
+
+    fn finish(&mut self) -> Outputs {
+        if self.contact_lines.is_empty() {
+            return Outputs::new();
+        }
+
+        let resampled_contact_lines = resample_onto_common_angles(&self.contact_lines);
+        self.contact_lines.clear();
+
+        let autocorrelation = Histogram {
+            x: self.times.split_off(0),
+            y: calc_autocorrelation(&resampled_contact_lines),
+            yerr: None,
+        };
+
+        vec![(
+            AUTOCORRELATION.to_string(),
+            Output::Histogram(autocorrelation),
+        )]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Options of how the maps are prepared for the analysis by `run_pipeline`.
+pub struct PipelineOptions {
+    /// Regions of the maps which are masked out before analysis.
+    pub mask: Vec<MaskRegion>,
+    /// Region of interest as `[xmin, xmax, ymin, ymax]` which the maps are cropped to.
+    pub roi: Option<[f64; 4]>,
+    /// Radius within which bins are smoothed with their neighbours.
+    pub smoothing_radius: f64,
+    /// How bins are smoothed with their neighbours.
+    pub smoothing: SmoothingOptions,
+    /// Subtract the vapor density from the maps after smoothing.
+    pub subtract_background: bool,
+    /// Options of the radial density distributions and unit of the lengths of the maps.
+    pub density_profile: DensityProfileOptions,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        PipelineOptions {
+            mask: Vec::new(),
+            roi: None,
+            smoothing_radius: 0.5,
+            smoothing: SmoothingOptions::default(),
+            subtract_background: false,
+            density_profile: DensityProfileOptions::default(),
+        }
+    }
+}
+
+/// Result of the analysis of a frame by `run_pipeline`.
+pub struct FrameResult<'a> {
+    /// Path of the map of the frame.
+    pub path: &'a Path,
+    /// Time of the frame.
+    pub time: f64,
+    /// The smoothed map which was analyzed.
+    pub densmap: &'a DensMap,
+    /// Radius of the droplet, if it could be found.
+    pub radius: Option<f64>,
+    /// Contact line of the droplet with absolute radii, if the radius could be found.
+    pub contact_line: Option<&'a Graph>,
+    /// Reason why the background could not be subtracted from the map, if it was to be.
+    pub background_error: Option<&'a str>,
+}
+
+/// Analyze the maps of a series like the command line tool does, handing the result
+/// of every frame to a callback.
+///
+/// The maps are read, masked, cropped and smoothed (with the background subtracted if
+/// set) one at a time, after which the droplet radius and contact line are found.
+///
+/// # Errors
+/// Returns an error if a map cannot be read or prepared for analysis, or the first
+/// error returned by the callback, after which no more frames are analyzed.
+pub fn run_pipeline<F>(
+    series: &DensMapSeries,
+    options: &PipelineOptions,
+    mut callback: F,
+) -> Result<(), io::Error>
+where
+    F: FnMut(FrameResult) -> Result<(), io::Error>,
+{
+    let mut smoother = Smoother::new(options.smoothing_radius, options.smoothing);
+    let mut background_binning = RadialBinning::with_options(options.density_profile);
+    let mut registry = AnalysisRegistry::new();
+    registry.set_density_profile_options(options.density_profile);
+
+    for (path, result) in series.paths().iter().zip(series.iter()) {
+        let with_path = |err: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("could not analyze '{}': {}", path.display(), err),
+            )
+        };
+
+        let (mut densmap, time) = result?;
+
+        densmap.mask(&options.mask).map_err(with_path)?;
+        if let Some([xmin, xmax, ymin, ymax]) = options.roi {
+            densmap = densmap.crop(xmin, xmax, ymin, ymax).map_err(with_path)?;
+        }
+
+        let (smoothed_densmap, background_error) = smoothen_densmap(
+            densmap,
+            &mut smoother,
+            &mut background_binning,
+            options.subtract_background,
+        );
+
+        let (results, _) = registry
+            .process_frame(&smoothed_densmap, time)
+            .map_err(with_path)?;
+
+        callback(FrameResult {
+            path,
+            time,
+            densmap: &smoothed_densmap,
+            radius: results.radius,
+            contact_line: results.contact_line.as_ref(),
+            background_error: background_error.as_deref(),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Smoothen a density map and optionally subtract its background.
+///
+/// The vapor is located using the radius of the droplet with the background still
+/// in the map. If that fails the map is returned as it is after smoothing, along with
+/// the reason why the background could not be subtracted.
+pub fn smoothen_densmap(
+    densmap: DensMap,
+    smoother: &mut Smoother,
+    radial_binning: &mut RadialBinning,
+    subtract: bool,
+) -> (DensMap, Option<String>) {
+    let smoothed_densmap = smoother.smoothen(densmap);
+
+    if subtract {
+        let radial_density = radial_binning.get_radial_density_distribution(&smoothed_densmap);
+
+        match get_radius_from_distribution(radial_density)
+            .and_then(|radius| estimate_background(&smoothed_densmap, radius))
+        {
+            Ok(background) => (subtract_background(smoothed_densmap, background), None),
+            Err(err) => (smoothed_densmap, Some(err)),
+        }
+    } else {
+        (smoothed_densmap, None)
+    }
+}
+
+/// Get a contact line with absolute radii relative to the droplet radius.
+pub fn get_relative_contact_line(contact_line: &Graph, radius: f64) -> Graph {
+    Graph::Polar {
+        angles: contact_line.x().to_vec(),
+        radius: contact_line.y().iter().map(|r| r - radius).collect(),
+        yerr: None,
+    }
+}
+
+/// Get a contact line with radii relative to the droplet radius back in absolute radii.
+pub fn get_absolute_contact_line(contact_line: &Graph, radius: f64) -> Graph {
+    Graph::Polar {
+        angles: contact_line.x().to_vec(),
+        radius: contact_line.y().iter().map(|dr| dr + radius).collect(),
+        yerr: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{densmap::write_densmap, generate::DensMapBuilder};
+
+    /// Analysis which counts the frames with a droplet.
+    #[derive(Default)]
+    struct CountDroplets(usize);
+
+    impl Analysis for CountDroplets {
+        fn process_frame(&mut self, frame: &Frame) -> Result<Outputs, String> {
+            if frame.radius.is_some() {
+                self.0 += 1;
+            }
+
+            Ok(Outputs::new())
+        }
+
+        fn finish(&mut self) -> Outputs {
+            let count = Histogram {
+                x: vec![0.0],
+                y: vec![self.0 as f64],
+                yerr: None,
+            };
+
+            vec![("count".to_string(), Output::Histogram(count))]
+        }
+    }
+
+    #[test]
+    fn test_registered_analyses_are_run_for_every_frame_in_order() {
+        let mut registry = AnalysisRegistry::new();
+        registry.register(InterfaceAnalysis);
+        registry.register(RadiusAnalysis::default());
+        registry.register(CountDroplets::default());
+
+        let builder = DensMapBuilder::new().shape([40, 40]);
+
+        for (i, &radius) in [1.0, 1.2].iter().enumerate() {
+            let densmap = builder.disc(radius, 0.1, 0.0);
+            let (results, outputs) = registry.process_frame(&densmap, 10.0 * i as f64).unwrap();
+
+            assert!((results.radius.unwrap() - radius).abs() < 0.1);
+            assert_eq!(
+                vec![INTERFACE, CONTACT_LINE],
+                outputs
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        let outputs = registry.finish();
+        assert_eq!(2, outputs.len());
+
+        match &outputs[0] {
+            (name, Output::Graph(graph)) => {
+                assert_eq!(RADIUS, name);
+                assert_eq!(&[0.0, 10.0], graph.x());
+            }
+            _ => panic!("expected the radius per time as a graph"),
+        }
+
+        assert_eq!(
+            (
+                "count".to_string(),
+                Output::Histogram(Histogram {
+                    x: vec![0.0],
+                    y: vec![2.0],
+                    yerr: None,
+                })
+            ),
+            outputs[1]
+        );
+    }
+
+    #[test]
+    fn test_registry_without_interface_sampling_finds_only_the_radius() {
+        let mut registry = AnalysisRegistry::new();
+        registry.register(InterfaceAnalysis);
+        registry.register(RadiusAnalysis::default());
+        registry.set_sample_interface(false);
+
+        let densmap = DensMapBuilder::new().shape([40, 40]).disc(1.0, 0.1, 0.0);
+        let (results, outputs) = registry.process_frame(&densmap, 0.0).unwrap();
+
+        assert!((results.radius.unwrap() - 1.0).abs() < 0.1);
+        assert!(results.contact_line.is_none());
+        assert!(outputs.is_empty());
+
+        match &registry.finish()[0] {
+            (name, Output::Graph(graph)) => {
+                assert_eq!(RADIUS, name);
+                assert_eq!(1, graph.x().len());
+            }
+            _ => panic!("expected the radius per time as a graph"),
+        }
+    }
+
+    #[test]
+    fn test_pipeline_hands_every_frame_to_the_callback() {
+        let dir = std::env::temp_dir().join("densmap_test_pipeline");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let builder = DensMapBuilder::new().shape([40, 40]);
+        let paths = [1.0, 1.5]
+            .iter()
+            .enumerate()
+            .map(|(i, &radius)| {
+                let path = dir.join(format!("map{}.dat", i));
+                let densmap = builder.disc(radius, 0.1, 0.0);
+                write_densmap(&path, &densmap, 10.0 * i as f64).unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+
+        let series = DensMapSeries::from_paths(paths);
+        let options = PipelineOptions {
+            smoothing_radius: 0.0,
+            ..PipelineOptions::default()
+        };
+
+        let mut frames = Vec::new();
+        let result = run_pipeline(&series, &options, |frame| {
+            assert!(frame.contact_line.is_some());
+            frames.push((frame.time, frame.radius.unwrap()));
+            Ok(())
+        });
+
+        let stopped = run_pipeline(&series, &options, |_| Err(io::Error::other("stop")));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(2, frames.len());
+        assert_eq!(10.0, frames[1].0);
+        assert!((frames[0].1 - 1.0).abs() < 0.2);
+        assert!((frames[1].1 - 1.5).abs() < 0.2);
+        assert!(stopped.is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::graphdata::{Graph, XYData};
+
+#[derive(Clone, Debug, PartialEq)]
+/// Contact line of a droplet as a closed polygon, along with its geometry.
+pub struct ContactLinePolygon {
+    /// Vertices of the polygon in Carthesian coordinates around the droplet center,
+    /// ordered counter-clockwise by their angle, ending with the first vertex again.
+    pub polygon: Graph,
+    /// Area which is enclosed by the polygon.
+    pub area: f64,
+    /// Length of the edges of the polygon, including the one which closes it.
+    pub perimeter: f64,
+}
+
+/// Get the closed polygon of a contact line around the droplet center, with the area
+/// which it encloses and its perimeter.
+///
+/// The points of the contact line are ordered by their angle around the center, which
+/// gives a simple polygon for contact lines with a single radius at every angle, like
+/// those of `sample_interface`. Points which are not finite are left out.
+pub fn get_contact_line_polygon(contact_line: &Graph) -> ContactLinePolygon {
+    let polar = contact_line.to_polar();
+    let carthesian = polar.to_carthesian();
+
+    let mut vertices = polar
+        .x()
+        .iter()
+        .zip(carthesian.x().iter().zip(carthesian.y()))
+        .filter(|(_, (x, y))| x.is_finite() && y.is_finite())
+        .map(|(&angle, (&x, &y))| (angle.rem_euclid(360.0), x, y))
+        .collect::<Vec<_>>();
+
+    vertices.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let (mut x, mut y): (Vec<f64>, Vec<f64>) = vertices.iter().map(|&(_, x, y)| (x, y)).unzip();
+
+    if let (Some(&x0), Some(&y0)) = (x.first(), y.first()) {
+        x.push(x0);
+        y.push(y0);
+    }
+
+    let edges = x.windows(2).zip(y.windows(2));
+
+    let area = 0.5
+        * edges
+            .clone()
+            .map(|(x, y)| x[0] * y[1] - x[1] * y[0])
+            .sum::<f64>()
+            .abs();
+    let perimeter = edges
+        .map(|(x, y)| (x[1] - x[0]).hypot(y[1] - y[0]))
+        .sum::<f64>();
+
+    ContactLinePolygon {
+        polygon: Graph::Carthesian { x, y, yerr: None },
+        area,
+        perimeter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_polygon_of_square_is_closed_and_ordered_by_angle() {
+        // The corners of a square with side 2 around the center, out of order.
+        let contact_line = Graph::Carthesian {
+            x: vec![1.0, -1.0, 1.0, -1.0, f64::NAN],
+            y: vec![1.0, -1.0, -1.0, 1.0, 0.0],
+            yerr: None,
+        };
+
+        let polygon = get_contact_line_polygon(&contact_line);
+
+        let expected = Graph::Carthesian {
+            x: vec![1.0, -1.0, -1.0, 1.0, 1.0],
+            y: vec![1.0, 1.0, -1.0, -1.0, 1.0],
+            yerr: None,
+        };
+
+        for (a, b) in polygon
+            .polygon
+            .x()
+            .iter()
+            .chain(polygon.polygon.y())
+            .zip(expected.x().iter().chain(expected.y()))
+        {
+            assert!((a - b).abs() < 1e-12, "{:?}", polygon.polygon);
+        }
+
+        assert!((polygon.area - 4.0).abs() < 1e-12);
+        assert!((polygon.perimeter - 8.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_polygon_of_circle_approaches_its_area_and_circumference() {
+        let contact_line = Graph::Polar {
+            angles: (0..360).map(|a| a as f64).collect(),
+            radius: vec![2.0; 360],
+            yerr: None,
+        };
+
+        let polygon = get_contact_line_polygon(&contact_line);
+
+        assert!((polygon.area - 4.0 * std::f64::consts::PI).abs() < 1e-3);
+        assert!((polygon.perimeter - 4.0 * std::f64::consts::PI).abs() < 1e-3);
+
+        let empty = Graph::Carthesian {
+            x: vec![],
+            y: vec![],
+            yerr: None,
+        };
+
+        assert_eq!(0.0, get_contact_line_polygon(&empty).area);
+    }
+}
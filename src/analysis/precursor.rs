@@ -0,0 +1,177 @@
+use crate::{
+    analysis::sample_interface::sample_interface_beyond,
+    densmap::{coord2index, DensMap},
+    graphdata::{append_xvg_columns, write_xvg_columns, Columns, Graph, XYData},
+};
+
+use std::{io, path::Path};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Measurements of the thin precursor film which lies ahead of the contact line.
+pub struct PrecursorFilm {
+    /// Mean radial distance from the contact line to the edge of the film.
+    pub extent: f64,
+    /// Mean density inside the film.
+    pub density: f64,
+}
+
+/// Detect the precursor film ahead of the contact line and measure its extent and density.
+///
+/// The film edge is sampled outwards from the contact line at every angle, using a density
+/// cutoff of `fraction` times the maximum density of the map. The fraction should thus
+/// be much smaller than the one used for the contact line itself (0.5).
+///
+/// The density of the film is averaged over the bins along the sampled rays which lie
+/// between the contact line and the film edge. If no film is found both values are zero.
+pub fn get_precursor_film(densmap: &DensMap, contact_line: &Graph, fraction: f64) -> PrecursorFilm {
+    let max = densmap.data.iter().fold(0.0, |acc: f64, &v| acc.max(v));
+    let cutoff = fraction * max;
+
+    let contact_line = contact_line.to_polar();
+    let film_edge = sample_interface_beyond(densmap, &contact_line, cutoff);
+
+    let x0 = densmap.center[0] - densmap.origin[0];
+    let y0 = densmap.center[1] - densmap.origin[1];
+
+    let [dx_bin, dy_bin, _] = densmap.bin_size;
+    let dr = dx_bin.min(dy_bin);
+
+    let mut extent = 0.0;
+    let mut density_sum = 0.0;
+    let mut num_samples = 0;
+
+    for ((&angle, &r0), &r1) in contact_line
+        .x()
+        .iter()
+        .zip(contact_line.y().iter())
+        .zip(film_edge.y().iter())
+    {
+        extent += (r1 - r0).max(0.0);
+
+        let (dy, dx) = angle.to_radians().sin_cos();
+        let mut radius = r0 + dr;
+
+        while radius <= r1 {
+            if let Some(i) = coord2index(
+                x0 + radius * dx,
+                y0 + radius * dy,
+                densmap.bin_size,
+                densmap.shape,
+            ) {
+                density_sum += densmap.data[i];
+                num_samples += 1;
+            }
+
+            radius += dr;
+        }
+    }
+
+    let num_angles = contact_line.x().len();
+
+    PrecursorFilm {
+        extent: if num_angles > 0 {
+            extent / num_angles as f64
+        } else {
+            0.0
+        },
+        density: if num_samples > 0 {
+            density_sum / num_samples as f64
+        } else {
+            0.0
+        },
+    }
+}
+
+/// Write the precursor film measurements of a trajectory to disk.
+///
+/// Every line contains the time, the film extent and the film density.
+pub fn write_precursor_films(
+    path: &Path,
+    times: &[f64],
+    films: &[PrecursorFilm],
+) -> Result<(), io::Error> {
+    write_xvg_columns(path, &get_precursor_film_columns(times, films))
+}
+
+/// Append precursor films to an existing file, or create it without a header
+/// if it does not exist.
+pub fn append_precursor_films(
+    path: &Path,
+    times: &[f64],
+    films: &[PrecursorFilm],
+) -> Result<(), io::Error> {
+    append_xvg_columns(path, &get_precursor_film_columns(times, films))
+}
+
+fn get_precursor_film_columns(times: &[f64], films: &[PrecursorFilm]) -> Columns {
+    Columns::new("time", times.to_vec())
+        .with_column("extent", films.iter().map(|film| film.extent).collect())
+        .with_column("density", films.iter().map(|film| film.density).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::sample_interface::sample_interface, densmap::LengthUnit};
+
+    /// Get a map with a droplet of radius 2 (density 1) and a film (density 0.1) which
+    /// extends to radius 3, centered in the system.
+    fn get_droplet_with_film() -> DensMap {
+        let shape = [80, 80];
+        let d = 0.1;
+        let center = [4.0, 4.0];
+
+        let data = (0..6400)
+            .map(|i| {
+                let x = d * (i % 80) as f64;
+                let y = d * (i / 80) as f64;
+                let r = ((x - center[0]).powi(2) + (y - center[1]).powi(2)).sqrt();
+
+                if r <= 2.0 {
+                    1.0
+                } else if r <= 3.0 {
+                    0.1
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        DensMap {
+            bin_size: [d, d, 0.0],
+            origin: [0.0, 0.0],
+            shape,
+            center,
+            data,
+        }
+    }
+
+    #[test]
+    fn test_precursor_film_extent_and_density_are_measured_ahead_of_contact_line() {
+        let densmap = get_droplet_with_film();
+        let contact_line = sample_interface(&densmap, 2.0, LengthUnit::Nm);
+
+        let film = get_precursor_film(&densmap, &contact_line, 0.05);
+
+        assert!(
+            (film.extent - 1.0).abs() < 0.2,
+            "extent was {}",
+            film.extent
+        );
+        assert!(
+            (film.density - 0.1).abs() < 0.02,
+            "density was {}",
+            film.density
+        );
+    }
+
+    #[test]
+    fn test_precursor_film_is_empty_if_cutoff_is_above_film_density() {
+        let densmap = get_droplet_with_film();
+        let contact_line = sample_interface(&densmap, 2.0, LengthUnit::Nm);
+
+        let film = get_precursor_film(&densmap, &contact_line, 0.2);
+
+        assert!(film.extent < 0.2);
+    }
+}
@@ -1,20 +1,415 @@
 use crate::{
-    densmap::{index2tuple, DensMap},
+    densmap::{DensMap, LengthUnit, Shape, Vec2, Vec3},
     graphdata::Histogram,
 };
 
+use std::f64::consts::PI;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+#[cfg(test)]
+use crate::generate::DensMapBuilder;
+
 /// Compute the radial density distribution function p(r) for the density map, using
 /// the center point of the droplet as the origin.
 ///
-/// The distribution is scaled to have units of mass / nm of the circumference at the radius.
+/// The distribution is scaled to have units of mass / length of the circumference at the
+/// radius. Bins closer than 1 nm to the center are excluded, for maps with lengths in nm.
+/// For other units or to instead widen the radial bins to a minimum number of map bins,
+/// use a `RadialBinning` with `DensityProfileOptions`.
+///
+/// The radial bins of the map are calculated for every call. When analyzing several maps
+/// use a `RadialBinning`, which reuses the bin positions between maps on the same grid.
 pub fn get_radial_density_distribution(densmap: &DensMap) -> Histogram {
-    let (rmin, dr, radius) = get_radius_values_for_histogram(&densmap);
-    let histogram = get_radial_mass_sum_of_densmap(&densmap, rmin, dr, radius.len());
-    let scaled_histogram = scale_histogram_to_per_unit_length(&histogram, &radius);
+    RadialBinning::new().get_radial_density_distribution(densmap)
+}
+
+/// Compute the fraction of bins with a density above a cutoff at every radius around
+/// the droplet center, which for a cutoff of 0 is the fraction of non-empty bins.
+///
+/// Smoothed maps rarely have empty bins, for which a cutoff like the one of the contact
+/// line is used to count the bins which belong to the droplet. Unlike the radial
+/// density distribution the occupancy does not depend on how many bins of a shell lie
+/// inside the map, which makes it a more robust indicator of the droplet radius when
+/// the droplet comes close to the edges of the map. Bins closer than 1 nm to the center
+/// are excluded, like for the radial density distribution.
+pub fn get_radial_occupancy(densmap: &DensMap, cutoff: f64) -> Histogram {
+    RadialBinning::new().get_radial_occupancy(densmap, cutoff)
+}
+
+/// Compute the mean density of the bins at every radius around the droplet center.
+///
+/// The density is normalized by the number of bins in every shell instead of by its
+/// circumference, so shells which are cut by the edges of the map are not diluted.
+/// Shells without any bins have no value (NaN). Bins closer than 1 nm to the center
+/// are excluded, like for the radial density distribution.
+pub fn get_radial_mean_density(densmap: &DensMap) -> Histogram {
+    RadialBinning::new().get_radial_mean_density(densmap)
+}
+
+/// Compute the radial profile of a quantity of every bin in the map, like its mass,
+/// occupancy or the magnitude of the density gradient.
+///
+/// The quantity is given by a function of the grid position (ix, iy) and value of
+/// every bin, which is combined into radial bins around a center as set by the options.
+/// The radius of every radial bin is its inner edge.
+///
+/// The radial bins of the map are calculated for every call. When analyzing several maps
+/// use a `RadialBinning`, which reuses the bin positions between maps on the same grid.
+pub fn radial_profile<F>(
+    densmap: &DensMap,
+    field_fn: F,
+    options: &RadialProfileOptions,
+) -> Histogram
+where
+    F: Fn(usize, usize, f64) -> f64,
+{
+    RadialBinning::new().get_radial_profile(densmap, field_fn, options)
+}
+
+/// How the values of the map bins in every radial bin are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RadialReduction {
+    /// Sum of the values, like the total mass at every radius.
+    #[default]
+    Sum,
+    /// Mean of the values, which is NaN for radial bins without any map bins.
+    Mean,
+}
+
+/// Options for the radial bins of a radial profile.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RadialProfileOptions {
+    /// Origin of the profile, or the droplet center of the map if not set.
+    pub center: Option<Vec2>,
+    /// Distance from the center within which bins are excluded from the profile.
+    pub rmin: f64,
+    /// Width of the radial bins, or the mean bin size of the map if not set.
+    pub dr: Option<f64>,
+    /// Minimum number of map bins in every radial bin. Bins of width `dr` are merged
+    /// from the inside out until they have this many, after which the radius of every
+    /// bin is the inner edge of the first. If not set all bins have width `dr`.
+    pub min_samples: Option<usize>,
+    /// How the values in every radial bin are combined.
+    pub reduction: RadialReduction,
+}
+
+/// Number of bins which are accumulated into every partial histogram.
+#[cfg(feature = "parallel")]
+const HISTOGRAM_CHUNK_SIZE: usize = 4096;
+
+/// Options of the radial density distribution and the other radial density profiles
+/// of a `RadialBinning`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DensityProfileOptions {
+    /// Minimum number of map bins in every radial bin. With a minimum the bins are
+    /// widened from the center outwards until they have enough map bins, instead of
+    /// excluding the noisy bins within 1 nm of the center. The distribution is then
+    /// scaled by the area of every bin instead of by its circumference, since the
+    /// innermost bin begins at the center. If not set the bins are of fixed width.
+    pub min_samples: Option<usize>,
+    /// Unit of the lengths of the maps, in which the distance of 1 nm from the center
+    /// within which bins are excluded is given.
+    pub length_unit: LengthUnit,
+}
+
+/// Calculation of radial profiles which caches the positions of the bins of the map.
+///
+/// The positions of the bins depend only on the grid of the maps, that is their bin
+/// size, origin and shape. They are calculated for the first map and reused for all
+/// following maps until the grid changes. The distance of every bin to the center of
+/// the profile, which for the droplet center moves between frames, is calculated for
+/// every map from the positions, after which the sum of every radial bin is a flat
+/// gather-add of the map data. The radial bins are only reused for profiles with the
+/// same center and bins, like several profiles of the same map.
+#[derive(Default)]
+pub struct RadialBinning {
+    options: DensityProfileOptions,
+    grid: Option<BinPositions>,
+    bins: Option<RadialBins>,
+}
+
+/// Positions of the bins of a grid relative to its origin, in the order of the map data.
+struct BinPositions {
+    bin_size: Vec3,
+    origin: Vec2,
+    shape: Shape,
+    positions: Vec<Vec2>,
+}
+
+impl BinPositions {
+    fn new(densmap: &DensMap) -> Self {
+        let [dx, dy, _] = densmap.bin_size;
+
+        BinPositions {
+            bin_size: densmap.bin_size,
+            origin: densmap.origin,
+            shape: densmap.shape,
+            positions: densmap
+                .iter_bins()
+                .map(|(ix, iy, _, _, _)| [dx * ix as f64, dy * iy as f64])
+                .collect(),
+        }
+    }
+
+    fn is_grid_of(&self, densmap: &DensMap) -> bool {
+        self.bin_size == densmap.bin_size
+            && self.origin == densmap.origin
+            && self.shape == densmap.shape
+    }
 
-    Histogram {
-        x: radius,
-        y: scaled_histogram,
+    /// Get the distance of every bin to a center in absolute coordinates.
+    fn get_distances(&self, center: Vec2) -> Vec<f64> {
+        let [xmin, ymin] = self.origin;
+        let x0 = center[0] - xmin;
+        let y0 = center[1] - ymin;
+
+        self.positions
+            .iter()
+            .map(|[x, y]| ((x0 - x).powi(2) + (y0 - y).powi(2)).sqrt())
+            .collect()
+    }
+}
+
+struct RadialBins {
+    center: Vec2,
+    rmin: f64,
+    dr: f64,
+    min_samples: Option<usize>,
+    radius: Vec<f64>,
+    /// Edges of the radial bins, which are one more than the bins.
+    edges: Vec<f64>,
+    /// Number of map bins in every radial bin.
+    counts: Vec<usize>,
+    /// Index of the radial bin of every map bin, where bins which are excluded
+    /// from the histogram have the index `radius.len()`.
+    indices: Vec<usize>,
+}
+
+impl RadialBinning {
+    pub fn new() -> Self {
+        RadialBinning::default()
+    }
+
+    /// Create a binning with options for the radial density distribution and the other
+    /// radial density profiles.
+    pub fn with_options(options: DensityProfileOptions) -> Self {
+        RadialBinning {
+            options,
+            grid: None,
+            bins: None,
+        }
+    }
+
+    /// Get the options of the radial density profiles of the binning.
+    pub fn options(&self) -> DensityProfileOptions {
+        self.options
+    }
+
+    /// Compute the radial density distribution of the map, see the free function
+    /// `get_radial_density_distribution` for details.
+    pub fn get_radial_density_distribution(&mut self, densmap: &DensMap) -> Histogram {
+        let options = self.get_density_profile_options(RadialReduction::Sum);
+
+        let histogram = self.get_profile(densmap, &densmap.data, &options);
+        let bins = self.bins.as_ref().unwrap();
+
+        let scaled_histogram = if options.min_samples.is_some() {
+            scale_histogram_by_shell_area(&histogram.y, &bins.edges, bins.dr)
+        } else {
+            scale_histogram_to_per_unit_length(&histogram.y, &histogram.x)
+        };
+
+        Histogram {
+            y: scaled_histogram,
+            ..histogram
+        }
+    }
+
+    /// Compute the fraction of bins with a density above the cutoff at every radius,
+    /// see the free function `get_radial_occupancy` for details.
+    pub fn get_radial_occupancy(&mut self, densmap: &DensMap, cutoff: f64) -> Histogram {
+        let options = self.get_density_profile_options(RadialReduction::Mean);
+        self.get_radial_profile(densmap, |_, _, v| (v > cutoff) as u8 as f64, &options)
+    }
+
+    /// Compute the mean density of the bins at every radius, see the free function
+    /// `get_radial_mean_density` for details.
+    pub fn get_radial_mean_density(&mut self, densmap: &DensMap) -> Histogram {
+        let options = self.get_density_profile_options(RadialReduction::Mean);
+        self.get_profile(densmap, &densmap.data, &options)
+    }
+
+    /// Get the edges of the radial bins of the density profiles of the map, which
+    /// are one more than the bins.
+    pub fn get_radial_density_bin_edges(&mut self, densmap: &DensMap) -> Vec<f64> {
+        let options = self.get_density_profile_options(RadialReduction::Sum);
+        self.get_bin_edges(densmap, &options)
+    }
+
+    /// Get the edges of the radial bins of profiles of the map with the options,
+    /// which are one more than the bins.
+    pub fn get_bin_edges(&mut self, densmap: &DensMap, options: &RadialProfileOptions) -> Vec<f64> {
+        self.get_bins(densmap, options).edges.clone()
+    }
+
+    /// Compute the radial profile of a quantity of every bin in the map, see the free
+    /// function `radial_profile` for details.
+    pub fn get_radial_profile<F>(
+        &mut self,
+        densmap: &DensMap,
+        field_fn: F,
+        options: &RadialProfileOptions,
+    ) -> Histogram
+    where
+        F: Fn(usize, usize, f64) -> f64,
+    {
+        let values = densmap
+            .iter_bins()
+            .map(|(ix, iy, _, _, v)| field_fn(ix, iy, v))
+            .collect::<Vec<_>>();
+
+        self.get_profile(densmap, &values, options)
+    }
+
+    /// Get the options of radial profiles of the density, which exclude the noisy bins
+    /// within 1 nm of the droplet center unless the bins are widened to a minimum
+    /// number of map bins, see `DensityProfileOptions`.
+    fn get_density_profile_options(&self, reduction: RadialReduction) -> RadialProfileOptions {
+        match self.options.min_samples {
+            Some(min_samples) => RadialProfileOptions {
+                min_samples: Some(min_samples),
+                reduction,
+                ..Default::default()
+            },
+            None => RadialProfileOptions {
+                rmin: self.options.length_unit.convert_nm(1.0),
+                reduction,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Combine values in the order of the map data into the radial bins of the map.
+    fn get_profile(
+        &mut self,
+        densmap: &DensMap,
+        values: &[f64],
+        options: &RadialProfileOptions,
+    ) -> Histogram {
+        let bins = self.get_bins(densmap, options);
+        let mut histogram = get_radial_mass_sum(values, &bins.indices, bins.radius.len());
+
+        if options.reduction == RadialReduction::Mean {
+            histogram
+                .iter_mut()
+                .zip(bins.counts.iter())
+                .for_each(|(v, &count)| *v /= count as f64);
+        }
+
+        Histogram {
+            x: bins.radius.clone(),
+            y: histogram,
+            yerr: None,
+        }
+    }
+
+    /// Get the radial bins of the map, calculating them unless they are those of the
+    /// last profile.
+    fn get_bins(&mut self, densmap: &DensMap, options: &RadialProfileOptions) -> &RadialBins {
+        let center = options.center.unwrap_or(densmap.center);
+        let rmin = options.rmin;
+        let dr = options
+            .dr
+            .unwrap_or_else(|| 0.5 * (densmap.bin_size[0] + densmap.bin_size[1]));
+
+        let is_same_grid = self
+            .grid
+            .as_ref()
+            .map(|grid| grid.is_grid_of(densmap))
+            .unwrap_or(false);
+
+        if !is_same_grid {
+            self.grid = Some(BinPositions::new(densmap));
+            self.bins = None;
+        }
+
+        let is_same_bins = self
+            .bins
+            .as_ref()
+            .map(|bins| {
+                bins.center == center
+                    && bins.rmin == rmin
+                    && bins.dr == dr
+                    && bins.min_samples == options.min_samples
+            })
+            .unwrap_or(false);
+
+        if !is_same_bins {
+            let distances = self.grid.as_ref().unwrap().get_distances(center);
+
+            let mut radius = get_radius_values_for_histogram(densmap, center, rmin, dr);
+            let mut indices = get_radial_bin_indices(&distances, rmin, dr, radius.len());
+            let mut counts = get_bin_counts(&indices, radius.len());
+            let mut edges = radius.clone();
+            edges.push(rmin + dr * radius.len() as f64);
+
+            if let Some(min_samples) = options.min_samples {
+                let (merged, num_merged) = get_merged_bin_indices(&counts, min_samples);
+
+                indices.iter_mut().for_each(|n| {
+                    *n = merged.get(*n).cloned().unwrap_or(num_merged);
+                });
+                counts = get_bin_counts(&indices, num_merged);
+
+                // The inner edge of every merged bin is that of its first bin.
+                let outer = edges.pop().unwrap();
+                edges = (0..merged.len())
+                    .filter(|&i| i == 0 || merged[i] != merged[i - 1])
+                    .map(|i| edges[i])
+                    .chain(std::iter::once(outer))
+                    .collect();
+                radius = edges[..num_merged].to_vec();
+            }
+
+            self.bins = Some(RadialBins {
+                center,
+                rmin,
+                dr,
+                min_samples: options.min_samples,
+                radius,
+                edges,
+                counts,
+                indices,
+            });
+        }
+
+        self.bins.as_ref().unwrap()
+    }
+}
+
+/// Get an azimuthally symmetric map around the droplet center, in which every bin holds
+/// the mean density of all bins at the same radius.
+///
+/// Bins are grouped into radial shells of width equal to the mean bin size, starting
+/// from the center.
+pub fn get_azimuthal_average_map(densmap: &DensMap) -> DensMap {
+    let options = RadialProfileOptions {
+        reduction: RadialReduction::Mean,
+        ..Default::default()
+    };
+
+    let mut binning = RadialBinning::new();
+    let profile = binning.get_profile(densmap, &densmap.data, &options);
+
+    // No bins are excluded since the profile begins at the center and extends to the
+    // furthest corner of the map.
+    let bins = binning.bins.as_ref().unwrap();
+
+    DensMap {
+        data: bins.indices.iter().map(|&n| profile.y[n]).collect(),
+        ..densmap.clone()
     }
 }
 
@@ -65,11 +460,7 @@ fn cut_bins_below_percentage_of_max(values: &[f64], perc: f64) -> Vec<f64> {
     let max = values.iter().fold(0.0, |acc: f64, &v| acc.max(v));
     let cutoff = 0.01 * perc * max;
 
-    values
-        .into_iter()
-        .cloned()
-        .filter(|&v| v >= cutoff)
-        .collect()
+    values.iter().cloned().filter(|&v| v >= cutoff).collect()
 }
 
 /// # Notes
@@ -84,13 +475,13 @@ fn get_percentile_values<T: Copy + PartialOrd>(
             "cannot compute percentile values from an empty array",
         ));
     }
-    if lower < 0.0 || lower > 100.0 {
+    if !(0.0..=100.0).contains(&lower) {
         return Err(format!(
             "lower percentile value must be between 0 and 100, was {}",
             lower
         ));
     }
-    if upper < 0.0 || upper > 100.0 {
+    if !(0.0..=100.0).contains(&upper) {
         return Err(format!(
             "upper percentile value must be between 0 and 100, was {}",
             upper
@@ -103,72 +494,161 @@ fn get_percentile_values<T: Copy + PartialOrd>(
             .expect("could not compare two values when calculating percentile values")
     });
 
-    let ilower = (0.01 * lower * sorted_values.len() as f64).round() as usize;
-    let iupper = (0.01 * upper * sorted_values.len() as f64).round() as usize;
+    // Rounding can give the index past the last value for high percentiles.
+    let imax = sorted_values.len() - 1;
+    let ilower = ((0.01 * lower * sorted_values.len() as f64).round() as usize).min(imax);
+    let iupper = ((0.01 * upper * sorted_values.len() as f64).round() as usize).min(imax);
 
     Ok((sorted_values[ilower], sorted_values[iupper]))
 }
 
-fn get_radius_values_for_histogram(densmap: &DensMap) -> (f64, f64, Vec<f64>) {
-    let [dx, dy, _] = densmap.bin_size;
-
-    let dr = 0.5 * (dx + dy);
-    let rmin = 1.0;
-    let rmax = calc_maximum_radius(&densmap);
-
-    let num_values = ((rmax - rmin) / dr) as usize;
-    let values = (0..=num_values).map(|n| rmin + dr * n as f64).collect();
-
-    (rmin, dr, values)
-}
-
-fn get_radial_mass_sum_of_densmap(
+/// Get the inner radius of every radial bin from `rmin` to the furthest away bin from
+/// the center.
+fn get_radius_values_for_histogram(
     densmap: &DensMap,
+    center: Vec2,
     rmin: f64,
     dr: f64,
-    num_bins: usize,
 ) -> Vec<f64> {
-    // Adjust the center coordinates to be relative to the bins, instead of adjusting
-    // the bin coordinates. Those coordinates would be shifted every iteration, which
-    // is unnecessary since the relative distance to the center is all we are interested in.
-    let [dx, dy, _] = densmap.bin_size;
-    let [xmin, ymin] = densmap.origin;
-    let x0 = densmap.center[0] - xmin;
-    let y0 = densmap.center[1] - ymin;
+    let rmax = calc_maximum_radius(densmap, center);
 
-    let mut histogram = vec![0.0; num_bins];
+    let num_values = ((rmax - rmin).max(0.0) / dr) as usize;
+    (0..=num_values).map(|n| rmin + dr * n as f64).collect()
+}
 
-    densmap
-        .data
+/// Get the index of the radial bin of every bin in the map from their distances to the
+/// center, in the order of the map data.
+///
+/// Bins which are closer to the center than `rmin` are excluded from the histogram
+/// by setting their index to `num_bins`, since they are noisy for the density.
+fn get_radial_bin_indices(distances: &[f64], rmin: f64, dr: f64, num_bins: usize) -> Vec<usize> {
+    distances
         .iter()
-        .enumerate()
-        // Get the 2D position of the bin from its 1D index.
-        .map(|(i, v)| (index2tuple(i, densmap.shape).unwrap(), v))
-        // Convert to system coordinates.
-        .map(|((ix, iy), v)| ((dx * ix as f64, dy * iy as f64), v))
-        // Calculate distance to center.
-        .map(|((x, y), v)| (((x0 - x).powi(2) + (y0 - y).powi(2)).sqrt(), v))
-        // Exclude points that are too close to the center, they're noisy.
-        .filter(|(r, _)| r >= &rmin)
-        // Add the value to the histogram at the radius.
-        .for_each(|(r, v)| {
-            let n = ((r - rmin) / dr) as usize;
-            histogram[n] += v;
-        });
+        .map(|&r| {
+            if r >= rmin {
+                (((r - rmin) / dr) as usize).min(num_bins)
+            } else {
+                num_bins
+            }
+        })
+        .collect()
+}
+
+/// Count the map bins in every radial bin by their radial bin indices, leaving out
+/// the excluded bins.
+fn get_bin_counts(indices: &[usize], num_bins: usize) -> Vec<usize> {
+    let mut counts = vec![0; num_bins + 1];
+    indices.iter().for_each(|&n| counts[n] += 1);
+    counts.truncate(num_bins);
+    counts
+}
+
+/// Merge consecutive radial bins from the inside out until every merged bin has at
+/// least `min_samples` map bins, returning the index of the merged bin of every bin
+/// along with the number of merged bins.
+///
+/// The outermost bins which do not reach the minimum are merged into the last bin
+/// which does, or into a single bin if none does.
+fn get_merged_bin_indices(counts: &[usize], min_samples: usize) -> (Vec<usize>, usize) {
+    let mut merged = Vec::with_capacity(counts.len());
+    let (mut n, mut sum) = (0, 0);
+
+    for &count in counts {
+        merged.push(n);
+        sum += count;
+
+        if sum >= min_samples {
+            n += 1;
+            sum = 0;
+        }
+    }
+
+    let mut num_merged = n;
+
+    if merged.last() == Some(&n) {
+        if n > 0 {
+            merged
+                .iter_mut()
+                .filter(|m| **m == n)
+                .for_each(|m| *m = n - 1);
+        } else {
+            num_merged = 1;
+        }
+    }
+
+    (merged, num_merged)
+}
+
+/// Sum the values of all bins into the radial histogram by their radial bin indices.
+///
+/// Chunks of bins are accumulated into partial histograms in parallel, which are then
+/// merged. Every partial histogram has an extra bin which excluded bins are added to,
+/// to keep the inner loop free of branches.
+#[cfg(feature = "parallel")]
+fn get_radial_mass_sum(data: &[f64], indices: &[usize], num_bins: usize) -> Vec<f64> {
+    let mut histogram = data
+        .par_chunks(HISTOGRAM_CHUNK_SIZE)
+        .zip(indices.par_chunks(HISTOGRAM_CHUNK_SIZE))
+        .fold(
+            || vec![0.0; num_bins + 1],
+            |mut histogram, (values, indices)| {
+                for (&v, &n) in values.iter().zip(indices.iter()) {
+                    histogram[n] += v;
+                }
+                histogram
+            },
+        )
+        .reduce(
+            || vec![0.0; num_bins + 1],
+            |mut histogram, partial| {
+                histogram
+                    .iter_mut()
+                    .zip(partial.iter())
+                    .for_each(|(v, w)| *v += w);
+                histogram
+            },
+        );
+
+    histogram.truncate(num_bins);
+    histogram
+}
+
+/// Sum the values of all bins into the radial histogram by their radial bin indices,
+/// with an extra bin which excluded bins are added to.
+#[cfg(not(feature = "parallel"))]
+fn get_radial_mass_sum(data: &[f64], indices: &[usize], num_bins: usize) -> Vec<f64> {
+    let mut histogram = vec![0.0; num_bins + 1];
 
+    for (&v, &n) in data.iter().zip(indices.iter()) {
+        histogram[n] += v;
+    }
+
+    histogram.truncate(num_bins);
     histogram
 }
 
+/// Scale the histogram to the mass per length of the circumference by dividing every bin
+/// by `2πr` at its radius `r`.
 fn scale_histogram_to_per_unit_length(histogram: &[f64], radius: &[f64]) -> Vec<f64> {
     histogram
         .iter()
         .zip(radius.iter())
-        .map(|(v, r)| v / (2.0 * std::f64::consts::PI * r))
+        .map(|(v, r)| v / (2.0 * PI * r))
+        .collect()
+}
+
+/// Scale the histogram of radial bins of any width to the mass per length of the
+/// circumference of a shell of width `dr`, from the area of every bin between its edges.
+fn scale_histogram_by_shell_area(histogram: &[f64], edges: &[f64], dr: f64) -> Vec<f64> {
+    histogram
+        .iter()
+        .zip(edges.windows(2))
+        .map(|(v, r)| v * dr / (PI * (r[1].powi(2) - r[0].powi(2))))
         .collect()
 }
 
 /// Calculate the distance from the fitted droplet to the furthest away bin in the system.
-fn calc_maximum_radius(densmap: &DensMap) -> f64 {
+fn calc_maximum_radius(densmap: &DensMap, center: Vec2) -> f64 {
     let [dx, dy, _] = densmap.bin_size;
     let [nx, ny] = densmap.shape;
 
@@ -176,7 +656,7 @@ fn calc_maximum_radius(densmap: &DensMap) -> f64 {
     let xmax = xmin + dx * nx as f64;
     let ymax = ymin + dy * ny as f64;
 
-    let [x0, y0] = densmap.center;
+    let [x0, y0] = center;
 
     let mut rmax2 = (x0 - xmin).powi(2) + (y0 - ymin).powi(2);
     rmax2 = rmax2.max((x0 - xmin).powi(2) + (y0 - ymax).powi(2));
@@ -187,6 +667,192 @@ fn calc_maximum_radius(densmap: &DensMap) -> f64 {
 }
 
 #[test]
+fn test_azimuthal_average_map_averages_bins_at_same_radius() {
+    let densmap = DensMap {
+        bin_size: [1.0, 1.0, 0.0],
+        origin: [0.0, 0.0],
+        shape: [3, 3],
+        center: [1.0, 1.0],
+        data: vec![0.0, 1.0, 0.0, 2.0, 5.0, 3.0, 4.0, 4.0, 8.0],
+    };
+
+    let average = get_azimuthal_average_map(&densmap);
+
+    // The bins at distances 1 and sqrt(2) from the center fall into the same shell.
+    assert_eq!(
+        vec![2.75, 2.75, 2.75, 2.75, 5.0, 2.75, 2.75, 2.75, 2.75],
+        average.data
+    );
+}
+
+#[test]
+fn test_radial_mass_sum_adds_bins_at_same_radius() {
+    let densmap = DensMap {
+        bin_size: [1.0, 1.0, 0.0],
+        origin: [0.0, 0.0],
+        shape: [50, 50],
+        center: [25.0, 25.0],
+        data: vec![1.0; 2500],
+    };
+
+    let num_bins = 35;
+    let distances = BinPositions::new(&densmap).get_distances(densmap.center);
+    let indices = get_radial_bin_indices(&distances, 1.0, 1.0, num_bins);
+    let histogram = get_radial_mass_sum(&densmap.data, &indices, num_bins);
+
+    assert_eq!(num_bins, histogram.len());
+
+    // The bins at distance 1 and sqrt(2) from the center
+    assert_eq!(8.0, histogram[0]);
+
+    // All bins except the center one are included
+    assert_eq!(2499.0, histogram.iter().sum::<f64>());
+}
+
+#[test]
+fn test_radial_binning_is_recalculated_when_center_changes() {
+    let densmap = DensMap {
+        bin_size: [1.0, 1.0, 0.0],
+        origin: [0.0, 0.0],
+        shape: [20, 20],
+        center: [10.0, 10.0],
+        data: (0..400).map(|i| (i % 7) as f64).collect(),
+    };
+
+    let moved = DensMap {
+        center: [6.0, 8.0],
+        ..densmap.clone()
+    };
+
+    let mut binning = RadialBinning::new();
+
+    for densmap in [&densmap, &moved, &densmap] {
+        let histogram = binning.get_radial_density_distribution(densmap);
+        let radius = get_radius_values_for_histogram(densmap, densmap.center, 1.0, 1.0);
+        let distances = BinPositions::new(densmap).get_distances(densmap.center);
+        let indices = get_radial_bin_indices(&distances, 1.0, 1.0, radius.len());
+        let expected = get_radial_mass_sum(&densmap.data, &indices, radius.len());
+
+        assert_eq!(radius, histogram.x);
+        assert_eq!(
+            scale_histogram_to_per_unit_length(&expected, &radius),
+            histogram.y
+        );
+    }
+}
+
+#[test]
+fn test_radial_profile_of_occupancy_around_other_center() {
+    let densmap = DensMapBuilder::new()
+        .bin_size([1.0, 1.0, 0.0])
+        .shape([3, 3])
+        .center([1.0, 1.0])
+        .from_data(vec![0.0, 1.0, 0.0, 2.0, 5.0, 3.0, 4.0, 4.0, 8.0])
+        .unwrap();
+
+    // Profiles are taken around the lower left bin, with radial bins out to the
+    // upper right corner of the map.
+    let options = RadialProfileOptions {
+        center: Some([0.0, 0.0]),
+        dr: Some(1.0),
+        ..Default::default()
+    };
+
+    let occupancy = radial_profile(&densmap, |_, _, v| (v > 0.0) as u8 as f64, &options);
+
+    assert_eq!(vec![0.0, 1.0, 2.0, 3.0, 4.0], occupancy.x);
+    assert_eq!(vec![0.0, 3.0, 4.0, 0.0, 0.0], occupancy.y);
+
+    let mean = radial_profile(
+        &densmap,
+        |_, _, v| v,
+        &RadialProfileOptions {
+            reduction: RadialReduction::Mean,
+            rmin: 1.0,
+            ..options
+        },
+    );
+
+    // The bin at the center is excluded and the outer radial bins are empty.
+    assert_eq!(vec![1.0, 2.0, 3.0, 4.0], mean.x);
+    assert_eq!(&[8.0 / 3.0, 19.0 / 5.0], &mean.y[..2]);
+    assert!(mean.y[2..].iter().all(|v| v.is_nan()));
+}
+
+#[test]
+fn test_occupancy_and_mean_density_are_not_diluted_by_map_edges() {
+    // A droplet of density 2 with radius 10 whose center lies on the left edge of
+    // the map, such that only half of every shell is inside of it.
+    let densmap = DensMapBuilder::new()
+        .bin_size([1.0, 1.0, 0.0])
+        .shape([30, 40])
+        .center([0.0, 20.0])
+        .from_fn(|x, y| {
+            if x.powi(2) + (y - 20.0).powi(2) < 100.0 {
+                2.0
+            } else {
+                0.0
+            }
+        });
+
+    let occupancy = get_radial_occupancy(&densmap, 0.0);
+    let mean_density = get_radial_mean_density(&densmap);
+    let radial_density = get_radial_density_distribution(&densmap);
+
+    assert_eq!(occupancy.x, mean_density.x);
+    assert_eq!(radial_density.x, mean_density.x);
+
+    // Shells well inside of the droplet are fully occupied with the bulk density,
+    // while those well outside of it are empty. The mass per circumference is only
+    // about half of the bulk density, since half of every shell is outside the map.
+    for (i, &x) in occupancy.x.iter().enumerate() {
+        if x < 8.0 {
+            assert_eq!((1.0, 2.0), (occupancy.y[i], mean_density.y[i]));
+
+            // The innermost shells are wider than their circumference.
+            if x >= 4.0 {
+                assert!(radial_density.y[i] < 1.5);
+            }
+        } else if x > 11.0 && x + 1.0 < 30.0 {
+            assert_eq!((0.0, 0.0), (occupancy.y[i], mean_density.y[i]));
+        }
+    }
+}
+
+#[test]
+fn test_radial_bins_are_merged_until_they_have_minimum_samples() {
+    assert_eq!(
+        (vec![0, 0, 0, 1, 2, 2, 2], 3),
+        get_merged_bin_indices(&[1, 2, 3, 6, 4, 2, 1], 6)
+    );
+    assert_eq!((vec![0, 0], 1), get_merged_bin_indices(&[1, 2], 6));
+
+    let densmap = DensMapBuilder::new()
+        .bin_size([1.0, 1.0, 0.0])
+        .shape([20, 20])
+        .uniform(1.0);
+
+    let options = RadialProfileOptions {
+        min_samples: Some(20),
+        ..Default::default()
+    };
+
+    let mut binning = RadialBinning::new();
+    let counts = binning.get_radial_profile(&densmap, |_, _, _| 1.0, &options);
+    let edges = binning.get_bin_edges(&densmap, &options);
+
+    // The center bin is included and the innermost bins are widened.
+    assert_eq!(0.0, edges[0]);
+    assert_eq!(counts.x.len() + 1, edges.len());
+    assert_eq!(&edges[..counts.x.len()], counts.x.as_slice());
+    assert!(edges[1] > 1.0);
+
+    assert!(counts.y.iter().all(|&count| count >= 20.0));
+    assert_eq!(400.0, counts.y.iter().sum::<f64>());
+}
+
+#[test]
+#[allow(clippy::useless_vec)]
 fn test_cut_bins_below_50_percent_of_max() {
     assert_eq!(
         vec![4.1, 5.0, 4.5, 8.0],
@@ -214,3 +880,47 @@ fn test_getting_invalid_percentiles_returns_error() {
     assert!(get_percentile_values(&values, 101.0, 0.0).is_err());
     assert!(get_percentile_values(&values, 0.0, 101.0).is_err());
 }
+
+#[test]
+fn test_binning_with_min_samples_widens_the_density_profile_bins() {
+    let densmap = DensMapBuilder::new()
+        .bin_size([0.1, 0.1, 0.0])
+        .shape([40, 40])
+        .uniform(1.0);
+
+    // Without a minimum the bins within 1 nm of the center are excluded.
+    let mut binning = RadialBinning::new();
+    assert_eq!(None, binning.options().min_samples);
+    assert!((binning.get_radial_density_distribution(&densmap).x[0] - 1.0).abs() < 1e-9);
+
+    let options = DensityProfileOptions {
+        min_samples: Some(20),
+        ..Default::default()
+    };
+    let mut binning = RadialBinning::with_options(options);
+    assert_eq!(options, binning.options());
+
+    let edges = binning.get_radial_density_bin_edges(&densmap);
+    assert_eq!(0.0, edges[0]);
+    assert_eq!(0.0, binning.get_radial_mean_density(&densmap).x[0]);
+}
+
+#[test]
+fn test_excluded_distance_from_center_is_converted_to_the_length_unit() {
+    // The same map in Å, in which the bins within 10 Å of the center are excluded.
+    let densmap = DensMapBuilder::new()
+        .bin_size([1.0, 1.0, 0.0])
+        .shape([40, 40])
+        .uniform(1.0);
+
+    let mut binning = RadialBinning::with_options(DensityProfileOptions {
+        length_unit: LengthUnit::Angstrom,
+        ..Default::default()
+    });
+
+    assert_eq!(10.0, binning.get_radial_density_distribution(&densmap).x[0]);
+    assert_eq!(
+        1.0,
+        RadialBinning::new().get_radial_mean_density(&densmap).x[0]
+    );
+}
@@ -0,0 +1,150 @@
+use crate::{
+    densmap::Vec2,
+    flow::FlowMap,
+    graphdata::{Graph, Histogram, XYData},
+};
+
+/// Get the radial velocity of the flow at the contact line for every angle of it.
+///
+/// The radial velocity is positive outwards from the droplet center. The contact line
+/// should have absolute radii, as returned by `sample_interface`. Angles at which the
+/// contact line lies outside of the flow map are skipped.
+pub fn get_contact_line_radial_velocity(
+    flow: &FlowMap,
+    center: Vec2,
+    contact_line: &Graph,
+) -> Graph {
+    let contact_line = contact_line.to_polar();
+
+    let (x, y) = contact_line
+        .x()
+        .iter()
+        .zip(contact_line.y().iter())
+        .filter_map(|(&angle, &radius)| {
+            get_radial_velocity(flow, center, angle, radius).map(|v| (angle, v))
+        })
+        .unzip();
+
+    Graph::Carthesian { x, y, yerr: None }
+}
+
+/// Get the profile of the radial velocity of the flow across the contact line, averaged
+/// over all angles.
+///
+/// The velocity is sampled along the radial ray of every angle within `width` of the
+/// contact line, in steps of the smallest bin size of the flow map. The distance from
+/// the contact line is negative inside of the droplet. Distances at which no sample
+/// lies inside of the flow map are left out of the profile.
+pub fn get_radial_velocity_profile(
+    flow: &FlowMap,
+    center: Vec2,
+    contact_line: &Graph,
+    width: f64,
+) -> Histogram {
+    let contact_line = contact_line.to_polar();
+
+    let [dx, dy, _] = flow.bin_size;
+    let dr = dx.min(dy);
+    let num_steps = (width / dr).floor() as isize;
+    let num_bins = (2 * num_steps + 1) as usize;
+
+    let mut sums = vec![0.0; num_bins];
+    let mut counts = vec![0; num_bins];
+
+    for (&angle, &radius) in contact_line.x().iter().zip(contact_line.y().iter()) {
+        for (n, step) in (-num_steps..=num_steps).enumerate() {
+            let r = radius + dr * step as f64;
+
+            if let Some(v) = get_radial_velocity(flow, center, angle, r) {
+                sums[n] += v;
+                counts[n] += 1;
+            }
+        }
+    }
+
+    let (x, y) = (-num_steps..=num_steps)
+        .zip(sums.iter().zip(counts.iter()))
+        .filter(|(_, (_, &count))| count > 0)
+        .map(|(step, (sum, &count))| (dr * step as f64, sum / count as f64))
+        .unzip();
+
+    Histogram { x, y, yerr: None }
+}
+
+/// Get the radial component of the velocity at a radius along an angle (in degrees)
+/// from the center, or `None` if the point lies outside of the flow map.
+fn get_radial_velocity(flow: &FlowMap, center: Vec2, angle: f64, radius: f64) -> Option<f64> {
+    if radius < 0.0 {
+        return None;
+    }
+
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let [xc, yc] = center;
+
+    flow.velocity_at(xc + radius * cos, yc + radius * sin)
+        .map(|[vx, vy]| vx * cos + vy * sin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flow which moves radially outwards from the center with a speed which increases
+    /// linearly with the radius, u_r = r.
+    fn get_radial_flow() -> (FlowMap, Vec2) {
+        let (nx, ny) = (20, 20);
+        let center = [5.0, 5.0];
+
+        let (vx, vy) = (0..ny)
+            .flat_map(|iy| (0..nx).map(move |ix| (ix, iy)))
+            .map(|(ix, iy)| {
+                let x = 0.5 * (ix as f64 + 0.5) - center[0];
+                let y = 0.5 * (iy as f64 + 0.5) - center[1];
+                (x, y)
+            })
+            .unzip();
+
+        let flow = FlowMap {
+            bin_size: [0.5, 0.5, 0.0],
+            origin: [0.0, 0.0],
+            shape: [nx, ny],
+            vx,
+            vy,
+        };
+
+        (flow, center)
+    }
+
+    fn get_circle(radius: f64) -> Graph {
+        let angles = (0..8).map(|i| 45.0 * i as f64).collect::<Vec<_>>();
+
+        Graph::Polar {
+            radius: vec![radius; angles.len()],
+            angles,
+            yerr: None,
+        }
+    }
+
+    #[test]
+    fn test_radial_velocity_at_contact_line_is_sampled_for_every_angle() {
+        let (flow, center) = get_radial_flow();
+        let velocity = get_contact_line_radial_velocity(&flow, center, &get_circle(2.0));
+
+        assert_eq!(8, velocity.x().len());
+        assert!(velocity.y().iter().all(|&v| (v - 2.0).abs() < 0.5));
+
+        // Outside of the map the contact line has no velocity.
+        let outside = get_contact_line_radial_velocity(&flow, center, &get_circle(20.0));
+        assert!(outside.x().is_empty());
+    }
+
+    #[test]
+    fn test_radial_velocity_profile_increases_across_contact_line() {
+        let (flow, center) = get_radial_flow();
+        let profile = get_radial_velocity_profile(&flow, center, &get_circle(2.0), 1.0);
+
+        assert_eq!(vec![-1.0, -0.5, 0.0, 0.5, 1.0], profile.x);
+        assert!(profile.y.windows(2).all(|w| w[1] > w[0]));
+        assert!((profile.y[2] - 2.0).abs() < 0.5);
+    }
+}
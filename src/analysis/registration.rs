@@ -0,0 +1,169 @@
+use crate::{
+    densmap::{DensMap, Vec2},
+    parallel::*,
+};
+
+/// Estimate the translation of a density map relative to a reference map, by finding
+/// the shift which maximizes the cross-correlation between them.
+///
+/// Integer bin shifts up to `max_shift` bins along x and y are searched, after which
+/// the maximum is refined to sub-bin precision by fitting parabolas through the
+/// neighbouring shifts. For every shift the correlation coefficient is calculated
+/// over the bins which overlap between the maps.
+///
+/// # Error
+/// Returns an error if the maps do not have identical shapes and bin sizes.
+pub fn estimate_translation(
+    reference: &DensMap,
+    densmap: &DensMap,
+    max_shift: usize,
+) -> Result<Vec2, String> {
+    if reference.shape != densmap.shape || reference.bin_size != densmap.bin_size {
+        return Err(format!(
+            "cannot register map with shape {:?} against shape {:?}",
+            densmap.shape, reference.shape
+        ));
+    }
+
+    let [nx, ny] = densmap.shape;
+    let (nx, ny) = (nx as isize, ny as isize);
+    let m = max_shift as isize;
+
+    // Pearson correlation coefficient of the bins which overlap after the shift
+    let correlation = |sx: isize, sy: isize| {
+        let pairs = (0.max(-sy)..ny.min(ny - sy))
+            .flat_map(|iy| (0.max(-sx)..nx.min(nx - sx)).map(move |ix| (ix, iy)))
+            .map(|(ix, iy)| {
+                (
+                    reference.data[(iy * nx + ix) as usize],
+                    densmap.data[((iy + sy) * nx + ix + sx) as usize],
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let n = pairs.len() as f64;
+        let mean_a = pairs.iter().map(|(a, _)| a).sum::<f64>() / n;
+        let mean_b = pairs.iter().map(|(_, b)| b).sum::<f64>() / n;
+
+        let (sab, saa, sbb) = pairs
+            .iter()
+            .fold((0.0, 0.0, 0.0), |(sab, saa, sbb), (a, b)| {
+                let (a, b) = (a - mean_a, b - mean_b);
+                (sab + a * b, saa + a * a, sbb + b * b)
+            });
+
+        // Overlaps without variation or with values which are not finite never match.
+        let value = sab / (saa * sbb).sqrt();
+        if pairs.is_empty() || !value.is_finite() {
+            f64::NEG_INFINITY
+        } else {
+            value
+        }
+    };
+
+    let shifts = (-m..=m)
+        .flat_map(|sy| (-m..=m).map(move |sx| (sx, sy)))
+        .collect::<Vec<_>>();
+
+    let values = shifts
+        .par_iter()
+        .map(|&(sx, sy)| correlation(sx, sy))
+        .collect::<Vec<_>>();
+
+    let (imax, &cmax) = values
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap();
+    let (sx, sy) = shifts[imax];
+
+    let value_at = |sx: isize, sy: isize| {
+        if sx.abs() <= m && sy.abs() <= m {
+            Some(values[((sy + m) * (2 * m + 1) + sx + m) as usize])
+        } else {
+            None
+        }
+    };
+
+    let refine = |c0: Option<f64>, c2: Option<f64>| match (c0, c2) {
+        (Some(c0), Some(c2)) => {
+            let denom = c0 - 2.0 * cmax + c2;
+            if denom < 0.0 && denom.is_finite() {
+                0.5 * (c0 - c2) / denom
+            } else {
+                0.0
+            }
+        }
+        _ => 0.0,
+    };
+
+    let fx = sx as f64 + refine(value_at(sx - 1, sy), value_at(sx + 1, sy));
+    let fy = sy as f64 + refine(value_at(sx, sy - 1), value_at(sx, sy + 1));
+
+    let [dx, dy, _] = densmap.bin_size;
+
+    Ok([fx * dx, fy * dy])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_blob_densmap(x0: f64, y0: f64) -> DensMap {
+        let shape = [20, 20];
+
+        let data = (0..400)
+            .map(|i| {
+                let x = 0.5 * (i % 20) as f64;
+                let y = 0.5 * (i / 20) as f64;
+                (-((x - x0).powi(2) + (y - y0).powi(2)) / 2.0).exp()
+            })
+            .collect();
+
+        DensMap {
+            bin_size: [0.5, 0.5, 0.0],
+            origin: [0.0, 0.0],
+            shape,
+            center: [5.0, 5.0],
+            data,
+        }
+    }
+
+    #[test]
+    fn test_translation_of_shifted_blob_is_found() {
+        let reference = get_blob_densmap(5.0, 5.0);
+
+        let densmap = get_blob_densmap(6.0, 4.5);
+        let [dx, dy] = estimate_translation(&reference, &densmap, 4).unwrap();
+        assert!((dx - 1.0).abs() < 0.1, "dx was {}", dx);
+        assert!((dy + 0.5).abs() < 0.1, "dy was {}", dy);
+
+        let densmap = get_blob_densmap(5.25, 5.0);
+        let [dx, dy] = estimate_translation(&reference, &densmap, 4).unwrap();
+        assert!((dx - 0.25).abs() < 0.1, "dx was {}", dx);
+        assert!(dy.abs() < 0.1, "dy was {}", dy);
+    }
+
+    #[test]
+    fn test_shifts_with_values_which_are_not_finite_in_the_overlap_are_skipped() {
+        let reference = get_blob_densmap(5.0, 5.0);
+
+        let mut densmap = get_blob_densmap(6.0, 4.5);
+        densmap.data[0] = f64::NAN;
+
+        let [dx, dy] = estimate_translation(&reference, &densmap, 4).unwrap();
+        assert!((dx - 1.0).abs() < 0.1, "dx was {}", dx);
+        assert!((dy + 0.5).abs() < 0.1, "dy was {}", dy);
+    }
+
+    #[test]
+    fn test_registering_maps_of_different_shapes_returns_error() {
+        let reference = get_blob_densmap(5.0, 5.0);
+        let densmap = DensMap {
+            shape: [10, 40],
+            ..get_blob_densmap(5.0, 5.0)
+        };
+
+        assert!(estimate_translation(&reference, &densmap, 2).is_err());
+    }
+}
@@ -1,27 +1,56 @@
 use crate::{
-    densmap::{coord2index, DensMap},
-    graphdata::Graph,
+    densmap::{coord2index, DensMap, LengthUnit},
+    graphdata::{Graph, XYData},
 };
 
 /// Sample the contact line interface per angle from the droplet center point. The returned
 /// values are relative to the base radius of the droplet.
-pub fn sample_interface(densmap: &DensMap, base_radius: f64) -> Graph {
+///
+/// The lengths of the map are in the given unit, which the angular resolution is set by.
+pub fn sample_interface(densmap: &DensMap, base_radius: f64, length_unit: LengthUnit) -> Graph {
     // For now, use an angular precision that gives a 0.1nm resolution at the droplet radius.
-    let num_values = (2.0 * std::f64::consts::PI * base_radius / 0.1).ceil() as usize;
+    let resolution = length_unit.convert_nm(0.1);
+    let num_values = (2.0 * std::f64::consts::PI * base_radius / resolution).ceil() as usize;
 
     // Create the angles array in degrees.
     let da = 360.0 / num_values as f64;
     let angles = (0..num_values).map(|n| da * n as f64).collect::<Vec<_>>();
 
-    let cutoff = get_density_cutoff(&densmap);
+    let cutoff = get_density_cutoff(densmap);
 
     let radius = angles
         .iter()
-        .map(|&a| sample_interface_at_angle(&densmap, a, base_radius, cutoff))
-        .map(|r| r)
+        .map(|&a| sample_interface_at_angle(densmap, a, base_radius, cutoff))
         .collect();
 
-    Graph::Polar { angles, radius }
+    Graph::Polar {
+        angles,
+        radius,
+        yerr: None,
+    }
+}
+
+/// Sample a second interface at another density cutoff outwards from an already sampled
+/// contact line, for every angle of it. This is used to find the edge of a thin film
+/// which lies ahead of the contact line, by using a cutoff which is much lower than that
+/// of the main interface.
+///
+/// The input contact line should have absolute radii, as returned by `sample_interface`.
+pub fn sample_interface_beyond(densmap: &DensMap, contact_line: &Graph, cutoff: f64) -> Graph {
+    let contact_line = contact_line.to_polar();
+    let angles = contact_line.x().to_vec();
+
+    let radius = angles
+        .iter()
+        .zip(contact_line.y().iter())
+        .map(|(&a, &r)| sample_interface_at_angle(densmap, a, r, cutoff))
+        .collect();
+
+    Graph::Polar {
+        angles,
+        radius,
+        yerr: None,
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -47,7 +76,7 @@ fn sample_interface_at_angle(densmap: &DensMap, angle: f64, base_radius: f64, cu
     let dr_abs = dx_bin.min(dy_bin);
 
     let (mut radius, direction) =
-        get_initial_radius_and_direction(&densmap, base_radius, angle, cutoff, dr_abs);
+        get_initial_radius_and_direction(densmap, base_radius, angle, cutoff, dr_abs);
 
     let dr = match direction {
         Direction::Increasing => dr_abs,
@@ -91,7 +120,7 @@ fn sample_interface_at_angle(densmap: &DensMap, angle: f64, base_radius: f64, cu
 }
 
 /// Get the density cutoff for contact line determination as half of the maximum density value.
-fn get_density_cutoff(densmap: &DensMap) -> f64 {
+pub fn get_density_cutoff(densmap: &DensMap) -> f64 {
     0.5 * densmap.data.iter().fold(0.0, |acc: f64, &v| acc.max(v))
 }
 
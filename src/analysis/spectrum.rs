@@ -0,0 +1,76 @@
+use crate::{analysis::kymograph::Kymograph, graphdata::Histogram};
+
+use std::f64::consts::PI;
+
+/// Calculate the power spectrum of the contact line fluctuations in a kymograph over
+/// the angular Fourier modes, averaged over all times.
+///
+/// The power of a mode q is the squared magnitude of the discrete Fourier coefficient
+/// of the fluctuations around the mean radius, (1/N) sum_j dr_j exp(-2 pi i q j / N),
+/// for modes from 0 up to half the number of angles. Since the fluctuations have zero
+/// mean the power of mode 0 is zero.
+pub fn calc_mode_spectrum(kymograph: &Kymograph) -> Histogram {
+    let fluctuations = kymograph.fluctuations();
+
+    let num_angles = kymograph.angles.len();
+    let num_modes = if num_angles > 0 {
+        num_angles / 2 + 1
+    } else {
+        0
+    };
+
+    let mut power = vec![0.0; num_modes];
+
+    for dr in &fluctuations {
+        for (q, sum) in power.iter_mut().enumerate() {
+            let (re, im) = dr.iter().enumerate().fold((0.0, 0.0), |(re, im), (j, r)| {
+                let phase = 2.0 * PI * (q * j) as f64 / num_angles as f64;
+                (re + r * phase.cos(), im - r * phase.sin())
+            });
+
+            let n = num_angles as f64;
+            *sum += (re / n).powi(2) + (im / n).powi(2);
+        }
+    }
+
+    let num_times = fluctuations.len().max(1) as f64;
+
+    Histogram {
+        x: (0..num_modes).map(|q| q as f64).collect(),
+        y: power.into_iter().map(|p| p / num_times).collect(),
+        yerr: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_mode_fluctuation_gives_power_in_that_mode() {
+        let num_angles = 16;
+        let angles = (0..num_angles)
+            .map(|i| 360.0 * i as f64 / num_angles as f64)
+            .collect::<Vec<_>>();
+
+        // A contact line with radius 1 + 0.2 cos(3 a) has power 0.1^2 in mode 3.
+        let radius = angles
+            .iter()
+            .map(|a: &f64| 1.0 + 0.2 * (3.0 * a.to_radians()).cos())
+            .collect::<Vec<_>>();
+
+        let kymograph = Kymograph {
+            times: vec![0.0, 1.0],
+            angles,
+            radius: vec![radius.clone(), radius],
+        };
+
+        let spectrum = calc_mode_spectrum(&kymograph);
+
+        assert_eq!(9, spectrum.x.len());
+        for (q, p) in spectrum.y.iter().enumerate() {
+            let expected = if q == 3 { 0.01 } else { 0.0 };
+            assert!((p - expected).abs() < 1e-9, "mode {}: {}", q, p);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::f64::consts::E;
+
+/// Fit a power law y = a x^n to data by least squares of the logarithms and return
+/// the exponent n, like the spreading exponent of the droplet radius over time.
+///
+/// Points with values which are not positive and finite are left out of the fit.
+/// Returns `None` if fewer than two points remain or they all have the same x.
+pub fn fit_power_law_exponent(x: &[f64], y: &[f64]) -> Option<f64> {
+    let is_valid = |v: f64| v.is_finite() && v > 0.0;
+
+    let (log_x, log_y): (Vec<f64>, Vec<f64>) = x
+        .iter()
+        .zip(y)
+        .filter(|(&x, &y)| is_valid(x) && is_valid(y))
+        .map(|(x, y)| (x.ln(), y.ln()))
+        .unzip();
+
+    if log_x.len() < 2 {
+        return None;
+    }
+
+    let n = log_x.len() as f64;
+    let mean_x = log_x.iter().sum::<f64>() / n;
+    let mean_y = log_y.iter().sum::<f64>() / n;
+
+    let covariance = log_x
+        .iter()
+        .zip(&log_y)
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum::<f64>();
+    let variance = log_x.iter().map(|x| (x - mean_x).powi(2)).sum::<f64>();
+
+    if variance > 0.0 {
+        Some(covariance / variance)
+    } else {
+        None
+    }
+}
+
+/// Get the time at which a normalized autocorrelation first decays below 1/e, relative
+/// to the first time and linearly interpolated between the times.
+///
+/// Returns `None` if the autocorrelation does not decay that far.
+pub fn calc_relaxation_time(times: &[f64], autocorrelation: &[f64]) -> Option<f64> {
+    let t0 = *times.first()?;
+    let threshold = 1.0 / E;
+
+    times
+        .windows(2)
+        .zip(autocorrelation.windows(2))
+        .find(|(_, c)| c[1] < threshold)
+        .map(|(t, c)| {
+            let fraction = if c[0] > c[1] {
+                ((c[0] - threshold) / (c[0] - c[1])).min(1.0)
+            } else {
+                1.0
+            };
+
+            t[0] + fraction * (t[1] - t[0]) - t0
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_power_law_exponent_skips_invalid_points() {
+        let x = vec![0.0, 1.0, 4.0, 9.0, 16.0];
+        let y = x.iter().map(|x: &f64| 2.0 * x.sqrt()).collect::<Vec<_>>();
+
+        // The point at x = 0 has no logarithm.
+        let exponent = fit_power_law_exponent(&x, &y).unwrap();
+        assert!((exponent - 0.5).abs() < 1e-12);
+
+        assert_eq!(None, fit_power_law_exponent(&[0.0, 1.0], &[1.0, 1.0]));
+        assert_eq!(None, fit_power_law_exponent(&[2.0, 2.0], &[1.0, 3.0]));
+    }
+
+    #[test]
+    fn test_relaxation_time_of_exponential_decay() {
+        let times = (0..50).map(|i| 100.0 + i as f64).collect::<Vec<_>>();
+        let autocorrelation = times
+            .iter()
+            .map(|t| (-(t - 100.0) / 10.0).exp())
+            .collect::<Vec<_>>();
+
+        let tau = calc_relaxation_time(&times, &autocorrelation).unwrap();
+        assert!((tau - 10.0).abs() < 0.1, "{}", tau);
+
+        assert_eq!(
+            None,
+            calc_relaxation_time(&times[..5], &autocorrelation[..5])
+        );
+        assert_eq!(None, calc_relaxation_time(&[], &[]));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::densmap::DensMap;
+
+#[derive(Clone, Debug, Default)]
+/// Online accumulation of the mean and variance of the density in every bin over
+/// a series of density maps, using Welford's algorithm.
+///
+/// The grid of the accumulated maps is taken from the first added map.
+pub struct VarianceMap {
+    grid: Option<DensMap>,
+    count: u64,
+    mean: Vec<f64>,
+    m2: Vec<f64>,
+}
+
+impl VarianceMap {
+    pub fn new() -> Self {
+        VarianceMap::default()
+    }
+
+    /// Add the densities of a map to the accumulated values.
+    ///
+    /// # Error
+    /// Returns an error if the map does not have the same shape as previously added maps.
+    pub fn add(&mut self, densmap: &DensMap) -> Result<(), String> {
+        match &self.grid {
+            Some(grid) if grid.shape != densmap.shape => {
+                return Err(format!(
+                    "cannot accumulate variance of map with shape {:?} onto shape {:?}",
+                    densmap.shape, grid.shape
+                ));
+            }
+            Some(_) => (),
+            None => {
+                self.grid = Some(DensMap {
+                    data: Vec::new(),
+                    ..densmap.clone()
+                });
+                self.mean = vec![0.0; densmap.data.len()];
+                self.m2 = vec![0.0; densmap.data.len()];
+            }
+        }
+
+        self.count += 1;
+        let n = self.count as f64;
+
+        for ((mean, m2), &v) in self
+            .mean
+            .iter_mut()
+            .zip(self.m2.iter_mut())
+            .zip(densmap.data.iter())
+        {
+            let delta = v - *mean;
+            *mean += delta / n;
+            *m2 += delta * (v - *mean);
+        }
+
+        Ok(())
+    }
+
+    /// Number of accumulated maps.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Get a map of the mean density in every bin, or `None` if no maps have been added.
+    pub fn mean(&self) -> Option<DensMap> {
+        self.to_densmap(self.mean.clone())
+    }
+
+    /// Get a map of the sample variance of the density in every bin, or `None` if no
+    /// maps have been added. The variance of a single map is zero.
+    pub fn variance(&self) -> Option<DensMap> {
+        let denom = self.count.saturating_sub(1).max(1) as f64;
+        self.to_densmap(self.m2.iter().map(|v| v / denom).collect())
+    }
+
+    fn to_densmap(&self, data: Vec<f64>) -> Option<DensMap> {
+        self.grid.as_ref().map(|grid| DensMap {
+            data,
+            ..grid.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_densmap(data: Vec<f64>) -> DensMap {
+        DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [2, 1],
+            center: [1.0, 0.5],
+            data,
+        }
+    }
+
+    #[test]
+    fn test_mean_and_variance_per_bin_are_accumulated_over_maps() {
+        let mut variance = VarianceMap::new();
+        assert_eq!(None, variance.variance());
+
+        for data in [vec![1.0, 2.0], vec![3.0, 2.0], vec![5.0, 2.0]] {
+            variance.add(&get_densmap(data)).unwrap();
+        }
+
+        assert_eq!(3, variance.count());
+        assert_eq!(vec![3.0, 2.0], variance.mean().unwrap().data);
+        assert_eq!(vec![4.0, 0.0], variance.variance().unwrap().data);
+        assert_eq!([2, 1], variance.variance().unwrap().shape);
+    }
+
+    #[test]
+    fn test_adding_map_of_different_shape_returns_error() {
+        let mut variance = VarianceMap::new();
+        variance.add(&get_densmap(vec![1.0, 2.0])).unwrap();
+
+        let densmap = DensMap {
+            shape: [1, 2],
+            ..get_densmap(vec![1.0, 2.0])
+        };
+        assert!(variance.add(&densmap).is_err());
+    }
+}
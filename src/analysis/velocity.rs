@@ -0,0 +1,78 @@
+use crate::graphdata::XYData;
+
+/// Calculate the local velocity of the contact line at every angle for every time,
+/// as the time derivative of the radius using finite differences. Central differences
+/// are used for all but the first and last times, for which one-sided differences are used.
+///
+/// # Notes
+/// Assumes that all contact lines are sampled at the same angles and that they are
+/// sorted in time. The velocity is zero if a single contact line is given. The radii
+/// must be absolute, since those relative to the droplet radius leave out its spreading.
+pub fn calc_contact_line_velocity<T: XYData>(times: &[f64], contact_lines: &[T]) -> Vec<Vec<f64>> {
+    let n = contact_lines.len().min(times.len());
+
+    (0..n)
+        .map(|i| {
+            if n < 2 {
+                return vec![0.0; contact_lines[i].y().len()];
+            }
+
+            let (i0, i1) = if i == 0 {
+                (0, 1)
+            } else if i == n - 1 {
+                (n - 2, n - 1)
+            } else {
+                (i - 1, i + 1)
+            };
+
+            let dt = times[i1] - times[i0];
+
+            contact_lines[i0]
+                .y()
+                .iter()
+                .zip(contact_lines[i1].y().iter())
+                .map(|(r0, r1)| (r1 - r0) / dt)
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphdata::Graph;
+
+    #[test]
+    fn test_contact_line_velocity_uses_central_differences_inside_and_one_sided_at_edges() {
+        let times = vec![0.0, 1.0, 2.0, 4.0];
+        let contact_lines = [0.0, 1.0, 4.0, 8.0]
+            .iter()
+            .map(|&r| Graph::Polar {
+                angles: vec![0.0, 180.0],
+                radius: vec![r, -r],
+                yerr: None,
+            })
+            .collect::<Vec<_>>();
+
+        let velocity = calc_contact_line_velocity(&times, &contact_lines);
+
+        assert_eq!(vec![1.0, -1.0], velocity[0]);
+        assert_eq!(vec![2.0, -2.0], velocity[1]);
+        assert_eq!(vec![7.0 / 3.0, -7.0 / 3.0], velocity[2]);
+        assert_eq!(vec![2.0, -2.0], velocity[3]);
+    }
+
+    #[test]
+    fn test_contact_line_velocity_of_single_frame_is_zero() {
+        let contact_lines = vec![Graph::Polar {
+            angles: vec![0.0, 180.0],
+            radius: vec![1.0, 2.0],
+            yerr: None,
+        }];
+
+        assert_eq!(
+            vec![vec![0.0, 0.0]],
+            calc_contact_line_velocity(&[0.0], &contact_lines)
+        );
+    }
+}
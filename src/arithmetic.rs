@@ -0,0 +1,232 @@
+use crate::densmap::DensMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Summary of the bin by bin difference between two maps.
+pub struct Difference {
+    /// Largest absolute difference of any bin.
+    pub max_abs: f64,
+    /// Index of the bin with the largest absolute difference, if any bin differs.
+    pub max_index: Option<usize>,
+    /// Mean absolute difference over the bins where both values are finite.
+    pub mean_abs: f64,
+    /// Number of bins where the values differ, including bins where only one is finite.
+    pub num_differing: usize,
+}
+
+impl DensMap {
+    /// Add the values of another map to this, bin by bin.
+    ///
+    /// # Error
+    /// Returns an error if the maps do not have the same grid.
+    pub fn add(&self, other: &DensMap) -> Result<DensMap, String> {
+        self.combine(other, |a, b| a + b)
+    }
+
+    /// Subtract the values of another map from this, bin by bin.
+    ///
+    /// # Error
+    /// Returns an error if the maps do not have the same grid.
+    pub fn subtract(&self, other: &DensMap) -> Result<DensMap, String> {
+        self.combine(other, |a, b| a - b)
+    }
+
+    /// Take the minimum value of this and another map, bin by bin.
+    ///
+    /// # Error
+    /// Returns an error if the maps do not have the same grid.
+    pub fn min(&self, other: &DensMap) -> Result<DensMap, String> {
+        self.combine(other, f64::min)
+    }
+
+    /// Take the maximum value of this and another map, bin by bin.
+    ///
+    /// # Error
+    /// Returns an error if the maps do not have the same grid.
+    pub fn max(&self, other: &DensMap) -> Result<DensMap, String> {
+        self.combine(other, f64::max)
+    }
+
+    /// Compare the values of this and another map, bin by bin.
+    ///
+    /// Bins where both values are NaN are equal. A bin where only one value is finite
+    /// is counted as differing, but does not affect the max or mean difference.
+    ///
+    /// # Error
+    /// Returns an error if the maps do not have the same grid.
+    pub fn compare(&self, other: &DensMap) -> Result<Difference, String> {
+        check_same_grid(self, other)?;
+
+        let mut max_abs = 0.0;
+        let mut max_index = None;
+        let mut sum_abs = 0.0;
+        let mut num_finite = 0;
+        let mut num_differing = 0;
+
+        for (i, (&a, &b)) in self.data.iter().zip(other.data.iter()).enumerate() {
+            if a == b || (a.is_nan() && b.is_nan()) {
+                num_finite += a.is_finite() as usize;
+                continue;
+            }
+
+            num_differing += 1;
+            let diff = (a - b).abs();
+
+            if diff.is_finite() {
+                sum_abs += diff;
+                num_finite += 1;
+
+                if diff > max_abs {
+                    max_abs = diff;
+                    max_index = Some(i);
+                }
+            }
+        }
+
+        let mean_abs = if num_finite > 0 {
+            sum_abs / num_finite as f64
+        } else {
+            0.0
+        };
+
+        Ok(Difference {
+            max_abs,
+            max_index,
+            mean_abs,
+            num_differing,
+        })
+    }
+
+    /// Multiply all values of the map by a factor.
+    pub fn scale(&self, factor: f64) -> DensMap {
+        DensMap {
+            data: self.data.iter().map(|v| v * factor).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Get a binary map which is 1 in all bins with a value larger than or equal to the cutoff,
+    /// and 0 elsewhere.
+    pub fn threshold(&self, cutoff: f64) -> DensMap {
+        DensMap {
+            data: self
+                .data
+                .iter()
+                .map(|&v| if v >= cutoff { 1.0 } else { 0.0 })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Combine the values of two maps bin by bin. The returned map has the center
+    /// of this map.
+    fn combine<F>(&self, other: &DensMap, func: F) -> Result<DensMap, String>
+    where
+        F: Fn(f64, f64) -> f64,
+    {
+        check_same_grid(self, other)?;
+
+        Ok(DensMap {
+            data: self
+                .data
+                .iter()
+                .zip(other.data.iter())
+                .map(|(&a, &b)| func(a, b))
+                .collect(),
+            ..self.clone()
+        })
+    }
+}
+
+/// Assert that two maps have identical shapes, bin sizes and origins.
+fn check_same_grid(a: &DensMap, b: &DensMap) -> Result<(), String> {
+    if a.shape != b.shape {
+        Err(format!(
+            "density maps have different shapes: {:?} and {:?}",
+            a.shape, b.shape
+        ))
+    } else if a.bin_size != b.bin_size {
+        Err(format!(
+            "density maps have different bin sizes: {:?} and {:?}",
+            a.bin_size, b.bin_size
+        ))
+    } else if a.origin != b.origin {
+        Err(format!(
+            "density maps have different origins: {:?} and {:?}",
+            a.origin, b.origin
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_densmap(data: Vec<f64>) -> DensMap {
+        DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [3, 1],
+            center: [1.5, 0.5],
+            data,
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_operations_are_applied_bin_by_bin() {
+        let a = get_densmap(vec![1.0, 5.0, 3.0]);
+        let b = get_densmap(vec![2.0, 2.0, 2.0]);
+
+        assert_eq!(vec![3.0, 7.0, 5.0], a.add(&b).unwrap().data);
+        assert_eq!(vec![-1.0, 3.0, 1.0], a.subtract(&b).unwrap().data);
+        assert_eq!(vec![1.0, 2.0, 2.0], a.min(&b).unwrap().data);
+        assert_eq!(vec![2.0, 5.0, 3.0], a.max(&b).unwrap().data);
+        assert_eq!(vec![0.5, 2.5, 1.5], a.scale(0.5).data);
+        assert_eq!(vec![0.0, 1.0, 1.0], a.threshold(3.0).data);
+    }
+
+    #[test]
+    fn test_arithmetic_on_maps_with_different_grids_returns_error() {
+        let a = get_densmap(vec![1.0, 5.0, 3.0]);
+
+        let b = DensMap {
+            shape: [1, 3],
+            ..get_densmap(vec![2.0, 2.0, 2.0])
+        };
+        assert!(a.add(&b).is_err());
+
+        let b = DensMap {
+            bin_size: [0.5, 1.0, 0.0],
+            ..get_densmap(vec![2.0, 2.0, 2.0])
+        };
+        assert!(a.subtract(&b).is_err());
+
+        let b = DensMap {
+            origin: [1.0, 0.0],
+            ..get_densmap(vec![2.0, 2.0, 2.0])
+        };
+        assert!(a.max(&b).is_err());
+        assert!(a.compare(&b).is_err());
+    }
+
+    #[test]
+    fn test_compare_maps_finds_largest_and_mean_absolute_difference() {
+        let a = get_densmap(vec![1.0, 5.0, f64::NAN]);
+        let b = get_densmap(vec![2.0, 2.0, f64::NAN]);
+
+        let diff = a.compare(&b).unwrap();
+        assert_eq!(3.0, diff.max_abs);
+        assert_eq!(Some(1), diff.max_index);
+        assert_eq!(2.0, diff.mean_abs);
+        assert_eq!(2, diff.num_differing);
+
+        let b = get_densmap(vec![1.0, 5.0, 0.0]);
+        let diff = a.compare(&b).unwrap();
+        assert_eq!(0.0, diff.max_abs);
+        assert_eq!(None, diff.max_index);
+        assert_eq!(1, diff.num_differing);
+
+        assert_eq!(0, a.compare(&a).unwrap().num_differing);
+    }
+}
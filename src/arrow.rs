@@ -0,0 +1,503 @@
+use crate::{
+    densmap::{DensMap, LengthUnit},
+    series::TimeUnit,
+};
+
+use std::{
+    f64::consts::PI,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Magic string which begins and ends an Arrow IPC file.
+const ARROW_MAGIC: &[u8; 6] = b"ARROW1";
+
+/// Marker which begins every message of the IPC format.
+const CONTINUATION: u32 = 0xffff_ffff;
+
+/// Enums of the Arrow flatbuffer schema.
+const METADATA_VERSION_V5: i16 = 4;
+const ENDIANNESS_LITTLE: i16 = 0;
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_RECORD_BATCH: u8 = 3;
+const TYPE_FLOATING_POINT: u8 = 3;
+const PRECISION_DOUBLE: i16 = 2;
+
+/// Columns of the table, which hold a single value per frame.
+const COLUMNS: [&str; 6] = ["time", "radius", "area", "mass", "center_x", "center_y"];
+
+/// Writer of the per-frame results of a trajectory as a table in an Arrow IPC file.
+///
+/// The file format is also known as Feather (version 2) and can be read by most data
+/// analysis tools, eg. `pyarrow.feather.read_table` or `pandas.read_feather`. Every
+/// frame adds a row of the columns `time`, `radius`, `area`, `mass`, `center_x` and
+/// `center_y` as 64 bit floats. The times and lengths are in the units of the analysis,
+/// which are set as the `unit` metadata of their columns, while the mass is the sum of
+/// the map in the units of its densities and has no unit in the file.
+///
+/// The rows are kept in memory and the table is written as a single record batch by
+/// `finish`, which must be called after the last frame.
+pub struct ArrowWriter<W: Write> {
+    writer: W,
+    units: [Option<String>; 6],
+    rows: Vec<[f64; 6]>,
+}
+
+impl ArrowWriter<BufWriter<File>> {
+    /// Create the file at the path.
+    pub fn create(
+        path: &Path,
+        time_unit: TimeUnit,
+        length_unit: LengthUnit,
+    ) -> Result<Self, io::Error> {
+        let fp = File::create(path)?;
+        Ok(ArrowWriter::new(BufWriter::new(fp), time_unit, length_unit))
+    }
+}
+
+impl<W: Write> ArrowWriter<W> {
+    /// Create the writer for frames with times and lengths in the given units.
+    pub fn new(writer: W, time_unit: TimeUnit, length_unit: LengthUnit) -> Self {
+        let length = length_unit.name();
+
+        ArrowWriter {
+            writer,
+            units: [
+                Some(time_unit.to_string()),
+                Some(length.to_string()),
+                Some(format!("{}2", length)),
+                None,
+                Some(length.to_string()),
+                Some(length.to_string()),
+            ],
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row of the results of a frame.
+    ///
+    /// The radius is `None` if it could not be determined for the frame, in which case
+    /// the radius and area are written as NaN.
+    pub fn write_frame(&mut self, densmap: &DensMap, time: f64, radius: Option<f64>) {
+        let radius = radius.unwrap_or(f64::NAN);
+        let mass = densmap.data.iter().sum();
+        let [xc, yc] = densmap.center;
+
+        self.rows
+            .push([time, radius, PI * radius.powi(2), mass, xc, yc]);
+    }
+
+    /// Write the table to the file and flush it.
+    pub fn finish(mut self) -> Result<W, io::Error> {
+        self.writer
+            .write_all(&encode_table(&COLUMNS, &self.units, &self.rows))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+/// Encode a table of 64 bit float columns as an Arrow IPC file.
+///
+/// The file begins with the magic string, followed by the schema and record batch
+/// messages of the stream format and a footer which points to the messages.
+fn encode_table<const N: usize>(
+    names: &[&str; N],
+    units: &[Option<String>; N],
+    rows: &[[f64; N]],
+) -> Vec<u8> {
+    let num_rows = rows.len() as i64;
+
+    let mut file = ARROW_MAGIC.to_vec();
+    pad_to(&mut file, 8);
+
+    let schema = get_message(HEADER_SCHEMA, get_schema(names, units), 0);
+    write_message(&mut file, &schema.encode(), &[]);
+
+    let mut body = Vec::new();
+    let mut buffers = Vec::new();
+
+    for i in 0..N {
+        let offset = body.len() as i64;
+
+        for row in rows {
+            body.extend_from_slice(&row[i].to_le_bytes());
+        }
+
+        // All values are valid, so the validity bitmap of every column is left empty.
+        buffers.extend_from_slice(&[offset, 0, offset, 8 * num_rows]);
+    }
+
+    let nodes = (0..N).flat_map(|_| vec![num_rows, 0]).collect::<Vec<_>>();
+    let record_batch = Object::Table(vec![
+        (0, Value::I64(num_rows)),
+        (1, Value::Object(Object::structs(&nodes, N))),
+        (2, Value::Object(Object::structs(&buffers, 2 * N))),
+    ]);
+
+    let message = get_message(HEADER_RECORD_BATCH, record_batch, body.len() as i64);
+    let block = write_message(&mut file, &message.encode(), &body);
+
+    // End of the stream.
+    file.extend_from_slice(&CONTINUATION.to_le_bytes());
+    file.extend_from_slice(&0u32.to_le_bytes());
+
+    let footer = Object::Table(vec![
+        (0, Value::I16(METADATA_VERSION_V5)),
+        (1, Value::Object(get_schema(names, units))),
+        (2, Value::Object(Object::structs(&[], 0))),
+        (
+            3,
+            Value::Object(Object::Structs {
+                bytes: block,
+                len: 1,
+            }),
+        ),
+    ])
+    .encode();
+
+    file.extend_from_slice(&footer);
+    file.extend_from_slice(&(footer.len() as u32).to_le_bytes());
+    file.extend_from_slice(ARROW_MAGIC);
+
+    file
+}
+
+/// Get the schema of the columns, with the units of those which have one as their
+/// custom metadata.
+fn get_schema(names: &[&str], units: &[Option<String>]) -> Object {
+    let fields = names
+        .iter()
+        .zip(units)
+        .map(|(name, unit)| {
+            let mut field = vec![
+                (0, Value::Object(Object::String(name.to_string()))),
+                (1, Value::Bool(false)),
+                (2, Value::U8(TYPE_FLOATING_POINT)),
+                (
+                    3,
+                    Value::Object(Object::Table(vec![(0, Value::I16(PRECISION_DOUBLE))])),
+                ),
+                (5, Value::Object(Object::Tables(Vec::new()))),
+            ];
+
+            if let Some(unit) = unit {
+                let metadata = Object::Table(vec![
+                    (0, Value::Object(Object::String("unit".to_string()))),
+                    (1, Value::Object(Object::String(unit.clone()))),
+                ]);
+                field.push((6, Value::Object(Object::Tables(vec![metadata]))));
+            }
+
+            Object::Table(field)
+        })
+        .collect();
+
+    Object::Table(vec![
+        (0, Value::I16(ENDIANNESS_LITTLE)),
+        (1, Value::Object(Object::Tables(fields))),
+    ])
+}
+
+fn get_message(header_type: u8, header: Object, body_length: i64) -> Object {
+    Object::Table(vec![
+        (0, Value::I16(METADATA_VERSION_V5)),
+        (1, Value::U8(header_type)),
+        (2, Value::Object(header)),
+        (3, Value::I64(body_length)),
+    ])
+}
+
+/// Write an encapsulated message with its body, returning the block of the footer
+/// which points to it.
+///
+/// The message metadata is prefixed by the continuation marker and its length, and
+/// since the metadata is padded the body begins at an 8 byte boundary.
+fn write_message(file: &mut Vec<u8>, metadata: &[u8], body: &[u8]) -> Vec<u8> {
+    let offset = file.len() as i64;
+
+    file.extend_from_slice(&CONTINUATION.to_le_bytes());
+    file.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    file.extend_from_slice(metadata);
+    file.extend_from_slice(body);
+    pad_to(file, 8);
+
+    let metadata_length = 8 + metadata.len() as i32;
+    let body_length = file.len() as i64 - offset - i64::from(metadata_length);
+
+    let mut block = Vec::with_capacity(24);
+    block.extend_from_slice(&offset.to_le_bytes());
+    block.extend_from_slice(&metadata_length.to_le_bytes());
+    block.extend_from_slice(&[0; 4]);
+    block.extend_from_slice(&body_length.to_le_bytes());
+
+    block
+}
+
+fn pad_to(buf: &mut Vec<u8>, align: usize) {
+    while !buf.len().is_multiple_of(align) {
+        buf.push(0);
+    }
+}
+
+/// Object of a flatbuffer.
+///
+/// Objects are encoded after the table which refers to them, since the offsets
+/// to them are unsigned.
+enum Object {
+    /// Table with values by their field index.
+    Table(Vec<(u16, Value)>),
+    String(String),
+    /// Vector of tables.
+    Tables(Vec<Object>),
+    /// Vector of structs with an alignment of 8 bytes, as their encoded bytes.
+    Structs {
+        bytes: Vec<u8>,
+        len: usize,
+    },
+}
+
+/// Value of a table field.
+enum Value {
+    Bool(bool),
+    U8(u8),
+    I16(i16),
+    I64(i64),
+    Object(Object),
+}
+
+impl Value {
+    fn size(&self) -> usize {
+        match self {
+            Value::Bool(_) | Value::U8(_) => 1,
+            Value::I16(_) => 2,
+            Value::Object(_) => 4,
+            Value::I64(_) => 8,
+        }
+    }
+}
+
+impl Object {
+    /// Create a vector of structs of 64 bit integer members.
+    fn structs(values: &[i64], len: usize) -> Object {
+        let bytes = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        Object::Structs { bytes, len }
+    }
+
+    /// Encode the object as the root of a flatbuffer, padded to 8 bytes.
+    fn encode(self) -> Vec<u8> {
+        let mut buf = vec![0; 4];
+        let root = self.write(&mut buf);
+        patch_offset(&mut buf, 0, root);
+        pad_to(&mut buf, 8);
+
+        buf
+    }
+
+    /// Write the object to the buffer, returning its position.
+    fn write(self, buf: &mut Vec<u8>) -> usize {
+        match self {
+            Object::String(s) => {
+                pad_to(buf, 4);
+                let pos = buf.len();
+                buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                buf.extend_from_slice(s.as_bytes());
+                buf.push(0);
+                pos
+            }
+            Object::Structs { bytes, len } => {
+                // The structs follow the length directly and must be aligned.
+                while !(buf.len() + 4).is_multiple_of(8) {
+                    buf.push(0);
+                }
+                let pos = buf.len();
+                buf.extend_from_slice(&(len as u32).to_le_bytes());
+                buf.extend_from_slice(&bytes);
+                pos
+            }
+            Object::Tables(tables) => {
+                pad_to(buf, 4);
+                let pos = buf.len();
+                buf.extend_from_slice(&(tables.len() as u32).to_le_bytes());
+
+                let slots = (0..tables.len())
+                    .map(|i| pos + 4 + 4 * i)
+                    .collect::<Vec<_>>();
+                buf.resize(pos + 4 + 4 * tables.len(), 0);
+
+                for (slot, table) in slots.into_iter().zip(tables) {
+                    let child = table.write(buf);
+                    patch_offset(buf, slot, child);
+                }
+
+                pos
+            }
+            Object::Table(mut fields) => {
+                let num_fields = fields
+                    .iter()
+                    .map(|(i, _)| *i as usize + 1)
+                    .max()
+                    .unwrap_or(0);
+
+                pad_to(buf, 2);
+                let vtable_pos = buf.len();
+                buf.resize(vtable_pos + 4 + 2 * num_fields, 0);
+
+                pad_to(buf, 8);
+                let table_pos = buf.len();
+                buf.extend_from_slice(&((table_pos - vtable_pos) as i32).to_le_bytes());
+
+                // Larger values first keeps the fields aligned without padding.
+                fields.sort_by_key(|(_, value)| std::cmp::Reverse(value.size()));
+
+                let mut children = Vec::new();
+
+                for (i, value) in fields {
+                    pad_to(buf, value.size());
+                    let field_offset = (buf.len() - table_pos) as u16;
+                    let entry = vtable_pos + 4 + 2 * i as usize;
+                    buf[entry..entry + 2].copy_from_slice(&field_offset.to_le_bytes());
+
+                    match value {
+                        Value::Bool(v) => buf.push(v as u8),
+                        Value::U8(v) => buf.push(v),
+                        Value::I16(v) => buf.extend_from_slice(&v.to_le_bytes()),
+                        Value::I64(v) => buf.extend_from_slice(&v.to_le_bytes()),
+                        Value::Object(object) => {
+                            children.push((buf.len(), object));
+                            buf.extend_from_slice(&[0; 4]);
+                        }
+                    }
+                }
+
+                let vtable_size = (4 + 2 * num_fields) as u16;
+                let table_size = (buf.len() - table_pos) as u16;
+                buf[vtable_pos..vtable_pos + 2].copy_from_slice(&vtable_size.to_le_bytes());
+                buf[vtable_pos + 2..vtable_pos + 4].copy_from_slice(&table_size.to_le_bytes());
+
+                for (slot, object) in children {
+                    let child = object.write(buf);
+                    patch_offset(buf, slot, child);
+                }
+
+                table_pos
+            }
+        }
+    }
+}
+
+/// Set the offset at a position to point to a later position.
+fn patch_offset(buf: &mut [u8], pos: usize, target: usize) {
+    buf[pos..pos + 4].copy_from_slice(&((target - pos) as u32).to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::DensMapBuilder;
+
+    fn read_u32(bytes: &[u8], pos: usize) -> usize {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(&bytes[pos..pos + 4]);
+        u32::from_le_bytes(buf) as usize
+    }
+
+    /// Get the position of a field of a table from its vtable, if it is set.
+    fn get_field(bytes: &[u8], table: usize, i: usize) -> Option<usize> {
+        let vtable = table - read_u32(bytes, table);
+        let vtable_size = u16::from_le_bytes([bytes[vtable], bytes[vtable + 1]]) as usize;
+        let entry = vtable + 4 + 2 * i;
+
+        if entry + 2 > vtable + vtable_size {
+            return None;
+        }
+
+        match u16::from_le_bytes([bytes[entry], bytes[entry + 1]]) as usize {
+            0 => None,
+            offset => Some(table + offset),
+        }
+    }
+
+    fn follow(bytes: &[u8], pos: usize) -> usize {
+        pos + read_u32(bytes, pos)
+    }
+
+    #[test]
+    fn test_flatbuffer_tables_point_to_their_fields() {
+        let bytes = Object::Table(vec![
+            (0, Value::I16(7)),
+            (2, Value::Object(Object::String("radius".to_string()))),
+            (3, Value::I64(-5)),
+        ])
+        .encode();
+
+        assert_eq!(0, bytes.len() % 8);
+
+        let table = follow(&bytes, 0);
+        assert_eq!(0, table % 8);
+
+        let short = get_field(&bytes, table, 0).unwrap();
+        assert_eq!(7, i16::from_le_bytes([bytes[short], bytes[short + 1]]));
+        assert_eq!(None, get_field(&bytes, table, 1));
+
+        let long = get_field(&bytes, table, 3).unwrap();
+        assert_eq!(0, long % 8);
+        let mut buf = [0; 8];
+        buf.copy_from_slice(&bytes[long..long + 8]);
+        assert_eq!(-5, i64::from_le_bytes(buf));
+
+        let string = follow(&bytes, get_field(&bytes, table, 2).unwrap());
+        assert_eq!(6, read_u32(&bytes, string));
+        assert_eq!(b"radius", &bytes[string + 4..string + 10]);
+    }
+
+    #[test]
+    fn test_table_is_written_as_an_arrow_file_with_a_footer() {
+        let densmap = DensMapBuilder::new()
+            .bin_size([1.0, 1.0, 0.0])
+            .shape([2, 1])
+            .from_data(vec![1.0, 2.0])
+            .unwrap();
+
+        let mut writer = ArrowWriter::new(Vec::new(), TimeUnit::Ns, LengthUnit::Angstrom);
+        writer.write_frame(&densmap, 0.0, Some(1.0));
+        writer.write_frame(&densmap, 10.0, None);
+        let bytes = writer.finish().unwrap();
+
+        assert_eq!(ARROW_MAGIC, &bytes[..6]);
+        assert_eq!(ARROW_MAGIC, &bytes[bytes.len() - 6..]);
+
+        // The footer points to the record batch, which is followed by its body.
+        let footer_len = read_u32(&bytes, bytes.len() - 10);
+        let footer = &bytes[bytes.len() - 10 - footer_len..bytes.len() - 10];
+
+        let root = follow(footer, 0);
+        let blocks = follow(footer, get_field(footer, root, 3).unwrap());
+        assert_eq!(1, read_u32(footer, blocks));
+
+        let block = &footer[blocks + 4..blocks + 28];
+        let mut buf = [0; 8];
+        buf.copy_from_slice(&block[..8]);
+        let offset = u64::from_le_bytes(buf) as usize;
+        let metadata_length = read_u32(block, 8);
+
+        assert_eq!(0, offset % 8);
+        assert_eq!(CONTINUATION as usize, read_u32(&bytes, offset));
+
+        // The time column is first in the body.
+        let body = offset + metadata_length;
+        buf.copy_from_slice(&bytes[body + 8..body + 16]);
+        assert_eq!(10.0, f64::from_le_bytes(buf));
+
+        // The mass column is the fourth.
+        buf.copy_from_slice(&bytes[body + 48..body + 56]);
+        assert_eq!(3.0, f64::from_le_bytes(buf));
+
+        // The units of the columns are in the metadata of the schema.
+        let contains = |s: &[u8]| bytes.windows(s.len()).any(|w| w == s);
+        assert!(contains(b"unit"));
+        assert!(contains(b"angstrom2"));
+        assert!(contains(b"ns"));
+    }
+}
@@ -1,61 +1,491 @@
-use crate::densmap::{index2tuple, tuple2index, DensMap, Shape, Vec3};
-use rayon::prelude::*;
+use crate::{
+    densmap::{index2tuple, tuple2index, DensMap, Shape, Vec3},
+    parallel::*,
+};
 
-pub fn smoothen_data_of_bins_within_radius(densmap: DensMap, radius: f64) -> DensMap {
-    let neighbours = get_system_bin_neighbours(radius, densmap.bin_size, densmap.shape);
+use std::str::FromStr;
 
-    DensMap {
-        data: get_averaged_system(densmap.data, neighbours),
-        ..densmap
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// How neighbours which lie outside of the system are handled when smoothing.
+pub enum EdgeMode {
+    /// Skip the neighbours, which averages edge bins over fewer bins.
+    Truncate,
+    /// Reflect the neighbours back into the system at the edge.
+    Mirror,
+    /// Use the closest bin at the edge of the system.
+    Clamp,
+    /// Wrap the neighbours across the system, for periodic systems.
+    Wrap,
+    /// Include the neighbours with a value of zero.
+    ZeroPad,
+}
+
+impl EdgeMode {
+    /// Get the bin index along an axis of `n` bins which a (possibly outside) index maps to.
+    ///
+    /// Returns `Err(())` if the bin is padded with zero and `Ok(None)` if it is skipped.
+    fn apply(self, i: isize, n: isize) -> Result<Option<isize>, ()> {
+        if i >= 0 && i < n {
+            return Ok(Some(i));
+        }
+
+        match self {
+            EdgeMode::Truncate => Ok(None),
+            EdgeMode::Mirror => {
+                let j = i.rem_euclid(2 * n);
+                Ok(Some(if j < n { j } else { 2 * n - 1 - j }))
+            }
+            EdgeMode::Clamp => Ok(Some(i.max(0).min(n - 1))),
+            EdgeMode::Wrap => Ok(Some(i.rem_euclid(n))),
+            EdgeMode::ZeroPad => Err(()),
+        }
+    }
+}
+
+impl FromStr for EdgeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truncate" => Ok(EdgeMode::Truncate),
+            "mirror" => Ok(EdgeMode::Mirror),
+            "clamp" => Ok(EdgeMode::Clamp),
+            "wrap" => Ok(EdgeMode::Wrap),
+            "zero" => Ok(EdgeMode::ZeroPad),
+            _ => Err(format!(
+                "invalid edge mode '{}': must be one of 'truncate', 'mirror', 'clamp', \
+                 'wrap' or 'zero'",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// How the values of a bin and its neighbours are combined when smoothing.
+pub enum Filter {
+    /// Take the mean value.
+    Mean,
+    /// Take the median value, which is robust against single bins with extreme values.
+    Median,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mean" => Ok(Filter::Mean),
+            "median" => Ok(Filter::Median),
+            _ => Err(format!(
+                "invalid smoothing filter '{}': must be 'mean' or 'median'",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Shape and weights of the neighbourhood of bins which is used when smoothing.
+pub enum Kernel {
+    /// Bins within a circle of the radius, with equal weights.
+    Disc,
+    /// Bins within a square of half side length equal to the radius, with equal weights.
+    Box,
+    /// Bins within a circle of the radius, with weights decreasing linearly
+    /// from the center as `1 - r / R`.
+    Tent,
+    /// Bins within a circle of the radius, with weights decreasing quadratically
+    /// from the center as `1 - (r / R)^2`.
+    Epanechnikov,
+}
+
+impl Kernel {
+    /// Get the weight of a bin at a distance from the center of the kernel.
+    fn weight(self, distance: f64, radius: f64) -> f64 {
+        let u = if radius > 0.0 { distance / radius } else { 0.0 };
+
+        match self {
+            Kernel::Disc | Kernel::Box => 1.0,
+            Kernel::Tent => (1.0 - u).max(0.0),
+            Kernel::Epanechnikov => (1.0 - u.powi(2)).max(0.0),
+        }
+    }
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disc" => Ok(Kernel::Disc),
+            "box" => Ok(Kernel::Box),
+            "tent" => Ok(Kernel::Tent),
+            "epanechnikov" => Ok(Kernel::Epanechnikov),
+            _ => Err(format!(
+                "invalid smoothing kernel '{}': must be one of 'disc', 'box', 'tent' \
+                 or 'epanechnikov'",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Options for how bins are smoothed with their neighbours.
+pub struct SmoothingOptions {
+    /// Shape of the neighbourhood.
+    pub kernel: Kernel,
+    /// How the values of the neighbourhood are combined.
+    pub filter: Filter,
+    /// How neighbours outside of the system are handled along x and y.
+    pub edge_modes: [EdgeMode; 2],
+}
+
+impl Default for SmoothingOptions {
+    fn default() -> Self {
+        SmoothingOptions {
+            kernel: Kernel::Disc,
+            filter: Filter::Mean,
+            edge_modes: [EdgeMode::Truncate; 2],
+        }
+    }
+}
+
+/// Smoothen the map by filtering every bin with its neighbours within the radius.
+///
+/// Neighbours which lie outside of the system are handled according to the edge
+/// modes along x and y. The mean over a box kernel is calculated with running sums
+/// along x and y, which is much faster than using lists of neighbours for large radii.
+///
+/// The neighbour lists are constructed for every call. When smoothing several maps
+/// use a `Smoother`, which reuses them between maps with identical grids.
+pub fn smoothen_data_of_bins_within_radius(
+    densmap: DensMap,
+    radius: f64,
+    options: SmoothingOptions,
+) -> DensMap {
+    Smoother::new(radius, options).smoothen(densmap)
+}
+
+/// Smoothing of density maps which caches the neighbour lists of bins.
+///
+/// The neighbour lists depend only on the grid of the maps, so they are constructed
+/// for the first map and reused for all following maps, until a map with a different
+/// bin size or shape is smoothed.
+pub struct Smoother {
+    radius: f64,
+    options: SmoothingOptions,
+    cache: Option<NeighbourCache>,
+    disabled: bool,
+}
+
+struct NeighbourCache {
+    bin_size: Vec3,
+    shape: Shape,
+    neighbours: Vec<Vec<(Option<usize>, f64)>>,
+}
+
+impl Smoother {
+    /// Create a smoother for the radius and options.
+    pub fn new(radius: f64, options: SmoothingOptions) -> Self {
+        Smoother {
+            radius,
+            options,
+            cache: None,
+            disabled: false,
+        }
+    }
+
+    /// Create a smoother which returns maps as they are, for maps which are already
+    /// smooth or should be analyzed without smoothing.
+    pub fn disabled(options: SmoothingOptions) -> Self {
+        Smoother {
+            disabled: true,
+            ..Smoother::new(0.0, options)
+        }
+    }
+
+    /// Smoothen a map, see `smoothen_data_of_bins_within_radius` for details.
+    pub fn smoothen(&mut self, densmap: DensMap) -> DensMap {
+        if self.disabled {
+            return densmap;
+        }
+
+        let radius = self.radius;
+        let options = self.options;
+
+        let data = match (options.kernel, options.filter) {
+            (Kernel::Box, Filter::Mean) => get_box_averaged_system(
+                &densmap.data,
+                radius,
+                densmap.bin_size,
+                densmap.shape,
+                options.edge_modes,
+            ),
+            _ => {
+                let neighbours = self.get_neighbours(densmap.bin_size, densmap.shape);
+                get_averaged_system(&densmap.data, neighbours, options.filter)
+            }
+        };
+
+        DensMap { data, ..densmap }
+    }
+
+    /// Get the neighbour lists for the grid, constructing them if they are not cached.
+    fn get_neighbours(&mut self, bin_size: Vec3, shape: Shape) -> &[Vec<(Option<usize>, f64)>] {
+        let is_cached = self
+            .cache
+            .as_ref()
+            .map(|cache| cache.bin_size == bin_size && cache.shape == shape)
+            .unwrap_or(false);
+
+        if !is_cached {
+            let neighbours = get_system_bin_neighbours(
+                self.radius,
+                bin_size,
+                shape,
+                self.options.kernel,
+                self.options.edge_modes,
+            );
+
+            self.cache = Some(NeighbourCache {
+                bin_size,
+                shape,
+                neighbours,
+            });
+        }
+
+        &self.cache.as_ref().unwrap().neighbours
     }
 }
 
 /// In order and for all bins in the system, get a list of its neighbours and return them all.
-pub fn get_system_bin_neighbours(radius: f64, bin_size: Vec3, shape: Shape) -> Vec<Vec<usize>> {
-    let sieve = get_averaging_bin_sieve(radius, bin_size);
+///
+/// Every neighbour is included along with its weight in the kernel. Neighbours which
+/// are padded with zero are included as `None`.
+pub fn get_system_bin_neighbours(
+    radius: f64,
+    bin_size: Vec3,
+    shape: Shape,
+    kernel: Kernel,
+    edge_modes: [EdgeMode; 2],
+) -> Vec<Vec<(Option<usize>, f64)>> {
+    let sieve = get_averaging_bin_sieve(radius, bin_size, kernel);
+    let weights = get_sieve_weights(&sieve, radius, bin_size, kernel);
     let [nx, ny] = shape;
     (0..(nx * ny) as usize)
         .into_par_iter()
-        .map(|i| get_bin_neighbours(i, shape, &sieve))
+        .map(|i| get_bin_neighbours(i, shape, &sieve, &weights, edge_modes))
         .collect()
 }
 
+/// For every bin in the system, average the value of bins within a box around it and
+/// return as a new system.
+///
+/// The box is separated into windows along x and y. Sums over the windows along x are
+/// calculated for every row, after which these are summed over windows along y
+/// for every column. The result is identical to averaging over the neighbour lists
+/// of a box kernel.
+fn get_box_averaged_system(
+    data: &[f64],
+    radius: f64,
+    [dx, dy, _]: Vec3,
+    [nx, ny]: Shape,
+    [mode_x, mode_y]: [EdgeMode; 2],
+) -> Vec<f64> {
+    let (nx, ny) = (nx as usize, ny as usize);
+    let mx = get_box_half_width(radius, dx);
+    let my = get_box_half_width(radius, dy);
+
+    if nx == 0 || ny == 0 {
+        return Vec::new();
+    }
+
+    let row_sums = data
+        .par_chunks(nx)
+        .map(|row| get_window_sums(|ix| row[ix], nx, mx, mode_x))
+        .collect::<Vec<_>>();
+
+    let column_sums = (0..nx)
+        .into_par_iter()
+        .map(|ix| get_window_sums(|iy| row_sums[iy][ix].0, ny, my, mode_y))
+        .collect::<Vec<_>>();
+
+    (0..ny)
+        .flat_map(|iy| (0..nx).map(move |ix| (ix, iy)))
+        .map(|(ix, iy)| {
+            let (sum, count_y) = column_sums[ix][iy];
+            let count_x = row_sums[0][ix].1;
+
+            sum / (count_x * count_y)
+        })
+        .collect()
+}
+
+/// For every bin along a line of `n` bins, get the sum of values and the number of bins
+/// within a window of `m` bins on either side of it.
+///
+/// Bins outside of the line are handled according to the edge mode. Padded bins are
+/// counted but do not add to the sum, while skipped bins are not counted.
+fn get_window_sums<F>(value: F, n: usize, m: usize, mode: EdgeMode) -> Vec<(f64, f64)>
+where
+    F: Fn(usize) -> f64,
+{
+    // Cumulative sums over the line, extended by m bins at either end.
+    let mut sums = vec![0.0; n + 2 * m + 1];
+    let mut counts = vec![0.0; n + 2 * m + 1];
+
+    for j in 0..(n + 2 * m) {
+        let (v, w) = match mode.apply(j as isize - m as isize, n as isize) {
+            Ok(Some(i)) => (value(i as usize), 1.0),
+            Ok(None) => (0.0, 0.0),
+            Err(()) => (0.0, 1.0),
+        };
+
+        sums[j + 1] = sums[j] + v;
+        counts[j + 1] = counts[j] + w;
+    }
+
+    (0..n)
+        .map(|i| {
+            let j = i + 2 * m + 1;
+            (sums[j] - sums[i], counts[j] - counts[i])
+        })
+        .collect()
+}
+
+/// Get the number of bins on either side of a bin which are within the radius along an axis.
+fn get_box_half_width(radius: f64, bin_size: f64) -> usize {
+    let n = (radius / bin_size).ceil().max(0.0) as usize;
+
+    (0..=n)
+        .rev()
+        .find(|&i| i as f64 * bin_size <= radius)
+        .unwrap_or(0)
+}
+
 /// For every bin in the system, average the value using its neighbours and return as a new system.
 ///
 /// # Notes
 /// Assumes that the index order of the input `data` and `neighbours` are identical and that
 /// the vectors are of equal size.
-fn get_averaged_system(data: Vec<f64>, neighbours: Vec<Vec<usize>>) -> Vec<f64> {
+fn get_averaged_system(
+    data: &[f64],
+    neighbours: &[Vec<(Option<usize>, f64)>],
+    filter: Filter,
+) -> Vec<f64> {
     neighbours
-        .into_par_iter()
-        .map(|bins| average_value_of_bins(&data, &bins))
+        .par_iter()
+        .map(|bins| match filter {
+            Filter::Mean => average_value_of_bins(data, bins),
+            Filter::Median => median_value_of_bins(data, bins),
+        })
         .collect()
 }
 
-/// Average the data of bins with input indices.
+/// Average the data of bins with input indices, weighted by their weights. Bins without
+/// an index have a value of zero.
 ///
 /// # Notes
 /// Assumes that all input indices are valid, ie. lie within bounds of the `data` array.
-fn average_value_of_bins(data: &Vec<f64>, bins: &Vec<usize>) -> f64 {
-    if bins.is_empty() {
+fn average_value_of_bins(data: &[f64], bins: &[(Option<usize>, f64)]) -> f64 {
+    let total_weight = bins.iter().map(|(_, w)| w).sum::<f64>();
+
+    if total_weight <= 0.0 {
         0.0
     } else {
-        bins.iter().map(|&i| data[i]).sum::<f64>() / bins.len() as f64
+        bins.iter()
+            .filter_map(|&(i, w)| i.map(|i| w * data[i]))
+            .sum::<f64>()
+            / total_weight
+    }
+}
+
+/// Get the weighted median of the data of bins with input indices. Bins without an index
+/// have a value of zero.
+///
+/// The median is the value at which the cumulative weight of the sorted values reaches
+/// half of the total. If it is reached exactly between two values their mean is returned,
+/// which for equal weights gives the regular median. Values which are not finite are left
+/// out, and the median is zero if no values remain.
+///
+/// # Notes
+/// Assumes that all input indices are valid, ie. lie within bounds of the `data` array.
+fn median_value_of_bins(data: &[f64], bins: &[(Option<usize>, f64)]) -> f64 {
+    let mut values = bins
+        .iter()
+        .filter(|(_, w)| *w > 0.0)
+        .map(|&(i, w)| (i.map(|i| data[i]).unwrap_or(0.0), w))
+        .filter(|(v, _)| v.is_finite())
+        .collect::<Vec<_>>();
+
+    values.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let half_weight = 0.5 * values.iter().map(|(_, w)| w).sum::<f64>();
+    let tolerance = 1e-12 * half_weight;
+    let mut cumulative_weight = 0.0;
+
+    for (k, &(v, w)) in values.iter().enumerate() {
+        cumulative_weight += w;
+
+        if (cumulative_weight - half_weight).abs() <= tolerance && k + 1 < values.len() {
+            return 0.5 * (v + values[k + 1].0);
+        } else if cumulative_weight > half_weight {
+            return v;
+        }
     }
+
+    0.0
 }
 
 /// Get the indices of neighbouring bins to the input bin, using the neighbour "sieve".
 /// Each candidate in the sieve is used along with the input bin to assert that it lies
 /// within the system. If so, the 1D index of that candidate is calculated and stored.
-/// The list of all valid candidates is returned.
-fn get_bin_neighbours(i: usize, shape: Shape, sieve: &Vec<(isize, isize)>) -> Vec<usize> {
+/// Candidates outside of the system are handled according to the edge modes along
+/// x and y, and are stored as `None` if they are padded with zero.
+/// The list of all valid candidates is returned along with their weights.
+fn get_bin_neighbours(
+    i: usize,
+    shape: Shape,
+    sieve: &[(isize, isize)],
+    weights: &[f64],
+    [mode_x, mode_y]: [EdgeMode; 2],
+) -> Vec<(Option<usize>, f64)> {
     let (ix, iy) = index2tuple(i, shape)
         .map(|(i, j)| (i as isize, j as isize))
         .unwrap();
+    let [nx, ny] = shape;
+
+    sieve
+        .iter()
+        .zip(weights.iter())
+        .filter_map(|((ix_add, iy_add), &w)| {
+            match (
+                mode_x.apply(ix + ix_add, nx as isize),
+                mode_y.apply(iy + iy_add, ny as isize),
+            ) {
+                (Ok(Some(jx)), Ok(Some(jy))) => Some((tuple2index(jx, jy, shape), w)),
+                (Ok(None), _) | (_, Ok(None)) => None,
+                _ => Some((None, w)),
+            }
+        })
+        .collect()
+}
 
+/// Get the kernel weight of every candidate in the sieve.
+fn get_sieve_weights(
+    sieve: &[(isize, isize)],
+    radius: f64,
+    [dx, dy, _]: Vec3,
+    kernel: Kernel,
+) -> Vec<f64> {
     sieve
         .iter()
-        .filter_map(|(ix_add, iy_add)| tuple2index(ix + ix_add, iy + iy_add, shape))
+        .map(|&(ix, iy)| {
+            let distance = ((ix as f64 * dx).powi(2) + (iy as f64 * dy).powi(2)).sqrt();
+            kernel.weight(distance, radius)
+        })
         .collect()
 }
 
@@ -66,7 +496,16 @@ fn get_bin_neighbours(i: usize, shape: Shape, sieve: &Vec<(isize, isize)>) -> Ve
 ///
 /// # Bugs
 /// Currently assumes that the bin size is equal along x and y.
-fn get_averaging_bin_sieve(radius: f64, [dx, dy, _]: Vec3) -> Vec<(isize, isize)> {
+fn get_averaging_bin_sieve(radius: f64, [dx, dy, _]: Vec3, kernel: Kernel) -> Vec<(isize, isize)> {
+    if kernel == Kernel::Box {
+        let nx = get_box_half_width(radius, dx) as isize;
+        let ny = get_box_half_width(radius, dy) as isize;
+
+        return (-nx..=nx)
+            .flat_map(|ix| (-ny..=ny).map(move |iy| (ix, iy)))
+            .collect();
+    }
+
     let nx = (radius / dx).ceil() as isize;
     let ny = (radius / dy).ceil() as isize;
 
@@ -91,27 +530,210 @@ fn get_averaging_bin_sieve(radius: f64, [dx, dy, _]: Vec3) -> Vec<(isize, isize)
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{generate::DensMapBuilder, transform::RebinMode};
+
+    fn unweighted(bins: &[Option<usize>]) -> Vec<(Option<usize>, f64)> {
+        bins.iter().map(|&i| (i, 1.0)).collect()
+    }
+
+    fn indices(neighbours: Vec<(Option<usize>, f64)>) -> Vec<Option<usize>> {
+        neighbours.into_iter().map(|(i, _)| i).collect()
+    }
 
     #[test]
     fn test_average_is_calculated_from_correct_bin_indices() {
         let data = vec![10.0, 20.0, 30.0];
 
-        assert_eq!(0.0, average_value_of_bins(&data, &vec![]));
-        assert_eq!(10.0, average_value_of_bins(&data, &vec![0]));
-        assert_eq!(15.0, average_value_of_bins(&data, &vec![0, 1]));
-        assert_eq!(20.0, average_value_of_bins(&data, &vec![0, 2]));
-        assert_eq!(20.0, average_value_of_bins(&data, &vec![0, 1, 2]));
+        assert_eq!(0.0, average_value_of_bins(&data, &unweighted(&[])));
+        assert_eq!(10.0, average_value_of_bins(&data, &unweighted(&[Some(0)])));
+        assert_eq!(
+            15.0,
+            average_value_of_bins(&data, &unweighted(&[Some(0), Some(1)]))
+        );
+        assert_eq!(
+            20.0,
+            average_value_of_bins(&data, &unweighted(&[Some(0), Some(2)]))
+        );
+        assert_eq!(
+            20.0,
+            average_value_of_bins(&data, &unweighted(&[Some(0), Some(1), Some(2)]))
+        );
+        assert_eq!(
+            5.0,
+            average_value_of_bins(&data, &unweighted(&[Some(0), None]))
+        );
+    }
+
+    #[test]
+    fn test_median_is_calculated_from_correct_bin_indices() {
+        let data = vec![10.0, 20.0, 1000.0];
+
+        assert_eq!(0.0, median_value_of_bins(&data, &unweighted(&[])));
+        assert_eq!(
+            20.0,
+            median_value_of_bins(&data, &unweighted(&[Some(0), Some(1), Some(2)]))
+        );
+        assert_eq!(
+            15.0,
+            median_value_of_bins(&data, &unweighted(&[Some(0), Some(1)]))
+        );
+        assert_eq!(
+            10.0,
+            median_value_of_bins(&data, &unweighted(&[None, Some(0), Some(2)]))
+        );
+    }
+
+    #[test]
+    fn test_median_leaves_out_values_which_are_not_finite() {
+        let data = vec![10.0, f64::NAN, 30.0, f64::INFINITY];
+
+        assert_eq!(
+            20.0,
+            median_value_of_bins(&data, &unweighted(&[Some(0), Some(1), Some(2), Some(3)]))
+        );
+        assert_eq!(0.0, median_value_of_bins(&data, &unweighted(&[Some(1)])));
+    }
+
+    #[test]
+    fn test_weighted_average_and_median_of_bins() {
+        let data = vec![10.0, 20.0, 1000.0];
+
+        assert_eq!(
+            12.5,
+            average_value_of_bins(&data, &[(Some(0), 3.0), (Some(1), 1.0)])
+        );
+        assert_eq!(
+            10.0,
+            median_value_of_bins(&data, &[(Some(0), 3.0), (Some(1), 1.0), (Some(2), 1.0)])
+        );
+        assert_eq!(
+            1000.0,
+            median_value_of_bins(&data, &[(Some(0), 1.0), (Some(2), 2.0), (Some(1), 0.0)])
+        );
+    }
+
+    #[test]
+    fn test_weighted_kernels_decrease_with_distance_from_center() {
+        let sieve = get_averaging_bin_sieve(2.0, [1.0, 1.0, 0.0], Kernel::Tent);
+        assert_eq!(
+            get_averaging_bin_sieve(2.0, [1.0, 1.0, 0.0], Kernel::Disc),
+            sieve
+        );
+
+        let tent = get_sieve_weights(&sieve, 2.0, [1.0, 1.0, 0.0], Kernel::Tent);
+        let epanechnikov = get_sieve_weights(&sieve, 2.0, [1.0, 1.0, 0.0], Kernel::Epanechnikov);
+
+        let weight_at =
+            |weights: &[f64], offset| weights[sieve.iter().position(|&o| o == offset).unwrap()];
+
+        assert_eq!(1.0, weight_at(&tent, (0, 0)));
+        assert_eq!(0.5, weight_at(&tent, (1, 0)));
+        assert_eq!(0.0, weight_at(&tent, (0, -2)));
+
+        assert_eq!(1.0, weight_at(&epanechnikov, (0, 0)));
+        assert_eq!(0.75, weight_at(&epanechnikov, (0, 1)));
+        assert!((weight_at(&epanechnikov, (1, 1)) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_median_filter_removes_single_hot_bin() {
+        let mut data = vec![1.0; 25];
+        data[12] = 100.0;
+
+        let densmap = DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [5, 5],
+            center: [2.5, 2.5],
+            data,
+        };
+
+        let options = SmoothingOptions {
+            filter: Filter::Median,
+            ..SmoothingOptions::default()
+        };
+
+        let smoothed = smoothen_data_of_bins_within_radius(densmap, 1.0, options);
+
+        assert!(smoothed.data.iter().all(|&v| v == 1.0));
     }
 
     #[test]
     fn test_getting_bin_neighbours_only_includes_bins_within_the_system() {
         let sieve = vec![(-1, -1), (0, 0), (1, 1)];
         let shape = [2, 2];
+        let weights = vec![1.0; sieve.len()];
+        let edge_modes = [EdgeMode::Truncate, EdgeMode::Truncate];
+
+        assert_eq!(
+            vec![Some(0), Some(3)],
+            indices(get_bin_neighbours(0, shape, &sieve, &weights, edge_modes))
+        );
+        assert_eq!(
+            vec![Some(1)],
+            indices(get_bin_neighbours(1, shape, &sieve, &weights, edge_modes))
+        );
+        assert_eq!(
+            vec![Some(2)],
+            indices(get_bin_neighbours(2, shape, &sieve, &weights, edge_modes))
+        );
+        assert_eq!(
+            vec![Some(0), Some(3)],
+            indices(get_bin_neighbours(3, shape, &sieve, &weights, edge_modes))
+        );
+    }
+
+    #[test]
+    fn test_getting_bin_neighbours_with_edge_modes() {
+        let sieve = vec![(-2, 0), (-1, 0), (0, 0), (0, 1)];
+        let shape = [3, 2];
+        let weights = vec![1.0; sieve.len()];
+        let neighbours = |i, mode_x, mode_y| {
+            indices(get_bin_neighbours(
+                i,
+                shape,
+                &sieve,
+                &weights,
+                [mode_x, mode_y],
+            ))
+        };
+
+        assert_eq!(
+            vec![Some(1), Some(2), Some(0), Some(3)],
+            neighbours(0, EdgeMode::Wrap, EdgeMode::Truncate)
+        );
+        assert_eq!(
+            vec![Some(1), Some(0), Some(0), Some(3)],
+            neighbours(0, EdgeMode::Mirror, EdgeMode::Truncate)
+        );
+        assert_eq!(
+            vec![Some(0), Some(0), Some(0), Some(3)],
+            neighbours(0, EdgeMode::Clamp, EdgeMode::Truncate)
+        );
+        assert_eq!(
+            vec![None, None, Some(0), Some(3)],
+            neighbours(0, EdgeMode::ZeroPad, EdgeMode::Truncate)
+        );
 
-        assert_eq!(vec![0, 3], get_bin_neighbours(0, shape, &sieve));
-        assert_eq!(vec![1], get_bin_neighbours(1, shape, &sieve));
-        assert_eq!(vec![2], get_bin_neighbours(2, shape, &sieve));
-        assert_eq!(vec![0, 3], get_bin_neighbours(3, shape, &sieve));
+        // Bins which are skipped along one axis are skipped even if padded along the other
+        assert_eq!(
+            vec![Some(3)],
+            indices(get_bin_neighbours(
+                3,
+                shape,
+                &[(-1, 1), (0, 0)],
+                &[1.0, 1.0],
+                [EdgeMode::ZeroPad, EdgeMode::Truncate]
+            ))
+        );
+        assert_eq!(
+            vec![Some(3), Some(4), None],
+            neighbours(4, EdgeMode::Truncate, EdgeMode::ZeroPad)
+        );
+        assert_eq!(
+            vec![None, None, Some(3)],
+            neighbours(3, EdgeMode::ZeroPad, EdgeMode::Truncate)
+        );
     }
 
     #[test]
@@ -119,7 +741,7 @@ mod tests {
         let bin_size = [1.0, 1.0, 0.0];
         let radius = 1.0;
 
-        let bins = get_averaging_bin_sieve(radius, bin_size);
+        let bins = get_averaging_bin_sieve(radius, bin_size, Kernel::Disc);
 
         assert_eq!(5, bins.len());
         assert!(bins.contains(&(0, 0)));
@@ -134,7 +756,7 @@ mod tests {
         let bin_size = [0.5, 0.5, 0.0];
         let radius = 0.75;
 
-        let bins = get_averaging_bin_sieve(radius, bin_size);
+        let bins = get_averaging_bin_sieve(radius, bin_size, Kernel::Disc);
 
         assert_eq!(9, bins.len());
         assert!(bins.contains(&(-1, -1)));
@@ -142,4 +764,106 @@ mod tests {
         assert!(bins.contains(&(1, -1)));
         assert!(bins.contains(&(1, 1)));
     }
+
+    #[test]
+    fn test_box_sieve_includes_all_bins_within_square() {
+        let bins = get_averaging_bin_sieve(1.0, [0.5, 1.0, 0.0], Kernel::Box);
+
+        assert_eq!(15, bins.len());
+        assert!(bins.contains(&(-2, -1)));
+        assert!(bins.contains(&(2, 1)));
+        assert!(!bins.contains(&(3, 0)));
+        assert!(!bins.contains(&(0, 2)));
+    }
+
+    #[test]
+    fn test_separable_box_average_equals_average_over_neighbour_lists() {
+        let densmap = DensMap {
+            bin_size: [0.5, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [7, 5],
+            center: [0.0, 0.0],
+            data: (0..35).map(|i| ((i * 7919) % 13) as f64).collect(),
+        };
+
+        let modes = [
+            EdgeMode::Truncate,
+            EdgeMode::Mirror,
+            EdgeMode::Clamp,
+            EdgeMode::Wrap,
+            EdgeMode::ZeroPad,
+        ];
+
+        for &mode_x in modes.iter() {
+            for &mode_y in modes.iter() {
+                let edge_modes = [mode_x, mode_y];
+                let radius = 1.2;
+
+                let separable = get_box_averaged_system(
+                    &densmap.data,
+                    radius,
+                    densmap.bin_size,
+                    densmap.shape,
+                    edge_modes,
+                );
+
+                let neighbours = get_system_bin_neighbours(
+                    radius,
+                    densmap.bin_size,
+                    densmap.shape,
+                    Kernel::Box,
+                    edge_modes,
+                );
+                let direct = get_averaged_system(&densmap.data, &neighbours, Filter::Mean);
+
+                for (a, b) in separable.iter().zip(direct.iter()) {
+                    assert!((a - b).abs() < 1e-9, "{:?}: {} != {}", edge_modes, a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_smoother_reuses_neighbours_until_grid_changes() {
+        let densmap = DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [4, 3],
+            center: [2.0, 1.5],
+            data: (0..12).map(|i| i as f64).collect(),
+        };
+
+        let mut smoother = Smoother::new(1.0, SmoothingOptions::default());
+
+        for densmap in [
+            densmap.clone(),
+            densmap.rebin(2, 1, RebinMode::Sum).unwrap(),
+            densmap.clone(),
+        ] {
+            let expected = smoothen_data_of_bins_within_radius(
+                densmap.clone(),
+                1.0,
+                SmoothingOptions::default(),
+            );
+            assert_eq!(expected, smoother.smoothen(densmap.clone()));
+
+            let cache = smoother.cache.as_ref().unwrap();
+            assert_eq!(densmap.shape, cache.shape);
+            assert_eq!(densmap.bin_size, cache.bin_size);
+        }
+    }
+
+    #[test]
+    fn test_disabled_smoother_returns_maps_as_they_are() {
+        let densmap = DensMapBuilder::new()
+            .bin_size([1.0, 1.0, 0.0])
+            .shape([4, 3])
+            .from_data((0..12).map(|i| i as f64).collect())
+            .unwrap();
+
+        let mut smoother = Smoother::disabled(SmoothingOptions::default());
+
+        assert_eq!(densmap, smoother.smoothen(densmap.clone()));
+        assert!(smoother.cache.is_none());
+    }
 }
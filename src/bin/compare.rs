@@ -0,0 +1,114 @@
+use serde_json::Value;
+use structopt::StructOpt;
+
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use densmap::{
+    analysis::comparison::compare_series,
+    graphdata::{read_xvg, write_xvg, Graph},
+    plot::write_svg_plot,
+};
+
+use crate::failure::Failure;
+
+#[derive(Debug, StructOpt)]
+/// Compare the droplet radius of two analysis runs
+pub struct CompareArgs {
+    #[structopt(
+        parse(from_os_str),
+        long_help = "Droplet radius file of the first run, or its manifest as written with '--manifest', in which case the radius file of the manifest is read."
+    )]
+    /// Radius file or manifest of the first run
+    a: PathBuf,
+
+    #[structopt(parse(from_os_str))]
+    /// Radius file or manifest of the second run
+    b: PathBuf,
+
+    #[structopt(short = "o", long = "output", value_name = "path", parse(from_os_str))]
+    /// Output file name for the difference of the first radius minus the second over time
+    output: Option<PathBuf>,
+
+    #[structopt(long = "plot", value_name = "path", parse(from_os_str))]
+    /// Output file name for an SVG plot of the difference over time
+    plot: Option<PathBuf>,
+
+    #[structopt(short = "t", long = "tolerance", value_name = "rmsd")]
+    /// Exit with an error if the root mean square deviation exceeds this value
+    tolerance: Option<f64>,
+}
+
+pub fn run_compare(args: CompareArgs) -> Result<(), io::Error> {
+    let a = read_radius(&args.a)?;
+    let b = read_radius(&args.b)?;
+
+    let comparison = compare_series(&a, &b).ok_or_else(|| {
+        Failure::NoFrames.error(format!(
+            "'{}' and '{}' have no times in common",
+            args.a.display(),
+            args.b.display()
+        ))
+    })?;
+
+    let times = &comparison.difference.x;
+    let (time, max_deviation) = comparison.max_deviation;
+
+    println!(
+        "Common times:             {} ({} to {})",
+        times.len(),
+        times[0],
+        times[times.len() - 1]
+    );
+    println!("RMSD:                     {}", comparison.rmsd);
+    println!("Mean difference:          {}", comparison.mean_difference);
+    println!("Max deviation:            {} (t = {})", max_deviation, time);
+
+    if let Some(path) = &args.output {
+        write_xvg(path, &comparison.difference)?;
+    }
+
+    if let Some(path) = &args.plot {
+        write_svg_plot(path, &comparison.difference, "Time", "Difference")?;
+    }
+
+    if let Some(tolerance) = args.tolerance {
+        if comparison.rmsd > tolerance {
+            return Err(Failure::Analysis.error(format!(
+                "root mean square deviation {} exceeds the tolerance {}",
+                comparison.rmsd, tolerance
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the droplet radius of a run from its radius file or manifest.
+///
+/// The radius file of a manifest is relative to the working directory of the run unless
+/// it is absolute.
+fn read_radius(path: &Path) -> Result<Graph, io::Error> {
+    if path.extension().map(|ext| ext != "json").unwrap_or(true) {
+        return read_xvg(path);
+    }
+
+    let manifest: Value = serde_json::from_reader(File::open(path)?)?;
+
+    let get_entry = |key| {
+        manifest[key].as_str().ok_or_else(|| {
+            Failure::BadArguments.file_error(
+                path,
+                format!("no entry '{}' in the manifest '{}'", key, path.display()),
+            )
+        })
+    };
+
+    let radius = get_entry("radius")?;
+    let dir = get_entry("working_directory")?;
+
+    read_xvg(&Path::new(dir).join(radius))
+}
@@ -0,0 +1,609 @@
+use rayon::prelude::*;
+use structopt::StructOpt;
+
+use std::{
+    ffi::{OsStr, OsString},
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use densmap::{
+    analysis::{
+        radial_density::{
+            get_azimuthal_average_map, get_radial_density_distribution,
+            get_radius_from_distribution,
+        },
+        sample_interface::sample_interface,
+    },
+    container::{Container, ContainerWriter},
+    densmap::{
+        is_stdio, read_densmap_with_metadata, write_densmap_with_metadata, DensMap, GridOptions,
+        LengthUnit, Metadata, Vec2,
+    },
+    graphdata::XYData,
+    npy::{write_npy, write_npy_metadata},
+    transform::{RebinMode, Transform},
+    vtk::{write_vtk, write_vtk_contact_line, VtkFormat},
+};
+
+use crate::{failure::Failure, grid::GridArgs, progress::ProgressArgs, verify::VerifyArgs};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// File formats which density maps can be converted to.
+enum Format {
+    DensMap,
+    Npy,
+    Vtk,
+    Container,
+}
+
+impl Format {
+    /// Get the format from the extension of a path, defaulting to density maps.
+    fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("npy") => Format::Npy,
+            Some("vtk") | Some("vti") => Format::Vtk,
+            Some("dmc") => Format::Container,
+            _ => Format::DensMap,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "densmap" => Ok(Format::DensMap),
+            "npy" => Ok(Format::Npy),
+            "vtk" => Ok(Format::Vtk),
+            "container" => Ok(Format::Container),
+            _ => Err(format!(
+                "invalid format '{}': must be 'densmap', 'npy', 'vtk' or 'container'",
+                s
+            )),
+        }
+    }
+}
+
+/// Extensions of the files which are converted from a directory.
+const BATCH_INPUT_EXTENSIONS: [&str; 6] = ["dat", "gz", "npy", "npz", "txt", "asc"];
+
+/// Number of maps which are converted in parallel before they are added to a container.
+const BATCH_CHUNK_SIZE: usize = 64;
+
+#[derive(Debug, StructOpt)]
+/// Convert a density map, optionally transforming it
+pub struct ConvertArgs {
+    #[structopt(
+        parse(from_os_str),
+        long_help = "Density map to convert, or '-' to read it from stdin. If this is a directory or a container (with the extension '.dmc') all of its maps are converted in parallel, either into the output directory or into a single container if the output path has the extension '.dmc'."
+    )]
+    /// Density map, directory or container to convert
+    input: PathBuf,
+
+    #[structopt(parse(from_os_str))]
+    /// Output path for converted density map, or '-' to write it to stdout
+    output: PathBuf,
+
+    #[structopt(
+        long = "to",
+        value_name = "densmap|npy|vtk|container",
+        long_help = "Format to convert to. A NumPy array is written along with a JSON file of the same name which contains the grid and time of the map. VTK images are written in the XML format if the output path has the extension '.vti' and in the legacy format otherwise. By default the format is chosen from the extension of the output path."
+    )]
+    /// Format to convert to
+    to: Option<Format>,
+
+    #[structopt(
+        long = "ext",
+        value_name = "ext",
+        long_help = "Extension of the converted files when converting a directory or container into a directory, eg. 'dat', 'dat.gz' or 'npy'. Files are compressed with gzip if it ends with 'gz'. By default files converted from a directory keep their extension and frames of a container are written as 'dat'."
+    )]
+    /// Extension of converted files when converting a directory or container
+    ext: Option<String>,
+
+    #[structopt(short = "j", long = "jobs", value_name = "n")]
+    /// Number of parallel workers when converting a directory or container [default: all cores]
+    jobs: Option<usize>,
+
+    #[structopt(
+        long = "rebin",
+        value_name = "fx,fy",
+        parse(try_from_str = "parse_factors")
+    )]
+    /// Combine blocks of fx by fy bins into single bins
+    rebin: Option<[u64; 2]>,
+
+    #[structopt(long = "rebin_mode", default_value = "mean", value_name = "sum|mean")]
+    /// Whether combined bins are summed or averaged when rebinning
+    rebin_mode: RebinMode,
+
+    #[structopt(
+        long = "upsample",
+        value_name = "fx,fy",
+        parse(try_from_str = "parse_factors")
+    )]
+    /// Interpolate onto a grid which is finer by fx by fy
+    upsample: Option<[u64; 2]>,
+
+    #[structopt(long = "conserve_mass")]
+    /// Conserve the total mass when upsampling instead of the density scale
+    conserve_mass: bool,
+
+    #[structopt(
+        long = "transform",
+        value_name = "rot90|rot180|rot270|mirror-x|mirror-y|rotate:deg",
+        number_of_values = 1
+    )]
+    /// Rotate or mirror the map, applied in the given order (can be used multiple times)
+    transform: Vec<Transform>,
+
+    #[structopt(long = "symmetrize")]
+    /// Replace every bin with the azimuthal average density at its radius
+    symmetrize: bool,
+
+    #[structopt(
+        long = "center",
+        value_name = "x,y",
+        parse(try_from_str = "parse_point")
+    )]
+    /// Center point to symmetrize around instead of the droplet center of the map
+    center: Option<Vec2>,
+
+    #[structopt(
+        long = "vtk_contact_line",
+        value_name = "path",
+        parse(from_os_str),
+        long_help = "Write the contact line of the converted map as a closed VTK polyline to this path, in the XML format if it has the extension '.vtp' and in the legacy format otherwise."
+    )]
+    /// Output path for the contact line of the map as a VTK polyline
+    vtk_contact_line: Option<PathBuf>,
+
+    #[structopt(
+        long = "metadata",
+        value_name = "key=value",
+        number_of_values = 1,
+        parse(try_from_str = "parse_metadata"),
+        long_help = "Set a metadata entry of the converted maps, like the simulation name or force field (can be used multiple times). Entries read from the input are kept unless they are replaced. Metadata is only written to maps in the density map format."
+    )]
+    /// Set a metadata entry of the converted maps (can be used multiple times)
+    metadata: Vec<(String, String)>,
+
+    #[structopt(flatten)]
+    grid: GridArgs,
+
+    #[structopt(flatten)]
+    verify: VerifyArgs,
+
+    #[structopt(flatten)]
+    progress: ProgressArgs,
+}
+
+pub fn run_convert(args: ConvertArgs) -> Result<(), io::Error> {
+    if args.input.is_dir() || Format::from_path(&args.input) == Format::Container {
+        return run_batch_convert(&args);
+    }
+
+    let (densmap, time, metadata) =
+        read_densmap_with_metadata(&args.input, &args.verify.apply(args.grid.options()))?;
+    let densmap = convert_densmap(densmap, &args)?;
+    let metadata = update_metadata(metadata, &args);
+
+    let format = args.to.unwrap_or_else(|| Format::from_path(&args.output));
+
+    if is_stdio(&args.output) && format != Format::DensMap {
+        return Err(invalid_input(
+            "only the density map format can be written to stdout",
+        ));
+    }
+
+    write_converted(&args.output, &densmap, time, &metadata, format)?;
+
+    if let Some(path) = &args.vtk_contact_line {
+        let points = get_contact_line_points(&densmap)?;
+        write_vtk_contact_line(path, &points, time, VtkFormat::from_path(path))?;
+    }
+
+    Ok(())
+}
+
+/// Apply the rebinning, upsampling, transforms and symmetrization to a map.
+///
+/// Factors of the rebinning or upsampling which do not fit the map are bad arguments.
+fn convert_densmap(densmap: DensMap, args: &ConvertArgs) -> Result<DensMap, io::Error> {
+    let convert_error =
+        |msg| Failure::BadArguments.error(format!("could not convert density map: {}", msg));
+
+    let densmap = match args.rebin {
+        Some([fx, fy]) => densmap.rebin(fx, fy, args.rebin_mode),
+        None => Ok(densmap),
+    }
+    .map_err(convert_error)?;
+
+    let densmap = match args.upsample {
+        Some([fx, fy]) => densmap.upsample(fx, fy, args.conserve_mass),
+        None => Ok(densmap),
+    }
+    .map_err(convert_error)?;
+
+    let densmap = args
+        .transform
+        .iter()
+        .fold(densmap, |densmap, &transform| densmap.transform(transform));
+
+    if args.symmetrize {
+        let center = args.center.unwrap_or(densmap.center);
+        Ok(get_azimuthal_average_map(&DensMap { center, ..densmap }))
+    } else {
+        Ok(densmap)
+    }
+}
+
+/// Set the metadata entries given as options.
+fn update_metadata(mut metadata: Metadata, args: &ConvertArgs) -> Metadata {
+    metadata.extend(args.metadata.iter().cloned());
+    metadata
+}
+
+/// Write a converted map to the path in the given format. The metadata is only written
+/// to maps in the density map format.
+fn write_converted(
+    path: &Path,
+    densmap: &DensMap,
+    time: f64,
+    metadata: &Metadata,
+    format: Format,
+) -> Result<(), io::Error> {
+    match format {
+        Format::DensMap => write_densmap_with_metadata(path, densmap, time, metadata),
+        Format::Npy => {
+            write_npy(path, densmap)?;
+            write_npy_metadata(&path.with_extension("json"), densmap, time)
+        }
+        Format::Vtk => write_vtk(path, densmap, time, VtkFormat::from_path(path)),
+        Format::Container => {
+            let mut writer = ContainerWriter::create(path)?;
+            writer.push(densmap, time)?;
+            writer.finish()
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// A map to convert in batch mode, along with the file name stem of its output.
+enum BatchInput {
+    File { path: PathBuf, stem: OsString },
+    Frame { index: usize, stem: OsString },
+}
+
+impl BatchInput {
+    fn stem(&self) -> &OsStr {
+        match self {
+            BatchInput::File { stem, .. } | BatchInput::Frame { stem, .. } => stem,
+        }
+    }
+
+    /// Add the map to an error of its conversion, as its file or the frame of the
+    /// container which is converted.
+    fn error(&self, source: &Path, err: io::Error) -> io::Error {
+        let (path, name) = match self {
+            BatchInput::File { path, .. } => (path.as_path(), format!("'{}'", path.display())),
+            BatchInput::Frame { index, .. } => {
+                (source, format!("frame {} of '{}'", index, source.display()))
+            }
+        };
+
+        Failure::of(&err).file_error(path, format!("could not convert {}: {}", name, err))
+    }
+}
+
+/// Convert all maps of a directory or container, in parallel.
+fn run_batch_convert(args: &ConvertArgs) -> Result<(), io::Error> {
+    if args.vtk_contact_line.is_some() {
+        return Err(invalid_input(
+            "the contact line can only be written when converting a single map",
+        ));
+    }
+
+    let inputs = get_batch_inputs(&args.input)?;
+
+    if inputs.is_empty() {
+        return Err(Failure::NoFrames.error(format!(
+            "no density maps found in '{}'",
+            args.input.display()
+        )));
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs.unwrap_or(0))
+        .build()
+        .map_err(|err| invalid_input(&err.to_string()))?;
+
+    let pb = Mutex::new(args.progress.start(inputs.len()));
+
+    let grid = args.verify.apply(args.grid.options());
+    let read_and_convert = |container: &mut Option<Container>, input: &BatchInput| {
+        let result = read_batch_input(&args.input, container, input, &grid).and_then(
+            |(densmap, time, metadata)| {
+                Ok((
+                    convert_densmap(densmap, args)?,
+                    time,
+                    update_metadata(metadata, args),
+                ))
+            },
+        );
+
+        pb.lock().unwrap().inc();
+        result.map_err(|err| input.error(&args.input, err))
+    };
+
+    let to_container = args.to == Some(Format::Container)
+        || (args.to.is_none() && Format::from_path(&args.output) == Format::Container);
+
+    pool.install(|| {
+        if to_container {
+            let mut writer = ContainerWriter::create(&args.output)?;
+
+            // Maps are converted in parallel but added to the container in order.
+            for chunk in inputs.chunks(BATCH_CHUNK_SIZE) {
+                let frames = chunk
+                    .par_iter()
+                    .map_init(|| None, read_and_convert)
+                    .collect::<Result<Vec<_>, io::Error>>()?;
+
+                for (densmap, time, _) in frames {
+                    writer.push(&densmap, time)?;
+                }
+            }
+
+            writer.finish()
+        } else {
+            fs::create_dir_all(&args.output)?;
+
+            // Every map is converted, after which the maps which failed are reported.
+            let errors = inputs
+                .par_iter()
+                .map_init(
+                    || None,
+                    |container, input| {
+                        let (densmap, time, metadata) = read_and_convert(container, input)?;
+                        let path = get_batch_output_path(args, input);
+                        let format = args.to.unwrap_or_else(|| Format::from_path(&path));
+
+                        write_converted(&path, &densmap, time, &metadata, format)
+                            .map_err(|err| input.error(&args.input, err))
+                    },
+                )
+                .filter_map(|result| result.err())
+                .collect::<Vec<_>>();
+
+            for err in &errors {
+                eprintln!("{}", err);
+            }
+
+            match errors.first() {
+                Some(err) => Err(Failure::of(err).error(format!(
+                    "could not convert {} of {} density maps",
+                    errors.len(),
+                    inputs.len()
+                ))),
+                None => Ok(()),
+            }
+        }
+    })?;
+
+    pb.into_inner()
+        .unwrap()
+        .finish("Converted all density maps.");
+
+    Ok(())
+}
+
+/// Get the maps of a directory, in order of file name, or all frames of a container.
+fn get_batch_inputs(input: &Path) -> Result<Vec<BatchInput>, io::Error> {
+    if input.is_dir() {
+        let mut paths = fs::read_dir(input)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .map(|ext| BATCH_INPUT_EXTENSIONS.contains(&ext))
+                        .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+
+        paths.sort();
+
+        Ok(paths
+            .into_iter()
+            .map(|path| {
+                let stem = get_file_stem(&path);
+                BatchInput::File { path, stem }
+            })
+            .collect())
+    } else {
+        let container = Container::open(input)?;
+        let base = get_file_stem(input);
+
+        Ok(container
+            .index()
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let mut stem = base.clone();
+                stem.push(format!("{:09.3}ps", entry.time));
+                BatchInput::Frame { index, stem }
+            })
+            .collect())
+    }
+}
+
+fn read_batch_input(
+    source: &Path,
+    container: &mut Option<Container>,
+    input: &BatchInput,
+    grid: &GridOptions,
+) -> Result<(DensMap, f64, Metadata), io::Error> {
+    match input {
+        BatchInput::File { path, .. } => read_densmap_with_metadata(path, grid),
+        BatchInput::Frame { index, .. } => {
+            // Every worker opens the container once to read frames from it.
+            if container.is_none() {
+                *container =
+                    Some(Container::open(source)?.with_checksum_verification(grid.verify_checksum));
+            }
+
+            container
+                .as_mut()
+                .unwrap()
+                .read_frame(*index)
+                .map(|(densmap, time)| (grid.apply(densmap), time, Metadata::new()))
+        }
+    }
+}
+
+/// Get the output path of a map in batch mode, keeping the extension of input files
+/// if no other is given.
+fn get_batch_output_path(args: &ConvertArgs, input: &BatchInput) -> PathBuf {
+    let ext = match (&args.ext, args.to, input) {
+        (Some(ext), _, _) => OsString::from(ext.trim_start_matches('.')),
+        (None, Some(Format::Npy), _) => OsString::from("npy"),
+        (None, Some(Format::Vtk), _) => OsString::from("vtk"),
+        (None, _, BatchInput::File { path, .. }) => get_file_extension(path),
+        (None, _, BatchInput::Frame { .. }) => OsString::from("dat"),
+    };
+
+    let mut name = input.stem().to_os_string();
+    name.push(".");
+    name.push(ext);
+
+    args.output.join(name)
+}
+
+/// Get the file name of a path without its extension, and without '.gz' before it.
+fn get_file_stem(path: &Path) -> OsString {
+    let path = if is_gzipped(path) {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    };
+
+    path.file_stem().unwrap_or_default().to_os_string()
+}
+
+/// Get the extension of a path, including the one before '.gz', or 'dat' if it has none.
+fn get_file_extension(path: &Path) -> OsString {
+    let inner = path.with_extension("");
+
+    match inner.extension() {
+        Some(ext) if is_gzipped(path) => {
+            let mut ext = ext.to_os_string();
+            ext.push(".gz");
+            ext
+        }
+        _ => path
+            .extension()
+            .unwrap_or_else(|| OsStr::new("dat"))
+            .to_os_string(),
+    }
+}
+
+fn is_gzipped(path: &Path) -> bool {
+    path.extension().map(|ext| ext == "gz").unwrap_or(false)
+}
+
+fn invalid_input(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Sample the contact line of the map and get its points in absolute coordinates,
+/// which fails if the droplet radius cannot be determined.
+fn get_contact_line_points(densmap: &DensMap) -> Result<Vec<Vec2>, io::Error> {
+    let radius =
+        get_radius_from_distribution(get_radial_density_distribution(densmap)).map_err(|msg| {
+            Failure::Analysis.error(format!("could not sample the contact line: {}", msg))
+        })?;
+
+    let [x0, y0] = densmap.center;
+
+    let contact_line = sample_interface(densmap, radius, LengthUnit::Nm).to_carthesian();
+
+    Ok(contact_line
+        .x()
+        .iter()
+        .zip(contact_line.y().iter())
+        .map(|(x, y)| [x0 + x, y0 + y])
+        .collect())
+}
+
+/// Parse a pair of factors given as 'fx,fy'.
+fn parse_factors(s: &str) -> Result<[u64; 2], String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("could not parse factors '{}': {}", s, err))?;
+
+    match values.as_slice() {
+        &[fx, fy] => Ok([fx, fy]),
+        _ => Err(format!("factors '{}' must be given as 'fx,fy'", s)),
+    }
+}
+
+/// Parse a metadata entry given as 'key=value'.
+pub fn parse_metadata(s: &str) -> Result<(String, String), String> {
+    match s.find('=') {
+        Some(i) if i > 0 => Ok((s[..i].to_string(), s[i + 1..].to_string())),
+        _ => Err(format!("metadata '{}' must be given as 'key=value'", s)),
+    }
+}
+
+/// Parse a point given as 'x,y'.
+pub fn parse_point(s: &str) -> Result<Vec2, String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("could not parse point '{}': {}", s, err))?;
+
+    match values.as_slice() {
+        &[x, y] => Ok([x, y]),
+        _ => Err(format!("point '{}' must be given as 'x,y'", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_stem_and_extension_keep_gz_and_non_utf8_names() {
+        assert_eq!(
+            OsStr::new("map"),
+            get_file_stem(Path::new("dir/map.dat.gz"))
+        );
+        assert_eq!(
+            OsStr::new("dat.gz"),
+            get_file_extension(Path::new("dir/map.dat.gz"))
+        );
+        assert_eq!(OsStr::new("map"), get_file_stem(Path::new("dir/map.gz")));
+        assert_eq!(
+            OsStr::new("gz"),
+            get_file_extension(Path::new("dir/map.gz"))
+        );
+        assert_eq!(OsStr::new("npy"), get_file_extension(Path::new("map.npy")));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let path = Path::new(OsStr::from_bytes(b"dir/m\xffp.dat"));
+            assert_eq!(OsStr::from_bytes(b"m\xffp"), get_file_stem(path));
+        }
+    }
+}
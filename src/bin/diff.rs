@@ -0,0 +1,86 @@
+use structopt::StructOpt;
+
+use std::{io, path::PathBuf};
+
+use densmap::densmap::{read_densmap_with_grid, write_densmap};
+
+use crate::{failure::Failure, verify::VerifyArgs};
+
+#[derive(Debug, StructOpt)]
+/// Compare two density maps bin by bin
+pub struct DiffArgs {
+    #[structopt(parse(from_os_str))]
+    /// First density map
+    a: PathBuf,
+
+    #[structopt(parse(from_os_str))]
+    /// Second density map
+    b: PathBuf,
+
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    /// Write the difference of the first map minus the second to this path
+    output: Option<PathBuf>,
+
+    #[structopt(short = "t", long = "tolerance")]
+    /// Exit with an error if the largest absolute difference exceeds this value
+    tolerance: Option<f64>,
+
+    #[structopt(flatten)]
+    verify: VerifyArgs,
+}
+
+pub fn run_diff(args: DiffArgs) -> Result<(), io::Error> {
+    let grid = args.verify.options();
+
+    let (a, time_a) = read_densmap_with_grid(&args.a, &grid)?;
+    let (b, time_b) = read_densmap_with_grid(&args.b, &grid)?;
+
+    let diff = a
+        .compare(&b)
+        .map_err(|err| Failure::BadArguments.error(format!("could not compare maps: {}", err)))?;
+
+    println!("Bins:                     {}", a.data.len());
+    println!("Differing bins:           {}", diff.num_differing);
+    println!("Max absolute difference:  {}", diff.max_abs);
+    println!("Mean absolute difference: {}", diff.mean_abs);
+
+    if let Some(i) = diff.max_index {
+        let nx = a.shape[0] as usize;
+        let (ix, iy) = (i % nx, i / nx);
+
+        println!(
+            "Largest difference at bin ({}, {}): {} and {}",
+            ix, iy, a.data[i], b.data[i]
+        );
+    }
+
+    if a.center != b.center {
+        println!(
+            "Centers differ: ({}, {}) and ({}, {})",
+            a.center[0], a.center[1], b.center[0], b.center[1]
+        );
+    }
+
+    if time_a != time_b {
+        println!("Times differ: {} and {}", time_a, time_b);
+    }
+
+    if let Some(path) = &args.output {
+        let difference = a.subtract(&b).map_err(|err| {
+            Failure::BadArguments.error(format!("could not subtract maps: {}", err))
+        })?;
+
+        write_densmap(path, &difference, time_a)?;
+    }
+
+    if let Some(tolerance) = args.tolerance {
+        if diff.max_abs > tolerance {
+            return Err(Failure::Analysis.error(format!(
+                "largest difference {} exceeds the tolerance {}",
+                diff.max_abs, tolerance
+            )));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use serde_json::json;
+
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Category of the failure of a command, which sets its exit code.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Failure {
+    /// Invalid arguments or options.
+    BadArguments,
+    /// No frames were found to analyze.
+    NoFrames,
+    /// A file could not be read or written.
+    Io,
+    /// The analysis of a frame failed.
+    Analysis,
+}
+
+impl Failure {
+    /// Get the exit code of the failure.
+    pub fn code(self) -> i32 {
+        match self {
+            Failure::BadArguments => 2,
+            Failure::NoFrames => 3,
+            Failure::Io => 4,
+            Failure::Analysis => 5,
+        }
+    }
+
+    /// Get the name of the failure in the error output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Failure::BadArguments => "bad_arguments",
+            Failure::NoFrames => "no_frames",
+            Failure::Io => "io",
+            Failure::Analysis => "analysis",
+        }
+    }
+
+    /// Create an error of this category.
+    pub fn error<T: fmt::Display>(self, message: T) -> io::Error {
+        self.new_error(None, message.to_string())
+    }
+
+    /// Create an error of this category for a file.
+    pub fn file_error<T: fmt::Display>(self, path: &Path, message: T) -> io::Error {
+        self.new_error(Some(path.to_path_buf()), message.to_string())
+    }
+
+    /// Get the category of an error, from its kind if it was not created as a `Failure`.
+    pub fn of(err: &io::Error) -> Failure {
+        match get_categorized(err) {
+            Some(categorized) => categorized.failure,
+            None if err.kind() == io::ErrorKind::InvalidInput => Failure::BadArguments,
+            None => Failure::Io,
+        }
+    }
+
+    fn new_error(self, file: Option<PathBuf>, message: String) -> io::Error {
+        let kind = match self {
+            Failure::BadArguments => io::ErrorKind::InvalidInput,
+            Failure::NoFrames => io::ErrorKind::NotFound,
+            Failure::Io | Failure::Analysis => io::ErrorKind::Other,
+        };
+
+        io::Error::new(
+            kind,
+            CategorizedError {
+                failure: self,
+                file,
+                message,
+            },
+        )
+    }
+}
+
+#[derive(Debug)]
+struct CategorizedError {
+    failure: Failure,
+    file: Option<PathBuf>,
+    message: String,
+}
+
+impl fmt::Display for CategorizedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for CategorizedError {}
+
+fn get_categorized(err: &io::Error) -> Option<&CategorizedError> {
+    err.get_ref()
+        .and_then(|inner| inner.downcast_ref::<CategorizedError>())
+}
+
+/// Write the outcome of a command as JSON, with its exit code and the files which
+/// failed: the frames which were skipped and the file which stopped the command.
+pub fn write_errors_json(
+    path: &Path,
+    result: &Result<(), io::Error>,
+    skipped: &[(PathBuf, String)],
+) -> Result<(), io::Error> {
+    let mut files = skipped
+        .iter()
+        .map(|(file, err)| {
+            json!({
+                "file": file.display().to_string(),
+                "error": err,
+                "skipped": true,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let error = match result {
+        Ok(()) => json!(null),
+        Err(err) => {
+            let failure = Failure::of(err);
+
+            if let Some(file) =
+                get_categorized(err).and_then(|categorized| categorized.file.as_ref())
+            {
+                files.push(json!({
+                    "file": file.display().to_string(),
+                    "error": err.to_string(),
+                    "skipped": false,
+                }));
+            }
+
+            json!({
+                "category": failure.name(),
+                "message": err.to_string(),
+            })
+        }
+    };
+
+    let exit_code = result
+        .as_ref()
+        .map(|_| 0)
+        .unwrap_or_else(|err| Failure::of(err).code());
+
+    let output = json!({
+        "exit_code": exit_code,
+        "error": error,
+        "files": files,
+    });
+
+    serde_json::to_writer_pretty(File::create(path)?, &output)?;
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use rand::{SeedableRng, StdRng};
+use structopt::StructOpt;
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use densmap::{
+    container::ContainerWriter,
+    densmap::{write_densmap_with_metadata, Metadata, Shape, Vec2, Vec3},
+    generate::{generate_droplet, DropletModel, RadiusSeries},
+};
+
+use crate::{convert::parse_point, grid::parse_bin_size};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Evolution of the droplet radius, with parameters given by separate options.
+enum Series {
+    Constant,
+    Spreading,
+    Oscillating,
+}
+
+impl FromStr for Series {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "constant" => Ok(Series::Constant),
+            "spreading" => Ok(Series::Spreading),
+            "oscillating" => Ok(Series::Oscillating),
+            _ => Err(format!(
+                "invalid series '{}': must be 'constant', 'spreading' or 'oscillating'",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+/// Generate density maps of idealized droplets with a known radius
+pub struct GenerateArgs {
+    #[structopt(
+        parse(from_os_str),
+        long_help = "Base output file name for the maps, which is followed by the time of every frame, eg. 'map' gives 'map00010.000ps.dat'. If the path has the extension '.dmc' all maps are written into a single container."
+    )]
+    /// Base output file name for the maps, or a container with the extension '.dmc'
+    output: PathBuf,
+
+    #[structopt(
+        long = "shape",
+        value_name = "nx,ny",
+        default_value = "80,80",
+        parse(try_from_str = "parse_shape")
+    )]
+    /// Number of bins along x and y
+    shape: Shape,
+
+    #[structopt(
+        long = "bin_size",
+        value_name = "dx,dy[,dz]",
+        default_value = "0.1,0.1",
+        parse(try_from_str = "parse_bin_size")
+    )]
+    /// Bin size of the maps
+    bin_size: Vec3,
+
+    #[structopt(
+        long = "center",
+        value_name = "x,y",
+        parse(try_from_str = "parse_point")
+    )]
+    /// Droplet center, by default the middle of the map
+    center: Option<Vec2>,
+
+    #[structopt(short = "r", long = "radius", default_value = "2.0")]
+    /// Base radius of the droplet at time 0
+    radius: f64,
+
+    #[structopt(short = "w", long = "interface_width", default_value = "0.2")]
+    /// Width of the tanh profile of the liquid-vapor interface
+    interface_width: f64,
+
+    #[structopt(long = "density", default_value = "1.0")]
+    /// Density inside of the droplet
+    density: f64,
+
+    #[structopt(long = "background", default_value = "0.0")]
+    /// Density of the vapor around the droplet
+    background: f64,
+
+    #[structopt(long = "noise", default_value = "0.0")]
+    /// Standard deviation of the Gaussian noise added to every bin, which can make bins negative
+    noise: f64,
+
+    #[structopt(long = "seed")]
+    /// Seed for the noise, which is random if not set
+    seed: Option<usize>,
+
+    #[structopt(short = "n", long = "num_frames", default_value = "1")]
+    /// Number of frames to generate
+    num_frames: usize,
+
+    #[structopt(long = "dt", default_value = "10.0")]
+    /// Time between frames
+    dt: f64,
+
+    #[structopt(
+        long = "series",
+        default_value = "constant",
+        raw(possible_values = r#"&["constant", "spreading", "oscillating"]"#),
+        long_help = "Evolution of the droplet radius with time. A spreading droplet follows Tanner's law, R(t) = R0 (1 + t / tau)^(1/10), and an oscillating radius is R(t) = R0 (1 + A sin(2 pi t / T))."
+    )]
+    /// Evolution of the droplet radius with time
+    series: Series,
+
+    #[structopt(long = "tau", default_value = "10.0")]
+    /// Time scale of a spreading droplet
+    tau: f64,
+
+    #[structopt(long = "amplitude", default_value = "0.1")]
+    /// Amplitude of an oscillating radius, relative to the initial radius
+    amplitude: f64,
+
+    #[structopt(long = "period", default_value = "100.0")]
+    /// Period of an oscillating radius
+    period: f64,
+}
+
+pub fn run_generate(args: GenerateArgs) -> Result<(), io::Error> {
+    let [nx, ny] = args.shape;
+    let [dx, dy, _] = args.bin_size;
+    let center = args
+        .center
+        .unwrap_or([0.5 * dx * nx as f64, 0.5 * dy * ny as f64]);
+
+    let series = match args.series {
+        Series::Constant => RadiusSeries::Constant,
+        Series::Spreading => RadiusSeries::Spreading { tau: args.tau },
+        Series::Oscillating => RadiusSeries::Oscillating {
+            amplitude: args.amplitude,
+            period: args.period,
+        },
+    };
+
+    let mut rng = match args.seed {
+        Some(seed) => StdRng::from_seed(&[seed][..]),
+        None => StdRng::new()?,
+    };
+
+    let mut container = if is_container(&args.output) {
+        Some(ContainerWriter::create(&args.output)?)
+    } else {
+        None
+    };
+
+    for i in 0..args.num_frames {
+        let time = i as f64 * args.dt;
+
+        let model = DropletModel {
+            radius: series.radius_at(args.radius, time),
+            interface_width: args.interface_width,
+            density: args.density,
+            background: args.background,
+            noise: args.noise,
+        };
+
+        let densmap = generate_droplet(
+            &model,
+            args.bin_size,
+            [0.0, 0.0],
+            args.shape,
+            center,
+            &mut rng,
+        );
+
+        match container.as_mut() {
+            Some(writer) => writer.push(&densmap, time)?,
+            None => {
+                let path = format!("{}{:09.3}ps.dat", args.output.display(), time);
+                let metadata = get_metadata(&model);
+                write_densmap_with_metadata(Path::new(&path), &densmap, time, &metadata)?;
+            }
+        }
+
+        // The true radius of every frame is printed for comparison with the analysis.
+        println!("{:12.3} {:12.6}", time, model.radius);
+    }
+
+    if let Some(writer) = container {
+        writer.finish()?;
+    }
+
+    Ok(())
+}
+
+/// Get the metadata of a generated map, which records the model of the droplet.
+fn get_metadata(model: &DropletModel) -> Metadata {
+    let mut metadata = Metadata::new();
+
+    metadata.insert(
+        "creator".to_string(),
+        format!("densmap-analysis {}", env!("CARGO_PKG_VERSION")),
+    );
+    metadata.insert("generated_radius".to_string(), model.radius.to_string());
+    metadata.insert(
+        "generated_interface_width".to_string(),
+        model.interface_width.to_string(),
+    );
+
+    metadata
+}
+
+fn is_container(path: &Path) -> bool {
+    path.extension().map(|ext| ext == "dmc").unwrap_or(false)
+}
+
+/// Parse a shape given as 'nx,ny'.
+fn parse_shape(s: &str) -> Result<Shape, String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("could not parse shape '{}': {}", s, err))?;
+
+    match *values.as_slice() {
+        [nx, ny] if nx > 0 && ny > 0 => Ok([nx, ny]),
+        _ => Err(format!(
+            "shape '{}' must be given as 'nx,ny' with positive sizes",
+            s
+        )),
+    }
+}
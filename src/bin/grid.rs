@@ -0,0 +1,121 @@
+use structopt::StructOpt;
+
+use densmap::{
+    densmap::{GridOptions, Vec2, Vec3},
+    densmap3d::ZSelection,
+};
+
+use crate::convert::parse_point;
+
+#[derive(Debug, StructOpt)]
+/// Grid of input density maps, for formats which do not store it, and the layers
+/// and field of maps to analyze
+pub struct GridArgs {
+    #[structopt(
+        long = "grid_bin_size",
+        value_name = "dx,dy[,dz]",
+        parse(try_from_str = "parse_bin_size"),
+        hidden_short_help = true
+    )]
+    /// Bin size of input maps, required for NumPy arrays and text grids which lack it
+    grid_bin_size: Option<Vec3>,
+
+    #[structopt(
+        long = "grid_origin",
+        value_name = "x,y",
+        parse(try_from_str = "parse_point"),
+        hidden_short_help = true
+    )]
+    /// Origin of input maps, replacing the origin read from the files
+    grid_origin: Option<Vec2>,
+
+    #[structopt(
+        long = "grid_center",
+        value_name = "x,y",
+        parse(try_from_str = "parse_point"),
+        hidden_short_help = true
+    )]
+    /// Droplet center of input maps, replacing the center read from the files
+    grid_center: Option<Vec2>,
+
+    #[structopt(
+        long = "z_slice",
+        value_name = "index",
+        raw(conflicts_with = r#""z_range""#),
+        hidden_short_help = true
+    )]
+    /// Analyze a single layer of z-resolved maps, counted from the bottom
+    z_slice: Option<usize>,
+
+    #[structopt(
+        long = "z_range",
+        value_name = "z0,z1",
+        parse(try_from_str = "parse_z_range"),
+        hidden_short_help = true,
+        long_help = "Analyze the density of z-resolved maps integrated over the layers with centers between z0 and z1. By default the density is integrated over all layers. Maps without layers are analyzed as they are."
+    )]
+    /// Analyze the density of z-resolved maps integrated between z0 and z1
+    z_range: Option<Vec2>,
+
+    #[structopt(
+        long = "field",
+        value_name = "name",
+        hidden_short_help = true,
+        long_help = "Analyze a single field of maps with several fields, like the density of one species of a mixture. By default the fields are summed."
+    )]
+    /// Analyze a single field of maps with several fields
+    field: Option<String>,
+}
+
+impl GridArgs {
+    pub fn options(&self) -> GridOptions {
+        GridOptions {
+            bin_size: self.grid_bin_size,
+            origin: self.grid_origin,
+            center: self.grid_center,
+            z: self.z_selection(),
+            field: self.field.clone(),
+            ..GridOptions::default()
+        }
+    }
+
+    fn z_selection(&self) -> ZSelection {
+        match (self.z_slice, self.z_range) {
+            (Some(iz), _) => ZSelection::Slice(iz),
+            (None, Some([z0, z1])) => ZSelection::Range(z0, z1),
+            (None, None) => ZSelection::All,
+        }
+    }
+}
+
+/// Parse a range along z given as 'z0,z1', with z0 not larger than z1.
+fn parse_z_range(s: &str) -> Result<Vec2, String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("could not parse range '{}': {}", s, err))?;
+
+    match *values.as_slice() {
+        [z0, z1] if z0 <= z1 => Ok([z0, z1]),
+        _ => Err(format!(
+            "range '{}' must be given as 'z0,z1' with z0 <= z1",
+            s
+        )),
+    }
+}
+
+/// Parse a bin size given as 'dx,dy' or 'dx,dy,dz'.
+pub fn parse_bin_size(s: &str) -> Result<Vec3, String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("could not parse bin size '{}': {}", s, err))?;
+
+    match *values.as_slice() {
+        [dx, dy] => Ok([dx, dy, 0.0]),
+        [dx, dy, dz] => Ok([dx, dy, dz]),
+        _ => Err(format!("bin size '{}' must be given as 'dx,dy[,dz]'", s)),
+    }
+}
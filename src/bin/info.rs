@@ -0,0 +1,117 @@
+use serde_json::{json, Value};
+use structopt::StructOpt;
+
+use std::{io, path::PathBuf};
+
+use densmap::densmap::read_field_maps_with_grid;
+
+use crate::verify::VerifyArgs;
+
+#[derive(Debug, StructOpt)]
+/// Print the grid and density range of density map files
+pub struct InfoArgs {
+    #[structopt(parse(from_os_str), required = true)]
+    /// List of density map files to inspect
+    filenames: Vec<PathBuf>,
+
+    #[structopt(long = "json")]
+    /// Print the information as a JSON array with an object for every file
+    json: bool,
+
+    #[structopt(flatten)]
+    verify: VerifyArgs,
+}
+
+/// Total mass and range of the finite values of a map, as (mass, min, max).
+fn get_mass_and_range(data: &[f64]) -> (f64, f64, f64) {
+    data.iter().filter(|v| v.is_finite()).fold(
+        (0.0, f64::INFINITY, f64::NEG_INFINITY),
+        |(mass, min, max), &v| (mass + v, min.min(v), max.max(v)),
+    )
+}
+
+pub fn run_info(args: InfoArgs) -> Result<(), io::Error> {
+    let grid = args.verify.options();
+
+    let mut values = Vec::with_capacity(args.filenames.len());
+
+    for (i, path) in args.filenames.iter().enumerate() {
+        let (maps, time, metadata) = read_field_maps_with_grid(path, &grid)?;
+        let names = if maps.is_single() {
+            Vec::new()
+        } else {
+            maps.names.clone()
+        };
+
+        let densmap = maps.into_field(None).unwrap();
+        let (mass, min, max) = get_mass_and_range(&densmap.data);
+
+        let [nx, ny, nz] = densmap.shape;
+        let [x0, y0, z0] = densmap.origin;
+        let is_layered = nz > 1;
+
+        if args.json {
+            let (shape, origin) = if is_layered {
+                (json!(densmap.shape), json!(densmap.origin))
+            } else {
+                (json!([nx, ny]), json!([x0, y0]))
+            };
+
+            values.push(json!({
+                "path": path,
+                "time": time,
+                "shape": shape,
+                "bin_size": densmap.bin_size,
+                "origin": origin,
+                "center": densmap.center,
+                "mass": mass,
+                "min": if min.is_finite() { json!(min) } else { Value::Null },
+                "max": if max.is_finite() { json!(max) } else { Value::Null },
+                "fields": names,
+                "metadata": metadata,
+            }));
+        } else {
+            let [dx, dy, dz] = densmap.bin_size;
+            let [xc, yc] = densmap.center;
+
+            if i > 0 {
+                println!();
+            }
+
+            println!("{}", path.display());
+            println!("  time:     {}", time);
+            if is_layered {
+                println!("  shape:    {} x {} x {}", nx, ny, nz);
+            } else {
+                println!("  shape:    {} x {}", nx, ny);
+            }
+            println!("  bin size: {} x {} x {}", dx, dy, dz);
+            if is_layered {
+                println!("  origin:   ({}, {}, {})", x0, y0, z0);
+            } else {
+                println!("  origin:   ({}, {})", x0, y0);
+            }
+            println!("  center:   ({}, {})", xc, yc);
+            println!("  mass:     {}", mass);
+            println!("  density:  {} to {}", min, max);
+
+            if !names.is_empty() {
+                println!("  fields:   {}", names.join(", "));
+            }
+
+            if !metadata.is_empty() {
+                println!("  metadata:");
+
+                for (key, value) in &metadata {
+                    println!("    {}: {}", key, value);
+                }
+            }
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&values)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use structopt::StructOpt;
+use walkdir::WalkDir;
+
+use std::{
+    env::current_dir,
+    ffi::{OsStr, OsString},
+    io,
+    path::{Path, PathBuf},
+};
+
+use densmap::series::{DensMapSeries, TimeFilter, DEFAULT_TIME_REGEX};
+
+#[derive(Debug, StructOpt)]
+/// Print the density maps which are found by a base file name, with their times
+pub struct ListArgs {
+    #[structopt(parse(from_os_str))]
+    /// Base file name of the density maps, as given to the analysis with --base
+    base: PathBuf,
+
+    #[structopt(long = "ext", default_value = "dat", parse(from_os_str))]
+    /// Extension for density map file names
+    ext: OsString,
+
+    #[structopt(
+        long = "time_sig",
+        raw(default_value = "DEFAULT_TIME_REGEX"),
+        value_name = "regex"
+    )]
+    /// Regular expression for time signature in file names
+    time_regex: String,
+
+    #[structopt(short = "b", long = "begin", value_name = "t0")]
+    /// Only include times for which t >= t0
+    begin: Option<f64>,
+
+    #[structopt(short = "e", long = "end", value_name = "t1")]
+    /// Only include times for which t <= t1
+    end: Option<f64>,
+
+    #[structopt(long = "dt", value_name = "dt")]
+    /// Only include times which are multiples of dt, allowing for rounding errors
+    dt: Option<f64>,
+
+    #[structopt(long = "max-depth", value_name = "depth", default_value = "1")]
+    /// Depth of subdirectories in which density maps are found
+    max_depth: usize,
+}
+
+pub fn run_list(args: ListArgs) -> Result<(), io::Error> {
+    // All files with a time signature are found, to show why they are left out.
+    let series = DensMapSeries::find_recursive(
+        &args.base,
+        &args.time_regex,
+        &args.ext,
+        TimeFilter::default(),
+        args.max_depth,
+        false,
+    )?;
+
+    let times = series.times().unwrap_or(&[]);
+
+    println!("{:>14}  {:<22}  file", "time", "status");
+
+    let mut num_included = 0;
+
+    for (path, &time) in series.paths().iter().zip(times) {
+        let status = get_status(&args, time);
+
+        if status == INCLUDED {
+            num_included += 1;
+        }
+
+        println!("{:>14.3}  {:<22}  {}", time, status, path.display());
+    }
+
+    let unmatched = find_unmatched_files(&args, series.paths())?;
+
+    if !unmatched.is_empty() {
+        println!();
+        println!("Files with the base file name and extension but no time signature:");
+
+        for path in &unmatched {
+            println!("    {}", path.display());
+        }
+    }
+
+    eprintln!(
+        "{} of {} files with a time signature are included.",
+        num_included,
+        series.len()
+    );
+
+    Ok(())
+}
+
+const INCLUDED: &str = "included";
+
+/// Get whether a time passes the time filter, or the first limit which it does not.
+fn get_status(args: &ListArgs, time: f64) -> &'static str {
+    let dt = TimeFilter {
+        dt: args.dt,
+        ..TimeFilter::default()
+    };
+
+    if args.begin.map(|begin| time < begin).unwrap_or(false) {
+        "before begin"
+    } else if args.end.map(|end| time > end).unwrap_or(false) {
+        "after end"
+    } else if !dt.contains(time) {
+        "not a multiple of dt"
+    } else {
+        INCLUDED
+    }
+}
+
+/// Find the files which begin with the base file name and have the extension, but
+/// which were not matched by the time signature.
+fn find_unmatched_files(args: &ListArgs, matched: &[PathBuf]) -> Result<Vec<PathBuf>, io::Error> {
+    // The directory is made absolute like for the paths which were found.
+    let dir = current_dir()?.join(args.base.parent().unwrap_or_else(|| Path::new("")));
+
+    let base = args
+        .base
+        .file_name()
+        .unwrap_or_else(|| OsStr::new(""))
+        .to_string_lossy()
+        .to_string();
+
+    let unmatched = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(args.max_depth)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| {
+            let path = entry.path();
+
+            path.file_name()
+                .map(|name| name.to_string_lossy().starts_with(&base))
+                .unwrap_or(false)
+                && path.extension() == Some(args.ext.as_os_str())
+                && !matched.iter().any(|matched| matched == path)
+        })
+        .map(|entry| entry.into_path())
+        .collect();
+
+    Ok(unmatched)
+}
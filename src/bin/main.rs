@@ -1,40 +1,183 @@
-use pbr::ProgressBar;
+mod compare;
+mod convert;
+mod diff;
+mod failure;
+mod generate;
+mod grid;
+mod info;
+mod list;
+mod math;
+mod migrate;
+mod pack;
+mod postprocess;
+mod progress;
+mod reader;
+mod validate;
+mod verify;
+mod view;
+
 use regex::Regex;
+use serde_json::{json, Value};
 use structopt::StructOpt;
-use walkdir::WalkDir;
 
 use std::{
-    env::current_dir,
     ffi::{OsStr, OsString},
-    io,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
     path::{Path, PathBuf},
     process::exit,
 };
 
 use densmap::{
     analysis::{
-        autocorrelation::calc_autocorrelation,
-        radial_density::{get_radial_density_distribution, get_radius_from_distribution},
-        sample_interface::sample_interface,
+        autocorrelation::{
+            calc_space_time_correlation, resample_onto_common_angles, AutocorrelationMap,
+        },
+        calibration::{calibrate_smoothing_radius, select_smoothing_radius, SmoothingCalibration},
+        droplet_frame::{
+            append_droplet_frames, get_droplet_frame, write_droplet_frames, DropletFrame,
+        },
+        ensemble::calc_ensemble_average,
+        gradient::get_gradient_magnitude_map,
+        kymograph::{write_kymograph, Kymograph},
+        pipeline::{
+            get_absolute_contact_line, get_relative_contact_line, smoothen_densmap,
+            AnalysisRegistry, ContactLineAutocorrelation, ContactLinePolygonAnalysis,
+            DensityDistributionAnalysis, Frame, FrameResults, InterfaceAnalysis, Output, Outputs,
+            RadialDensityAnalysis, RadialMeanDensityAnalysis, RadialOccupancyAnalysis,
+            RadiusAnalysis, AUTOCORRELATION, CONTACT_LINE, CONTACT_LINE_POLYGON,
+            DENSITY_DISTRIBUTION, DENSITY_DISTRIBUTION_AVERAGE, INTERFACE, RADIAL_BIN_EDGES,
+            RADIAL_DENSITY, RADIAL_MEAN_DENSITY, RADIAL_OCCUPANCY, RADIUS,
+        },
+        polygon::get_contact_line_polygon,
+        precursor::{
+            append_precursor_films, get_precursor_film, write_precursor_films, PrecursorFilm,
+        },
+        radial_density::{get_azimuthal_average_map, DensityProfileOptions, RadialBinning},
+        radial_velocity::{get_contact_line_radial_velocity, get_radial_velocity_profile},
+        registration::estimate_translation,
+        sample_interface::get_density_cutoff,
+        spectrum::calc_mode_spectrum,
+        statistics::{calc_relaxation_time, fit_power_law_exponent},
+        variance::VarianceMap,
+        velocity::calc_contact_line_velocity,
+    },
+    arrow::ArrowWriter,
+    average::{EdgeMode, Filter, Kernel, Smoother, SmoothingOptions},
+    container::Container,
+    densmap::{is_stdio, write_densmap, DensMap, GridOptions, LengthUnit, Vec2},
+    flow::read_flow_map,
+    gnuplot::{GnuplotScript, PlotStyle},
+    graphdata::{
+        append_xvg, append_xvg_columns, append_xvg_set, read_xvg, write_contact_line_csv,
+        write_grid, write_xvg, write_xvg_columns, Columns, XvgWriter,
     },
-    average::smoothen_data_of_bins_within_radius,
-    densmap::{read_densmap, write_densmap},
-    graphdata::{write_xvg, Graph, Histogram, XYData},
+    image::write_pgm,
+    mask::MaskRegion,
+    netcdf::NetcdfWriter,
+    plot::write_svg_plot,
+    report::Report,
+    series::{
+        check_time_steps, get_file_name_time, times_agree, DensMapSeries, FrameRange,
+        TimeConversion, TimeFilter, TimeSource, TimeUnit, DEFAULT_TIME_REGEX,
+    },
+};
+
+use crate::{
+    compare::{run_compare, CompareArgs},
+    convert::{run_convert, ConvertArgs},
+    diff::{run_diff, DiffArgs},
+    failure::{write_errors_json, Failure},
+    generate::{run_generate, GenerateArgs},
+    grid::GridArgs,
+    info::{run_info, InfoArgs},
+    list::{run_list, ListArgs},
+    math::{run_math, MathArgs},
+    migrate::{run_migrate, MigrateArgs},
+    pack::{run_pack, PackArgs},
+    postprocess::{run_postprocess, PostprocessArgs},
+    progress::ProgressArgs,
+    reader::{FrameReader, FrameSource},
+    validate::{run_validate, ValidateArgs},
+    verify::VerifyArgs,
+    view::{run_view, ViewArgs},
 };
 
 #[derive(Debug, StructOpt)]
 #[structopt(raw(
     setting = "structopt::clap::AppSettings::ColoredHelp",
-    setting = "structopt::clap::AppSettings::DeriveDisplayOrder"
+    setting = "structopt::clap::AppSettings::DeriveDisplayOrder",
+    setting = "structopt::clap::AppSettings::ArgsNegateSubcommands",
+    setting = "structopt::clap::AppSettings::SubcommandsNegateReqs"
 ))]
 struct Args {
-    #[structopt(parse(from_os_str), conflicts_with = "base", required_unless = "base")]
-    /// List of density map files to analyze
+    #[structopt(subcommand)]
+    command: Option<Command>,
+
+    #[structopt(
+        parse(from_os_str),
+        raw(conflicts_with_all = r#"&["base", "container", "files_from", "trajectories"]"#),
+        raw(required_unless_one = r#"&["base", "container", "files_from", "trajectories"]"#)
+    )]
+    /// List of density map files or glob patterns to analyze, where '-' reads a map from stdin
     filenames: Vec<PathBuf>,
 
-    #[structopt(long = "base", value_name = "path", parse(from_os_str))]
-    /// Base file name for density maps
-    base: Option<PathBuf>,
+    #[structopt(
+        long = "base",
+        value_name = "path",
+        parse(from_os_str),
+        raw(number_of_values = "1"),
+        long_help = "Base file name for density maps, which are found by the base followed by their time signature and extension. Can be given several times to analyze several trajectories, see --trajectories."
+    )]
+    /// Base file name for density maps (can be used multiple times)
+    base: Vec<PathBuf>,
+
+    #[structopt(
+        long = "trajectories",
+        value_name = "path",
+        parse(from_os_str),
+        raw(conflicts_with_all = r#"&["container", "files_from"]"#),
+        long_help = "File with a list of base file names of trajectories to analyze, one per line, in addition to those given by --base. Empty lines and lines beginning with '#' are skipped. Every trajectory is analyzed separately with the same options and its outputs of the full trajectory are written to a subdirectory of the working directory, named as the directory of its maps (or its base file name), eg. 'run1/radius.xvg' for the base 'sims/run1/map'. Outputs of frames are written to the same subdirectory, unless --frame_output_dir is given."
+    )]
+    /// File with a list of base file names of trajectories to analyze separately
+    trajectories: Option<PathBuf>,
+
+    #[structopt(
+        long = "max-depth",
+        value_name = "depth",
+        default_value = "1",
+        hidden_short_help = true,
+        long_help = "Also find density maps by their base file name in subdirectories of its directory, down to this depth. A depth of 1 only searches the directory itself, while 2 also searches its subdirectories, like 'run1/part001/' and 'run1/part002/' for the base 'run1/map'. The maps of all directories are sorted by time. Outputs of frames are written next to their maps. Only used with --base."
+    )]
+    /// Depth of subdirectories in which density maps are found by their base file name
+    max_depth: usize,
+
+    #[structopt(
+        long = "no-index",
+        hidden_short_help = true,
+        long_help = "Do not keep an index of the density maps which are found by their base file name. The index is a hidden file next to the maps, named like '.map.densmap-index' for the base 'map', with the time of every map. It is read instead of searching the directories as long as none of them have been modified, which saves time for directories with many files on networked storage. Directories which cannot be written to are not indexed. Only used with --base."
+    )]
+    /// Do not keep an index of the density maps which are found by their base file name
+    no_index: bool,
+
+    #[structopt(
+        long = "container",
+        value_name = "path",
+        conflicts_with = "base",
+        parse(from_os_str)
+    )]
+    /// Container file to analyze the density maps of
+    container: Option<PathBuf>,
+
+    #[structopt(
+        long = "files-from",
+        value_name = "path",
+        raw(conflicts_with_all = r#"&["base", "container"]"#),
+        parse(from_os_str),
+        long_help = "Read the list of density map files to analyze from a file, or from stdin if the path is '-'. Every line is the path of one map, in the order in which they are analyzed. Empty lines and lines beginning with '#' are skipped. The paths are not expanded as glob patterns."
+    )]
+    /// File with a list of density map files to analyze, where '-' reads it from stdin
+    files_from: Option<PathBuf>,
 
     #[structopt(short = "d", long = "densmap", value_name = "path", parse(from_os_str))]
     /// Base output file name for smoothed density maps
@@ -57,6 +200,53 @@ struct Args {
     /// Base output file name for interface graphs
     interface: Option<PathBuf>,
 
+    #[structopt(
+        long = "polygon",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Base output file name for the contact line of every frame as a closed polygon, in Carthesian coordinates around the droplet center. The vertices are ordered counter-clockwise by their angle and the first vertex is repeated as the last, so that the polygon can be used as it is for geometry. Its area and perimeter are written with --geometry."
+    )]
+    /// Base output file name for the contact line as a closed polygon
+    contact_line_polygon: Option<PathBuf>,
+
+    #[structopt(
+        long = "geometry",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Output file name for the area which is enclosed by the contact line polygon of every frame and its perimeter, as a function of time. These are calculated from the same polygon which is written with --polygon."
+    )]
+    /// Output file name for the area and perimeter of the contact line per time
+    geometry: Option<PathBuf>,
+
+    #[structopt(
+        long = "residual",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
+    )]
+    /// Base output file name for maps of the density minus its azimuthal average
+    residual: Option<PathBuf>,
+
+    #[structopt(
+        long = "binary",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
+    )]
+    /// Base output file name for binary maps of bins above the interface cutoff
+    binary: Option<PathBuf>,
+
+    #[structopt(
+        long = "gradient",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
+    )]
+    /// Base output file name for maps of the density gradient magnitude
+    gradient: Option<PathBuf>,
+
     #[structopt(
         short = "r",
         long = "radius",
@@ -64,7 +254,7 @@ struct Args {
         value_name = "path",
         parse(from_os_str)
     )]
-    /// Output file name for droplet radius time series
+    /// Output file name for droplet radius time series, written as every frame is analyzed
     radius: PathBuf,
 
     #[structopt(
@@ -76,6 +266,64 @@ struct Args {
     /// Base output file name for radial density distributions
     radial_density: Option<PathBuf>,
 
+    #[structopt(
+        long = "rdd_min_samples",
+        value_name = "N",
+        hidden_short_help = true,
+        long_help = "Widen the radial bins of the radial density distributions, occupancies and mean densities from the droplet center outwards until every bin has at least N bins of the map. This reduces the noise of the innermost bins, which are otherwise left out within 1 nm of the center. The distribution is then scaled by the area of every bin. The inner and outer edges of the bins are written next to the radial density distributions, with the suffix '_edges'."
+    )]
+    /// Widen the radial bins until they have at least N bins of the map
+    rdd_min_samples: Option<usize>,
+
+    #[structopt(
+        long = "occupancy",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Base output file name for the fraction of bins at every radius around the droplet center with a density above the cutoff of the contact line, half of the maximum density. Unlike the radial density distribution, which is the mass per circumference, the occupancy is not lowered for shells which are cut by the edges of the map, which makes it a more robust indicator of the droplet radius for droplets close to the edges."
+    )]
+    /// Base output file name for the fraction of droplet bins per radius
+    radial_occupancy: Option<PathBuf>,
+
+    #[structopt(
+        long = "rdd_mean",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Base output file name for the mean density of the bins at every radius around the droplet center. The density is normalized by the number of bins of every shell instead of by its circumference, so shells which are cut by the edges of the map are not diluted."
+    )]
+    /// Base output file name for the mean density of the bins per radius
+    radial_mean_density: Option<PathBuf>,
+
+    #[structopt(
+        long = "dd",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Base output file name for the probability distribution of the densities of the bins of every frame. For a droplet the distribution has a peak for the vapor and one for the liquid, between which the density cutoff of the interface can be picked. The histogram bins are set by the maximum density of the first frame and shared by all frames."
+    )]
+    /// Base output file name for the distribution of bin densities
+    density_distribution: Option<PathBuf>,
+
+    #[structopt(
+        long = "dd_average",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Output file name for the probability distribution of the densities of the bins of all frames, which shows the phases of the droplet over the whole trajectory. A phase change shows as a shift of weight between the vapor and liquid peaks of the distributions of the frames."
+    )]
+    /// Output file name for the distribution of bin densities of all frames
+    density_distribution_average: Option<PathBuf>,
+
+    #[structopt(
+        long = "dd_bins",
+        value_name = "N",
+        default_value = "100",
+        hidden_short_help = true
+    )]
+    /// Number of bins of density distributions up to the maximum density of the first frame
+    density_distribution_bins: usize,
+
     #[structopt(
         long = "ac",
         value_name = "path",
@@ -85,212 +333,2812 @@ struct Args {
     /// Output file name for contact line autocorrelation
     autocorrelation: Option<PathBuf>,
 
-    #[structopt(long = "ext", default_value = "dat", parse(from_os_str))]
-    /// Extension for density map file names
-    ext: OsString,
     #[structopt(
-        long = "time_sig",
-        long_help = "Regular expression for time signature in file names. The expression must include a capture group around the time value.",
-        default_value = r"([0-9]{5}\.[0-9]{3})ps",
-        value_name = "regex"
+        long = "variance",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
     )]
-    /// Regular expression for time signature in file names
-    time_regex: String,
+    /// Output file name for map of the density variance in every bin over all frames
+    variance: Option<PathBuf>,
 
-    #[structopt(short = "b", long = "begin", requires = "base", value_name = "t0")]
-    /// Only include times for which t >= t0
-    begin: Option<f64>,
-    #[structopt(short = "e", long = "end", requires = "base", value_name = "t1")]
-    /// Only include times for which t <= t1
-    end: Option<f64>,
-    #[structopt(long = "dt", requires = "base", value_name = "dt")]
-    /// Only include times for which t % dt = 0
-    dt: Option<f64>,
-}
+    #[structopt(
+        long = "st_corr",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
+    )]
+    /// Output file name for the space-time correlation of the contact line
+    space_time_correlation: Option<PathBuf>,
 
-fn main() -> Result<(), io::Error> {
-    let args = Args::from_args();
+    #[structopt(
+        long = "st_corr_max_lag",
+        value_name = "frames",
+        default_value = "50",
+        hidden_short_help = true
+    )]
+    /// Maximum time lag, in number of frames, of the space-time correlation
+    space_time_correlation_max_lag: usize,
 
-    let filenames = match args.base {
-        Some(base) => construct_file_list(
-            &base,
-            &args.time_regex,
-            &args.ext,
-            args.begin,
-            args.end,
-            args.dt,
-        ),
-        None => args.filenames,
-    };
+    #[structopt(
+        long = "spectrum",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
+    )]
+    /// Output file name for the power spectrum of the angular modes of the contact line
+    spectrum: Option<PathBuf>,
 
-    if filenames.is_empty() {
-        eprintln!("No input files could be detected.");
-        exit(1);
-    }
+    #[structopt(
+        long = "ensemble-dir",
+        value_name = "path",
+        default_value = "ensemble",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Directory for the ensemble averages of several trajectories, which are analyzed as replicas. The droplet radius, contact line autocorrelation and mode spectrum of the replicas are averaged over their common range of times or modes, with the standard error of the mean as a third column, and written with the file names of the outputs of the trajectories."
+    )]
+    /// Directory for the ensemble averages of several trajectories
+    ensemble_dir: PathBuf,
+
+    #[structopt(
+        long = "ac_map",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
+    )]
+    /// Base output file name for maps of the density autocorrelation in every bin
+    autocorrelation_map: Option<PathBuf>,
 
-    let mut radius_time_series = Vec::with_capacity(filenames.len());
-    let mut times = Vec::with_capacity(filenames.len());
+    #[structopt(
+        long = "ac_map_lags",
+        value_name = "lags",
+        default_value = "1",
+        hidden_short_help = true,
+        raw(use_delimiter = "true")
+    )]
+    /// Comma separated time lags, in number of frames, for the autocorrelation maps
+    autocorrelation_map_lags: Vec<usize>,
 
-    // To calculate the autocorrelation of contact line fluctuations we need to save
-    // the contact line for every time step.
-    let mut contact_line_per_time = Vec::with_capacity(filenames.len());
+    #[structopt(
+        long = "tidy",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
+    )]
+    /// Output file name for a long-format CSV table of the contact line of all frames
+    tidy: Option<PathBuf>,
 
-    let mut pb = ProgressBar::new(filenames.len() as u64);
-    pb.format("[=> ]");
+    #[structopt(
+        long = "contact_line_dump",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Write the resampled contact lines of all frames, along with their angles and times, to a binary file. The values are kept exactly, for later analysis with the 'postprocess' subcommand or other tools. The file begins with the magic string 'DMCL', a 32 bit format version and the number of times and angles as 64 bit integers, followed by the angles, times and radii of every time as 64 bit floats, all little endian."
+    )]
+    /// Output file name for a binary dump of the contact line of all frames
+    contact_line_dump: Option<PathBuf>,
 
-    for (i, filename) in filenames.into_iter().enumerate() {
-        pb.message(&format!(
-            "Processing '{}' ",
-            &filename.file_name().unwrap().to_str().unwrap()
-        ));
-        pb.inc();
+    #[structopt(
+        long = "droplet_frame",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
+    )]
+    /// Output file name for transforms from lab coordinates to the droplet frame
+    droplet_frame: Option<PathBuf>,
 
-        let (densmap, time) = read_densmap(&filename)?;
+    #[structopt(long = "frame_rotation", hidden_short_help = true)]
+    /// Align the droplet frame with the principal axis of the droplet
+    frame_rotation: bool,
 
-        let dir = filename.parent().unwrap();
-        let time_signature = read_time_signature_or_default(&filename, &args.time_regex, i);
+    #[structopt(
+        long = "precursor",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
+    )]
+    /// Output file name for precursor film extent and density time series
+    precursor: Option<PathBuf>,
 
-        let smoothed_densmap = smoothen_data_of_bins_within_radius(densmap, 0.5);
-        if let Some(base) = &args.smooth {
-            let path = construct_file_name(&base, &time_signature, &args.ext, &dir);
-            write_densmap(&path, &smoothed_densmap, time)?;
-        }
+    #[structopt(
+        long = "precursor_cutoff",
+        default_value = "0.05",
+        value_name = "fraction",
+        hidden_short_help = true
+    )]
+    /// Density cutoff for the precursor film edge, as a fraction of the maximum density
+    precursor_cutoff: f64,
 
-        let radial_density = get_radial_density_distribution(&smoothed_densmap);
-        if let Some(base) = &args.radial_density {
-            let path = construct_file_name(&base, &time_signature, OsStr::new("xvg"), &dir);
-            write_xvg(&path, &radial_density)?;
-        }
+    #[structopt(
+        long = "smoothing_radius",
+        default_value = "0.5",
+        value_name = "radius"
+    )]
+    /// Radius within which bins are averaged when smoothing the density maps
+    smoothing_radius: f64,
 
-        if let Ok(radius) = get_radius_from_distribution(radial_density) {
-            radius_time_series.push(radius);
-            times.push(time);
+    #[structopt(
+        long = "periodic",
+        value_name = "x|y|xy",
+        default_value = "",
+        hide_default_value = true,
+        parse(try_from_str = "parse_periodic")
+    )]
+    /// Axes along which the system is periodic, which smoothing wraps across
+    periodic: [bool; 2],
 
-            let contact_line = sample_interface(&smoothed_densmap, radius);
-            let interface = contact_line.to_carthesian();
-            if let Some(base) = &args.interface {
-                let path = construct_file_name(&base, &time_signature, OsStr::new("xvg"), &dir);
-                write_xvg(&path, &interface)?;
-            }
+    #[structopt(
+        long = "edge_mode",
+        value_name = "mode[,mode]",
+        default_value = "truncate",
+        parse(try_from_str = "parse_edge_modes"),
+        long_help = "How neighbours outside of the system are handled when smoothing: 'truncate' skips them, 'mirror' reflects them into the system, 'clamp' uses the closest edge bin, 'wrap' wraps them across the system and 'zero' includes them as empty bins. A mode per axis can be given as 'xmode,ymode'. Periodic axes always wrap."
+    )]
+    /// How neighbours outside of the system are handled when smoothing
+    edge_mode: [EdgeMode; 2],
 
-            let relative_contact_line = Graph::Polar {
-                angles: contact_line.x().to_vec(),
-                radius: contact_line.y().iter().map(|r| r - radius).collect(),
-            };
+    #[structopt(
+        long = "smoothing_filter",
+        value_name = "mean|median",
+        default_value = "mean"
+    )]
+    /// Whether bins are smoothed with the mean or median value of their neighbours
+    smoothing_filter: Filter,
 
-            if let Some(base) = &args.contact_line {
-                let path = construct_file_name(&base, &time_signature, OsStr::new("xvg"), &dir);
-                write_xvg(&path, &relative_contact_line)?;
-            }
+    #[structopt(
+        long = "smoothing_kernel",
+        value_name = "disc|box|tent|epanechnikov",
+        default_value = "disc",
+        long_help = "Kernel of neighbours to smooth bins with: 'disc' and 'box' give equal weight to all bins within a circle or square of the radius, while 'tent' and 'epanechnikov' weigh bins within the circle by 1 - r/R and 1 - (r/R)^2 to let closer bins contribute more."
+    )]
+    /// Kernel of neighbours which bins are smoothed with
+    smoothing_kernel: Kernel,
 
-            contact_line_per_time.push(relative_contact_line);
-        }
-    }
+    #[structopt(
+        long = "calibrate_smoothing",
+        value_name = "frames",
+        hidden_short_help = true,
+        long_help = "Analyze the first frames with several smoothing radii and select the radius which gives the best trade-off between bias of the droplet radius and noise of the contact line. The selected radius replaces the one given by --smoothing_radius."
+    )]
+    /// Select the smoothing radius by analyzing the first frames
+    calibrate_smoothing: Option<usize>,
 
-    pb.finish_print("Processed all density maps.");
-    eprintln!("");
+    #[structopt(
+        long = "calibration_bias_weight",
+        value_name = "weight",
+        default_value = "1.0",
+        hidden_short_help = true,
+        long_help = "Weight of the bias of the droplet radius relative to the noise of the contact line when selecting the smoothing radius by --calibrate_smoothing. The bias is measured relative to the droplet radius of the frames without smoothing."
+    )]
+    /// Weight of the radius bias when calibrating the smoothing
+    calibration_bias_weight: f64,
 
-    if let Some(filename) = args.autocorrelation {
-        let mut pb = ProgressBar::new(contact_line_per_time.len() as u64);
-        pb.message("Calculating autocorrelation of contact line ");
+    #[structopt(
+        long = "no-smooth",
+        hidden_short_help = true,
+        raw(conflicts_with_all = r#"&["calibrate_smoothing"]"#)
+    )]
+    /// Analyze the density maps without smoothing them
+    no_smooth: bool,
 
-        // To calculate the autocorrelation for the contact line we resample the data onto
-        // a common set of angles. We use the largest number of sample points as the base.
-        let resample_xvals = contact_line_per_time
-            .iter()
-            .max_by(|&a, &b| a.x().len().cmp(&b.x().len()))
-            .unwrap()
-            .x();
-        let resampled_contact_lines = contact_line_per_time
-            .iter()
-            .map(|contact_line| contact_line.resample(&resample_xvals))
-            .collect::<Vec<_>>();
+    #[structopt(
+        long = "no-interface",
+        hidden_short_help = true,
+        raw(
+            conflicts_with_all = r#"&["interface", "contact_line", "contact_line_polygon", "geometry", "autocorrelation", "space_time_correlation", "spectrum", "tidy", "contact_line_dump", "precursor", "flow", "report"]"#
+        ),
+        long_help = "Do not sample the interface of the droplets, which takes most of the time of the analysis of a frame. The droplet radius is still found from the radial density distribution, but the outputs which need the contact line cannot be written. The interface is also not sampled when no such outputs are given."
+    )]
+    /// Do not sample the interface of the droplets
+    no_interface: bool,
 
-        let autocorrelation_yvals = calc_autocorrelation(&resampled_contact_lines);
-        let autocorrelation = Histogram {
-            x: times.clone(),
-            y: autocorrelation_yvals,
-        };
+    #[structopt(
+        long = "only",
+        value_name = "output",
+        use_delimiter = true,
+        hidden_short_help = true,
+        raw(possible_values = "&ONLY_OUTPUTS"),
+        long_help = "Only write these outputs of the droplet analysis, separated by commas, and skip the analysis of the others even if their files are given. The droplet radius is always written. All other outputs are written as given."
+    )]
+    /// Only write these outputs of the droplet analysis
+    only: Vec<String>,
 
-        write_xvg(&filename, &autocorrelation)?;
-        pb.finish_print("Finished autocorrelation calculation.");
-    }
+    #[structopt(
+        long = "mask",
+        value_name = "region",
+        number_of_values = 1,
+        long_help = "Mask out a region of the density maps by setting its bins to zero. Regions are given as 'rect:xmin,xmax,ymin,ymax', 'below-x:value', 'below-y:value', 'above-x:value', 'above-y:value' or 'file:path', where the non-zero bins of the density map at the path are masked. Can be given several times."
+    )]
+    /// Mask out regions of the density maps
+    mask: Vec<MaskRegion>,
 
-    let radius_per_time = Graph::Carthesian {
-        x: times,
-        y: radius_time_series,
-    };
-    write_xvg(&args.radius, &radius_per_time)?;
+    #[structopt(
+        long = "roi",
+        value_name = "xmin,xmax,ymin,ymax",
+        parse(try_from_str = "parse_region")
+    )]
+    /// Crop the density maps to a region of interest before analysis
+    roi: Option<[f64; 4]>,
 
-    Ok(())
-}
+    #[structopt(
+        long = "chunk-size",
+        value_name = "frames",
+        raw(
+            conflicts_with_all = r#"&["autocorrelation", "space_time_correlation", "spectrum", "tidy", "contact_line_dump"]"#
+        ),
+        long_help = "Process the trajectory in blocks of frames. After every block the time series of the precursor film, droplet frame and registration are appended to their files and dropped from memory, and the variance and autocorrelation maps are written with the frames so far. Analyses which need the contact lines of the full trajectory cannot be combined with this mode."
+    )]
+    /// Process the trajectory in blocks of frames, writing outputs after every block
+    chunk_size: Option<usize>,
 
-fn construct_file_name(base: &Path, time_sig: &str, ext: &OsStr, dir: &Path) -> PathBuf {
-    let file_name =
-        PathBuf::from(base.to_str().unwrap().to_string() + time_sig + "." + ext.to_str().unwrap());
-    dir.join(&file_name)
-}
+    #[structopt(
+        long = "prefetch",
+        value_name = "frames",
+        default_value = "2",
+        hidden_short_help = true
+    )]
+    /// Number of frames to read ahead in a separate thread while analyzing
+    prefetch: usize,
 
-fn read_time_signature_or_default(path: &Path, time_regex: &str, index: usize) -> String {
-    // For now we can recompile the regular expression for every time step, even though
-    // it does not change. The compilation time is marginal at best compared to all the analysis.
-    // It may be poor practice, but eh.
-    let re = Regex::new(&time_regex).unwrap();
+    #[structopt(long = "register")]
+    /// Track the droplet center by registering consecutive maps against each other
+    register: bool,
 
-    match re.captures(&path.to_str().unwrap()) {
-        Some(capture) => String::from(capture.get(0).unwrap().as_str()),
-        None => format!("{:05}", index + 1),
-    }
-}
+    #[structopt(
+        long = "register_max_shift",
+        value_name = "bins",
+        default_value = "5",
+        hidden_short_help = true
+    )]
+    /// Maximum shift in number of bins between consecutive maps when registering
+    register_max_shift: usize,
 
-/// Use the given base filename path along with the regular expression for time signatures
-/// and the file extension to identify file names and return them as an array.
-///
-/// Filter out file names with times which do not lie within the (optional) interval.
-fn construct_file_list(
-    base_path: &Path,
-    time_regex: &str,
-    ext: &OsStr,
-    begin: Option<f64>,
-    end: Option<f64>,
-    dt: Option<f64>,
-) -> Vec<PathBuf> {
-    let dir = base_path.parent().unwrap_or(&Path::new("./"));
-    let abs_dir = if dir.is_absolute() {
-        dir.to_path_buf()
-    } else {
-        current_dir().unwrap().join(dir)
-    };
+    #[structopt(
+        long = "registration",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str)
+    )]
+    /// Output file name for the translation between consecutive maps
+    registration: Option<PathBuf>,
 
-    let base = base_path
-        .file_name()
-        .unwrap_or(&OsStr::new(""))
-        .to_str()
-        .unwrap();
+    #[structopt(
+        long = "netcdf",
+        value_name = "path",
+        parse(from_os_str),
+        long_help = "Output file name for a NetCDF file which bundles the smoothed maps of all frames with the time series of the droplet radius, base area and center. The file uses the classic format with time as the unlimited dimension and can be read by most analysis tools, eg. xarray or ncview."
+    )]
+    /// Output file name for a NetCDF file of all maps and the droplet time series
+    netcdf: Option<PathBuf>,
 
-    let regex_string = format!(r"^{}{}\.{}$", base, time_regex, ext.to_str().unwrap());
-    let re = Regex::new(&regex_string).unwrap();
+    #[structopt(
+        long = "arrow",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Output file name for an Arrow IPC (Feather) file with a table of the per-frame results: the time, droplet radius, base area, mass and center. The table can be read with eg. pandas.read_feather."
+    )]
+    /// Output file name for an Arrow table of the per-frame results
+    arrow: Option<PathBuf>,
 
-    WalkDir::new(abs_dir)
-        .min_depth(1)
-        .max_depth(1)
-        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
-        .into_iter()
-        .filter_entry(|entry| {
-            let file_name = entry.file_name().to_str().unwrap();
+    #[structopt(
+        long = "report",
+        value_name = "path",
+        parse(from_os_str),
+        long_help = "Output file name for a self-contained HTML report of the analysis, with plots of the droplet radius, contact line autocorrelation and average radial density along with snapshots of a few maps. Everything is embedded in the file, which can be opened in any browser."
+    )]
+    /// Output file name for an HTML report of the analysis
+    report: Option<PathBuf>,
 
-            match re.captures(&file_name) {
-                Some(captures) => {
-                    let time = captures.get(1).unwrap().as_str().parse::<f64>().unwrap();
+    #[structopt(
+        long = "manifest",
+        value_name = "path",
+        parse(from_os_str),
+        long_help = "Output file name for a JSON manifest of the run, written when it has finished. The manifest holds the version of the tool, the command line and working directory, the smoothing radius that was used, the number of analyzed and skipped frames, their time range, the mean droplet radius, the frames in which no droplet was found or which could not be read, the droplet radius file and the files which were written for the full trajectory."
+    )]
+    /// Output file name for a JSON manifest of the parameters and results of the run
+    manifest: Option<PathBuf>,
 
-                    begin.map(|b| time >= b).unwrap_or(true)
-                        && end.map(|e| time <= e).unwrap_or(true)
-                        && dt.map(|d| time % d == 0.0).unwrap_or(true)
-                }
-                None => false,
-            }
-        })
-        .filter_map(|entry| entry.ok())
-        .map(|entry| entry.into_path())
-        .collect()
+    #[structopt(
+        long = "errors-json",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Output file name for a JSON summary of the errors of the run, written when it has finished or failed. It holds the exit code, the category and message of the error which stopped the run and the files which failed: frames which were skipped with '--skip-errors' and the file which stopped the run. The exit code is 2 for bad arguments, 3 if no frames were found, 4 if a file could not be read or written and 5 if the analysis of a frame failed."
+    )]
+    /// Output file name for a JSON summary of the errors of the run
+    errors_json: Option<PathBuf>,
+
+    #[structopt(
+        long = "frame_output_dir",
+        value_name = "dir",
+        parse(from_os_str),
+        long_help = "Directory to write the outputs of frames to (smoothed maps, radial density profiles, interfaces, contact lines and so on), instead of next to their maps. With --trajectories a relative directory is created within the output directory of every trajectory."
+    )]
+    /// Directory to write the outputs of frames to, instead of next to their maps
+    frame_output_dir: Option<PathBuf>,
+
+    #[structopt(
+        long = "stats",
+        raw(conflicts_with_all = "&STATS_CONFLICTS"),
+        long_help = "Only print summary statistics of the analysis when it has finished, without writing any files, not even the droplet radius. The statistics are the number of analyzed frames and their time range, the mean droplet radius, the spreading exponent n of a fit of the radius to t^n over frames with positive times, the relaxation time at which the contact line autocorrelation decays below 1/e and the relative drift of the total mass from the first to the last map. Use this for a quick look at many simulations."
+    )]
+    /// Only print summary statistics of the analysis, without writing any files
+    stats: bool,
+
+    #[structopt(
+        long = "stats-json",
+        hidden_short_help = true,
+        raw(conflicts_with_all = "&STATS_CONFLICTS")
+    )]
+    /// Print the summary statistics as JSON, which implies --stats
+    stats_json: bool,
+
+    #[structopt(
+        long = "gnuplot",
+        hidden_short_help = true,
+        long_help = "Write a gnuplot script next to every written xvg and CSV file, with the same name and the extension '.gp'. The scripts set the labels and ranges of the data, so that 'gnuplot radius.gp' produces the figure 'radius.png'."
+    )]
+    /// Write a gnuplot script for every xvg and CSV output
+    gnuplot: bool,
+
+    #[structopt(
+        long = "plot",
+        hidden_short_help = true,
+        long_help = "Render the droplet radius, contact line autocorrelation and interface graphs as SVG images next to their xvg files, with the same name and the extension '.svg'. This allows a quick look at the results without plotting tools."
+    )]
+    /// Render the radius, autocorrelation and interface graphs as SVG images
+    plot: bool,
+
+    #[structopt(
+        long = "multiset",
+        hidden_short_help = true,
+        long_help = "Write the radial density, interface and contact line graphs of all frames to single xvg files, one for each output, instead of one file per frame. The files are named by the base output path with the extension '.xvg' and hold one data set per frame, separated by '&', which xmgrace reads as separate sets and can animate. No gnuplot scripts or plots are written for these files."
+    )]
+    /// Write the graphs of all frames as data sets of single xvg files
+    multiset: bool,
+
+    #[structopt(
+        long = "skip-existing",
+        hidden_short_help = true,
+        conflicts_with = "multiset",
+        long_help = "Skip frames for which all of the per-frame outputs that are set (smoothed maps, radial density profiles, interfaces and contact lines) already exist, for cheap reruns after frames have been added to a trajectory. Skipped frames are left out of the time series and all outputs over the full trajectory, except that the droplet radius is appended to its existing file instead of replacing it."
+    )]
+    /// Skip frames for which the per-frame outputs already exist
+    skip_existing: bool,
+
+    #[structopt(
+        long = "dry-run",
+        long_help = "Print the frames which would be analyzed, with the times parsed from their file names after filtering, and the paths of all data files which would be written for every frame and the full trajectory. Nothing is read or written. Use this to check the time signature and time filter before a long analysis."
+    )]
+    /// Print the frames and output files of the analysis without running it
+    dry_run: bool,
+
+    #[structopt(
+        long = "skip-errors",
+        hidden_short_help = true,
+        long_help = "Skip frames which cannot be read, like truncated or corrupt files, instead of stopping the analysis. Every skipped frame is reported as it is reached and the skipped frames are listed when the analysis has finished. They are left out of all outputs."
+    )]
+    /// Skip frames which cannot be read instead of stopping the analysis
+    skip_errors: bool,
+
+    #[structopt(
+        long = "components",
+        value_name = "fields",
+        hidden_short_help = true,
+        raw(use_delimiter = "true"),
+        long_help = "Comma separated fields of maps with several fields, like the species of a mixture, which are also analyzed as separate components in the same pass. The droplet radius, radial density profiles, interfaces and contact lines of every component are written to the outputs of the main analysis with the field name as a suffix, eg. 'radius_water.xvg' and 'rdd_water00010.000ps.xvg'."
+    )]
+    /// Fields of the maps to also analyze as separate components
+    components: Vec<String>,
+
+    #[structopt(
+        long = "flow",
+        value_name = "path",
+        hidden_short_help = true,
+        parse(from_os_str),
+        long_help = "Base file name of flow maps which accompany the density maps, found by the time signature of every density map, eg. 'flow' gives 'flow00010.000ps.dat'. Flow maps hold the velocity along x and y in the fields 'vx' and 'vy'."
+    )]
+    /// Base file name of flow maps which accompany the density maps
+    flow: Option<PathBuf>,
+
+    #[structopt(
+        long = "flow_profile",
+        value_name = "path",
+        hidden_short_help = true,
+        raw(requires = r#""flow""#),
+        parse(from_os_str)
+    )]
+    /// Base output file name for radial velocity profiles across the contact line
+    flow_profile: Option<PathBuf>,
+
+    #[structopt(
+        long = "flow_contact_line",
+        value_name = "path",
+        hidden_short_help = true,
+        raw(requires = r#""flow""#),
+        parse(from_os_str)
+    )]
+    /// Base output file name for the radial velocity at the contact line per angle
+    flow_contact_line: Option<PathBuf>,
+
+    #[structopt(
+        long = "flow_width",
+        value_name = "width",
+        default_value = "1.0",
+        hidden_short_help = true
+    )]
+    /// Distance from the contact line within which the radial velocity profile is sampled
+    flow_width: f64,
+
+    #[structopt(flatten)]
+    grid: GridArgs,
+
+    #[structopt(flatten)]
+    verify: VerifyArgs,
+
+    #[structopt(flatten)]
+    progress: ProgressArgs,
+
+    #[structopt(long = "subtract-background")]
+    /// Subtract the vapor density from the density maps before analysis
+    subtract_background: bool,
+
+    #[structopt(long = "ext", default_value = "dat", parse(from_os_str))]
+    /// Extension for density map file names
+    ext: OsString,
+    #[structopt(
+        long = "time_sig",
+        long_help = "Regular expression for time signature in file names. The expression must include a capture group around the time value.",
+        raw(default_value = "DEFAULT_TIME_REGEX"),
+        value_name = "regex"
+    )]
+    /// Regular expression for time signature in file names
+    time_regex: String,
+
+    #[structopt(short = "b", long = "begin", value_name = "t0")]
+    /// Only include times for which t >= t0
+    begin: Option<f64>,
+    #[structopt(short = "e", long = "end", value_name = "t1")]
+    /// Only include times for which t <= t1
+    end: Option<f64>,
+    #[structopt(long = "dt", value_name = "dt")]
+    /// Only include times which are multiples of dt, allowing for rounding errors
+    dt: Option<f64>,
+    #[structopt(
+        long = "time-unit",
+        value_name = "ps|ns",
+        default_value = "ps",
+        long_help = "Unit of the times of the analysis. The times of file names and headers of density maps are in ps and are converted to the unit, which is used for the time filters, the time axes of all outputs and their labels."
+    )]
+    /// Unit of the times of the analysis
+    time_unit: TimeUnit,
+    #[structopt(
+        long = "time-source",
+        value_name = "filename|header",
+        default_value = "filename",
+        hidden_short_help = true,
+        long_help = "Source of the times of the frames, which are used to select and sort them and for the time axes of the outputs. 'filename' takes the time of the time signature of the file name, or of the header for maps without one. 'header' takes the time of the header, which requires every map to be read an extra time before the analysis when selecting or finding maps. Outputs of frames are named by the time signatures of their file names with both. A warning is printed for maps with different times in their file name and header."
+    )]
+    /// Source of the times of the frames
+    time_source: TimeSource,
+    #[structopt(
+        long = "length-unit",
+        value_name = "nm|A",
+        default_value = "nm",
+        long_help = "Unit of the lengths of the analysis. The bin sizes, origins and centers of density maps (and the velocities of flow maps), which are in nm, are converted to the unit when read, so that all distances of the outputs and their labels are in the unit. Lengths which are given as options, like the smoothing radius and regions, must also be given in the unit, eg. '--smoothing_radius 5' for the default of 0.5 nm in Å. Written density maps are converted back to nm. The densities are not converted."
+    )]
+    /// Unit of the lengths of the analysis
+    length_unit: LengthUnit,
+    #[structopt(
+        long = "time-scale",
+        value_name = "factor",
+        default_value = "1",
+        hidden_short_help = true,
+        long_help = "Scale the times of file names and headers after converting them to the time unit, eg. by 1000 for maps written by tools which write times in ns instead of ps. Applied before the time offset."
+    )]
+    /// Scale the times of the density maps
+    time_scale: f64,
+    #[structopt(
+        long = "time-offset",
+        value_name = "t",
+        default_value = "0",
+        hidden_short_help = true,
+        long_help = "Offset of the times of file names and headers in the time unit, which is added after scaling them. Can be used to continue the times of a trajectory which was restarted from zero."
+    )]
+    /// Offset of the times of the density maps
+    time_offset: f64,
+    #[structopt(
+        long = "frames",
+        value_name = "start:stop:step",
+        long_help = "Only include frames by their position in the sorted list of frames, after the time filter, as 'start:stop:step' where the stop index is excluded. Parts can be left out, eg. '100:' includes all frames from index 100 and '::10' every tenth frame. This is independent of the times of the frames."
+    )]
+    /// Only include frames by index given as 'start:stop:step'
+    frames: Option<FrameRange>,
+}
+
+// The arguments are parsed once, so the size of the variants does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, StructOpt)]
+enum Command {
+    #[structopt(
+        name = "compare",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Compare the droplet radius of two analysis runs
+    Compare(CompareArgs),
+
+    #[structopt(
+        name = "convert",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Convert a density map, optionally transforming it
+    Convert(ConvertArgs),
+
+    #[structopt(
+        name = "diff",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Compare two density maps bin by bin
+    Diff(DiffArgs),
+
+    #[structopt(
+        name = "generate",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Generate density maps of idealized droplets with a known radius
+    Generate(GenerateArgs),
+
+    #[structopt(
+        name = "info",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Print the grid and density range of density map files
+    Info(InfoArgs),
+
+    #[structopt(
+        name = "list",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Print the density maps which are found by a base file name, with their times
+    List(ListArgs),
+
+    #[structopt(
+        name = "math",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Arithmetic operations on density maps
+    Math(MathArgs),
+
+    #[structopt(
+        name = "migrate",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Rewrite density map files in place, optionally changing their compression
+    Migrate(MigrateArgs),
+
+    #[structopt(
+        name = "pack",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Pack density maps into a single container file
+    Pack(PackArgs),
+
+    #[structopt(
+        name = "postprocess",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Analyze the contact lines of previously written xvg files, without the density maps
+    Postprocess(PostprocessArgs),
+
+    #[structopt(
+        name = "validate",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// Check that density map files are complete and hold valid densities
+    Validate(ValidateArgs),
+
+    #[structopt(
+        name = "view",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+    )]
+    /// View density maps in the terminal, with their contact line
+    View(ViewArgs),
+}
+
+fn main() {
+    // Help and version messages exit as usual, other errors with the code for bad arguments.
+    let args = Args::from_iter_safe(std::env::args_os()).unwrap_or_else(|err| {
+        if !err.use_stderr() {
+            err.exit();
+        }
+
+        eprintln!("{}", err.message);
+        exit(Failure::BadArguments.code());
+    });
+
+    let result = match args.command {
+        Some(Command::Compare(compare_args)) => run_compare(compare_args),
+        Some(Command::Convert(convert_args)) => run_convert(convert_args),
+        Some(Command::Diff(diff_args)) => run_diff(diff_args),
+        Some(Command::Generate(generate_args)) => run_generate(generate_args),
+        Some(Command::Info(info_args)) => run_info(info_args),
+        Some(Command::List(list_args)) => run_list(list_args),
+        Some(Command::Math(math_args)) => run_math(math_args),
+        Some(Command::Migrate(migrate_args)) => run_migrate(migrate_args),
+        Some(Command::Pack(pack_args)) => run_pack(pack_args),
+        Some(Command::Postprocess(postprocess_args)) => run_postprocess(postprocess_args),
+        Some(Command::Validate(validate_args)) => run_validate(validate_args),
+        Some(Command::View(view_args)) => run_view(view_args),
+        None if args.base.len() > 1 || args.trajectories.is_some() => run_trajectories(args),
+        None => run_analysis(args),
+    };
+
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        exit(Failure::of(&err).code());
+    }
+}
+
+/// Analyze several trajectories separately, writing the outputs of every trajectory
+/// to its own directory.
+fn run_trajectories(args: Args) -> Result<(), io::Error> {
+    let mut bases = args.base.clone();
+    if let Some(path) = &args.trajectories {
+        bases.extend(read_file_list(path)?);
+    }
+
+    let dirs = get_trajectory_dirs(&bases);
+    let num_trajectories = bases.len();
+
+    // Outputs of every trajectory which are averaged over the ensemble.
+    let mut replica_outputs: Vec<Vec<(&str, PathBuf, PathBuf)>> = Vec::new();
+
+    for (i, (base, dir)) in bases.into_iter().zip(dirs).enumerate() {
+        eprintln!(
+            "Analyzing trajectory {} of {}: '{}' with outputs in '{}'.",
+            i + 1,
+            num_trajectories,
+            base.display(),
+            dir.display()
+        );
+
+        if !args.dry_run && !args.is_stats() {
+            fs::create_dir_all(&dir)?;
+        }
+
+        // Arguments are parsed again for every trajectory, since they are consumed
+        // by the analysis.
+        let mut trajectory_args = Args::from_args();
+        trajectory_args.base = vec![base];
+        trajectory_args.trajectories = None;
+        trajectory_args.set_output_dir(&dir);
+
+        let outputs = [
+            (RADIUS, Some(&args.radius), Some(&trajectory_args.radius)),
+            (
+                AUTOCORRELATION,
+                args.autocorrelation.as_ref(),
+                trajectory_args.autocorrelation.as_ref(),
+            ),
+            (
+                SPECTRUM,
+                args.spectrum.as_ref(),
+                trajectory_args.spectrum.as_ref(),
+            ),
+        ];
+
+        replica_outputs.push(
+            outputs
+                .iter()
+                .filter_map(|&(kind, name, path)| {
+                    name.zip(path)
+                        .map(|(name, path)| (kind, name.clone(), path.clone()))
+                })
+                .collect(),
+        );
+
+        run_analysis(trajectory_args)?;
+    }
+
+    if num_trajectories > 1 && !args.dry_run && !args.is_stats() {
+        write_ensemble_averages(
+            &args.ensemble_dir,
+            &replica_outputs,
+            args.gnuplot,
+            args.units(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write the ensemble averages of the outputs of replicate trajectories to a directory.
+///
+/// The outputs of every replica are given as the kind of output, its file name and
+/// the path of the file of the replica, and are averaged with the same outputs of
+/// the other replicas.
+fn write_ensemble_averages(
+    dir: &Path,
+    replica_outputs: &[Vec<(&str, PathBuf, PathBuf)>],
+    gnuplot: bool,
+    units: Units,
+) -> Result<(), io::Error> {
+    fs::create_dir_all(dir)?;
+
+    let num_outputs = replica_outputs
+        .first()
+        .map(|outputs| outputs.len())
+        .unwrap_or(0);
+
+    for i in 0..num_outputs {
+        let (kind, name, _) = &replica_outputs[0][i];
+
+        let replicas = replica_outputs
+            .iter()
+            .map(|outputs| read_xvg(&outputs[i].2))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(average) = calc_ensemble_average(&replicas) {
+            let path = dir.join(name.file_name().unwrap_or_else(|| name.as_os_str()));
+            write_xvg(&path, &average)?;
+
+            if gnuplot {
+                get_plot_script(kind, units).write(&path)?;
+            }
+        }
+    }
+
+    eprintln!(
+        "Wrote the ensemble averages of {} trajectories to '{}'.",
+        replica_outputs.len(),
+        dir.display()
+    );
+
+    Ok(())
+}
+
+/// Get unique names of output directories for trajectories, from the directories of
+/// their maps or their base file names.
+fn get_trajectory_dirs(bases: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::with_capacity(bases.len());
+
+    for base in bases {
+        let name = base
+            .parent()
+            .and_then(|dir| dir.file_name())
+            .or_else(|| base.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| String::from("trajectory"));
+
+        let mut dir = PathBuf::from(&name);
+        let mut n = 1;
+
+        while dirs.contains(&dir) {
+            n += 1;
+            dir = PathBuf::from(format!("{}_{}", name, n));
+        }
+
+        dirs.push(dir);
+    }
+
+    dirs
+}
+
+/// Outputs of the droplet analysis which are written for every frame.
+const FRAME_OUTPUTS: [&str; 8] = [
+    RADIAL_DENSITY,
+    RADIAL_BIN_EDGES,
+    RADIAL_OCCUPANCY,
+    RADIAL_MEAN_DENSITY,
+    DENSITY_DISTRIBUTION,
+    INTERFACE,
+    CONTACT_LINE,
+    CONTACT_LINE_POLYGON,
+];
+
+/// Outputs of the droplet analysis which can be selected with `--only`.
+const ONLY_OUTPUTS: [&str; 5] = [
+    RADIUS,
+    RADIAL_DENSITY,
+    INTERFACE,
+    CONTACT_LINE,
+    AUTOCORRELATION,
+];
+
+/// Outputs which cannot be written when only printing summary statistics.
+const STATS_CONFLICTS: [&str; 34] = [
+    "smooth",
+    "contact_line",
+    "interface",
+    "contact_line_polygon",
+    "geometry",
+    "residual",
+    "binary",
+    "gradient",
+    "radial_density",
+    "radial_occupancy",
+    "radial_mean_density",
+    "density_distribution",
+    "density_distribution_average",
+    "autocorrelation",
+    "variance",
+    "space_time_correlation",
+    "spectrum",
+    "autocorrelation_map",
+    "tidy",
+    "contact_line_dump",
+    "droplet_frame",
+    "precursor",
+    "registration",
+    "netcdf",
+    "arrow",
+    "report",
+    "manifest",
+    "gnuplot",
+    "plot",
+    "multiset",
+    "chunk_size",
+    "skip_existing",
+    "components",
+    "flow",
+];
+
+impl Args {
+    fn units(&self) -> Units {
+        Units {
+            time: self.time_unit,
+            length: self.length_unit,
+        }
+    }
+
+    /// Get the options of the radial density profiles, for maps which have been scaled
+    /// to the length unit of the analysis.
+    fn density_profile_options(&self) -> DensityProfileOptions {
+        DensityProfileOptions {
+            min_samples: self.rdd_min_samples,
+            length_unit: self.length_unit,
+        }
+    }
+
+    /// Get the options for reading the maps, with the grid values and the verification
+    /// of checksums which are set.
+    fn grid_options(&self) -> GridOptions {
+        self.verify.apply(self.grid.options())
+    }
+
+    fn time_conversion(&self) -> TimeConversion {
+        TimeConversion {
+            unit: self.time_unit,
+            scale: self.time_scale,
+            offset: self.time_offset,
+        }
+    }
+
+    /// Get whether only the summary statistics of the analysis are printed.
+    fn is_stats(&self) -> bool {
+        self.stats || self.stats_json
+    }
+
+    /// Remove the outputs of the droplet analysis which are not selected with `--only`.
+    fn apply_only(&mut self) {
+        if self.only.is_empty() {
+            return;
+        }
+
+        let only = self.only.clone();
+
+        for (name, path) in [
+            (RADIAL_DENSITY, &mut self.radial_density),
+            (INTERFACE, &mut self.interface),
+            (CONTACT_LINE, &mut self.contact_line),
+            (AUTOCORRELATION, &mut self.autocorrelation),
+        ] {
+            if !only.iter().any(|selected| selected == name) {
+                *path = None;
+            }
+        }
+    }
+
+    /// Move the relative output paths of the full trajectory into a directory.
+    fn set_output_dir(&mut self, dir: &Path) {
+        let move_path = |path: &mut PathBuf| {
+            if path.is_relative() {
+                *path = dir.join(&path);
+            }
+        };
+
+        move_path(&mut self.radius);
+
+        match self.frame_output_dir.as_mut() {
+            Some(path) => move_path(path),
+            None => self.frame_output_dir = Some(dir.to_path_buf()),
+        }
+
+        let mut paths = [
+            &mut self.autocorrelation,
+            &mut self.density_distribution_average,
+            &mut self.variance,
+            &mut self.space_time_correlation,
+            &mut self.spectrum,
+            &mut self.autocorrelation_map,
+            &mut self.tidy,
+            &mut self.contact_line_dump,
+            &mut self.droplet_frame,
+            &mut self.geometry,
+            &mut self.precursor,
+            &mut self.registration,
+            &mut self.netcdf,
+            &mut self.arrow,
+            &mut self.report,
+            &mut self.manifest,
+            &mut self.errors_json,
+        ];
+
+        for path in paths.iter_mut().filter_map(|path| path.as_mut()) {
+            move_path(path);
+        }
+    }
+}
+
+/// Number of maps with different times in their file name and header which are warned
+/// about, after which only their total number is.
+const MAX_TIME_MISMATCH_WARNINGS: usize = 5;
+
+/// Number of maps from which the background could not be subtracted which are warned
+/// about, after which only their total number is.
+const MAX_BACKGROUND_WARNINGS: usize = 5;
+
+/// Number of maps which are included as snapshots in the report.
+const REPORT_SNAPSHOTS: usize = 4;
+
+fn run_analysis(args: Args) -> Result<(), io::Error> {
+    let errors_json = match args.dry_run {
+        true => None,
+        false => args.errors_json.clone(),
+    };
+
+    let mut summary = RunSummary::default();
+    let result = analyze(args, &mut summary);
+
+    if let Some(path) = errors_json {
+        write_errors_json(&path, &result, &summary.read_errors)?;
+    }
+
+    result
+}
+
+fn analyze(mut args: Args, summary: &mut RunSummary) -> Result<(), io::Error> {
+    args.apply_only();
+
+    // The statistics are printed as JSON to the standard output, which is kept clean.
+    let stats = args.is_stats();
+    if args.stats_json {
+        args.progress.hide();
+    }
+
+    let conversion = args.time_conversion();
+    let length_scale = 1.0 / args.length_unit.in_nm();
+    let units = args.units();
+    let filter = TimeFilter {
+        begin: args.begin,
+        end: args.end,
+        dt: args.dt,
+        conversion,
+        source: args.time_source,
+    };
+
+    let source = match (args.base.first(), &args.container) {
+        (Some(base), _) => {
+            let series = DensMapSeries::find_recursive(
+                base,
+                &args.time_regex,
+                &args.ext,
+                filter,
+                args.max_depth,
+                !args.no_index,
+            )?;
+            if let Some(times) = series.times() {
+                warn_time_steps(times);
+            }
+            FrameSource::Files(series.paths().to_vec())
+        }
+        (None, Some(path)) => {
+            let container = Container::open(path)?;
+            let frames = (0..container.index().len())
+                .filter(|&i| filter.contains(conversion.apply(container.index()[i].time)))
+                .collect::<Vec<_>>();
+
+            let times = frames
+                .iter()
+                .map(|&i| conversion.apply(container.index()[i].time))
+                .collect::<Vec<_>>();
+            warn_time_steps(&times);
+
+            FrameSource::Container {
+                path: path.clone(),
+                frames,
+            }
+        }
+        (None, None) => {
+            let series = match &args.files_from {
+                Some(path) => DensMapSeries::from_paths(read_file_list(path)?),
+                None => DensMapSeries::from_patterns(&args.filenames, &args.time_regex)?,
+            }
+            .with_grid(args.grid_options())
+            .filter(&args.time_regex, filter)?;
+            if let Some(times) = series.times() {
+                warn_time_steps(times);
+            }
+            FrameSource::Files(series.paths().to_vec())
+        }
+    };
+
+    let source = match &args.frames {
+        Some(range) => source.select(range),
+        None => source,
+    };
+
+    if source.is_empty() {
+        return Err(Failure::NoFrames.error("no input files could be detected"));
+    }
+
+    if args.dry_run {
+        return print_dry_run(&args, &source);
+    }
+
+    if let Some(dir) = &args.frame_output_dir {
+        fs::create_dir_all(dir)?;
+    }
+
+    // The outputs are collected before the arguments are consumed by the analysis.
+    let manifest_outputs = args.manifest.as_ref().map(|_| {
+        get_trajectory_output_paths(
+            &args,
+            &get_component_paths(&args),
+            &get_source_dir(&args, &source),
+        )
+    });
+
+    let smoothing_options = {
+        let [mode_x, mode_y] = args.edge_mode;
+        let [periodic_x, periodic_y] = args.periodic;
+        let get_mode = |mode, periodic| if periodic { EdgeMode::Wrap } else { mode };
+
+        SmoothingOptions {
+            kernel: args.smoothing_kernel,
+            filter: args.smoothing_filter,
+            edge_modes: [get_mode(mode_x, periodic_x), get_mode(mode_y, periodic_y)],
+        }
+    };
+
+    let (smoothing_radius, calibration) = match args.calibrate_smoothing {
+        Some(num_frames) => {
+            let (selected, calibration) = calibrate_smoothing(
+                source.take(num_frames),
+                &args,
+                length_scale,
+                smoothing_options,
+            )?;
+
+            (selected.unwrap_or(args.smoothing_radius), Some(calibration))
+        }
+        None => (args.smoothing_radius, None),
+    };
+
+    let num_frames = source.len();
+    let chunk_size = args.chunk_size.unwrap_or(num_frames).max(1);
+
+    let mut series = TimeSeries::default();
+    let series_outputs = TimeSeriesOutputs {
+        radius: args.radius.clone(),
+        precursor: args.precursor.clone(),
+        registration: args.registration.clone(),
+        droplet_frame: args.droplet_frame.clone(),
+        geometry: args.geometry.clone(),
+        gnuplot: args.gnuplot,
+        plot: args.plot,
+        units: args.units(),
+    };
+
+    // The radius is streamed to its file as every frame is analyzed.
+    // When skipping frames its rows of earlier runs are kept.
+    let mut radius_writer = if stats {
+        None
+    } else if args.skip_existing {
+        Some(XvgWriter::append(&args.radius)?)
+    } else {
+        Some(XvgWriter::create(&args.radius)?)
+    };
+
+    // To analyze the contact line over time we need to save it for every time step.
+    let keep_contact_lines = args.tidy.is_some()
+        || args.space_time_correlation.is_some()
+        || args.spectrum.is_some()
+        || args.contact_line_dump.is_some();
+    let mut contact_line_per_time = Vec::new();
+
+    let mut registry = AnalysisRegistry::new();
+    registry.set_density_profile_options(args.density_profile_options());
+    if args.radial_density.is_some() {
+        registry.register(RadialDensityAnalysis::new(args.density_profile_options()));
+    }
+    if args.radial_occupancy.is_some() {
+        registry.register(RadialOccupancyAnalysis::new(args.density_profile_options()));
+    }
+    if args.radial_mean_density.is_some() {
+        registry.register(RadialMeanDensityAnalysis::new(
+            args.density_profile_options(),
+        ));
+    }
+    if args.density_distribution.is_some() || args.density_distribution_average.is_some() {
+        registry.register(DensityDistributionAnalysis::new(
+            args.density_distribution_bins,
+        ));
+    }
+    if args.interface.is_some() || args.contact_line.is_some() {
+        registry.register(InterfaceAnalysis);
+    }
+    if args.contact_line_polygon.is_some() {
+        registry.register(ContactLinePolygonAnalysis);
+    }
+    if args.autocorrelation.is_some() || stats {
+        registry.register(ContactLineAutocorrelation::default());
+    }
+    registry.set_sample_interface(
+        !args.no_interface
+            && (args.interface.is_some()
+                || args.contact_line.is_some()
+                || args.contact_line_polygon.is_some()
+                || args.geometry.is_some()
+                || args.autocorrelation.is_some()
+                || stats
+                || args.precursor.is_some()
+                || args.flow.is_some()
+                || args.report.is_some()
+                || keep_contact_lines),
+    );
+    let mut output_paths = OutputPaths::new(&args);
+
+    let mut previous_densmap: Option<DensMap> = None;
+
+    let mut smoother = if args.no_smooth {
+        Smoother::disabled(smoothing_options)
+    } else {
+        Smoother::new(smoothing_radius, smoothing_options)
+    };
+    let mut radial_binning = RadialBinning::with_options(args.density_profile_options());
+
+    let mut variance_map = VarianceMap::new();
+    let mut autocorrelation_map = AutocorrelationMap::new(&args.autocorrelation_map_lags);
+    let mut last_time = 0.0;
+
+    let mut netcdf_writer = None;
+    let mut arrow_writer = args
+        .arrow
+        .as_deref()
+        .map(|path| ArrowWriter::create(path, args.time_unit, args.length_unit))
+        .transpose()?;
+    let mut report = args.report.as_ref().map(|_| {
+        Report::new("Density map analysis", num_frames, REPORT_SNAPSHOTS)
+            .with_time_unit(args.time_unit)
+            .with_length_unit(args.length_unit)
+    });
+    let grid = args.grid_options();
+
+    let mut components = args
+        .components
+        .iter()
+        .map(|name| Component::new(name, &args))
+        .collect::<Vec<_>>();
+
+    let time_re = Regex::new(&args.time_regex).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid time signature '{}': {}", args.time_regex, err),
+        )
+    })?;
+    let mut num_time_mismatches = 0;
+    let mut num_background_errors = 0;
+
+    let mut pb = args.progress.start(num_frames);
+
+    let frames = FrameReader::spawn(source, grid.clone(), args.components.clone(), args.prefetch);
+
+    for (i, (filename, result)) in frames.enumerate() {
+        pb.message(&format!(
+            "Processing '{}' ",
+            filename
+                .file_name()
+                .unwrap_or_else(|| filename.as_os_str())
+                .to_string_lossy()
+        ));
+        pb.inc();
+
+        let dir = get_frame_output_dir(&args, &filename);
+        let time_signature = read_time_signature_or_default(&filename, &time_re, i);
+
+        if args.skip_existing && frame_outputs_exist(&args, &output_paths, &time_signature, dir) {
+            summary.num_skipped += 1;
+            continue;
+        }
+
+        let (densmap, time, component_maps) = match result {
+            Ok((densmap, header_time, component_maps)) => {
+                let file_time = get_file_name_time(&filename, &time_re);
+
+                if let Some(file_time) = file_time.filter(|&t| !times_agree(t, header_time)) {
+                    num_time_mismatches += 1;
+
+                    if num_time_mismatches <= MAX_TIME_MISMATCH_WARNINGS {
+                        eprintln!(
+                            "Warning: '{}' has the time {} ps in its file name but {} ps in its header.",
+                            filename.display(),
+                            file_time,
+                            header_time
+                        );
+                    }
+                }
+
+                let time = match (args.time_source, file_time) {
+                    (TimeSource::Filename, Some(time)) => time,
+                    _ => header_time,
+                };
+
+                (
+                    densmap.scale_lengths(length_scale),
+                    conversion.apply(time),
+                    component_maps,
+                )
+            }
+            Err(err) if args.skip_errors => {
+                eprintln!("Skipping '{}': {}", filename.display(), err);
+                summary.read_errors.push((filename, err.to_string()));
+                continue;
+            }
+            Err(err) => {
+                return Err(Failure::Io.file_error(
+                    &filename,
+                    format!("could not read '{}': {}", filename.display(), err),
+                ));
+            }
+        };
+
+        let densmap = mask_and_crop(densmap, &args, &filename)?;
+        last_time = time;
+
+        if stats {
+            summary.add_mass(&densmap);
+        }
+
+        if args.variance.is_some() {
+            if let Err(msg) = variance_map.add(&densmap) {
+                return Err(Failure::Analysis.file_error(
+                    &filename,
+                    format!(
+                        "could not add '{}' to variance: {}",
+                        filename.display(),
+                        msg
+                    ),
+                ));
+            }
+        }
+
+        if args.autocorrelation_map.is_some() {
+            if let Err(msg) = autocorrelation_map.add(&densmap) {
+                return Err(Failure::Analysis.file_error(
+                    &filename,
+                    format!(
+                        "could not add '{}' to autocorrelation: {}",
+                        filename.display(),
+                        msg
+                    ),
+                ));
+            }
+        }
+
+        let (smoothed_densmap, background_error) = smoothen_densmap(
+            densmap,
+            &mut smoother,
+            &mut radial_binning,
+            args.subtract_background,
+        );
+        warn_background_error(
+            &filename,
+            None,
+            background_error,
+            &mut num_background_errors,
+        );
+
+        for ((component, densmap), name) in components
+            .iter_mut()
+            .zip(component_maps)
+            .zip(&args.components)
+        {
+            let densmap = mask_and_crop(densmap.scale_lengths(length_scale), &args, &filename)?;
+            let (smoothed_densmap, background_error) = smoothen_densmap(
+                densmap,
+                &mut smoother,
+                &mut component.radial_binning,
+                args.subtract_background,
+            );
+            warn_background_error(
+                &filename,
+                Some(name),
+                background_error,
+                &mut num_background_errors,
+            );
+
+            let (_, outputs) =
+                process_frame(&mut component.registry, &smoothed_densmap, time, &filename)?;
+            component
+                .paths
+                .write_frame_outputs(&outputs, &time_signature, dir)?;
+        }
+
+        // When registering, the center of the first map is kept and moved along with
+        // the translation of every following map relative to the previous one.
+        let smoothed_densmap = if args.register || args.registration.is_some() {
+            let mut registered_densmap = smoothed_densmap;
+
+            if let Some(previous) = &previous_densmap {
+                let translation = match estimate_translation(
+                    previous,
+                    &registered_densmap,
+                    args.register_max_shift,
+                ) {
+                    Ok(translation) => translation,
+                    Err(msg) => {
+                        return Err(Failure::Analysis.file_error(
+                            &filename,
+                            format!("could not register '{}': {}", filename.display(), msg),
+                        ));
+                    }
+                };
+
+                if args.register {
+                    registered_densmap.center = [
+                        previous.center[0] + translation[0],
+                        previous.center[1] + translation[1],
+                    ];
+                }
+
+                series.registration_times.push(time);
+                series.translations.push(translation);
+            }
+
+            previous_densmap = Some(registered_densmap.clone());
+            registered_densmap
+        } else {
+            smoothed_densmap
+        };
+
+        if let Some(base) = &args.smooth {
+            let path = construct_file_name(base, &time_signature, &args.ext, dir);
+            write_map(&path, &smoothed_densmap, time, args.length_unit)?;
+        }
+
+        if let Some(base) = &args.residual {
+            let symmetric_densmap = get_azimuthal_average_map(&smoothed_densmap);
+            let residual = smoothed_densmap.subtract(&symmetric_densmap).unwrap();
+
+            let path = construct_file_name(base, &time_signature, &args.ext, dir);
+            write_map(&path, &residual, time, args.length_unit)?;
+        }
+
+        if let Some(base) = &args.gradient {
+            let gradient = get_gradient_magnitude_map(&smoothed_densmap);
+
+            let path = construct_file_name(base, &time_signature, &args.ext, dir);
+            write_map(&path, &gradient, time, args.length_unit)?;
+        }
+
+        // Binary maps are written both as density maps and as images.
+        if let Some(base) = &args.binary {
+            let binary = smoothed_densmap.threshold(get_density_cutoff(&smoothed_densmap));
+
+            let path = construct_file_name(base, &time_signature, &args.ext, dir);
+            write_map(&path, &binary, time, args.length_unit)?;
+
+            let path = construct_file_name(base, &time_signature, OsStr::new("pgm"), dir);
+            write_pgm(&path, &binary, 0.0, 1.0)?;
+        }
+
+        if args.droplet_frame.is_some() {
+            series.droplet_frame_times.push(time);
+            series
+                .droplet_frames
+                .push(get_droplet_frame(&smoothed_densmap, args.frame_rotation));
+        }
+
+        let (results, outputs) = process_frame(&mut registry, &smoothed_densmap, time, &filename)?;
+        output_paths.write_frame_outputs(&outputs, &time_signature, dir)?;
+
+        let radius = results.radius;
+        summary.add_frame(&filename, time, radius);
+
+        if let (Some(_), Some(contact_line)) = (&args.geometry, &results.contact_line) {
+            let polygon = get_contact_line_polygon(contact_line);
+            series
+                .geometry
+                .push((time, polygon.area, polygon.perimeter));
+        }
+
+        if let Some(path) = &args.netcdf {
+            if netcdf_writer.is_none() {
+                netcdf_writer = Some(NetcdfWriter::create(
+                    path,
+                    &smoothed_densmap,
+                    args.time_unit,
+                    args.length_unit,
+                )?);
+            }
+
+            if let Some(writer) = netcdf_writer.as_mut() {
+                writer.write_frame(&smoothed_densmap, time, radius)?;
+            }
+        }
+
+        if let Some(writer) = arrow_writer.as_mut() {
+            writer.write_frame(&smoothed_densmap, time, radius);
+        }
+
+        if let Some(report) = report.as_mut() {
+            let frame = Frame {
+                densmap: &smoothed_densmap,
+                time,
+                radial_density: &results.radial_density,
+                radius,
+                contact_line: results.contact_line.as_ref(),
+            };
+
+            report.add_frame(&frame).map_err(|err| {
+                io::Error::other(format!("could not add frame to report: {}", err))
+            })?;
+        }
+
+        if let Some(radius) = radius {
+            series.radius.push(radius);
+            series.times.push(time);
+
+            if let Some(writer) = radius_writer.as_mut() {
+                writer.write_row(time, &[radius])?;
+            }
+        }
+
+        if let (Some(radius), Some(contact_line)) = (radius, &results.contact_line) {
+            if args.precursor.is_some() {
+                series.precursor_films.push(get_precursor_film(
+                    &smoothed_densmap,
+                    contact_line,
+                    args.precursor_cutoff,
+                ));
+            }
+
+            if keep_contact_lines {
+                contact_line_per_time.push(get_relative_contact_line(contact_line, radius));
+            }
+
+            if let Some(base) = &args.flow {
+                let path = construct_file_name(base, &time_signature, &args.ext, dir);
+                let (flow, _) = read_flow_map(&path, &grid).map_err(|err| {
+                    io::Error::new(
+                        err.kind(),
+                        format!("could not read flow map '{}': {}", path.display(), err),
+                    )
+                })?;
+                let flow = flow.scale_lengths(length_scale);
+
+                let center = smoothed_densmap.center;
+
+                if let Some(base) = &args.flow_profile {
+                    let profile =
+                        get_radial_velocity_profile(&flow, center, contact_line, args.flow_width);
+                    let path = construct_file_name(base, &time_signature, OsStr::new("xvg"), dir);
+                    write_xvg(&path, &profile)?;
+
+                    if args.gnuplot {
+                        GnuplotScript::new(
+                            "Radial velocity profile",
+                            &format!("distance ({})", args.length_unit),
+                            "u_r",
+                        )
+                        .write(&path)?;
+                    }
+                }
+
+                if let Some(base) = &args.flow_contact_line {
+                    let velocity = get_contact_line_radial_velocity(&flow, center, contact_line);
+                    let path = construct_file_name(base, &time_signature, OsStr::new("xvg"), dir);
+                    write_xvg(&path, &velocity)?;
+
+                    if args.gnuplot {
+                        GnuplotScript::new("Contact line velocity", "angle (deg)", "u_r")
+                            .write(&path)?;
+                    }
+                }
+            }
+        }
+
+        if (i + 1) % chunk_size == 0 && i + 1 < num_frames {
+            series.write(&series_outputs, i + 1 > chunk_size)?;
+            series = TimeSeries::default();
+
+            for component in components.iter_mut() {
+                let outputs = component.registry.finish();
+                component
+                    .paths
+                    .write_outputs(&outputs, i + 1 > chunk_size)?;
+            }
+
+            write_map_aggregates(
+                &args.variance,
+                &variance_map,
+                &args.autocorrelation_map,
+                &autocorrelation_map,
+                &args.ext,
+                last_time,
+                args.length_unit,
+            )?;
+        }
+    }
+
+    pb.finish("Processed all density maps.");
+
+    if num_time_mismatches > MAX_TIME_MISMATCH_WARNINGS {
+        eprintln!(
+            "Warning: {} maps have different times in their file name and header.",
+            num_time_mismatches
+        );
+    }
+
+    if num_background_errors > MAX_BACKGROUND_WARNINGS {
+        eprintln!(
+            "Warning: the background could not be subtracted from {} maps.",
+            num_background_errors
+        );
+    }
+
+    if let Some(writer) = netcdf_writer {
+        writer.finish()?;
+    }
+
+    if let Some(writer) = arrow_writer {
+        writer.finish()?;
+    }
+
+    if let (Some(report), Some(path)) = (report.as_mut(), &args.report) {
+        report.write(path)?;
+    }
+
+    if !summary.read_errors.is_empty() {
+        eprintln!(
+            "Skipped {} frames which could not be read:",
+            summary.read_errors.len()
+        );
+
+        for (path, err) in &summary.read_errors {
+            eprintln!("  '{}': {}", path.display(), err);
+        }
+    }
+
+    if args.calibrate_smoothing.is_some() {
+        eprintln!(
+            "Used smoothing radius {} selected by calibration.",
+            smoothing_radius
+        );
+    }
+
+    let outputs = registry.finish();
+
+    if stats {
+        return print_statistics(summary, &series, &outputs, args.stats_json);
+    }
+
+    output_paths.write_outputs(&outputs, false)?;
+
+    // To analyze the contact line over time we resample the data onto a common set
+    // of angles.
+    let resampled_contact_lines = resample_onto_common_angles(&contact_line_per_time);
+
+    write_map_aggregates(
+        &args.variance,
+        &variance_map,
+        &args.autocorrelation_map,
+        &autocorrelation_map,
+        &args.ext,
+        last_time,
+        args.length_unit,
+    )?;
+
+    if let Some(filename) = args.space_time_correlation {
+        // The resampled contact lines all have identical angles.
+        let kymograph =
+            Kymograph::from_contact_lines(&series.times, &resampled_contact_lines).unwrap();
+        let correlation =
+            calc_space_time_correlation(&kymograph, args.space_time_correlation_max_lag);
+
+        write_grid(
+            &filename,
+            &correlation.angles,
+            &correlation.times,
+            &correlation.values,
+        )?;
+
+        if args.gnuplot {
+            GnuplotScript::new(
+                "Space-time correlation",
+                "angle lag (deg)",
+                &format!("time lag ({})", args.time_unit),
+            )
+            .with_style(PlotStyle::Image)
+            .write(&filename)?;
+        }
+    }
+
+    if let Some(filename) = args.tidy {
+        // The velocity is that of the contact line itself, which includes the change
+        // of the droplet radius that the relative contact lines are subtracted by.
+        let absolute_contact_lines = resampled_contact_lines
+            .iter()
+            .zip(&series.radius)
+            .map(|(contact_line, &radius)| get_absolute_contact_line(contact_line, radius))
+            .collect::<Vec<_>>();
+        let velocities = calc_contact_line_velocity(&series.times, &absolute_contact_lines);
+        write_contact_line_csv(
+            &filename,
+            &series.times,
+            &series.radius,
+            &resampled_contact_lines,
+            &velocities,
+        )?;
+
+        if args.gnuplot {
+            GnuplotScript::new(
+                "Contact line",
+                &format!("t ({})", args.time_unit),
+                &format!("r ({})", args.length_unit),
+            )
+            .with_columns(1, 3)
+            .with_style(PlotStyle::Points)
+            .write(&filename)?;
+        }
+    }
+
+    if let Some(filename) = args.spectrum {
+        // The resampled contact lines all have identical angles.
+        let kymograph =
+            Kymograph::from_contact_lines(&series.times, &resampled_contact_lines).unwrap();
+        write_xvg(&filename, &calc_mode_spectrum(&kymograph))?;
+
+        if args.gnuplot {
+            get_plot_script(SPECTRUM, units).write(&filename)?;
+        }
+    }
+
+    if let Some(filename) = args.contact_line_dump {
+        // The resampled contact lines all have identical angles.
+        let kymograph =
+            Kymograph::from_contact_lines(&series.times, &resampled_contact_lines).unwrap();
+        write_kymograph(&filename, &kymograph)?;
+    }
+
+    // The last block is appended to the previous ones in chunked mode.
+    series.write(&series_outputs, num_frames > chunk_size)?;
+
+    for component in components.iter_mut() {
+        let outputs = component.registry.finish();
+        component
+            .paths
+            .write_outputs(&outputs, num_frames > chunk_size)?;
+    }
+
+    if let (Some(path), Some(outputs)) = (&args.manifest, manifest_outputs) {
+        let manifest =
+            summary.to_manifest(smoothing_radius, calibration, &args.radius, &outputs)?;
+        serde_json::to_writer_pretty(File::create(path)?, &manifest)?;
+    }
+
+    Ok(())
+}
+
+/// Summary of the analyzed frames of a run, for its manifest.
+#[derive(Default)]
+struct RunSummary {
+    num_frames: usize,
+    num_skipped: usize,
+    time_range: Option<(f64, f64)>,
+    radius_sum: f64,
+    num_radius: usize,
+    /// Frames in which no droplet was found, with their times.
+    failures: Vec<(PathBuf, f64)>,
+    /// Frames which could not be read, with their errors.
+    read_errors: Vec<(PathBuf, String)>,
+    /// Total mass of the first and last frames.
+    masses: Option<(f64, f64)>,
+}
+
+impl RunSummary {
+    fn add_frame(&mut self, filename: &Path, time: f64, radius: Option<f64>) {
+        self.num_frames += 1;
+
+        let (begin, end) = self.time_range.unwrap_or((time, time));
+        self.time_range = Some((begin.min(time), end.max(time)));
+
+        match radius {
+            Some(radius) => {
+                self.radius_sum += radius;
+                self.num_radius += 1;
+            }
+            None => self.failures.push((filename.to_path_buf(), time)),
+        }
+    }
+
+    fn add_mass(&mut self, densmap: &DensMap) {
+        let mass = densmap.data.iter().filter(|v| v.is_finite()).sum();
+        let (first, _) = self.masses.unwrap_or((mass, mass));
+
+        self.masses = Some((first, mass));
+    }
+
+    fn mean_radius(&self) -> Option<f64> {
+        match self.num_radius {
+            0 => None,
+            n => Some(self.radius_sum / n as f64),
+        }
+    }
+
+    /// Get the relative drift of the total mass from the first to the last frame.
+    fn mass_drift(&self) -> Option<f64> {
+        self.masses
+            .filter(|&(first, _)| first != 0.0)
+            .map(|(first, last)| (last - first) / first)
+    }
+
+    /// Get the manifest of the run with its parameters, summary and output files.
+    fn to_manifest(
+        &self,
+        smoothing_radius: f64,
+        calibration: Option<Value>,
+        radius: &Path,
+        outputs: &[PathBuf],
+    ) -> Result<Value, io::Error> {
+        let mean_radius = self.mean_radius();
+
+        let failures = self
+            .failures
+            .iter()
+            .map(|(path, time)| json!({ "file": path.display().to_string(), "time": time }))
+            .collect::<Vec<_>>();
+
+        let read_errors = self
+            .read_errors
+            .iter()
+            .map(|(path, err)| json!({ "file": path.display().to_string(), "error": err }))
+            .collect::<Vec<_>>();
+
+        Ok(json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "arguments": std::env::args().collect::<Vec<_>>(),
+            "working_directory": std::env::current_dir()?.display().to_string(),
+            "smoothing_radius": smoothing_radius,
+            "smoothing_calibration": calibration,
+            "frames": {
+                "analyzed": self.num_frames,
+                "skipped": self.num_skipped,
+                "without_droplet": self.failures.len(),
+                "unreadable": self.read_errors.len(),
+            },
+            "time_range": self.time_range.map(|(begin, end)| vec![begin, end]),
+            "mean_radius": mean_radius,
+            "failures": failures,
+            "read_errors": read_errors,
+            "radius": radius.display().to_string(),
+            "outputs": outputs
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>(),
+        }))
+    }
+}
+
+/// Print the summary statistics of a run, as text or JSON.
+fn print_statistics(
+    summary: &RunSummary,
+    series: &TimeSeries,
+    outputs: &Outputs,
+    json: bool,
+) -> Result<(), io::Error> {
+    let spreading_exponent = fit_power_law_exponent(&series.times, &series.radius);
+    let relaxation_time = outputs.iter().find_map(|(name, output)| match output {
+        Output::Histogram(autocorrelation) if name == AUTOCORRELATION => {
+            calc_relaxation_time(&autocorrelation.x, &autocorrelation.y)
+        }
+        _ => None,
+    });
+
+    if json {
+        let statistics = json!({
+            "frames": summary.num_frames,
+            "time_range": summary.time_range.map(|(begin, end)| vec![begin, end]),
+            "mean_radius": summary.mean_radius(),
+            "spreading_exponent": spreading_exponent,
+            "relaxation_time": relaxation_time,
+            "mass_drift": summary.mass_drift(),
+        });
+
+        println!("{}", serde_json::to_string_pretty(&statistics)?);
+    } else {
+        let format = |value: Option<f64>| match value {
+            Some(value) => value.to_string(),
+            None => "-".to_string(),
+        };
+
+        let time_range = match summary.time_range {
+            Some((begin, end)) => format!("{} to {}", begin, end),
+            None => "-".to_string(),
+        };
+
+        println!("Frames:                   {}", summary.num_frames);
+        println!("Time range:               {}", time_range);
+        println!(
+            "Mean radius:              {}",
+            format(summary.mean_radius())
+        );
+        println!("Spreading exponent:       {}", format(spreading_exponent));
+        println!("Relaxation time:          {}", format(relaxation_time));
+        println!("Mass drift:               {}", format(summary.mass_drift()));
+    }
+
+    Ok(())
+}
+
+/// Mask and crop a density map before analysis.
+fn mask_and_crop(mut densmap: DensMap, args: &Args, filename: &Path) -> Result<DensMap, io::Error> {
+    densmap.mask(&args.mask).map_err(|msg| {
+        Failure::Analysis.file_error(
+            filename,
+            format!("could not mask '{}': {}", filename.display(), msg),
+        )
+    })?;
+
+    match args.roi {
+        Some([xmin, xmax, ymin, ymax]) => densmap.crop(xmin, xmax, ymin, ymax).map_err(|msg| {
+            Failure::Analysis.file_error(
+                filename,
+                format!("could not crop '{}': {}", filename.display(), msg),
+            )
+        }),
+        None => Ok(densmap),
+    }
+}
+
+/// Run the registered analyses for a frame.
+fn process_frame(
+    registry: &mut AnalysisRegistry,
+    smoothed_densmap: &DensMap,
+    time: f64,
+    filename: &Path,
+) -> Result<(FrameResults, Outputs), io::Error> {
+    registry
+        .process_frame(smoothed_densmap, time)
+        .map_err(|msg| {
+            Failure::Analysis.file_error(
+                filename,
+                format!("could not analyze '{}': {}", filename.display(), msg),
+            )
+        })
+}
+
+/// Output files of the analyses, by the names of their outputs.
+///
+/// Outputs of every frame are written to files named by the base and the time signature
+/// of the frame, while outputs of all frames are written to the files as they are.
+/// Outputs without a file are not written.
+#[derive(Clone)]
+struct OutputPaths {
+    paths: Vec<(&'static str, PathBuf)>,
+    gnuplot: bool,
+    plot: bool,
+    units: Units,
+    /// Write the outputs of frames as data sets of a single file.
+    multiset: bool,
+    /// Outputs of frames for which the file of data sets has been created.
+    started: Vec<String>,
+}
+
+impl OutputPaths {
+    fn new(args: &Args) -> Self {
+        let paths = vec![
+            (RADIAL_DENSITY, &args.radial_density),
+            (RADIAL_OCCUPANCY, &args.radial_occupancy),
+            (RADIAL_MEAN_DENSITY, &args.radial_mean_density),
+            (DENSITY_DISTRIBUTION, &args.density_distribution),
+            (
+                DENSITY_DISTRIBUTION_AVERAGE,
+                &args.density_distribution_average,
+            ),
+            (INTERFACE, &args.interface),
+            (CONTACT_LINE, &args.contact_line),
+            (CONTACT_LINE_POLYGON, &args.contact_line_polygon),
+            (AUTOCORRELATION, &args.autocorrelation),
+        ]
+        .into_iter()
+        .filter_map(|(name, path)| path.clone().map(|path| (name, path)))
+        .chain(
+            args.radial_density
+                .as_ref()
+                .filter(|_| args.rdd_min_samples.is_some())
+                .map(|path| (RADIAL_BIN_EDGES, add_suffix(path, "edges"))),
+        )
+        .chain(std::iter::once((RADIUS, args.radius.clone())))
+        .collect();
+
+        OutputPaths {
+            paths,
+            gnuplot: args.gnuplot,
+            plot: args.plot,
+            units: args.units(),
+            multiset: args.multiset,
+            started: Vec::new(),
+        }
+    }
+
+    fn with_suffix(&self, suffix: &str) -> Self {
+        OutputPaths {
+            paths: self
+                .paths
+                .iter()
+                .map(|(name, path)| (*name, add_suffix(path, suffix)))
+                .collect(),
+            gnuplot: self.gnuplot,
+            plot: self.plot,
+            units: self.units,
+            multiset: self.multiset,
+            started: Vec::new(),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&Path> {
+        self.paths
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, path)| path.as_path())
+    }
+
+    /// Write the outputs of a frame to files of its time signature, or as new data sets
+    /// of their files if set.
+    fn write_frame_outputs(
+        &mut self,
+        outputs: &Outputs,
+        time_signature: &str,
+        dir: &Path,
+    ) -> Result<(), io::Error> {
+        for (name, output) in outputs {
+            if let (true, Some(base)) = (self.multiset, self.get(name)) {
+                let path = construct_file_name(base, "", OsStr::new("xvg"), dir);
+
+                // Files from previous runs are replaced.
+                if !self.started.contains(name) {
+                    File::create(&path)?;
+                    self.started.push(name.clone());
+                }
+
+                match output {
+                    Output::Graph(graph) => append_xvg_set(&path, graph)?,
+                    Output::Histogram(histogram) => append_xvg_set(&path, histogram)?,
+                }
+            } else if let Some(base) = self.get(name) {
+                let path = construct_file_name(base, time_signature, OsStr::new("xvg"), dir);
+                write_output(&path, output, false)?;
+
+                if self.gnuplot {
+                    get_plot_script(name, self.units).write(&path)?;
+                }
+
+                if self.plot {
+                    write_plot(&path, name, self.units)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the files of the outputs of a frame with its time signature.
+    ///
+    /// Frames have no files of their own when their outputs are written as data sets.
+    fn get_frame_paths(&self, time_signature: &str, dir: &Path) -> Vec<PathBuf> {
+        if self.multiset {
+            return Vec::new();
+        }
+
+        FRAME_OUTPUTS
+            .iter()
+            .filter_map(|name| self.get(name))
+            .map(|base| construct_file_name(base, time_signature, OsStr::new("xvg"), dir))
+            .collect()
+    }
+
+    /// Write the outputs of all frames to their files, appending to them if set.
+    fn write_outputs(&self, outputs: &Outputs, append: bool) -> Result<(), io::Error> {
+        for (name, output) in outputs {
+            if let Some(path) = self.get(name) {
+                write_output(path, output, append)?;
+
+                if self.gnuplot {
+                    get_plot_script(name, self.units).write(path)?;
+                }
+
+                if self.plot {
+                    write_plot(path, name, self.units)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Read a list of files with one path per line from a file or stdin, skipping
+/// empty lines and comments.
+fn read_file_list(path: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    let reader: Box<dyn BufRead> = if is_stdio(path) {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        let fp = File::open(path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("could not read file list '{}': {}", path.display(), err),
+            )
+        })?;
+
+        Box::new(BufReader::new(fp))
+    };
+
+    let mut paths = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if !line.is_empty() && !line.starts_with('#') {
+            paths.push(PathBuf::from(line));
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Warn about duplicate times and gaps in the times of the frames of a trajectory.
+fn warn_time_steps(times: &[f64]) {
+    for issue in check_time_steps(times) {
+        eprintln!("Warning: {}.", issue);
+    }
+}
+
+/// Print the frames which would be analyzed and the files which would be written for
+/// them and the full trajectory, without reading any maps.
+fn print_dry_run(args: &Args, source: &FrameSource) -> Result<(), io::Error> {
+    let re = Regex::new(&args.time_regex).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid time signature '{}': {}", args.time_regex, err),
+        )
+    })?;
+    let conversion = args.time_conversion();
+
+    let frames = match source {
+        FrameSource::Files(paths) => paths
+            .iter()
+            .map(|path| {
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+
+                let time = re
+                    .captures(&file_name)
+                    .and_then(|captures| captures.get(1))
+                    .and_then(|time| time.as_str().parse::<f64>().ok())
+                    .map(|time| conversion.apply(time));
+
+                (path.clone(), time)
+            })
+            .collect::<Vec<_>>(),
+        FrameSource::Container { path, frames } => {
+            let container = Container::open(path)?;
+
+            frames
+                .iter()
+                .map(|&i| {
+                    let time = container.index()[i].time;
+                    let label = format!("{}[{:09.3}ps]", path.display(), time);
+
+                    (PathBuf::from(label), Some(conversion.apply(time)))
+                })
+                .collect()
+        }
+    };
+
+    let component_paths = get_component_paths(args);
+
+    println!("Frames ({}):", frames.len());
+
+    for (i, (path, time)) in frames.iter().enumerate() {
+        match time {
+            Some(time) => println!("{} (t = {} {})", path.display(), time, args.time_unit),
+            None => println!("{} (no time in file name)", path.display()),
+        }
+
+        let dir = get_frame_output_dir(args, path);
+        let time_signature = read_time_signature_or_default(path, &re, i);
+
+        for output in get_frame_output_paths(args, &component_paths, &time_signature, dir) {
+            println!("    {}", output.display());
+        }
+    }
+
+    println!("Outputs of the trajectory:");
+    for output in get_trajectory_output_paths(args, &component_paths, &get_source_dir(args, source))
+    {
+        println!("    {}", output.display());
+    }
+
+    Ok(())
+}
+
+/// Get the output paths of the components, which have their names as suffixes.
+fn get_component_paths(args: &Args) -> Vec<OutputPaths> {
+    let output_paths = OutputPaths::new(args);
+
+    args.components
+        .iter()
+        .map(|name| output_paths.with_suffix(name))
+        .collect()
+}
+
+/// Warn that the background could not be subtracted from the map of a frame or one of
+/// its components, as long as fewer than `MAX_BACKGROUND_WARNINGS` have been.
+fn warn_background_error(
+    path: &Path,
+    component: Option<&str>,
+    error: Option<String>,
+    num_errors: &mut usize,
+) {
+    if let Some(err) = error {
+        *num_errors += 1;
+
+        if *num_errors <= MAX_BACKGROUND_WARNINGS {
+            let name = match component {
+                Some(component) => format!("component '{}' of '{}'", component, path.display()),
+                None => format!("'{}'", path.display()),
+            };
+
+            eprintln!(
+                "Warning: could not subtract the background of {}, which is analyzed with it: {}.",
+                name, err
+            );
+        }
+    }
+}
+
+/// Get the directory in which the outputs of a frame are written, which is that of its
+/// map unless another is set.
+fn get_frame_output_dir<'a>(args: &'a Args, path: &'a Path) -> &'a Path {
+    args.frame_output_dir
+        .as_deref()
+        .or_else(|| path.parent())
+        .unwrap_or_else(|| Path::new(""))
+}
+
+/// Get the directory in which the outputs of the first frame are written.
+fn get_source_dir(args: &Args, source: &FrameSource) -> PathBuf {
+    let path = match source {
+        FrameSource::Files(paths) => paths.first(),
+        FrameSource::Container { path, .. } => Some(path),
+    };
+
+    path.map(|path| get_frame_output_dir(args, path).to_path_buf())
+        .unwrap_or_default()
+}
+
+/// Get the files which are written for a frame with its time signature.
+fn get_frame_output_paths(
+    args: &Args,
+    component_paths: &[OutputPaths],
+    time_signature: &str,
+    dir: &Path,
+) -> Vec<PathBuf> {
+    let get_path = |base: &Path, ext: &OsStr| construct_file_name(base, time_signature, ext, dir);
+
+    let mut paths = [&args.smooth, &args.residual, &args.gradient, &args.binary]
+        .iter()
+        .filter_map(|base| base.as_ref())
+        .map(|base| get_path(base, &args.ext))
+        .collect::<Vec<_>>();
+
+    if let Some(base) = &args.binary {
+        paths.push(get_path(base, OsStr::new("pgm")));
+    }
+
+    let output_paths =
+        std::iter::once(OutputPaths::new(args)).chain(component_paths.iter().cloned());
+    for outputs in output_paths {
+        paths.extend(outputs.get_frame_paths(time_signature, dir));
+    }
+
+    if args.flow.is_some() {
+        paths.extend(
+            [&args.flow_profile, &args.flow_contact_line]
+                .iter()
+                .filter_map(|base| base.as_ref())
+                .map(|base| get_path(base, OsStr::new("xvg"))),
+        );
+    }
+
+    paths
+}
+
+/// Get the files which are written for the full trajectory.
+fn get_trajectory_output_paths(
+    args: &Args,
+    component_paths: &[OutputPaths],
+    dir: &Path,
+) -> Vec<PathBuf> {
+    let mut paths = vec![args.radius.clone()];
+
+    for outputs in std::iter::once(OutputPaths::new(args)).chain(component_paths.iter().cloned()) {
+        if outputs.multiset {
+            paths.extend(
+                FRAME_OUTPUTS
+                    .iter()
+                    .filter_map(|name| outputs.get(name))
+                    .map(|base| construct_file_name(base, "", OsStr::new("xvg"), dir)),
+            );
+        }
+    }
+
+    paths.extend(args.autocorrelation.clone());
+    paths.extend(args.density_distribution_average.clone());
+    paths.extend(
+        component_paths
+            .iter()
+            .filter_map(|outputs| outputs.get(RADIUS))
+            .map(|path| path.to_path_buf()),
+    );
+    paths.extend(args.variance.clone());
+
+    if let Some(base) = &args.autocorrelation_map {
+        paths.extend(
+            args.autocorrelation_map_lags
+                .iter()
+                .map(|&lag| get_lag_path(base, lag, &args.ext)),
+        );
+    }
+
+    paths.extend(
+        [
+            &args.space_time_correlation,
+            &args.spectrum,
+            &args.tidy,
+            &args.contact_line_dump,
+            &args.droplet_frame,
+            &args.geometry,
+            &args.precursor,
+            &args.registration,
+            &args.netcdf,
+            &args.arrow,
+            &args.report,
+        ]
+        .iter()
+        .filter_map(|path| path.as_ref().cloned()),
+    );
+
+    paths
+}
+
+/// Whether any per-frame outputs are set for a frame and all of their files exist.
+fn frame_outputs_exist(
+    args: &Args,
+    output_paths: &OutputPaths,
+    time_signature: &str,
+    dir: &Path,
+) -> bool {
+    let paths = output_paths
+        .get_frame_paths(time_signature, dir)
+        .into_iter()
+        .chain(
+            args.smooth
+                .iter()
+                .map(|base| construct_file_name(base, time_signature, &args.ext, dir)),
+        )
+        .collect::<Vec<_>>();
+
+    !paths.is_empty() && paths.iter().all(|path| path.exists())
+}
+
+/// Get the title and axis labels of a named analysis output, with times and lengths
+/// in the units.
+fn get_plot_labels(name: &str, units: Units) -> (String, String, String) {
+    let time = |label| format!("{} ({})", label, units.time);
+    let length = |label| format!("{} ({})", label, units.length);
+
+    match name {
+        RADIAL_DENSITY => (
+            "Radial density".to_string(),
+            length("r"),
+            "density".to_string(),
+        ),
+        RADIAL_BIN_EDGES => (
+            "Radial bin edges".to_string(),
+            length("inner edge"),
+            length("outer edge"),
+        ),
+        DENSITY_DISTRIBUTION | DENSITY_DISTRIBUTION_AVERAGE => (
+            "Density distribution".to_string(),
+            "density".to_string(),
+            "p".to_string(),
+        ),
+        RADIAL_OCCUPANCY => (
+            "Radial occupancy".to_string(),
+            length("r"),
+            "occupancy".to_string(),
+        ),
+        RADIAL_MEAN_DENSITY => (
+            "Radial mean density".to_string(),
+            length("r"),
+            "density".to_string(),
+        ),
+        INTERFACE | CONTACT_LINE_POLYGON => ("Interface".to_string(), length("x"), length("y")),
+        CONTACT_LINE => (
+            "Contact line".to_string(),
+            "angle (deg)".to_string(),
+            length("dr"),
+        ),
+        RADIUS => ("Droplet radius".to_string(), time("t"), length("r")),
+        AUTOCORRELATION => (
+            "Contact line autocorrelation".to_string(),
+            time("t"),
+            "C(t)".to_string(),
+        ),
+        SPECTRUM => (
+            "Mode spectrum".to_string(),
+            "q".to_string(),
+            "power".to_string(),
+        ),
+        _ => (name.to_string(), "x".to_string(), "y".to_string()),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+/// Units of the times and lengths of the outputs.
+struct Units {
+    time: TimeUnit,
+    length: LengthUnit,
+}
+
+/// Name of the mode spectrum output, which is not an analysis of the pipeline.
+const SPECTRUM: &str = "spectrum";
+
+/// Get the gnuplot script of a named analysis output.
+fn get_plot_script(name: &str, units: Units) -> GnuplotScript {
+    let (title, xlabel, ylabel) = get_plot_labels(name, units);
+    GnuplotScript::new(&title, &xlabel, &ylabel)
+}
+
+/// Render the graph in a written xvg file of a named output as an SVG image next to it,
+/// if it is one of the outputs which are plotted.
+///
+/// The graph is read back from the file to include all of the data in appended files.
+fn write_plot(path: &Path, name: &str, units: Units) -> Result<(), io::Error> {
+    if [RADIUS, AUTOCORRELATION, INTERFACE].contains(&name) {
+        let (_, xlabel, ylabel) = get_plot_labels(name, units);
+        write_svg_plot(
+            &path.with_extension("svg"),
+            &read_xvg(path)?,
+            &xlabel,
+            &ylabel,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_output(path: &Path, output: &Output, append: bool) -> Result<(), io::Error> {
+    match (output, append) {
+        (Output::Graph(graph), false) => write_xvg(path, graph),
+        (Output::Graph(graph), true) => append_xvg(path, graph),
+        (Output::Histogram(histogram), false) => write_xvg(path, histogram),
+        (Output::Histogram(histogram), true) => append_xvg(path, histogram),
+    }
+}
+
+/// Field of multi-field maps which is analyzed as a separate component, with outputs
+/// which are suffixed by its name.
+struct Component {
+    registry: AnalysisRegistry,
+    paths: OutputPaths,
+    radial_binning: RadialBinning,
+}
+
+impl Component {
+    fn new(name: &str, args: &Args) -> Self {
+        let mut registry = AnalysisRegistry::new();
+        registry.set_density_profile_options(args.density_profile_options());
+        if args.radial_density.is_some() {
+            registry.register(RadialDensityAnalysis::new(args.density_profile_options()));
+        }
+        if args.radial_occupancy.is_some() {
+            registry.register(RadialOccupancyAnalysis::new(args.density_profile_options()));
+        }
+        if args.radial_mean_density.is_some() {
+            registry.register(RadialMeanDensityAnalysis::new(
+                args.density_profile_options(),
+            ));
+        }
+        if args.interface.is_some() || args.contact_line.is_some() {
+            registry.register(InterfaceAnalysis);
+        }
+        registry.register(RadiusAnalysis::default());
+        registry.set_sample_interface(args.interface.is_some() || args.contact_line.is_some());
+
+        let paths = OutputPaths::new(args).with_suffix(name);
+
+        Component {
+            registry,
+            paths,
+            radial_binning: RadialBinning::with_options(args.density_profile_options()),
+        }
+    }
+}
+
+/// Add a suffix to the file name of a path, before its extension if it has one.
+fn add_suffix(path: &Path, suffix: &str) -> PathBuf {
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => {
+            let mut file_name = stem.to_os_string();
+            file_name.push(format!("_{}.", suffix));
+            file_name.push(ext);
+
+            path.with_file_name(file_name)
+        }
+        _ => {
+            let mut path = path.as_os_str().to_os_string();
+            path.push(format!("_{}", suffix));
+
+            PathBuf::from(path)
+        }
+    }
+}
+
+/// Time series of results for every analyzed frame.
+#[derive(Default)]
+struct TimeSeries {
+    times: Vec<f64>,
+    radius: Vec<f64>,
+    precursor_films: Vec<PrecursorFilm>,
+    registration_times: Vec<f64>,
+    translations: Vec<Vec2>,
+    droplet_frame_times: Vec<f64>,
+    droplet_frames: Vec<DropletFrame>,
+    /// Times with the area and perimeter of the contact line polygon.
+    geometry: Vec<(f64, f64, f64)>,
+}
+
+/// Output files of the time series.
+struct TimeSeriesOutputs {
+    radius: PathBuf,
+    precursor: Option<PathBuf>,
+    registration: Option<PathBuf>,
+    droplet_frame: Option<PathBuf>,
+    geometry: Option<PathBuf>,
+    gnuplot: bool,
+    plot: bool,
+    units: Units,
+}
+
+impl TimeSeries {
+    /// Write the time series to their output files, appending to them if set.
+    fn write(&self, outputs: &TimeSeriesOutputs, append: bool) -> Result<(), io::Error> {
+        if let Some(filename) = &outputs.registration {
+            write_translations(
+                filename,
+                &self.registration_times,
+                &self.translations,
+                append,
+            )?;
+        }
+
+        if let Some(filename) = &outputs.droplet_frame {
+            if append {
+                append_droplet_frames(filename, &self.droplet_frame_times, &self.droplet_frames)?;
+            } else {
+                write_droplet_frames(filename, &self.droplet_frame_times, &self.droplet_frames)?;
+            }
+        }
+
+        if let Some(filename) = &outputs.geometry {
+            let (times, (area, perimeter)): (Vec<f64>, (Vec<f64>, Vec<f64>)) = self
+                .geometry
+                .iter()
+                .map(|&(time, area, perimeter)| (time, (area, perimeter)))
+                .unzip();
+
+            let columns = Columns::new("t", times)
+                .with_column("area", area)
+                .with_column("perimeter", perimeter);
+
+            if append {
+                append_xvg_columns(filename, &columns)?;
+            } else {
+                write_xvg_columns(filename, &columns)?;
+            }
+        }
+
+        if let Some(filename) = &outputs.precursor {
+            if append {
+                append_precursor_films(filename, &self.times, &self.precursor_films)?;
+            } else {
+                write_precursor_films(filename, &self.times, &self.precursor_films)?;
+            }
+        }
+
+        // The radius is written as it is calculated for every frame.
+        if outputs.gnuplot {
+            get_plot_script(RADIUS, outputs.units).write(&outputs.radius)?;
+        }
+
+        if outputs.plot {
+            write_plot(&outputs.radius, RADIUS, outputs.units)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Write the variance and autocorrelation maps of the frames which have been analyzed.
+fn write_map_aggregates(
+    variance_path: &Option<PathBuf>,
+    variance_map: &VarianceMap,
+    autocorrelation_base: &Option<PathBuf>,
+    autocorrelation_map: &AutocorrelationMap,
+    ext: &OsStr,
+    time: f64,
+    length_unit: LengthUnit,
+) -> Result<(), io::Error> {
+    if let (Some(filename), Some(densmap)) = (variance_path, variance_map.variance()) {
+        write_map(filename, &densmap, time, length_unit)?;
+    }
+
+    if let Some(base) = autocorrelation_base {
+        for (lag, densmap) in autocorrelation_map.get_maps() {
+            write_map(&get_lag_path(base, lag, ext), &densmap, time, length_unit)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write a density map with its lengths converted from the unit of the analysis to nm.
+fn write_map(
+    path: &Path,
+    densmap: &DensMap,
+    time: f64,
+    length_unit: LengthUnit,
+) -> Result<(), io::Error> {
+    match length_unit {
+        LengthUnit::Nm => write_densmap(path, densmap, time),
+        _ => write_densmap(
+            path,
+            &densmap.clone().scale_lengths(length_unit.in_nm()),
+            time,
+        ),
+    }
+}
+
+/// Write the translation of every map relative to the previous one, optionally
+/// appending to the file without a header.
+fn write_translations(
+    path: &Path,
+    times: &[f64],
+    translations: &[Vec2],
+    append: bool,
+) -> Result<(), io::Error> {
+    let columns = Columns::new("time", times.to_vec())
+        .with_column("dx", translations.iter().map(|[dx, _]| *dx).collect())
+        .with_column("dy", translations.iter().map(|[_, dy]| *dy).collect());
+
+    if append {
+        append_xvg_columns(path, &columns)
+    } else {
+        write_xvg_columns(path, &columns)
+    }
+}
+
+/// Parse the periodic axes given as 'x', 'y' or 'xy'.
+fn parse_periodic(s: &str) -> Result<[bool; 2], String> {
+    match s {
+        "" => Ok([false, false]),
+        "x" => Ok([true, false]),
+        "y" => Ok([false, true]),
+        "xy" | "yx" => Ok([true, true]),
+        _ => Err(format!(
+            "invalid periodic axes '{}': must be 'x', 'y' or 'xy'",
+            s
+        )),
+    }
+}
+
+/// Parse the edge modes given as 'mode' for both axes or 'xmode,ymode'.
+fn parse_edge_modes(s: &str) -> Result<[EdgeMode; 2], String> {
+    let modes = s
+        .split(',')
+        .map(|v| v.trim().parse::<EdgeMode>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match *modes.as_slice() {
+        [mode] => Ok([mode, mode]),
+        [mode_x, mode_y] => Ok([mode_x, mode_y]),
+        _ => Err(format!(
+            "edge modes '{}' must be given as 'mode' or 'xmode,ymode'",
+            s
+        )),
+    }
+}
+
+/// Parse a region given as 'xmin,xmax,ymin,ymax'.
+fn parse_region(s: &str) -> Result<[f64; 4], String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("could not parse region '{}': {}", s, err))?;
+
+    match values.as_slice() {
+        &[xmin, xmax, ymin, ymax] => Ok([xmin, xmax, ymin, ymax]),
+        _ => Err(format!(
+            "region '{}' must be given as 'xmin,xmax,ymin,ymax'",
+            s
+        )),
+    }
+}
+
+/// Smoothing radii which are tried when calibrating the smoothing.
+const CALIBRATION_SMOOTHING_RADII: [f64; 6] = [0.25, 0.5, 0.75, 1.0, 1.5, 2.0];
+
+/// Analyze the input files without smoothing and with several smoothing radii, print
+/// the results of every radius and return the selected one along with a record of the
+/// calibration for the manifest.
+///
+/// The maps are masked, cropped and have their background subtracted like in the
+/// analysis of the full trajectory.
+fn calibrate_smoothing(
+    source: FrameSource,
+    args: &Args,
+    length_scale: f64,
+    options: SmoothingOptions,
+) -> Result<(Option<f64>, Value), io::Error> {
+    let mut densmaps = Vec::with_capacity(source.len());
+    let mut filenames = Vec::with_capacity(source.len());
+
+    // Frames which cannot be read are skipped like in the analysis, which reports them.
+    for (filename, result) in FrameReader::spawn(source, args.grid_options(), Vec::new(), 1) {
+        let densmap = match result {
+            Ok((densmap, _, _)) => densmap,
+            Err(_) if args.skip_errors => continue,
+            Err(err) => {
+                return Err(Failure::Io.file_error(
+                    &filename,
+                    format!("could not read '{}': {}", filename.display(), err),
+                ));
+            }
+        };
+
+        densmaps.push(mask_and_crop(
+            densmap.scale_lengths(length_scale),
+            args,
+            &filename,
+        )?);
+        filenames.push(filename);
+    }
+
+    let frames = match (filenames.first(), filenames.last()) {
+        (Some(first), Some(last)) => format!(
+            "{} frames from '{}' to '{}'",
+            filenames.len(),
+            first.display(),
+            last.display()
+        ),
+        _ => String::from("no frames"),
+    };
+
+    let mut radial_binning = RadialBinning::with_options(args.density_profile_options());
+    let calibration = calibrate_smoothing_radius(
+        &densmaps,
+        &CALIBRATION_SMOOTHING_RADII,
+        options,
+        RadialBinning::with_options(args.density_profile_options()),
+        |densmap, smoother| {
+            smoothen_densmap(
+                densmap,
+                smoother,
+                &mut radial_binning,
+                args.subtract_background,
+            )
+            .0
+        },
+    );
+    let selected = select_smoothing_radius(&calibration, args.calibration_bias_weight);
+
+    eprintln!("Calibrated smoothing radius using {}:", frames);
+    eprintln!(
+        "{:>12} {:>12} {:>12} {:>12} {:>12}",
+        "smoothing", "radius", "noise", "bias", "width"
+    );
+    for c in calibration.reference.iter().chain(&calibration.candidates) {
+        eprintln!(
+            "{:12.5} {:12.5} {:12.5} {:12.5} {:12.5}{}",
+            c.smoothing_radius,
+            c.droplet_radius,
+            c.noise,
+            c.bias,
+            c.interface_width,
+            if Some(c.smoothing_radius) == selected {
+                "  (selected)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    if selected.is_none() {
+        eprintln!(
+            "Warning: no smoothing radius could be selected by calibration using {}, using {}.",
+            frames, args.smoothing_radius
+        );
+    }
+
+    let to_json = |c: &SmoothingCalibration| {
+        json!({
+            "smoothing_radius": c.smoothing_radius,
+            "droplet_radius": c.droplet_radius,
+            "noise": c.noise,
+            "bias": c.bias,
+            "interface_width": c.interface_width,
+        })
+    };
+
+    let record = json!({
+        "frames": densmaps.len(),
+        "bias_weight": args.calibration_bias_weight,
+        "reference": calibration.reference.as_ref().map(to_json),
+        "candidates": calibration.candidates.iter().map(to_json).collect::<Vec<_>>(),
+        "selected": selected,
+    });
+
+    Ok((selected, record))
+}
+
+/// The file name is joined from its parts as they are, which keeps any bytes of paths
+/// which are not valid UTF-8.
+fn construct_file_name(base: &Path, time_sig: &str, ext: &OsStr, dir: &Path) -> PathBuf {
+    let mut file_name = base.as_os_str().to_os_string();
+    file_name.push(time_sig);
+    file_name.push(".");
+    file_name.push(ext);
+
+    dir.join(file_name)
+}
+
+/// Get the path of the autocorrelation map at a time lag, eg. 'acflag10.dat'.
+fn get_lag_path(base: &Path, lag: usize, ext: &OsStr) -> PathBuf {
+    construct_file_name(base, &format!("lag{}", lag), ext, Path::new(""))
+}
+
+fn read_time_signature_or_default(path: &Path, re: &Regex, index: usize) -> String {
+    match re.captures(&path.to_string_lossy()) {
+        Some(capture) => String::from(capture.get(0).unwrap().as_str()),
+        None => format!("{:05}", index + 1),
+    }
 }
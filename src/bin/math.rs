@@ -0,0 +1,94 @@
+use structopt::StructOpt;
+
+use std::{io, path::PathBuf};
+
+use densmap::densmap::{read_densmap_with_grid, write_densmap, DensMap};
+
+use crate::{failure::Failure, verify::VerifyArgs};
+
+#[derive(Debug, StructOpt)]
+/// Arithmetic operations on density maps
+pub enum MathArgs {
+    #[structopt(name = "add")]
+    /// Add two density maps
+    Add(BinaryArgs),
+    #[structopt(name = "sub")]
+    /// Subtract the second density map from the first
+    Sub(BinaryArgs),
+    #[structopt(name = "min")]
+    /// Take the minimum of two density maps in every bin
+    Min(BinaryArgs),
+    #[structopt(name = "max")]
+    /// Take the maximum of two density maps in every bin
+    Max(BinaryArgs),
+    #[structopt(
+        name = "scale",
+        raw(setting = "structopt::clap::AppSettings::AllowNegativeNumbers")
+    )]
+    /// Multiply a density map by a factor
+    Scale(ScaleArgs),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct BinaryArgs {
+    #[structopt(parse(from_os_str))]
+    /// First density map
+    a: PathBuf,
+
+    #[structopt(parse(from_os_str))]
+    /// Second density map
+    b: PathBuf,
+
+    #[structopt(parse(from_os_str))]
+    /// Output path for the resulting density map, which gets the time of the first map
+    output: PathBuf,
+
+    #[structopt(flatten)]
+    verify: VerifyArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ScaleArgs {
+    #[structopt(parse(from_os_str))]
+    /// Density map to scale
+    input: PathBuf,
+
+    /// Factor to multiply all values by
+    factor: f64,
+
+    #[structopt(parse(from_os_str))]
+    /// Output path for the resulting density map
+    output: PathBuf,
+
+    #[structopt(flatten)]
+    verify: VerifyArgs,
+}
+
+pub fn run_math(args: MathArgs) -> Result<(), io::Error> {
+    match args {
+        MathArgs::Scale(args) => {
+            let (densmap, time) = read_densmap_with_grid(&args.input, &args.verify.options())?;
+            write_densmap(&args.output, &densmap.scale(args.factor), time)
+        }
+        MathArgs::Add(args) => run_binary(args, |a, b| a.add(b)),
+        MathArgs::Sub(args) => run_binary(args, |a, b| a.subtract(b)),
+        MathArgs::Min(args) => run_binary(args, |a, b| a.min(b)),
+        MathArgs::Max(args) => run_binary(args, |a, b| a.max(b)),
+    }
+}
+
+fn run_binary<F>(args: BinaryArgs, op: F) -> Result<(), io::Error>
+where
+    F: FnOnce(&DensMap, &DensMap) -> Result<DensMap, String>,
+{
+    let grid = args.verify.options();
+
+    let (a, time) = read_densmap_with_grid(&args.a, &grid)?;
+    let (b, _) = read_densmap_with_grid(&args.b, &grid)?;
+
+    let densmap = op(&a, &b).map_err(|msg| {
+        Failure::BadArguments.error(format!("could not combine density maps: {}", msg))
+    })?;
+
+    write_densmap(&args.output, &densmap, time)
+}
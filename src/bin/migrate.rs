@@ -0,0 +1,121 @@
+use structopt::StructOpt;
+
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use densmap::densmap::{read_field_maps_with_grid, write_field_maps_with_level, GridOptions};
+
+use crate::{progress::ProgressArgs, verify::VerifyArgs};
+
+#[derive(Debug, StructOpt)]
+/// Rewrite density map files in place, optionally changing their compression
+pub struct MigrateArgs {
+    #[structopt(parse(from_os_str), required = true)]
+    /// List of density map files to rewrite
+    filenames: Vec<PathBuf>,
+
+    #[structopt(long = "compress", conflicts_with = "decompress")]
+    /// Compress the files with gzip, adding '.gz' to their file names
+    compress: bool,
+
+    #[structopt(long = "decompress")]
+    /// Write the files uncompressed, removing '.gz' from their file names
+    decompress: bool,
+
+    #[structopt(long = "level", default_value = "6", value_name = "level")]
+    /// Compression level for gzipped files, from 0 (none) to 9 (best)
+    level: u32,
+
+    #[structopt(flatten)]
+    verify: VerifyArgs,
+
+    #[structopt(flatten)]
+    progress: ProgressArgs,
+}
+
+pub fn run_migrate(args: MigrateArgs) -> Result<(), io::Error> {
+    let grid = args.verify.options();
+
+    if args.level > 9 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "compression level must be between 0 and 9, was {}",
+                args.level
+            ),
+        ));
+    }
+
+    let mut pb = args.progress.start(args.filenames.len());
+
+    for path in &args.filenames {
+        pb.message(&format!("Migrating '{}' ", path.display()));
+        pb.inc();
+
+        let target = get_target_path(path, args.compress, args.decompress);
+        migrate_file(path, &target, args.level, &grid)?;
+    }
+
+    pb.finish("Migrated all density maps.");
+
+    Ok(())
+}
+
+/// Get the path to write a migrated file to, which only differs from the input path
+/// if the compression is changed.
+fn get_target_path(path: &Path, compress: bool, decompress: bool) -> PathBuf {
+    let is_compressed = path.extension().map(|ext| ext == "gz").unwrap_or(false);
+
+    if compress && !is_compressed {
+        let mut file_name = path
+            .file_name()
+            .map(|s| s.to_os_string())
+            .unwrap_or_default();
+        file_name.push(".gz");
+        path.with_file_name(file_name)
+    } else if decompress && is_compressed {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Read a density map and rewrite it along with its metadata to the target path. The map
+/// is first written to a temporary file next to the target, which is then moved into place.
+/// If the target path differs from the input path the input file is removed.
+///
+/// The layers and fields of maps are kept, see `write_field_maps_to_writer`.
+fn migrate_file(
+    path: &Path,
+    target: &Path,
+    level: u32,
+    grid: &GridOptions,
+) -> Result<(), io::Error> {
+    let (maps, time, metadata) = read_field_maps_with_grid(path, grid)?;
+
+    // Keep the extension of the target to write with the same compression.
+    let mut tmp_name = OsString::from(".migrate-");
+    tmp_name.push(target.file_name().unwrap_or_default());
+    let tmp_path = target.with_file_name(tmp_name);
+
+    // The original is only replaced once the new file is known to be on disk.
+    let written = write_field_maps_with_level(&tmp_path, &maps, time, &metadata, level)
+        .and_then(|_| File::open(&tmp_path)?.sync_all());
+
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+
+    fs::rename(&tmp_path, target)?;
+
+    if target != path {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use structopt::StructOpt;
+
+use std::{io, path::PathBuf};
+
+use densmap::{container::ContainerWriter, densmap::read_densmap_with_grid};
+
+use crate::{progress::ProgressArgs, verify::VerifyArgs};
+
+#[derive(Debug, StructOpt)]
+/// Pack density maps into a single container file
+pub struct PackArgs {
+    #[structopt(parse(from_os_str))]
+    /// Output path for the container
+    output: PathBuf,
+
+    #[structopt(parse(from_os_str), required = true)]
+    /// List of density map files to pack, in order
+    filenames: Vec<PathBuf>,
+
+    #[structopt(long = "append")]
+    /// Append the maps to the container if it exists instead of replacing it
+    append: bool,
+
+    #[structopt(flatten)]
+    verify: VerifyArgs,
+
+    #[structopt(flatten)]
+    progress: ProgressArgs,
+}
+
+pub fn run_pack(args: PackArgs) -> Result<(), io::Error> {
+    let grid = args.verify.options();
+
+    let mut writer = if args.append {
+        ContainerWriter::append(&args.output)?
+    } else {
+        ContainerWriter::create(&args.output)?
+    };
+
+    let mut pb = args.progress.start(args.filenames.len());
+
+    for path in &args.filenames {
+        pb.message(&format!("Packing '{}' ", path.display()));
+        pb.inc();
+
+        let (densmap, time) = read_densmap_with_grid(path, &grid)?;
+        writer.push(&densmap, time)?;
+    }
+
+    writer.finish()?;
+
+    pb.finish("Packed all density maps.");
+
+    Ok(())
+}
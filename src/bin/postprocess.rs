@@ -0,0 +1,198 @@
+use regex::Regex;
+use structopt::StructOpt;
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use densmap::{
+    analysis::{
+        autocorrelation::{
+            calc_autocorrelation, calc_space_time_correlation, resample_onto_common_angles,
+        },
+        kymograph::{read_kymograph, Kymograph, KYMOGRAPH_MAGIC},
+        spectrum::calc_mode_spectrum,
+    },
+    graphdata::{read_xvg_sets, write_grid, write_xvg, Graph, Histogram},
+    series::{get_file_name_time, DEFAULT_TIME_REGEX},
+};
+
+#[derive(Debug, StructOpt)]
+/// Analyze the contact lines of previously written xvg files, without the density maps
+pub struct PostprocessArgs {
+    #[structopt(
+        parse(from_os_str),
+        required = true,
+        long_help = "Contact line files written by the analysis with '--contact_line', in order of time. Every file is a frame, except for files with several data sets (as written with '--multiset') in which every set is a frame. Binary files written with '--contact_line_dump' have all their frames, with their times, read exactly."
+    )]
+    /// Contact line xvg files to analyze, in order of time
+    filenames: Vec<PathBuf>,
+
+    #[structopt(
+        long = "time_sig",
+        long_help = "Regular expression for the time signature in file names, with a capture group around the time value. It is used for the times of frames with one file each.",
+        raw(default_value = "DEFAULT_TIME_REGEX"),
+        value_name = "regex"
+    )]
+    /// Regular expression for the time signature in file names
+    time_regex: String,
+
+    #[structopt(
+        long = "dt",
+        default_value = "1.0",
+        value_name = "dt",
+        long_help = "Time between frames which are data sets of a file or have no time signature in their file name."
+    )]
+    /// Time between frames without a time signature
+    dt: f64,
+
+    #[structopt(long = "ac", value_name = "path", parse(from_os_str))]
+    /// Output file name for the autocorrelation of the contact line fluctuations
+    autocorrelation: Option<PathBuf>,
+
+    #[structopt(long = "spectrum", value_name = "path", parse(from_os_str))]
+    /// Output file name for the power spectrum of the angular modes of the contact line
+    spectrum: Option<PathBuf>,
+
+    #[structopt(long = "kymograph", value_name = "path", parse(from_os_str))]
+    /// Output file name for the contact line as a function of angle and time
+    kymograph: Option<PathBuf>,
+
+    #[structopt(long = "st_corr", value_name = "path", parse(from_os_str))]
+    /// Output file name for the space-time correlation of the contact line fluctuations
+    space_time_correlation: Option<PathBuf>,
+
+    #[structopt(long = "st_corr_max_lag", default_value = "50", value_name = "frames")]
+    /// Largest time separation in number of frames for the space-time correlation
+    space_time_correlation_max_lag: usize,
+}
+
+pub fn run_postprocess(args: PostprocessArgs) -> Result<(), io::Error> {
+    let re = Regex::new(&args.time_regex).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid time signature '{}': {}", args.time_regex, err),
+        )
+    })?;
+
+    let (times, contact_lines) = read_contact_lines(&args.filenames, &re, args.dt)?;
+
+    if contact_lines.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no contact lines could be read",
+        ));
+    }
+
+    eprintln!("Read {} contact lines.", contact_lines.len());
+
+    let resampled_contact_lines = resample_onto_common_angles(&contact_lines);
+
+    if let Some(path) = &args.autocorrelation {
+        let autocorrelation = Histogram {
+            x: times.clone(),
+            y: calc_autocorrelation(&resampled_contact_lines),
+            yerr: None,
+        };
+
+        write_xvg(path, &autocorrelation)?;
+    }
+
+    // The resampled contact lines all have identical angles.
+    let kymograph = Kymograph::from_contact_lines(&times, &resampled_contact_lines).unwrap();
+
+    if let Some(path) = &args.spectrum {
+        write_xvg(path, &calc_mode_spectrum(&kymograph))?;
+    }
+
+    if let Some(path) = &args.kymograph {
+        write_grid(path, &kymograph.angles, &kymograph.times, &kymograph.radius)?;
+    }
+
+    if let Some(path) = &args.space_time_correlation {
+        let correlation =
+            calc_space_time_correlation(&kymograph, args.space_time_correlation_max_lag);
+
+        write_grid(
+            path,
+            &correlation.angles,
+            &correlation.times,
+            &correlation.values,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Read the contact lines of all files along with their times.
+///
+/// Binary files with a dump of contact lines keep all their frames and times. Other
+/// files with a single data set get the time of their file name if it has a time
+/// signature, while other frames are separated by `dt` from the previous frame.
+fn read_contact_lines(
+    paths: &[PathBuf],
+    re: &Regex,
+    dt: f64,
+) -> Result<(Vec<f64>, Vec<Graph>), io::Error> {
+    let mut times = Vec::new();
+    let mut contact_lines = Vec::new();
+
+    for path in paths {
+        if is_kymograph_file(path) {
+            let kymograph = read_kymograph(path).map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("could not read '{}': {}", path.display(), err),
+                )
+            })?;
+
+            contact_lines.extend(kymograph.contact_lines());
+            times.extend(kymograph.times);
+
+            continue;
+        }
+
+        let sets = read_xvg_sets(path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("could not read '{}': {}", path.display(), err),
+            )
+        })?;
+
+        let file_time = match sets.len() {
+            1 => get_file_name_time(path, re),
+            _ => None,
+        };
+
+        for set in sets {
+            let radius = set
+                .columns
+                .into_iter()
+                .next()
+                .map(|(_, values)| values)
+                .unwrap_or_default();
+
+            let time = file_time.unwrap_or_else(|| times.last().map(|t| t + dt).unwrap_or(0.0));
+
+            times.push(time);
+            contact_lines.push(Graph::Polar {
+                angles: set.x,
+                radius,
+                yerr: None,
+            });
+        }
+    }
+
+    Ok((times, contact_lines))
+}
+
+fn is_kymograph_file(path: &Path) -> bool {
+    let mut magic = [0; 4];
+
+    File::open(path)
+        .and_then(|mut fp| fp.read_exact(&mut magic))
+        .map(|_| &magic == KYMOGRAPH_MAGIC)
+        .unwrap_or(false)
+}
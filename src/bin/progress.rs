@@ -0,0 +1,126 @@
+use pbr::ProgressBar;
+use structopt::StructOpt;
+
+use std::{
+    io::{self, IsTerminal, Stdout},
+    time::{Duration, Instant},
+};
+
+/// Time between the lines of progress when not writing to a terminal.
+const LINE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, StructOpt)]
+/// Reporting of the progress of long-running commands
+pub struct ProgressArgs {
+    #[structopt(long = "no-progress", hidden_short_help = true)]
+    /// Do not report the progress of the command
+    no_progress: bool,
+}
+
+impl ProgressArgs {
+    /// Do not report the progress, like when the standard output is used for data.
+    pub fn hide(&mut self) {
+        self.no_progress = true;
+    }
+
+    /// Start reporting the progress over a number of items.
+    ///
+    /// A progress bar is drawn if the standard output is a terminal. Otherwise, like in
+    /// batch jobs or when redirecting the output to a file, a line with the progress
+    /// and estimated time left is printed at regular intervals to not fill logs with
+    /// the control characters of the bar.
+    pub fn start(&self, total: usize) -> Progress {
+        let mode = if self.no_progress {
+            Mode::Hidden
+        } else if io::stdout().is_terminal() {
+            let mut pb = ProgressBar::new(total as u64);
+            pb.format("[=> ]");
+
+            Mode::Bar(Box::new(pb))
+        } else {
+            Mode::Lines {
+                start: Instant::now(),
+                last: Instant::now(),
+            }
+        };
+
+        Progress {
+            mode,
+            count: 0,
+            total,
+        }
+    }
+}
+
+enum Mode {
+    Bar(Box<ProgressBar<Stdout>>),
+    Lines { start: Instant, last: Instant },
+    Hidden,
+}
+
+/// Progress over a number of items, see `ProgressArgs::start`.
+pub struct Progress {
+    mode: Mode,
+    count: usize,
+    total: usize,
+}
+
+impl Progress {
+    /// Set the message of the progress bar, which is not shown as lines.
+    pub fn message(&mut self, message: &str) {
+        if let Mode::Bar(pb) = &mut self.mode {
+            pb.message(message);
+        }
+    }
+
+    /// Advance the progress by one item.
+    pub fn inc(&mut self) {
+        self.count += 1;
+
+        match &mut self.mode {
+            Mode::Bar(pb) => {
+                pb.inc();
+            }
+            Mode::Lines { start, last } if last.elapsed() >= LINE_INTERVAL => {
+                *last = Instant::now();
+
+                let elapsed = start.elapsed();
+                let left = elapsed
+                    .mul_f64(self.total.saturating_sub(self.count) as f64 / self.count as f64);
+
+                println!(
+                    "{}/{} ({}%), {} elapsed, {} left",
+                    self.count,
+                    self.total,
+                    100 * self.count / self.total.max(1),
+                    format_duration(elapsed),
+                    format_duration(left)
+                );
+            }
+            _ => (),
+        }
+    }
+
+    /// Finish the progress with a message.
+    pub fn finish(self, message: &str) {
+        match self.mode {
+            Mode::Bar(mut pb) => {
+                pb.finish_print(message);
+                eprintln!();
+            }
+            Mode::Lines { .. } => println!("{}", message),
+            Mode::Hidden => (),
+        }
+    }
+}
+
+/// Format a duration in whole seconds, minutes and hours.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{} s", s),
+        (0, m, s) => format!("{} min {} s", m, s),
+        (h, m, _) => format!("{} h {} min", h, m),
+    }
+}
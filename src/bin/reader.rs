@@ -0,0 +1,297 @@
+use std::{
+    io,
+    path::PathBuf,
+    sync::mpsc::{sync_channel, Receiver, SyncSender},
+    thread::{self, JoinHandle},
+};
+
+use densmap::{
+    container::Container,
+    densmap::{read_densmap_and_fields, DensMap, GridOptions},
+    fields::DEFAULT_FIELD,
+    series::FrameRange,
+};
+
+/// A density map read from a file, along with its time and the maps of the fields
+/// which are analyzed as separate components.
+///
+/// Frames of a container are named by the container path and their time, as
+/// 'path[00010.000ps]', which matches the default time signature of file names.
+pub type Frame = (PathBuf, Result<(DensMap, f64, Vec<DensMap>), io::Error>);
+
+#[derive(Clone, Debug)]
+/// Source of the density maps to analyze.
+pub enum FrameSource {
+    /// Density maps in separate files.
+    Files(Vec<PathBuf>),
+    /// Frames of a container file, given by their indices.
+    Container { path: PathBuf, frames: Vec<usize> },
+}
+
+impl FrameSource {
+    pub fn len(&self) -> usize {
+        match self {
+            FrameSource::Files(filenames) => filenames.len(),
+            FrameSource::Container { frames, .. } => frames.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get a source of the frames which are selected by a range of their indices.
+    pub fn select(self, range: &FrameRange) -> FrameSource {
+        match self {
+            FrameSource::Files(filenames) => FrameSource::Files(select(filenames, range)),
+            FrameSource::Container { path, frames } => FrameSource::Container {
+                path,
+                frames: select(frames, range),
+            },
+        }
+    }
+
+    /// Get a source of the first (at most) `n` frames.
+    pub fn take(&self, n: usize) -> FrameSource {
+        match self {
+            FrameSource::Files(filenames) => {
+                FrameSource::Files(filenames.iter().take(n).cloned().collect())
+            }
+            FrameSource::Container { path, frames } => FrameSource::Container {
+                path: path.clone(),
+                frames: frames.iter().take(n).cloned().collect(),
+            },
+        }
+    }
+}
+
+fn select<T>(items: Vec<T>, range: &FrameRange) -> Vec<T> {
+    items
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| range.contains(*i))
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// Reader of density maps in a separate thread.
+///
+/// Files are read, and decompressed if necessary, in order ahead of the consumer.
+/// At most `capacity` frames are buffered, after which the reader waits for frames
+/// to be consumed. This overlaps the reading with the analysis of the current frame.
+pub struct FrameReader {
+    receiver: Receiver<Frame>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl FrameReader {
+    pub fn spawn(
+        source: FrameSource,
+        grid: GridOptions,
+        components: Vec<String>,
+        capacity: usize,
+    ) -> Self {
+        let (sender, receiver) = sync_channel(capacity);
+
+        let handle = thread::spawn(move || match source {
+            FrameSource::Files(filenames) => {
+                for filename in filenames {
+                    let result = read_densmap_and_fields(&filename, &grid, &components);
+
+                    // The consumer has stopped reading if the channel is closed.
+                    if sender.send((filename, result)).is_err() {
+                        break;
+                    }
+                }
+            }
+            FrameSource::Container { path, frames } => {
+                read_container_frames(path, &frames, grid, &components, sender);
+            }
+        });
+
+        FrameReader {
+            receiver,
+            handle: Some(handle),
+        }
+    }
+}
+
+/// Read frames of a container, which only have the single field of their density.
+fn read_container_frames(
+    path: PathBuf,
+    frames: &[usize],
+    grid: GridOptions,
+    components: &[String],
+    sender: SyncSender<Frame>,
+) {
+    let mut container = match Container::open(&path) {
+        Ok(container) => container.with_checksum_verification(grid.verify_checksum),
+        Err(err) => {
+            let _ = sender.send((path, Err(err)));
+            return;
+        }
+    };
+
+    for &i in frames {
+        let label = match container.index().get(i) {
+            Some(entry) => format!("{}[{:09.3}ps]", path.display(), entry.time),
+            None => format!("{}[{}]", path.display(), i),
+        };
+
+        let result = container.read_frame(i).and_then(|(densmap, time)| {
+            let densmap = grid.apply(densmap);
+
+            let fields = components
+                .iter()
+                .map(|name| {
+                    if name == DEFAULT_FIELD {
+                        Ok(densmap.clone())
+                    } else {
+                        Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("container frames have no field '{}'", name),
+                        ))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok((densmap, time, fields))
+        });
+
+        if sender.send((PathBuf::from(label), result)).is_err() {
+            break;
+        }
+    }
+}
+
+impl Drop for FrameReader {
+    /// Close the channel and wait for the reader thread, which stops at the next frame
+    /// it tries to send when the consumer stops reading early.
+    fn drop(&mut self) {
+        let (_, closed) = sync_channel(0);
+        drop(std::mem::replace(&mut self.receiver, closed));
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Iterator for FrameReader {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.receiver.recv() {
+            Ok(frame) => Some(frame),
+            Err(_) => {
+                if let Some(handle) = self.handle.take() {
+                    handle.join().expect("density map reader thread panicked");
+                }
+
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use densmap::{densmap::write_densmap, generate::DensMapBuilder};
+    use std::fs;
+
+    /// Write maps with the given times to a directory which is unique to the test.
+    fn write_maps(name: &str, times: &[f64]) -> (PathBuf, Vec<PathBuf>) {
+        let dir = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+
+        let densmap = DensMapBuilder::new().shape([4, 4]).uniform(1.0);
+        let paths = times
+            .iter()
+            .map(|&time| {
+                let path = dir.join(format!("map{:09.3}ps.dat", time));
+                write_densmap(&path, &densmap, time).unwrap();
+                path
+            })
+            .collect();
+
+        (dir, paths)
+    }
+
+    #[test]
+    fn test_frames_are_read_ahead_in_order() {
+        let times = (0..8).map(|i| 10.0 * i as f64).collect::<Vec<_>>();
+        let (dir, paths) = write_maps("densmap_test_reader_order", &times);
+
+        for &capacity in &[1, 3, 16] {
+            let frames = FrameReader::spawn(
+                FrameSource::Files(paths.clone()),
+                GridOptions::default(),
+                Vec::new(),
+                capacity,
+            )
+            .map(|(path, result)| (path, result.unwrap().1))
+            .collect::<Vec<_>>();
+
+            let expected = paths.iter().cloned().zip(times.iter().cloned());
+            assert_eq!(expected.collect::<Vec<_>>(), frames);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_errors_are_returned_for_their_frame() {
+        let (dir, mut paths) = write_maps("densmap_test_reader_errors", &[0.0, 20.0]);
+        let missing = dir.join("missing.dat");
+        paths.insert(1, missing.clone());
+
+        let frames = FrameReader::spawn(
+            FrameSource::Files(paths),
+            GridOptions::default(),
+            Vec::new(),
+            2,
+        )
+        .collect::<Vec<_>>();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The reader continues with the following frames after an error.
+        assert_eq!(3, frames.len());
+        assert_eq!(0.0, frames[0].1.as_ref().unwrap().1);
+        assert_eq!(missing, frames[1].0);
+        assert_eq!(
+            io::ErrorKind::NotFound,
+            frames[1].1.as_ref().unwrap_err().kind()
+        );
+        assert_eq!(20.0, frames[2].1.as_ref().unwrap().1);
+
+        let source = FrameSource::Container {
+            path: missing,
+            frames: vec![0, 1],
+        };
+        let frames =
+            FrameReader::spawn(source, GridOptions::default(), Vec::new(), 1).collect::<Vec<_>>();
+        assert_eq!(1, frames.len());
+        assert!(frames[0].1.is_err());
+    }
+
+    #[test]
+    fn test_reader_stops_when_the_consumer_drops_it_early() {
+        let times = (0..32).map(|i| i as f64).collect::<Vec<_>>();
+        let (dir, paths) = write_maps("densmap_test_reader_drop", &times);
+
+        let mut reader = FrameReader::spawn(
+            FrameSource::Files(paths),
+            GridOptions::default(),
+            Vec::new(),
+            1,
+        );
+        assert_eq!(0.0, reader.next().unwrap().1.unwrap().1);
+
+        // Dropping the reader waits for its thread, which must not block on the full
+        // channel after the consumer is gone.
+        drop(reader);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,360 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use flate2::read::GzDecoder;
+use structopt::StructOpt;
+
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use densmap::densmap::{
+    detect_legacy_byte_order, detect_version_byte_order, get_checksum, get_versioned_checksum,
+    is_stdio, read_densmap, read_densmap_from_reader, DensMap, Endianness, Metadata, DENSMAP_MAGIC,
+    FLAG_CHECKSUM, FLAG_FIELDS, FLAG_LAYERED, FLAG_METADATA, FORMAT_VERSION, FULL_CHECKSUM_VERSION,
+    LAYER_HEADER_SIZE, LEGACY_HEADER_SIZE, VERSIONED_HEADER_SIZE,
+};
+
+use crate::failure::Failure;
+
+/// Size of the header of the binary format: bin size, origin, shape, center and time.
+const HEADER_SIZE: usize = LEGACY_HEADER_SIZE as usize;
+
+/// Offset of the shape in the header of the binary format.
+const SHAPE_OFFSET: usize = 5 * 8;
+
+#[derive(Debug, StructOpt)]
+/// Check that density map files are complete and hold valid densities
+pub struct ValidateArgs {
+    #[structopt(parse(from_os_str), required = true)]
+    /// List of density map files to check
+    filenames: Vec<PathBuf>,
+
+    #[structopt(short = "v", long = "verbose")]
+    /// Also print the files which are valid
+    verbose: bool,
+}
+
+pub fn run_validate(args: ValidateArgs) -> Result<(), io::Error> {
+    let mut num_bad = 0;
+
+    for path in &args.filenames {
+        let problems = check_file(path);
+
+        if !problems.is_empty() {
+            num_bad += 1;
+            println!("{}: {}", path.display(), problems.join("; "));
+        } else if args.verbose {
+            println!("{}: ok", path.display());
+        }
+    }
+
+    eprintln!(
+        "{} of {} files are valid, {} are bad.",
+        args.filenames.len() - num_bad,
+        args.filenames.len(),
+        num_bad
+    );
+
+    if num_bad > 0 {
+        return Err(Failure::Io.error(format!("found {} bad files", num_bad)));
+    }
+
+    Ok(())
+}
+
+/// Get the problems of a file, which is valid if there are none.
+///
+/// Files in the binary format must hold exactly the data of their shape, which must
+/// match its checksum if the file has one. The other formats are checked by reading
+/// them. All values must be finite and non-negative.
+fn check_file(path: &Path) -> Vec<String> {
+    let result = match path.extension().and_then(|ext| ext.to_str()) {
+        _ if is_stdio(path) => read_stdin().map(|bytes| check_binary_format(&bytes)),
+        Some("npy") | Some("npz") | Some("txt") | Some("asc") => {
+            read_densmap(path).map(|(densmap, _)| (densmap, Vec::new()))
+        }
+        Some("gz") => fs::File::open(path).and_then(|fp| {
+            let mut bytes = Vec::new();
+            GzDecoder::new(fp).read_to_end(&mut bytes)?;
+            Ok(check_binary_format(&bytes))
+        }),
+        _ => fs::read(path).map(|bytes| check_binary_format(&bytes)),
+    };
+
+    match result {
+        Ok((densmap, mut problems)) => {
+            problems.extend(check_values(&densmap));
+            problems
+        }
+        Err(err) => vec![format!("could not be read: {}", err)],
+    }
+}
+
+/// Read all bytes from stdin, decompressing them if they begin with the gzip magic bytes.
+fn read_stdin() -> Result<Vec<u8>, io::Error> {
+    let mut bytes = Vec::new();
+    io::stdin().read_to_end(&mut bytes)?;
+
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decoded = Vec::new();
+        GzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+        Ok(decoded)
+    } else {
+        Ok(bytes)
+    }
+}
+
+/// Parse a map in the binary format and check that its size matches its shape
+/// and that its data matches the checksum of a versioned header.
+fn check_binary_format(bytes: &[u8]) -> (DensMap, Vec<String>) {
+    if !bytes.starts_with(DENSMAP_MAGIC) {
+        return match bytes.get(..HEADER_SIZE).map(detect_legacy_byte_order) {
+            Some(Endianness::Big) => check_header_and_data::<BigEndian>(bytes, &Layout::LEGACY),
+            _ => check_header_and_data::<LittleEndian>(bytes, &Layout::LEGACY),
+        };
+    }
+
+    let prefix_size = VERSIONED_HEADER_SIZE as usize;
+
+    if bytes.len() < prefix_size {
+        return (
+            get_empty_densmap(),
+            vec![truncated_header(bytes.len(), prefix_size + HEADER_SIZE)],
+        );
+    }
+
+    let mut version = [0; 4];
+    version.copy_from_slice(&bytes[4..8]);
+
+    match detect_version_byte_order(version) {
+        Some(Endianness::Little) => check_versioned::<LittleEndian>(bytes),
+        Some(Endianness::Big) => check_versioned::<BigEndian>(bytes),
+        None => (
+            get_empty_densmap(),
+            vec![format!(
+                "format version {} is not supported, the latest is {}",
+                LittleEndian::read_u32(&version),
+                FORMAT_VERSION
+            )],
+        ),
+    }
+}
+
+/// Check a map of the versioned binary format with the given byte order.
+fn check_versioned<B: ByteOrder>(bytes: &[u8]) -> (DensMap, Vec<String>) {
+    let mut prefix_size = VERSIONED_HEADER_SIZE as usize;
+
+    let version = B::read_u32(&bytes[4..]);
+    let flags = B::read_u32(&bytes[8..]);
+    let checksum = Some(B::read_u32(&bytes[12..])).filter(|_| (flags & FLAG_CHECKSUM) != 0);
+    let full_checksum = version >= FULL_CHECKSUM_VERSION;
+
+    if (flags & FLAG_METADATA) != 0 {
+        let end = bytes
+            .get(prefix_size..prefix_size + 8)
+            .and_then(|len| B::read_u64(len).checked_add(prefix_size as u64 + 8))
+            .filter(|&end| end <= bytes.len() as u64)
+            .map(|end| end as usize);
+
+        let metadata = match end {
+            Some(end) => &bytes[prefix_size + 8..end],
+            None => {
+                return (
+                    get_empty_densmap(),
+                    vec!["truncated metadata block".to_string()],
+                );
+            }
+        };
+
+        if serde_json::from_slice::<Metadata>(metadata).is_err() {
+            return (
+                get_empty_densmap(),
+                vec!["metadata is not a valid JSON object of strings".to_string()],
+            );
+        }
+
+        prefix_size += 8 + metadata.len();
+    }
+
+    let layout = Layout {
+        layered: (flags & FLAG_LAYERED) != 0,
+        has_fields: (flags & FLAG_FIELDS) != 0,
+        checksum: checksum.filter(|_| !full_checksum),
+    };
+
+    let (densmap, mut problems) = check_header_and_data::<B>(&bytes[prefix_size..], &layout);
+
+    // The checksum of the complete map can only be compared if it is complete.
+    if let Some(checksum) = checksum.filter(|_| full_checksum) {
+        if problems.is_empty() && get_versioned_checksum(bytes) != checksum {
+            problems.push("map does not match its checksum".to_string());
+        }
+    }
+
+    (densmap, problems)
+}
+
+/// Parts of the header of a map which are set by the flags of the versioned format.
+struct Layout {
+    /// Whether the header holds the layers of a z-resolved map.
+    layered: bool,
+    /// Whether the header holds the names of the fields of the map.
+    has_fields: bool,
+    /// Checksum of the data, for versions in which it does not cover the complete map.
+    checksum: Option<u32>,
+}
+
+impl Layout {
+    /// Layout of the original format, which has none of the parts.
+    const LEGACY: Layout = Layout {
+        layered: false,
+        has_fields: false,
+        checksum: None,
+    };
+}
+
+/// Parse the header and data of a map and check that its size matches its shape,
+/// and that the data matches its checksum if it has one.
+///
+/// A map is returned for files which have a complete header, with only the values
+/// which could be read if it is truncated. The values of all layers and fields of
+/// a map are returned as a single 2D map.
+fn check_header_and_data<B: ByteOrder>(bytes: &[u8], layout: &Layout) -> (DensMap, Vec<String>) {
+    let mut header_size = if layout.layered {
+        HEADER_SIZE + LAYER_HEADER_SIZE as usize
+    } else {
+        HEADER_SIZE
+    };
+
+    if bytes.len() < header_size {
+        return (
+            get_empty_densmap(),
+            vec![truncated_header(bytes.len(), header_size)],
+        );
+    }
+
+    let nx = B::read_u64(&bytes[SHAPE_OFFSET..]);
+    let ny = B::read_u64(&bytes[SHAPE_OFFSET + 8..]);
+
+    let (mut expected, mut shape) = if layout.layered {
+        let nz = B::read_u64(&bytes[HEADER_SIZE..]);
+        (
+            nx.saturating_mul(ny).saturating_mul(nz),
+            format!("{}x{}x{}", nx, ny, nz),
+        )
+    } else {
+        (nx.saturating_mul(ny), format!("{}x{}", nx, ny))
+    };
+
+    if layout.has_fields {
+        let end = bytes
+            .get(header_size..header_size + 8)
+            .and_then(|len| B::read_u64(len).checked_add(header_size as u64 + 8))
+            .filter(|&end| end <= bytes.len() as u64)
+            .map(|end| end as usize);
+
+        let names = match end {
+            Some(end) => &bytes[header_size + 8..end],
+            None => {
+                return (
+                    get_empty_densmap(),
+                    vec!["truncated field names".to_string()],
+                );
+            }
+        };
+
+        let num_fields = match serde_json::from_slice::<Vec<String>>(names) {
+            Ok(names) => names.len(),
+            Err(_) => {
+                return (
+                    get_empty_densmap(),
+                    vec!["field names are not a valid JSON array of strings".to_string()],
+                );
+            }
+        };
+
+        header_size += 8 + names.len();
+        expected = expected.saturating_mul(num_fields as u64);
+        shape = format!("{} with {} fields", shape, num_fields);
+    }
+
+    let found = ((bytes.len() - header_size) / 8) as u64;
+
+    let mut problems = Vec::new();
+
+    if found < expected {
+        problems.push(format!(
+            "truncated data of {} values, expected {} for shape {}",
+            found, expected, shape
+        ));
+    } else if bytes.len() as u64 > header_size as u64 + 8 * expected {
+        problems.push(format!(
+            "{} bytes of trailing data after the {} values of shape {}",
+            bytes.len() as u64 - header_size as u64 - 8 * expected,
+            expected,
+            shape
+        ));
+    }
+
+    // Read the header and the complete values of a truncated map by giving it
+    // the shape of the data which is present.
+    let num_values = found.min(expected) as usize;
+    let mut header = bytes[..HEADER_SIZE].to_vec();
+    B::write_u64(&mut header[SHAPE_OFFSET..], num_values as u64);
+    B::write_u64(&mut header[SHAPE_OFFSET + 8..], 1);
+
+    let mut reader = header
+        .as_slice()
+        .chain(&bytes[header_size..header_size + 8 * num_values]);
+    let (densmap, _) = read_densmap_from_reader(&mut reader, true).unwrap();
+
+    // The checksum can only be compared if the data is complete.
+    if let Some(checksum) = layout.checksum {
+        if problems.is_empty() && get_checksum(&bytes[header_size..]) != checksum {
+            problems.push("data does not match its checksum".to_string());
+        }
+    }
+
+    (
+        DensMap {
+            shape: [nx, ny],
+            ..densmap
+        },
+        problems,
+    )
+}
+
+fn get_empty_densmap() -> DensMap {
+    DensMap {
+        bin_size: [0.0; 3],
+        origin: [0.0; 2],
+        shape: [0, 0],
+        center: [0.0; 2],
+        data: Vec::new(),
+    }
+}
+
+fn truncated_header(len: usize, expected: usize) -> String {
+    format!(
+        "truncated header of {} bytes, expected {} bytes",
+        len, expected
+    )
+}
+
+fn check_values(densmap: &DensMap) -> Vec<String> {
+    let num_non_finite = densmap.data.iter().filter(|v| !v.is_finite()).count();
+    let num_negative = densmap.data.iter().filter(|&&v| v < 0.0).count();
+
+    let mut problems = Vec::new();
+
+    if num_non_finite > 0 {
+        problems.push(format!("{} values are not finite", num_non_finite));
+    }
+
+    if num_negative > 0 {
+        problems.push(format!("{} values are negative", num_negative));
+    }
+
+    problems
+}
@@ -0,0 +1,31 @@
+use structopt::StructOpt;
+
+use densmap::densmap::GridOptions;
+
+#[derive(Debug, StructOpt)]
+/// Verification of the checksums of input density maps
+pub struct VerifyArgs {
+    #[structopt(long = "no-verify", hidden_short_help = true)]
+    /// Read density maps even if their data does not match the checksum of their header
+    no_verify: bool,
+}
+
+impl VerifyArgs {
+    /// Whether the checksums of the maps which are read are verified.
+    pub fn verify_checksum(&self) -> bool {
+        !self.no_verify
+    }
+
+    /// Set the verification of checksums in the options for reading maps.
+    pub fn apply(&self, grid: GridOptions) -> GridOptions {
+        GridOptions {
+            verify_checksum: self.verify_checksum(),
+            ..grid
+        }
+    }
+
+    /// Get the options for reading maps with their own grid.
+    pub fn options(&self) -> GridOptions {
+        self.apply(GridOptions::default())
+    }
+}
@@ -0,0 +1,197 @@
+use structopt::StructOpt;
+
+use std::{
+    f64::consts::PI,
+    io::{self, BufRead, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use densmap::{
+    analysis::sample_interface::sample_interface_beyond,
+    densmap::{read_densmap_with_grid, DensMap},
+    graphdata::Graph,
+    terminal::{render_braille_map, render_text_map},
+};
+
+use crate::verify::VerifyArgs;
+
+/// Step of the cutoff when adjusting it in the viewer.
+const CUTOFF_STEP: f64 = 0.05;
+
+#[derive(Debug, StructOpt)]
+/// View density maps in the terminal, with their contact line
+pub struct ViewArgs {
+    #[structopt(parse(from_os_str), required = true)]
+    /// Density maps to view, in order
+    filenames: Vec<PathBuf>,
+
+    #[structopt(long = "cutoff", default_value = "0.5", value_name = "fraction")]
+    /// Density cutoff of the contact line, as a fraction of the maximum density
+    cutoff: f64,
+
+    #[structopt(long = "width", default_value = "80", value_name = "columns")]
+    /// Width of the view in characters
+    width: usize,
+
+    #[structopt(long = "height", default_value = "32", value_name = "rows")]
+    /// Height of the view in characters
+    height: usize,
+
+    #[structopt(
+        long = "braille",
+        long_help = "Draw the bins at or above the cutoff with braille characters, which have 2 by 4 dots each, instead of shading the density. This shows the shape of the droplet in more detail, but needs a font with braille characters."
+    )]
+    /// Draw the bins above the cutoff with braille characters
+    braille: bool,
+
+    #[structopt(flatten)]
+    verify: VerifyArgs,
+}
+
+pub fn run_view(args: ViewArgs) -> Result<(), io::Error> {
+    let grid = args.verify.options();
+
+    let mut index = 0;
+    let mut cutoff = args.cutoff;
+    let mut braille = args.braille;
+
+    let stdin = io::stdin();
+    let mut commands = stdin.lock().lines();
+
+    let mut frame = read_densmap_with_grid(&args.filenames[index], &grid)?;
+
+    loop {
+        draw_frame(&args, index, &frame, cutoff, braille)?;
+
+        let command = match commands.next() {
+            Some(line) => line?,
+            None => break,
+        };
+
+        let previous = index;
+
+        match command.trim() {
+            "" | "n" => index = (index + 1).min(args.filenames.len() - 1),
+            "p" => index = index.saturating_sub(1),
+            "+" => cutoff = (cutoff + CUTOFF_STEP).min(1.0),
+            "-" => cutoff = (cutoff - CUTOFF_STEP).max(CUTOFF_STEP),
+            "b" => braille = !braille,
+            "q" => break,
+            _ => (),
+        }
+
+        if index != previous {
+            frame = read_densmap_with_grid(&args.filenames[index], &grid)?;
+        }
+    }
+
+    // The prompt of the last frame is ended.
+    println!();
+
+    Ok(())
+}
+
+/// Draw a frame with the contact line at a cutoff and the commands of the viewer.
+///
+/// The screen is cleared before drawing if the output is a terminal, to draw the
+/// frames over each other.
+fn draw_frame(
+    args: &ViewArgs,
+    index: usize,
+    (densmap, time): &(DensMap, f64),
+    cutoff: f64,
+    braille: bool,
+) -> Result<(), io::Error> {
+    let max = densmap.data.iter().fold(0.0, |acc: f64, &v| acc.max(v));
+    let density_cutoff = cutoff * max;
+    let size = [args.width, args.height];
+
+    let stdout = io::stdout();
+    let mut writer = stdout.lock();
+
+    if writer.is_terminal() {
+        write!(writer, "\x1b[2J\x1b[H")?;
+    }
+
+    let (rows, radius) = if braille {
+        (render_braille_map(densmap, size, density_cutoff), None)
+    } else {
+        match get_contact_line(densmap, density_cutoff) {
+            Some((centered, radius, contact_line)) => (
+                render_text_map(&centered, size, max, Some(&contact_line)),
+                Some(radius),
+            ),
+            None => (render_text_map(densmap, size, max, None), None),
+        }
+    };
+
+    for row in rows {
+        writeln!(writer, "{}", row)?;
+    }
+
+    let radius = match radius {
+        Some(radius) => format!("{:.2}", radius),
+        None => "-".to_string(),
+    };
+
+    writeln!(
+        writer,
+        "Frame {}/{}: '{}' at t = {}, cutoff {:.2} ({:.3}), radius {}",
+        index + 1,
+        args.filenames.len(),
+        args.filenames[index].display(),
+        time,
+        cutoff,
+        density_cutoff,
+        radius
+    )?;
+    write!(
+        writer,
+        "[n]ext (enter), [p]revious, [+/-] cutoff, [b]raille, [q]uit: "
+    )?;
+
+    writer.flush()
+}
+
+/// Get the contact line of a map at a density cutoff, along with the map centered on
+/// the bins at or above the cutoff and the radius of a circle of their area.
+///
+/// The contact line is sampled outwards from that circle. Returns `None` if no bins
+/// are at or above the cutoff.
+fn get_contact_line(densmap: &DensMap, cutoff: f64) -> Option<(DensMap, f64, Graph)> {
+    let [nx, _] = densmap.shape;
+    let [dx, dy, _] = densmap.bin_size;
+
+    let (sum_x, sum_y, count) = densmap
+        .data
+        .iter()
+        .enumerate()
+        .filter(|(_, &v)| v >= cutoff)
+        .fold((0.0, 0.0, 0), |(sum_x, sum_y, count), (i, _)| {
+            let (ix, iy) = (i as u64 % nx, i as u64 / nx);
+
+            (
+                sum_x + densmap.origin[0] + (ix as f64 + 0.5) * dx,
+                sum_y + densmap.origin[1] + (iy as f64 + 0.5) * dy,
+                count + 1,
+            )
+        });
+
+    if count == 0 {
+        return None;
+    }
+
+    let mut centered = densmap.clone();
+    centered.center = [sum_x / count as f64, sum_y / count as f64];
+
+    let radius = (count as f64 * dx * dy / PI).sqrt();
+    let circle = Graph::Polar {
+        angles: (0..360).map(|a| a as f64).collect(),
+        radius: vec![radius; 360],
+        yerr: None,
+    };
+
+    let contact_line = sample_interface_beyond(&centered, &circle, cutoff);
+
+    Some((centered, radius, contact_line))
+}
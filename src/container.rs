@@ -0,0 +1,363 @@
+use crate::densmap::{
+    get_encoded_size, read_densmap_from_reader, write_densmap_to_writer, DensMap,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// Magic string which begins every container file.
+const CONTAINER_MAGIC: &[u8; 8] = b"DMAPCONT";
+
+/// Magic string which ends the trailer of the index.
+const INDEX_MAGIC: &[u8; 8] = b"DMAPINDX";
+
+/// Size of the trailer: number of frames, offset of the index and the magic string.
+const TRAILER_SIZE: u64 = 8 + 8 + 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Entry of a frame in the index of a container.
+pub struct IndexEntry {
+    /// Time of the frame.
+    pub time: f64,
+    /// Offset of the frame from the beginning of the file, in bytes.
+    pub offset: u64,
+}
+
+/// Writer of density maps to a container file.
+///
+/// A container stores many density maps in a single file, which avoids the overhead
+/// of hundreds of thousands of small files on cluster file systems. The maps are stored
+/// one after another in the (uncompressed) density map format, after the magic string
+/// `DMAPCONT`. They are followed by an index of the time and offset of every frame,
+/// and a trailer with the number of frames, the offset of the index and the magic
+/// string `DMAPINDX`. All values are little endian.
+///
+/// When appending to a container it is copied to a temporary file next to it, in which
+/// the index is overwritten by the new frames. The extended index is written by `finish`,
+/// after which the copy replaces the container. The container is thus left as it was if
+/// appending is interrupted. A container without a valid trailer cannot be read, thus
+/// `finish` must be called after the last frame.
+pub struct ContainerWriter {
+    writer: BufWriter<File>,
+    index: Vec<IndexEntry>,
+    position: u64,
+    /// Temporary file which is written when appending and the container it replaces.
+    replace: Option<(PathBuf, PathBuf)>,
+}
+
+impl ContainerWriter {
+    /// Create a new, empty container at the path.
+    pub fn create(path: &Path) -> Result<Self, io::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(CONTAINER_MAGIC)?;
+
+        Ok(ContainerWriter {
+            writer,
+            index: Vec::new(),
+            position: CONTAINER_MAGIC.len() as u64,
+            replace: None,
+        })
+    }
+
+    /// Open an existing container to append frames to it, or create it if it does
+    /// not exist.
+    pub fn append(path: &Path) -> Result<Self, io::Error> {
+        if !path.exists() {
+            return ContainerWriter::create(path);
+        }
+
+        // The index is read before copying to not copy a container which is invalid.
+        let (index, index_offset) = read_index(&mut File::open(path)?)?;
+
+        let temp_path = get_temp_path(path);
+        fs::copy(path, &temp_path)?;
+
+        let mut file = OpenOptions::new().read(true).write(true).open(&temp_path)?;
+
+        // New frames replace the index, which is written again after them.
+        file.set_len(index_offset)?;
+        file.seek(SeekFrom::Start(index_offset))?;
+
+        Ok(ContainerWriter {
+            writer: BufWriter::new(file),
+            index,
+            position: index_offset,
+            replace: Some((temp_path, path.to_path_buf())),
+        })
+    }
+
+    /// Write a density map as the next frame of the container.
+    pub fn push(&mut self, densmap: &DensMap, time: f64) -> Result<(), io::Error> {
+        write_densmap_to_writer(&mut self.writer, densmap, time)?;
+
+        self.index.push(IndexEntry {
+            time,
+            offset: self.position,
+        });
+
+        self.position += get_encoded_size(densmap);
+
+        Ok(())
+    }
+
+    /// Write the index and trailer of the container.
+    pub fn finish(mut self) -> Result<(), io::Error> {
+        for entry in &self.index {
+            self.writer.write_f64::<LittleEndian>(entry.time)?;
+            self.writer.write_u64::<LittleEndian>(entry.offset)?;
+        }
+
+        self.writer
+            .write_u64::<LittleEndian>(self.index.len() as u64)?;
+        self.writer.write_u64::<LittleEndian>(self.position)?;
+        self.writer.write_all(INDEX_MAGIC)?;
+
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+
+        if let Some((temp_path, path)) = &self.replace {
+            fs::rename(temp_path, path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the path of the temporary file which is written when appending to a container.
+fn get_temp_path(path: &Path) -> PathBuf {
+    let mut file_name = std::ffi::OsString::from(".");
+    file_name.push(path.file_name().unwrap_or_default());
+    file_name.push(".append");
+
+    path.with_file_name(file_name)
+}
+
+/// Reader of density maps from a container file, see `ContainerWriter` for the format.
+pub struct Container {
+    reader: BufReader<File>,
+    index: Vec<IndexEntry>,
+    verify_checksum: bool,
+}
+
+impl Container {
+    /// Open a container and read its index.
+    pub fn open(path: &Path) -> Result<Self, io::Error> {
+        let mut file = File::open(path)?;
+
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+
+        if &magic != CONTAINER_MAGIC {
+            return Err(invalid_data(&format!(
+                "'{}' is not a density map container",
+                path.display()
+            )));
+        }
+
+        let (index, _) = read_index(&mut file)?;
+
+        Ok(Container {
+            reader: BufReader::new(file),
+            index,
+            verify_checksum: true,
+        })
+    }
+
+    /// Set whether the frames are verified against their checksum when they are read,
+    /// which they are by default.
+    pub fn with_checksum_verification(self, verify_checksum: bool) -> Self {
+        Container {
+            verify_checksum,
+            ..self
+        }
+    }
+
+    pub fn index(&self) -> &[IndexEntry] {
+        &self.index
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Get the indices of the frames with times within the (optional) interval.
+    pub fn find_range(&self, begin: Option<f64>, end: Option<f64>) -> Vec<usize> {
+        self.index
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                begin.map(|b| entry.time >= b).unwrap_or(true)
+                    && end.map(|e| entry.time <= e).unwrap_or(true)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Read the density map and time of a frame.
+    pub fn read_frame(&mut self, i: usize) -> Result<(DensMap, f64), io::Error> {
+        let entry = self.index.get(i).ok_or_else(|| {
+            invalid_data(&format!(
+                "frame {} is out of range for a container with {} frames",
+                i,
+                self.index.len()
+            ))
+        })?;
+
+        self.reader.seek(SeekFrom::Start(entry.offset))?;
+        read_densmap_from_reader(&mut self.reader, self.verify_checksum)
+    }
+
+    /// Read the density maps and times of all frames within the (optional) interval.
+    pub fn read_range(
+        &mut self,
+        begin: Option<f64>,
+        end: Option<f64>,
+    ) -> Result<Vec<(DensMap, f64)>, io::Error> {
+        self.find_range(begin, end)
+            .into_iter()
+            .map(|i| self.read_frame(i))
+            .collect()
+    }
+}
+
+/// Read the index of a container and return it along with its offset.
+fn read_index<R: Read + Seek>(reader: &mut R) -> Result<(Vec<IndexEntry>, u64), io::Error> {
+    let len = reader.seek(SeekFrom::End(0))?;
+
+    if len < CONTAINER_MAGIC.len() as u64 + TRAILER_SIZE {
+        return Err(invalid_data("container is too small to hold an index"));
+    }
+
+    reader.seek(SeekFrom::End(-(TRAILER_SIZE as i64)))?;
+    let num_frames = reader.read_u64::<LittleEndian>()?;
+    let index_offset = reader.read_u64::<LittleEndian>()?;
+
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+
+    // A corrupt trailer may give values which overflow when adding up the size.
+    let size = num_frames
+        .checked_mul(16)
+        .and_then(|size| size.checked_add(index_offset))
+        .and_then(|size| size.checked_add(TRAILER_SIZE));
+
+    if &magic != INDEX_MAGIC || size != Some(len) {
+        return Err(invalid_data(
+            "container has no valid index, it may not have been finished after writing",
+        ));
+    }
+
+    reader.seek(SeekFrom::Start(index_offset))?;
+    let index = (0..num_frames)
+        .map(|_| {
+            Ok(IndexEntry {
+                time: reader.read_f64::<LittleEndian>()?,
+                offset: reader.read_u64::<LittleEndian>()?,
+            })
+        })
+        .collect::<Result<Vec<_>, io::Error>>()?;
+
+    Ok((index, index_offset))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env::temp_dir, fs};
+
+    fn get_densmap(value: f64) -> DensMap {
+        DensMap {
+            bin_size: [0.5, 0.25, 0.0],
+            origin: [1.0, 2.0],
+            shape: [3, 2],
+            center: [1.75, 2.25],
+            data: vec![value; 6],
+        }
+    }
+
+    #[test]
+    fn test_frames_appended_to_container_are_read_by_time_range() {
+        let path = temp_dir().join("densmap_test_container_append.dmc");
+
+        let mut writer = ContainerWriter::create(&path).unwrap();
+        writer.push(&get_densmap(0.0), 0.0).unwrap();
+        writer.push(&get_densmap(1.0), 10.0).unwrap();
+        writer.finish().unwrap();
+
+        let mut writer = ContainerWriter::append(&path).unwrap();
+        writer.push(&get_densmap(2.0), 20.0).unwrap();
+        writer.finish().unwrap();
+
+        let mut container = Container::open(&path).unwrap();
+        let frames = container.read_range(Some(5.0), None).unwrap();
+        let first = container.read_frame(0).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(3, container.len());
+        assert_eq!(vec![1, 2], container.find_range(Some(5.0), None));
+        assert_eq!(vec![0, 1], container.find_range(None, Some(10.0)));
+
+        assert_eq!((get_densmap(0.0), 0.0), first);
+        assert_eq!(
+            vec![(get_densmap(1.0), 10.0), (get_densmap(2.0), 20.0)],
+            frames
+        );
+        assert!(container.read_frame(3).is_err());
+    }
+
+    #[test]
+    fn test_unfinished_container_cannot_be_opened() {
+        let path = temp_dir().join("densmap_test_container_unfinished.dmc");
+
+        let mut writer = ContainerWriter::create(&path).unwrap();
+        writer.push(&get_densmap(0.0), 0.0).unwrap();
+        drop(writer);
+
+        let result = Container::open(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_interrupted_append_leaves_container_readable() {
+        let path = temp_dir().join("densmap_test_container_interrupted.dmc");
+
+        let mut writer = ContainerWriter::create(&path).unwrap();
+        writer.push(&get_densmap(0.0), 0.0).unwrap();
+        writer.finish().unwrap();
+
+        let mut writer = ContainerWriter::append(&path).unwrap();
+        writer.push(&get_densmap(1.0), 10.0).unwrap();
+        drop(writer);
+
+        let result = Container::open(&path).map(|container| container.len());
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(get_temp_path(&path)).unwrap();
+
+        assert_eq!(1, result.unwrap());
+    }
+
+    #[test]
+    fn test_container_with_overflowing_trailer_is_invalid() {
+        let mut bytes = CONTAINER_MAGIC.to_vec();
+        bytes.write_u64::<LittleEndian>(u64::MAX / 8).unwrap();
+        bytes.write_u64::<LittleEndian>(u64::MAX - 8).unwrap();
+        bytes.extend_from_slice(INDEX_MAGIC);
+
+        assert!(read_index(&mut io::Cursor::new(bytes)).is_err());
+    }
+}
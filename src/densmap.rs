@@ -1,16 +1,65 @@
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use flate2::{bufread::GzDecoder, write::GzEncoder, Compression};
+use crate::{
+    densmap3d::{DensMap3D, ZSelection},
+    fields::FieldMaps,
+    npy::{read_npy, read_npz},
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::{bufread::GzDecoder, write::GzEncoder, Compression, Crc, CrcReader};
 
 use std::{
+    collections::BTreeMap,
+    fmt,
     fs::File,
-    io::{self, BufReader, BufWriter, Read, Write},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
     path::Path,
+    str::FromStr,
 };
 
+/// Magic string which begins a density map in the versioned binary format.
+pub const DENSMAP_MAGIC: &[u8; 4] = b"DMAP";
+
+/// Version of the binary format which maps are written in.
+pub const FORMAT_VERSION: u32 = 3;
+
+/// First version of the binary format in which the checksum covers the complete map,
+/// instead of only its data.
+pub const FULL_CHECKSUM_VERSION: u32 = 3;
+
+/// Flag of the versioned header which marks that it holds a checksum of the map.
+pub const FLAG_CHECKSUM: u32 = 1;
+
+/// Flag of the versioned header which marks that it is followed by a metadata block.
+pub const FLAG_METADATA: u32 = 2;
+
+/// Flag of the versioned header which marks that all values are big endian.
+pub const FLAG_BIG_ENDIAN: u32 = 4;
+
+/// Flag of the versioned header which marks that the map is resolved into layers along z.
+pub const FLAG_LAYERED: u32 = 8;
+
+/// Flag of the versioned header which marks that the map holds several named fields.
+pub const FLAG_FIELDS: u32 = 16;
+
+/// Size of the versioned header: magic string, version, flags and checksum.
+pub const VERSIONED_HEADER_SIZE: u64 = 4 * 4;
+
+/// Size of the header of the original format: bin size, origin, shape, center and time.
+pub const LEGACY_HEADER_SIZE: u64 = 10 * 8;
+
+/// Size of the header of the layers of a z-resolved map: their number and origin along z.
+pub const LAYER_HEADER_SIZE: u64 = 2 * 8;
+
 pub type Vec2 = [f64; 2];
 pub type Vec3 = [f64; 3];
 pub type Shape = [u64; 2];
 
+/// Free-form metadata of a density map, like the simulation name, force field or version
+/// of the program which created it.
+pub type Metadata = BTreeMap<String, String>;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DensMap {
     /// Bin size in all directions.
     pub bin_size: Vec3,
@@ -24,107 +73,1279 @@ pub struct DensMap {
     pub data: Vec<f64>,
 }
 
+impl DensMap {
+    /// Scale the lengths of the grid of the map, its bin size, origin and center, by
+    /// a factor to convert them to another unit. The densities are kept as they are.
+    pub fn scale_lengths(self, factor: f64) -> DensMap {
+        let [dx, dy, dz] = self.bin_size;
+        let [x0, y0] = self.origin;
+        let [xc, yc] = self.center;
+
+        DensMap {
+            bin_size: [factor * dx, factor * dy, factor * dz],
+            origin: [factor * x0, factor * y0],
+            center: [factor * xc, factor * yc],
+            ..self
+        }
+    }
+
+    /// Iterate over the bins of the map in the order of the data, yielding the grid
+    /// position `(ix, iy)`, the absolute coordinate `(x, y)` of the bin center and
+    /// the value of every bin.
+    pub fn iter_bins(&self) -> impl Iterator<Item = (usize, usize, f64, f64, f64)> + '_ {
+        let get_position = self.get_bin_position();
+        self.data.iter().enumerate().map(move |(i, &v)| {
+            let (ix, iy, x, y) = get_position(i);
+            (ix, iy, x, y, v)
+        })
+    }
+
+    /// Iterate over the bins of the map like `iter_bins`, yielding a mutable reference
+    /// to the value of every bin.
+    pub fn iter_bins_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (usize, usize, f64, f64, &mut f64)> + '_ {
+        let get_position = self.get_bin_position();
+        self.data.iter_mut().enumerate().map(move |(i, v)| {
+            let (ix, iy, x, y) = get_position(i);
+            (ix, iy, x, y, v)
+        })
+    }
+
+    /// Get a function of the grid position and bin center coordinate of a data index.
+    fn get_bin_position(&self) -> impl Fn(usize) -> (usize, usize, f64, f64) {
+        let [dx, dy, _] = self.bin_size;
+        let [x0, y0] = self.origin;
+        let nx = self.shape[0].max(1) as usize;
+
+        move |i| {
+            let (ix, iy) = (i % nx, i / nx);
+            (
+                ix,
+                iy,
+                x0 + dx * (ix as f64 + 0.5),
+                y0 + dy * (iy as f64 + 0.5),
+            )
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Unit of lengths. Density maps are stored in nm.
+pub enum LengthUnit {
+    #[default]
+    Nm,
+    Angstrom,
+}
+
+impl LengthUnit {
+    /// Number of nm in the unit.
+    pub fn in_nm(&self) -> f64 {
+        match self {
+            LengthUnit::Nm => 1.0,
+            LengthUnit::Angstrom => 0.1,
+        }
+    }
+
+    /// Convert a length in nm to the unit.
+    pub fn convert_nm(&self, length: f64) -> f64 {
+        length / self.in_nm()
+    }
+
+    /// Name of the unit as written to the attributes of data files, which is
+    /// the ASCII name that UDUNITS recognizes.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LengthUnit::Nm => "nm",
+            LengthUnit::Angstrom => "angstrom",
+        }
+    }
+}
+
+impl fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LengthUnit::Nm => write!(f, "nm"),
+            LengthUnit::Angstrom => write!(f, "Å"),
+        }
+    }
+}
+
+impl FromStr for LengthUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "nm" => Ok(LengthUnit::Nm),
+            "A" | "Å" | "angstrom" => Ok(LengthUnit::Angstrom),
+            _ => Err(format!("invalid length unit '{}': expected 'nm' or 'A'", s)),
+        }
+    }
+}
+
+/// Grid values of a density map which replace those read from a file, and whether
+/// the map is verified against its checksum.
+///
+/// Input formats which do not store the grid of a map, like NumPy arrays, take
+/// their grid from these values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GridOptions {
+    /// Bin size in all directions.
+    pub bin_size: Option<Vec3>,
+    /// Origin of system along x and y.
+    pub origin: Option<Vec2>,
+    /// Center of fitted spherical cap along x and y.
+    pub center: Option<Vec2>,
+    /// Layers of z-resolved maps which are used as the 2D map.
+    pub z: ZSelection,
+    /// Field of maps with several fields which is used, or the sum of all fields if
+    /// it is not set.
+    pub field: Option<String>,
+    /// Verify the data of maps of the binary format against their checksum, which is
+    /// enabled by default. Disabling it allows corrupted maps to be read.
+    pub verify_checksum: bool,
+}
+
+impl Default for GridOptions {
+    fn default() -> Self {
+        GridOptions {
+            bin_size: None,
+            origin: None,
+            center: None,
+            z: ZSelection::default(),
+            field: None,
+            verify_checksum: true,
+        }
+    }
+}
+
+impl GridOptions {
+    /// Replace the grid values of a density map with those which are set.
+    pub fn apply(&self, densmap: DensMap) -> DensMap {
+        DensMap {
+            bin_size: self.bin_size.unwrap_or(densmap.bin_size),
+            origin: self.origin.unwrap_or(densmap.origin),
+            center: self.center.unwrap_or(densmap.center),
+            ..densmap
+        }
+    }
+
+    /// Construct a density map from its shape and data, with grid values from these
+    /// options or else from those read along with the data.
+    ///
+    /// If the origin is set in neither the map begins at (0, 0) and if the center is
+    /// set in neither it is placed at the middle of the map.
+    ///
+    /// # Errors
+    /// Returns an error if the bin size is set in neither or if the number of values
+    /// does not match the shape.
+    pub fn construct_densmap(
+        &self,
+        shape: Shape,
+        data: Vec<f64>,
+        read_grid: &GridOptions,
+    ) -> Result<DensMap, String> {
+        let [nx, ny] = shape;
+
+        if data.len() as u64 != nx * ny {
+            return Err(format!(
+                "read {} values, but a map of shape {}x{} has {}",
+                data.len(),
+                nx,
+                ny,
+                nx * ny
+            ));
+        }
+
+        let bin_size = self.bin_size.or(read_grid.bin_size).ok_or_else(|| {
+            "no bin size is given for the map, neither by the file nor as an option".to_string()
+        })?;
+        let origin = self.origin.or(read_grid.origin).unwrap_or([0.0, 0.0]);
+        let center = self.center.or(read_grid.center).unwrap_or([
+            origin[0] + 0.5 * bin_size[0] * nx as f64,
+            origin[1] + 0.5 * bin_size[1] * ny as f64,
+        ]);
+
+        Ok(DensMap {
+            bin_size,
+            origin,
+            shape,
+            center,
+            data,
+        })
+    }
+}
+
+/// Path which reads from stdin or writes to stdout instead of a file.
+pub const STDIO_PATH: &str = "-";
+
+/// Whether a path refers to stdin or stdout, see `STDIO_PATH`.
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO_PATH)
+}
+
+/// Magic bytes which begin a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
 /// Read a density map from the input path.
 ///
 /// If the path extension ends with '.gz' the file is assumed to be compressed with gzip
-/// and is decompressed during reading.
+/// and is decompressed during reading. Paths with the extension '.npy' or '.npz' are
+/// read as NumPy arrays, see `read_npy` and `read_npz`, and paths with the extension
+/// '.txt' or '.asc' as plain text grids, see `read_ascii_grid`.
+///
+/// If the path is '-' the map is read from stdin in the binary format, which is
+/// decompressed if it begins with the gzip magic bytes.
 pub fn read_densmap(path: &Path) -> Result<(DensMap, f64), io::Error> {
-    let fp = File::open(&path)?;
-    let mut reader = BufReader::new(fp);
+    read_densmap_with_grid(path, &GridOptions::default())
+}
 
-    match path.extension().map(|p| p.to_str().unwrap()) {
-        Some("gz") => read_densmap_from_reader(&mut GzDecoder::new(reader)),
-        _ => read_densmap_from_reader(&mut reader),
+/// Read a density map from the input path, replacing its grid values with those
+/// which are set in the options.
+pub fn read_densmap_with_grid(
+    path: &Path,
+    grid: &GridOptions,
+) -> Result<(DensMap, f64), io::Error> {
+    read_densmap_with_metadata(path, grid).map(|(densmap, time, _)| (densmap, time))
+}
+
+/// Read a density map and its metadata from the input path, replacing its grid values
+/// with those which are set in the options.
+///
+/// Only the binary format stores metadata, which is empty for maps of other formats.
+pub fn read_densmap_with_metadata(
+    path: &Path,
+    grid: &GridOptions,
+) -> Result<(DensMap, f64, Metadata), io::Error> {
+    let (maps, time, metadata) = read_field_maps_with_grid(path, grid)?;
+    let densmap = maps
+        .into_field(grid.field.as_deref())
+        .and_then(|densmap| select_2d(densmap, grid))
+        .map_err(invalid_input)?;
+
+    Ok((densmap, time, metadata))
+}
+
+/// Read the time of a density map from the input path.
+///
+/// Only the header is read of maps in the binary format, also when they are compressed,
+/// which is much cheaper than reading the full map. Maps of the other formats are read
+/// in full, see `read_densmap_with_grid`.
+///
+/// # Errors
+/// Returns an error if the header cannot be read or if its format is not supported.
+pub fn read_densmap_time(path: &Path, grid: &GridOptions) -> Result<f64, io::Error> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        _ if is_stdio(path) => read_densmap_with_grid(path, grid).map(|(_, time)| time),
+        Some("npy") | Some("npz") | Some("txt") | Some("asc") => {
+            read_densmap_with_grid(path, grid).map(|(_, time)| time)
+        }
+        Some("gz") => {
+            let reader = BufReader::new(File::open(path)?);
+            read_time_from_reader(&mut GzDecoder::new(reader))
+        }
+        _ => read_time_from_reader(&mut BufReader::new(File::open(path)?)),
     }
 }
 
-fn read_densmap_from_reader<R: Read>(reader: &mut R) -> Result<(DensMap, f64), io::Error> {
+/// Read the time from the header of a map of the binary format, see
+/// `read_densmap_from_reader`, skipping the metadata.
+fn read_time_from_reader<R: Read>(reader: &mut R) -> Result<f64, io::Error> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+
+    if &magic != DENSMAP_MAGIC {
+        let mut header = [0; LEGACY_HEADER_SIZE as usize];
+        header[..4].copy_from_slice(&magic);
+        reader.read_exact(&mut header[4..])?;
+
+        let time = &header[LEGACY_HEADER_SIZE as usize - 8..];
+
+        return match detect_legacy_byte_order(&header) {
+            Endianness::Little => Ok(LittleEndian::read_f64(time)),
+            Endianness::Big => Ok(BigEndian::read_f64(time)),
+        };
+    }
+
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+
+    match detect_version_byte_order(version) {
+        Some(Endianness::Little) => read_versioned_time::<LittleEndian, _>(reader),
+        Some(Endianness::Big) => read_versioned_time::<BigEndian, _>(reader),
+        None => Err(invalid_data(format!(
+            "density map format version {} is not supported, the latest is {}",
+            LittleEndian::read_u32(&version),
+            FORMAT_VERSION
+        ))),
+    }
+}
+
+/// Read the time from the part of the versioned header which follows the version.
+fn read_versioned_time<B: ByteOrder, R: Read>(reader: &mut R) -> Result<f64, io::Error> {
+    let flags = reader.read_u32::<B>()?;
+    let _checksum = reader.read_u32::<B>()?;
+
+    if (flags & FLAG_METADATA) != 0 {
+        let len = reader.read_u64::<B>()?;
+
+        if io::copy(&mut reader.take(len), &mut io::sink())? < len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "metadata of density map is shorter than its length",
+            ));
+        }
+    }
+
+    // The time follows the bin size, origin, shape and center.
+    let mut grid = [0; LEGACY_HEADER_SIZE as usize - 8];
+    reader.read_exact(&mut grid)?;
+
+    reader.read_f64::<B>()
+}
+
+/// Read a density map and its time from the bytes of a map of the binary format, which
+/// may be compressed with gzip, like the contents of a file which is loaded in a browser.
+///
+/// The map is selected by the options like for `read_densmap_with_grid`. No files are
+/// read, which makes this usable on targets without a file system.
+pub fn read_densmap_from_bytes(
+    bytes: &[u8],
+    grid: &GridOptions,
+) -> Result<(DensMap, f64), io::Error> {
+    let (maps, time, _) = read_field_maps_from_stream(bytes, grid.verify_checksum)?;
+    let densmap = maps
+        .into_field(grid.field.as_deref())
+        .and_then(|densmap| select_2d(densmap, grid))
+        .map_err(invalid_input)?;
+
+    Ok((densmap, time))
+}
+
+/// Read the density map selected by the options along with the maps of the named fields,
+/// replacing their grid values with those which are set in the options.
+///
+/// The file is only read once, and all maps are of the layers selected by the options.
+///
+/// # Errors
+/// Returns an error if the map has no field of a name.
+pub fn read_densmap_and_fields(
+    path: &Path,
+    grid: &GridOptions,
+    names: &[String],
+) -> Result<(DensMap, f64, Vec<DensMap>), io::Error> {
+    let (maps, time, _) = read_field_maps_with_grid(path, grid)?;
+
+    let fields = names
+        .iter()
+        .map(|name| {
+            maps.get(name)
+                .and_then(|densmap| select_2d(densmap.clone(), grid))
+                .map_err(invalid_input)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let densmap = maps
+        .into_field(grid.field.as_deref())
+        .and_then(|densmap| select_2d(densmap, grid))
+        .map_err(invalid_input)?;
+
+    Ok((densmap, time, fields))
+}
+
+/// Get the 2D map of the layers selected by the options, with their grid values.
+fn select_2d(densmap: DensMap3D, grid: &GridOptions) -> Result<DensMap, String> {
+    densmap.into_2d(grid.z).map(|densmap| grid.apply(densmap))
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Read a z-resolved density map and its metadata from the input path.
+///
+/// Maps without layers along z, including those of the NumPy and plain text formats,
+/// are read as maps of a single layer. The fields of maps with several fields are
+/// summed. See `read_densmap` for the formats.
+pub fn read_densmap3d(path: &Path) -> Result<(DensMap3D, f64, Metadata), io::Error> {
+    let (maps, time, metadata) = read_field_maps(path)?;
+    let densmap = maps.into_field(None).map_err(invalid_data)?;
+
+    Ok((densmap, time, metadata))
+}
+
+/// Read all fields of a density map and its metadata from the input path.
+///
+/// Maps without fields, including those of the NumPy and plain text formats, are read
+/// with their density as a single field. See `read_densmap` for the formats.
+pub fn read_field_maps(path: &Path) -> Result<(FieldMaps, f64, Metadata), io::Error> {
+    read_field_maps_with_grid(path, &GridOptions::default())
+}
+
+/// Read all fields of a density map and its metadata from the input path, taking the
+/// grid of formats which do not store it from the options.
+pub fn read_field_maps_with_grid(
+    path: &Path,
+    grid: &GridOptions,
+) -> Result<(FieldMaps, f64, Metadata), io::Error> {
+    let from_2d = |(densmap, time)| {
+        let maps = FieldMaps::from_single(DensMap3D::from_2d(densmap));
+        (maps, time, Metadata::new())
+    };
+
+    if is_stdio(path) {
+        let stdin = io::stdin();
+        return read_field_maps_from_stream(stdin.lock(), grid.verify_checksum);
+    }
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("npy") => read_npy(path, grid).map(from_2d),
+        Some("npz") => read_npz(path, grid).map(from_2d),
+        Some("txt") | Some("asc") => read_ascii_grid(path, grid).map(from_2d),
+        Some("gz") => {
+            let reader = BufReader::new(File::open(path)?);
+            read_field_maps_from_reader(&mut GzDecoder::new(reader), grid.verify_checksum)
+        }
+        _ => read_field_maps_from_reader(
+            &mut BufReader::new(File::open(path)?),
+            grid.verify_checksum,
+        ),
+    }
+}
+
+/// Read a density map from a stream of the binary format, which is decompressed if
+/// it begins with the gzip magic bytes.
+fn read_field_maps_from_stream<R: BufRead>(
+    mut reader: R,
+    verify_checksum: bool,
+) -> Result<(FieldMaps, f64, Metadata), io::Error> {
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        read_field_maps_from_reader(&mut GzDecoder::new(reader), verify_checksum)
+    } else {
+        read_field_maps_from_reader(&mut reader, verify_checksum)
+    }
+}
+
+/// Read a density map and its time from a reader of the (uncompressed) binary format.
+///
+/// The format begins with the magic string `DMAP` followed by the format version, a set
+/// of flags and a CRC32 checksum as 32 bit values. The checksum covers the version,
+/// flags and everything which follows the checksum, while in versions before
+/// `FULL_CHECKSUM_VERSION` it only covers the data. If the flags mark it, this is
+/// followed by the length of the metadata as a 64 bit value and the metadata as a JSON
+/// object of strings. Then follow the bin size, origin, shape, center and time of the
+/// map as 64 bit values. Maps which are resolved along z are marked by a flag and
+/// continue with the number of layers and their origin along z, and their data holds
+/// the layers in order from the bottom. Maps with several fields are marked by a flag
+/// and continue with the length of the field names as a 64 bit value and the names as a
+/// JSON array, and their data holds the fields in this order. Finally follows the data.
+/// Maps are written as little endian, while big endian maps are marked by a flag and
+/// their byte order is detected from the version. Files of the original format, which
+/// begin directly with the bin size, are read as well, see `detect_legacy_byte_order`
+/// for their byte order.
+///
+/// # Errors
+/// Returns an error if the format version or flags are not supported, if the shape
+/// of the map is too large to be read, or if the map does not match its checksum
+/// while `verify_checksum` is set.
+pub fn read_densmap_from_reader<R: Read>(
+    reader: &mut R,
+    verify_checksum: bool,
+) -> Result<(DensMap, f64), io::Error> {
+    read_densmap_with_metadata_from_reader(reader, verify_checksum)
+        .map(|(densmap, time, _)| (densmap, time))
+}
+
+/// Read a density map, its time and its metadata from a reader of the (uncompressed)
+/// binary format, see `read_densmap_from_reader`.
+///
+/// The density of z-resolved maps is integrated over all layers and the fields of maps
+/// with several fields are summed.
+pub fn read_densmap_with_metadata_from_reader<R: Read>(
+    reader: &mut R,
+    verify_checksum: bool,
+) -> Result<(DensMap, f64, Metadata), io::Error> {
+    let (densmap, time, metadata) = read_densmap3d_from_reader(reader, verify_checksum)?;
+    let densmap = densmap.into_2d(ZSelection::All).map_err(invalid_data)?;
+
+    Ok((densmap, time, metadata))
+}
+
+/// Read a z-resolved density map, its time and its metadata from a reader of the
+/// (uncompressed) binary format, see `read_densmap_from_reader`.
+pub fn read_densmap3d_from_reader<R: Read>(
+    reader: &mut R,
+    verify_checksum: bool,
+) -> Result<(DensMap3D, f64, Metadata), io::Error> {
+    let (maps, time, metadata) = read_field_maps_from_reader(reader, verify_checksum)?;
+    let densmap = maps.into_field(None).map_err(invalid_data)?;
+
+    Ok((densmap, time, metadata))
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Get the CRC32 checksum of the data block of a map, which is the checksum of maps
+/// of versions before `FULL_CHECKSUM_VERSION`.
+pub fn get_checksum(bytes: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+/// Get the CRC32 checksum of a complete map of the versioned format, which covers
+/// its version and flags and everything which follows the checksum.
+///
+/// # Panics
+/// Panics if the bytes are shorter than the versioned header.
+pub fn get_versioned_checksum(bytes: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(&bytes[4..12]);
+    crc.update(&bytes[VERSIONED_HEADER_SIZE as usize..]);
+    crc.sum()
+}
+
+/// Get the size in bytes of a map written in the binary format.
+pub fn get_encoded_size(densmap: &DensMap) -> u64 {
+    VERSIONED_HEADER_SIZE + LEGACY_HEADER_SIZE + 8 * densmap.data.len() as u64
+}
+
+/// Read all fields of a density map, its time and its metadata from a reader of the
+/// (uncompressed) binary format, see `read_densmap_from_reader`.
+pub fn read_field_maps_from_reader<R: Read>(
+    reader: &mut R,
+    verify: bool,
+) -> Result<(FieldMaps, f64, Metadata), io::Error> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+
+    // Files of the original format begin directly with the bin size.
+    if &magic != DENSMAP_MAGIC {
+        let mut header = [0; LEGACY_HEADER_SIZE as usize];
+        header[..4].copy_from_slice(&magic);
+        reader.read_exact(&mut header[4..])?;
+
+        let mut reader = (&header[..]).chain(reader);
+        let (densmap, time, _) = match detect_legacy_byte_order(&header) {
+            Endianness::Little => {
+                read_header_and_data::<LittleEndian, _>(&mut reader, false, false)?
+            }
+            Endianness::Big => read_header_and_data::<BigEndian, _>(&mut reader, false, false)?,
+        };
+
+        return Ok((densmap, time, Metadata::new()));
+    }
+
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+
+    match detect_version_byte_order(version) {
+        Some(Endianness::Little) => {
+            let version = LittleEndian::read_u32(&version);
+            read_versioned::<LittleEndian, _>(reader, version, verify)
+        }
+        Some(Endianness::Big) => {
+            let version = BigEndian::read_u32(&version);
+            read_versioned::<BigEndian, _>(reader, version, verify)
+        }
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "density map format version {} is not supported, the latest is {}",
+                LittleEndian::read_u32(&version),
+                FORMAT_VERSION
+            ),
+        )),
+    }
+}
+
+/// Read the part of the versioned format which follows the version, in the given
+/// byte order.
+fn read_versioned<B: ByteOrder, R: Read>(
+    reader: &mut R,
+    version: u32,
+    verify: bool,
+) -> Result<(FieldMaps, f64, Metadata), io::Error> {
+    let flags = reader.read_u32::<B>()?;
+    let checksum = reader.read_u32::<B>()?;
+
+    // The checksum of the version and flags is combined with that of the rest of the
+    // map as it is read.
+    let mut crc = Crc::new();
+    let mut prefix = [0; 8];
+    B::write_u32(&mut prefix[..4], version);
+    B::write_u32(&mut prefix[4..], flags);
+    crc.update(&prefix);
+
+    let mut reader = CrcReader::new(reader);
+
+    let supported = FLAG_CHECKSUM | FLAG_METADATA | FLAG_BIG_ENDIAN | FLAG_LAYERED | FLAG_FIELDS;
+
+    if (flags & !supported) != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("density map has unsupported flags {:#x}", flags),
+        ));
+    }
+
+    // The flag must agree with the byte order which the version was read in.
+    if ((flags & FLAG_BIG_ENDIAN) != 0) != (get_endianness::<B>() == Endianness::Big) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "byte order of density map does not match the flag of its header",
+        ));
+    }
+
+    let metadata = if (flags & FLAG_METADATA) != 0 {
+        let bytes = read_block::<B, _>(&mut reader, "metadata")?;
+
+        serde_json::from_slice(&bytes).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("could not parse metadata of density map: {}", err),
+            )
+        })?
+    } else {
+        Metadata::new()
+    };
+
+    let layered = (flags & FLAG_LAYERED) != 0;
+    let has_fields = (flags & FLAG_FIELDS) != 0;
+    let (densmap, time, bytes) = read_header_and_data::<B, _>(&mut reader, layered, has_fields)?;
+
+    if verify && (flags & FLAG_CHECKSUM) != 0 {
+        let found = if version >= FULL_CHECKSUM_VERSION {
+            crc.combine(reader.crc());
+            crc.sum()
+        } else {
+            get_checksum(&bytes)
+        };
+
+        if found != checksum {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "checksum {:08x} of the map does not match {:08x} of the header, \
+                     the map may be corrupted",
+                    found, checksum
+                ),
+            ));
+        }
+    }
+
+    Ok((densmap, time, metadata))
+}
+
+/// Read a block of bytes which is prefixed by its length as a 64 bit value.
+///
+/// The length is not trusted before the block has been read, so the block only grows
+/// as it is read and an error is returned if it is shorter than its length.
+fn read_block<B: ByteOrder, R: Read>(reader: &mut R, name: &str) -> Result<Vec<u8>, io::Error> {
+    let len = reader.read_u64::<B>()?;
+
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+
+    if (bytes.len() as u64) < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "{} of density map has {} bytes, expected {}",
+                name,
+                bytes.len(),
+                len
+            ),
+        ));
+    }
+
+    Ok(bytes)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Byte order of the values of a density map file.
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+fn get_endianness<B: ByteOrder>() -> Endianness {
+    if B::read_u16(&[0, 1]) == 1 {
+        Endianness::Big
+    } else {
+        Endianness::Little
+    }
+}
+
+/// Detect the byte order of a versioned map from the bytes of its version, which is
+/// only a supported version in one of the byte orders.
+pub fn detect_version_byte_order(version: [u8; 4]) -> Option<Endianness> {
+    let is_supported = |v| (1..=FORMAT_VERSION).contains(&v);
+
+    if is_supported(LittleEndian::read_u32(&version)) {
+        Some(Endianness::Little)
+    } else if is_supported(BigEndian::read_u32(&version)) {
+        Some(Endianness::Big)
+    } else {
+        None
+    }
+}
+
+/// Guess the byte order of a map of the original format, which does not record it,
+/// from its header.
+///
+/// Maps are assumed to be little endian unless their shape is only plausible when read
+/// as big endian, ie. if it has a size of at most 2^32 bins.
+pub fn detect_legacy_byte_order(header: &[u8]) -> Endianness {
+    let is_plausible = |nx: u64, ny: u64| {
+        nx > 0
+            && ny > 0
+            && nx
+                .checked_mul(ny)
+                .map(|n| n <= u64::from(u32::MAX))
+                .unwrap_or(false)
+    };
+
+    let offset = 5 * 8;
+    let little = (
+        LittleEndian::read_u64(&header[offset..]),
+        LittleEndian::read_u64(&header[offset + 8..]),
+    );
+    let big = (
+        BigEndian::read_u64(&header[offset..]),
+        BigEndian::read_u64(&header[offset + 8..]),
+    );
+
+    if !is_plausible(little.0, little.1) && is_plausible(big.0, big.1) {
+        Endianness::Big
+    } else {
+        Endianness::Little
+    }
+}
+
+/// Read the grid, time and data of a map, along with the bytes of the data. If the map
+/// is layered the header is followed by the number of layers and their origin along z,
+/// and if it has fields by the names of its fields.
+fn read_header_and_data<B: ByteOrder, R: Read>(
+    reader: &mut R,
+    layered: bool,
+    has_fields: bool,
+) -> Result<(FieldMaps, f64, Vec<u8>), io::Error> {
     let bin_size = [
-        reader.read_f64::<LittleEndian>()?,
-        reader.read_f64::<LittleEndian>()?,
-        reader.read_f64::<LittleEndian>()?,
+        reader.read_f64::<B>()?,
+        reader.read_f64::<B>()?,
+        reader.read_f64::<B>()?,
     ];
 
-    let origin = [
-        reader.read_f64::<LittleEndian>()?,
-        reader.read_f64::<LittleEndian>()?,
-    ];
+    let origin = [reader.read_f64::<B>()?, reader.read_f64::<B>()?];
 
-    let shape = [
-        reader.read_u64::<LittleEndian>()?,
-        reader.read_u64::<LittleEndian>()?,
-    ];
+    let shape = [reader.read_u64::<B>()?, reader.read_u64::<B>()?];
 
-    let center = [
-        reader.read_f64::<LittleEndian>()?,
-        reader.read_f64::<LittleEndian>()?,
-    ];
+    let center = [reader.read_f64::<B>()?, reader.read_f64::<B>()?];
+
+    let time = reader.read_f64::<B>()?;
+
+    let (nz, z0) = if layered {
+        (reader.read_u64::<B>()?, reader.read_f64::<B>()?)
+    } else {
+        (1, 0.0)
+    };
 
-    let time = reader.read_f64::<LittleEndian>()?;
+    let names = if has_fields {
+        let bytes = read_block::<B, _>(reader, "field names")?;
+
+        serde_json::from_slice::<Vec<String>>(&bytes).map_err(|err| {
+            invalid_data(format!(
+                "could not parse field names of density map: {}",
+                err
+            ))
+        })?
+    } else {
+        Vec::new()
+    };
 
     let [nx, ny] = shape;
-    let num_bins = nx * ny;
+    let num_fields = names.len().max(1) as u64;
+
+    // The shape is not trusted before the data has been read, so the size is checked
+    // and the data only grows as it is read.
+    let num_bins = nx.checked_mul(ny).and_then(|n| n.checked_mul(nz));
+    let size = num_bins
+        .and_then(|n| n.checked_mul(num_fields))
+        .and_then(|n| n.checked_mul(8))
+        .filter(|&size| size <= usize::MAX as u64);
+
+    let (num_bins, size) = match (num_bins, size) {
+        (Some(num_bins), Some(size)) => (num_bins, size),
+        _ => {
+            return Err(invalid_data(format!(
+                "shape {}x{}x{} of density map with {} fields is too large",
+                nx, ny, nz, num_fields
+            )));
+        }
+    };
 
-    let mut data: Vec<f64> = Vec::with_capacity(num_bins as usize);
+    let mut bytes = Vec::new();
+    reader.take(size).read_to_end(&mut bytes)?;
 
-    for _ in 0..num_bins {
-        data.push(reader.read_f64::<LittleEndian>()?);
+    if (bytes.len() as u64) < size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "density map has {} bytes of data, expected {} for shape {}x{}x{}",
+                bytes.len(),
+                size,
+                nx,
+                ny,
+                nz
+            ),
+        ));
     }
 
-    Ok((
-        DensMap {
-            bin_size,
-            origin,
-            shape,
-            center,
-            data,
-        },
-        time,
-    ))
+    let get_field = |data: &[u8]| DensMap3D {
+        bin_size,
+        origin: [origin[0], origin[1], z0],
+        shape: [nx, ny, nz],
+        center,
+        data: data.chunks_exact(8).map(B::read_f64).collect(),
+    };
+
+    let maps = if has_fields {
+        let fields = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let n = 8 * num_bins as usize;
+                (name.clone(), get_field(&bytes[i * n..(i + 1) * n]))
+            })
+            .collect();
+
+        FieldMaps::new(fields).map_err(invalid_data)?
+    } else {
+        FieldMaps::from_single(get_field(&bytes))
+    };
+
+    Ok((maps, time, bytes))
+}
+
+/// Read a density map from a plain text grid of whitespace separated values.
+///
+/// Every line holds the values of a row of bins along x, beginning with the row at
+/// the lowest y. The grid and time of the map may be given in a header of comment
+/// lines which begin with '#':
+///
+/// ```text
+/// # bin_size 0.25 0.25
+/// # origin 0.0 0.0
+/// # center 1.0 0.5
+/// # time 10.0
+/// 0.0 0.1 0.2 0.1
+/// 0.1 0.4 0.5 0.2
+/// ...
+/// ```
+///
+/// Other comments and empty lines are ignored. Values which are set in the grid
+/// options take precedence over the header, see `GridOptions::construct_densmap`.
+/// If no time is given it is set to 0.
+///
+/// # Errors
+/// Returns an error if a value cannot be parsed, if the rows are of different lengths
+/// or if the bin size is not given by either the header or the grid options.
+pub fn read_ascii_grid(path: &Path, grid: &GridOptions) -> Result<(DensMap, f64), io::Error> {
+    let fp = File::open(path)?;
+    read_ascii_grid_from_reader(BufReader::new(fp), grid)
+}
+
+fn read_ascii_grid_from_reader<R: BufRead>(
+    reader: R,
+    grid: &GridOptions,
+) -> Result<(DensMap, f64), io::Error> {
+    let invalid_data = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let mut header = GridOptions::default();
+    let mut time = None;
+
+    let mut data = Vec::new();
+    let mut nx = None;
+    let mut ny = 0;
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+
+        let parse_values = |s: &str| {
+            s.split_whitespace()
+                .map(|v| v.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| invalid_data(format!("could not parse line {}: {}", i + 1, err)))
+        };
+
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut words = comment.trim_start().splitn(2, char::is_whitespace);
+            let key = words.next().unwrap_or("");
+            let rest = words.next().unwrap_or("");
+
+            match key {
+                "bin_size" => {
+                    header.bin_size = match *parse_values(rest)?.as_slice() {
+                        [dx, dy] => Some([dx, dy, 0.0]),
+                        [dx, dy, dz] => Some([dx, dy, dz]),
+                        _ => {
+                            return Err(invalid_data(format!(
+                                "bin size on line {} must be given as 'dx dy [dz]'",
+                                i + 1
+                            )))
+                        }
+                    }
+                }
+                "origin" | "center" => {
+                    let point = match *parse_values(rest)?.as_slice() {
+                        [x, y] => [x, y],
+                        _ => {
+                            return Err(invalid_data(format!(
+                                "{} on line {} must be given as 'x y'",
+                                key,
+                                i + 1
+                            )))
+                        }
+                    };
+
+                    if key == "origin" {
+                        header.origin = Some(point);
+                    } else {
+                        header.center = Some(point);
+                    }
+                }
+                "time" => time = parse_values(rest)?.first().cloned(),
+                _ => (),
+            }
+        } else if !line.is_empty() {
+            let row = parse_values(line)?;
+
+            match nx {
+                None => nx = Some(row.len()),
+                Some(n) if n != row.len() => {
+                    return Err(invalid_data(format!(
+                        "row on line {} has {} values, but the previous rows have {}",
+                        i + 1,
+                        row.len(),
+                        n
+                    )));
+                }
+                _ => (),
+            }
+
+            data.extend(row);
+            ny += 1;
+        }
+    }
+
+    let shape = [nx.unwrap_or(0) as u64, ny];
+    let densmap = grid
+        .construct_densmap(shape, data, &header)
+        .map_err(invalid_data)?;
+
+    Ok((densmap, time.unwrap_or(0.0)))
 }
 
 /// Write a density map to the input path.
 ///
 /// If the extension ends with '.gz' the file will be encoded as a gzipped file.
+/// If the path is '-' the map is written to stdout in the uncompressed binary format.
 pub fn write_densmap(path: &Path, densmap: &DensMap, time: f64) -> Result<(), io::Error> {
-    let fp = File::create(&path)?;
+    write_densmap_with_metadata(path, densmap, time, &Metadata::new())
+}
+
+/// Write a density map along with its metadata to the input path, see `write_densmap`.
+pub fn write_densmap_with_metadata(
+    path: &Path,
+    densmap: &DensMap,
+    time: f64,
+    metadata: &Metadata,
+) -> Result<(), io::Error> {
+    write_densmap_with_level(path, densmap, time, metadata, Compression::fast().level())
+}
+
+/// Write a density map and its metadata to the input path, using the given compression
+/// level (0 to 9) if the file is encoded as a gzipped file.
+///
+/// If the extension ends with '.gz' the file will be encoded as a gzipped file.
+pub fn write_densmap_with_level(
+    path: &Path,
+    densmap: &DensMap,
+    time: f64,
+    metadata: &Metadata,
+    level: u32,
+) -> Result<(), io::Error> {
+    write_to_path(path, level, |writer| {
+        write_densmap_with_metadata_to_writer(writer, densmap, time, metadata)
+    })
+}
+
+/// Write a z-resolved density map and its metadata to the input path, see
+/// `write_densmap`.
+pub fn write_densmap3d(
+    path: &Path,
+    densmap: &DensMap3D,
+    time: f64,
+    metadata: &Metadata,
+) -> Result<(), io::Error> {
+    write_densmap3d_with_level(path, densmap, time, metadata, Compression::fast().level())
+}
+
+/// Write a z-resolved density map and its metadata to the input path, using the given
+/// compression level (0 to 9) if the file is encoded as a gzipped file.
+pub fn write_densmap3d_with_level(
+    path: &Path,
+    densmap: &DensMap3D,
+    time: f64,
+    metadata: &Metadata,
+    level: u32,
+) -> Result<(), io::Error> {
+    write_to_path(path, level, |writer| {
+        write_densmap3d_to_writer(writer, densmap, time, metadata)
+    })
+}
+
+/// Write all fields of a density map and its metadata to the input path, using the
+/// given compression level (0 to 9) if the file is encoded as a gzipped file.
+pub fn write_field_maps_with_level(
+    path: &Path,
+    maps: &FieldMaps,
+    time: f64,
+    metadata: &Metadata,
+    level: u32,
+) -> Result<(), io::Error> {
+    write_to_path(path, level, |writer| {
+        write_field_maps_to_writer(writer, maps, time, metadata)
+    })
+}
+
+/// Open a writer to the path, which is stdout for '-' and compressed with gzip if the
+/// extension ends with '.gz', and write to it.
+fn write_to_path<F>(path: &Path, level: u32, write: F) -> Result<(), io::Error>
+where
+    F: FnOnce(&mut dyn Write) -> Result<(), io::Error>,
+{
+    if is_stdio(path) {
+        let stdout = io::stdout();
+        let mut writer = BufWriter::new(stdout.lock());
+        write(&mut writer)?;
+
+        return writer.flush();
+    }
+
+    let fp = File::create(path)?;
     let mut writer = BufWriter::new(fp);
 
-    match path.extension().map(|p| p.to_str().unwrap()) {
-        Some("gz") => write_densmap_to_writer(
-            &mut GzEncoder::new(writer, Compression::fast()),
-            &densmap,
-            time,
-        ),
-        _ => write_densmap_to_writer(&mut writer, &densmap, time),
+    // Errors of compressing and writing the last data are only seen when finishing
+    // the encoder and flushing the file, which must be done explicitly.
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => {
+            let mut encoder = GzEncoder::new(writer, Compression::new(level));
+            write(&mut encoder)?;
+            encoder.finish()?.flush()
+        }
+        _ => {
+            write(&mut writer)?;
+            writer.flush()
+        }
     }
 }
 
-fn write_densmap_to_writer<W: Write>(
+/// Write a density map and its time to a writer in the (uncompressed) binary format,
+/// with a checksum of its data. See `read_densmap_from_reader` for the format.
+pub fn write_densmap_to_writer<W: Write>(
     writer: &mut W,
     densmap: &DensMap,
     time: f64,
 ) -> Result<(), io::Error> {
-    writer.write_f64::<LittleEndian>(densmap.bin_size[0])?;
-    writer.write_f64::<LittleEndian>(densmap.bin_size[1])?;
-    writer.write_f64::<LittleEndian>(densmap.bin_size[2])?;
+    write_densmap_with_metadata_to_writer(writer, densmap, time, &Metadata::new())
+}
 
-    writer.write_f64::<LittleEndian>(densmap.origin[0])?;
-    writer.write_f64::<LittleEndian>(densmap.origin[1])?;
+/// Write a density map, its time and its metadata to a writer in the (uncompressed)
+/// binary format. No metadata block is written if the metadata is empty.
+pub fn write_densmap_with_metadata_to_writer<W: Write + ?Sized>(
+    writer: &mut W,
+    densmap: &DensMap,
+    time: f64,
+    metadata: &Metadata,
+) -> Result<(), io::Error> {
+    write_encoded::<LittleEndian, _>(writer, &Encoded::from_2d(densmap), time, metadata)
+}
 
-    writer.write_u64::<LittleEndian>(densmap.shape[0])?;
-    writer.write_u64::<LittleEndian>(densmap.shape[1])?;
+/// Write a z-resolved density map, its time and its metadata to a writer in the
+/// (uncompressed) binary format.
+///
+/// The map is marked as layered even if it has a single layer, to keep its origin
+/// along z.
+pub fn write_densmap3d_to_writer<W: Write + ?Sized>(
+    writer: &mut W,
+    densmap: &DensMap3D,
+    time: f64,
+    metadata: &Metadata,
+) -> Result<(), io::Error> {
+    write_encoded::<LittleEndian, _>(writer, &Encoded::from_3d(densmap), time, metadata)
+}
 
-    writer.write_f64::<LittleEndian>(densmap.center[0])?;
-    writer.write_f64::<LittleEndian>(densmap.center[1])?;
+/// Write all fields of a density map, its time and its metadata to a writer in the
+/// (uncompressed) binary format.
+///
+/// Maps of a single density are written without field names, and maps of a single
+/// layer at z = 0 without layers.
+pub fn write_field_maps_to_writer<W: Write + ?Sized>(
+    writer: &mut W,
+    maps: &FieldMaps,
+    time: f64,
+    metadata: &Metadata,
+) -> Result<(), io::Error> {
+    write_encoded::<LittleEndian, _>(writer, &Encoded::from_fields(maps), time, metadata)
+}
+
+/// Values of a map which are written in the binary format.
+struct Encoded<'a> {
+    bin_size: Vec3,
+    origin: Vec2,
+    shape: Shape,
+    center: Vec2,
+    /// Number of layers and their origin along z, for z-resolved maps.
+    layers: Option<(u64, f64)>,
+    /// Names of the fields, for maps with several fields.
+    names: Option<&'a [String]>,
+    /// Data of every field.
+    data: Vec<&'a [f64]>,
+}
+
+impl<'a> Encoded<'a> {
+    fn from_2d(densmap: &'a DensMap) -> Self {
+        Encoded {
+            bin_size: densmap.bin_size,
+            origin: densmap.origin,
+            shape: densmap.shape,
+            center: densmap.center,
+            layers: None,
+            names: None,
+            data: vec![&densmap.data],
+        }
+    }
+
+    fn from_3d(densmap: &'a DensMap3D) -> Self {
+        let [nx, ny, nz] = densmap.shape;
+        let [x0, y0, z0] = densmap.origin;
+
+        Encoded {
+            bin_size: densmap.bin_size,
+            origin: [x0, y0],
+            shape: [nx, ny],
+            center: densmap.center,
+            layers: Some((nz, z0)),
+            names: None,
+            data: vec![&densmap.data],
+        }
+    }
+
+    fn from_fields(maps: &'a FieldMaps) -> Self {
+        let first = &maps.fields[0];
+        let is_flat = first.shape[2] == 1 && first.origin[2] == 0.0;
+
+        Encoded {
+            layers: if is_flat {
+                None
+            } else {
+                Encoded::from_3d(first).layers
+            },
+            names: if maps.is_single() {
+                None
+            } else {
+                Some(&maps.names)
+            },
+            data: maps
+                .fields
+                .iter()
+                .map(|densmap| densmap.data.as_slice())
+                .collect(),
+            ..Encoded::from_3d(first)
+        }
+    }
+}
+
+/// Write a map in the binary format with the given byte order.
+fn write_encoded<B: ByteOrder, W: Write + ?Sized>(
+    writer: &mut W,
+    densmap: &Encoded,
+    time: f64,
+    metadata: &Metadata,
+) -> Result<(), io::Error> {
+    let num_values = densmap.data.iter().map(|data| data.len()).sum::<usize>();
+
+    let mut flags = FLAG_CHECKSUM;
+
+    if !metadata.is_empty() {
+        flags |= FLAG_METADATA;
+    }
+
+    if densmap.layers.is_some() {
+        flags |= FLAG_LAYERED;
+    }
+
+    if densmap.names.is_some() {
+        flags |= FLAG_FIELDS;
+    }
+
+    if get_endianness::<B>() == Endianness::Big {
+        flags |= FLAG_BIG_ENDIAN;
+    }
+
+    // The map is encoded before it is written, since the checksum which precedes it
+    // covers all of it.
+    let mut bytes = Vec::with_capacity(LEGACY_HEADER_SIZE as usize + 8 * num_values);
+    bytes.write_u32::<B>(FORMAT_VERSION)?;
+    bytes.write_u32::<B>(flags)?;
+
+    if !metadata.is_empty() {
+        let json = serde_json::to_vec(metadata)?;
+        bytes.write_u64::<B>(json.len() as u64)?;
+        bytes.write_all(&json)?;
+    }
+
+    bytes.write_f64::<B>(densmap.bin_size[0])?;
+    bytes.write_f64::<B>(densmap.bin_size[1])?;
+    bytes.write_f64::<B>(densmap.bin_size[2])?;
+
+    bytes.write_f64::<B>(densmap.origin[0])?;
+    bytes.write_f64::<B>(densmap.origin[1])?;
+
+    bytes.write_u64::<B>(densmap.shape[0])?;
+    bytes.write_u64::<B>(densmap.shape[1])?;
+
+    bytes.write_f64::<B>(densmap.center[0])?;
+    bytes.write_f64::<B>(densmap.center[1])?;
+
+    bytes.write_f64::<B>(time)?;
+
+    if let Some((nz, z0)) = densmap.layers {
+        bytes.write_u64::<B>(nz)?;
+        bytes.write_f64::<B>(z0)?;
+    }
 
-    writer.write_f64::<LittleEndian>(time)?;
+    if let Some(names) = densmap.names {
+        let json = serde_json::to_vec(names)?;
+        bytes.write_u64::<B>(json.len() as u64)?;
+        bytes.write_all(&json)?;
+    }
 
-    for v in densmap.data.iter().cloned() {
-        writer.write_f64::<LittleEndian>(v)?;
+    for v in densmap.data.iter().flat_map(|data| data.iter().cloned()) {
+        bytes.write_f64::<B>(v)?;
     }
 
-    Ok(())
+    let mut crc = Crc::new();
+    crc.update(&bytes);
+
+    writer.write_all(DENSMAP_MAGIC)?;
+    writer.write_all(&bytes[..8])?;
+    writer.write_u32::<B>(crc.sum())?;
+    writer.write_all(&bytes[8..])
 }
 
 /// Get the 2D grid position from a 1D index in the array.
@@ -141,12 +1362,36 @@ pub fn index2tuple(i: usize, [nx, ny]: Shape) -> Option<(usize, usize)> {
 /// Get the 1D array index from a 2D grid position.
 ///
 /// Return `None` if the 2D position lies outside of the system.
-pub fn tuple2index(ix: isize, iy: isize, [nx, ny]: Shape) -> Option<usize> {
-    if ix >= 0 && ix < nx as isize && iy >= 0 && iy < ny as isize {
-        Some((iy * nx as isize + ix) as usize)
-    } else {
-        None
-    }
+pub fn tuple2index(ix: isize, iy: isize, shape: Shape) -> Option<usize> {
+    tuple2index_periodic(ix, iy, shape, [false, false])
+}
+
+/// Get the 1D array index from a 2D grid position, wrapping the position across
+/// the system along the axes which are set as periodic.
+///
+/// Return `None` if the 2D position lies outside of the system along a non-periodic axis.
+pub fn tuple2index_periodic(
+    ix: isize,
+    iy: isize,
+    [nx, ny]: Shape,
+    [periodic_x, periodic_y]: [bool; 2],
+) -> Option<usize> {
+    let wrap = |i: isize, n: u64, periodic: bool| {
+        let n = n as isize;
+
+        if periodic && n > 0 {
+            Some(i.rem_euclid(n))
+        } else if i >= 0 && i < n {
+            Some(i)
+        } else {
+            None
+        }
+    };
+
+    let ix = wrap(ix, nx, periodic_x)?;
+    let iy = wrap(iy, ny, periodic_y)?;
+
+    Some((iy * nx as isize + ix) as usize)
 }
 
 /// Get the 1D array index from a 2D system coordinate.
@@ -158,6 +1403,300 @@ pub fn coord2index(x: f64, y: f64, [dx, dy, _]: Vec3, shape: Shape) -> Option<us
     tuple2index(ix, iy, shape)
 }
 
+#[test]
+fn test_iterating_over_bins_yields_their_positions_and_centers() {
+    let mut densmap = DensMap {
+        shape: [3, 2],
+        data: vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0],
+        ..get_test_densmap()
+    };
+
+    let bins = densmap.iter_bins().collect::<Vec<_>>();
+    assert_eq!(6, bins.len());
+    assert_eq!((0, 0, 1.25, 2.125, 0.0), bins[0]);
+    assert_eq!((2, 1, 2.25, 2.375, 5.0), bins[5]);
+
+    for (ix, iy, _, _, v) in densmap.iter_bins_mut() {
+        *v = (10 * iy + ix) as f64;
+    }
+    assert_eq!(vec![0.0, 1.0, 2.0, 10.0, 11.0, 12.0], densmap.data);
+}
+
+#[test]
+fn test_scaling_lengths_converts_the_grid_but_not_the_densities() {
+    let densmap = get_test_densmap();
+    let factor = 1.0 / LengthUnit::Angstrom.in_nm();
+    let scaled = densmap.clone().scale_lengths(factor);
+
+    for (a, b) in scaled.bin_size.iter().zip(densmap.bin_size.iter()) {
+        assert!((a - 10.0 * b).abs() < 1e-12);
+    }
+    assert!((scaled.origin[0] - 10.0 * densmap.origin[0]).abs() < 1e-12);
+    assert!((scaled.center[1] - 10.0 * densmap.center[1]).abs() < 1e-12);
+    assert_eq!(densmap.shape, scaled.shape);
+    assert_eq!(densmap.data, scaled.data);
+
+    assert_eq!(Ok(LengthUnit::Angstrom), "Å".parse());
+    assert_eq!(Ok(LengthUnit::Nm), "nm".parse());
+    assert!("m".parse::<LengthUnit>().is_err());
+}
+
+#[test]
+fn test_ascii_grid_is_read_with_its_header() {
+    let text = "# bin_size 0.5 0.25\n# origin 1.0 2.0\n# time 10.0\n# comment\n0 1 2\n\n3 4 5\n";
+    let (densmap, time) =
+        read_ascii_grid_from_reader(text.as_bytes(), &GridOptions::default()).unwrap();
+
+    assert_eq!([0.5, 0.25, 0.0], densmap.bin_size);
+    assert_eq!([1.0, 2.0], densmap.origin);
+    assert_eq!([3, 2], densmap.shape);
+    assert_eq!([1.75, 2.25], densmap.center);
+    assert_eq!(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0], densmap.data);
+    assert_eq!(10.0, time);
+}
+
+#[test]
+fn test_ascii_grid_takes_geometry_from_options_and_requires_a_bin_size() {
+    let text = "# bin_size 0.5 0.25\n0 1\n2 3\n";
+    let grid = GridOptions {
+        bin_size: Some([1.0, 1.0, 1.0]),
+        center: Some([0.0, 0.0]),
+        ..GridOptions::default()
+    };
+
+    let (densmap, time) = read_ascii_grid_from_reader(text.as_bytes(), &grid).unwrap();
+    assert_eq!([1.0, 1.0, 1.0], densmap.bin_size);
+    assert_eq!([0.0, 0.0], densmap.origin);
+    assert_eq!([0.0, 0.0], densmap.center);
+    assert_eq!(0.0, time);
+
+    let text = "0 1\n2 3\n";
+    assert!(read_ascii_grid_from_reader(text.as_bytes(), &GridOptions::default()).is_err());
+}
+
+#[test]
+fn test_ascii_grid_with_rows_of_different_lengths_is_not_read() {
+    let text = "# bin_size 1.0 1.0\n0 1 2\n3 4\n";
+    assert!(read_ascii_grid_from_reader(text.as_bytes(), &GridOptions::default()).is_err());
+}
+
+#[test]
+fn test_streams_are_read_with_or_without_gzip_compression() {
+    let densmap = get_test_densmap();
+
+    let mut bytes = Vec::new();
+    write_densmap_to_writer(&mut bytes, &densmap, 10.0).unwrap();
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    write_densmap_to_writer(&mut encoder, &densmap, 10.0).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    assert_eq!(
+        (densmap.clone(), 10.0),
+        read_densmap_from_bytes(&compressed, &GridOptions::default()).unwrap()
+    );
+
+    let maps = FieldMaps::from_single(DensMap3D::from_2d(densmap));
+
+    assert_eq!(
+        (maps.clone(), 10.0, Metadata::new()),
+        read_field_maps_from_stream(bytes.as_slice(), true).unwrap()
+    );
+    assert_eq!(
+        (maps, 10.0, Metadata::new()),
+        read_field_maps_from_stream(compressed.as_slice(), true).unwrap()
+    );
+    assert!(is_stdio(Path::new("-")));
+    assert!(!is_stdio(Path::new("./-")));
+}
+
+#[test]
+fn test_time_is_read_from_the_header_alone() {
+    let densmap = get_test_densmap();
+    let metadata = vec![("name".to_string(), "droplet".to_string())]
+        .into_iter()
+        .collect::<Metadata>();
+    let dir = std::env::temp_dir();
+
+    for name in &["densmap_test_time.dat", "densmap_test_time.dat.gz"] {
+        let path = dir.join(format!("{}_{}", std::process::id(), name));
+        write_densmap_with_metadata(&path, &densmap, 10.0, &metadata).unwrap();
+
+        let time = read_densmap_time(&path, &GridOptions::default());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(10.0, time.unwrap());
+    }
+
+    let mut bytes = Vec::new();
+    write_densmap_to_writer(&mut bytes, &densmap, 10.0).unwrap();
+
+    // The header is enough, both of the versioned and the original format.
+    let header_size = (VERSIONED_HEADER_SIZE + LEGACY_HEADER_SIZE) as usize;
+    let legacy = &bytes[VERSIONED_HEADER_SIZE as usize..header_size];
+    assert_eq!(
+        10.0,
+        read_time_from_reader(&mut &bytes[..header_size]).unwrap()
+    );
+    assert_eq!(10.0, read_time_from_reader(&mut &legacy[..]).unwrap());
+    assert!(read_time_from_reader(&mut &bytes[..header_size - 1]).is_err());
+}
+
+#[cfg(test)]
+fn get_test_densmap() -> DensMap {
+    DensMap {
+        bin_size: [0.5, 0.25, 0.0],
+        origin: [1.0, 2.0],
+        shape: [3, 1],
+        center: [1.75, 2.125],
+        data: vec![0.0, 1.0, 2.0],
+    }
+}
+
+#[test]
+fn test_versioned_format_has_a_checksum_of_the_data() {
+    let densmap = get_test_densmap();
+
+    let mut bytes = Vec::new();
+    write_densmap_to_writer(&mut bytes, &densmap, 10.0).unwrap();
+
+    assert_eq!(get_encoded_size(&densmap), bytes.len() as u64);
+    assert_eq!(DENSMAP_MAGIC, &bytes[..4]);
+    assert_eq!(
+        (densmap.clone(), 10.0, Metadata::new()),
+        read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), true).unwrap()
+    );
+
+    // Corrupt the last value of the data.
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0x01;
+
+    assert!(read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), true).is_err());
+    assert!(read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), false).is_ok());
+}
+
+#[test]
+fn test_checksum_covers_the_header_of_the_map() {
+    let densmap = get_test_densmap();
+
+    let mut bytes = Vec::new();
+    write_densmap_to_writer(&mut bytes, &densmap, 10.0).unwrap();
+    assert_eq!(
+        get_versioned_checksum(&bytes),
+        LittleEndian::read_u32(&bytes[12..])
+    );
+
+    // Corrupt the center of the map.
+    let center = VERSIONED_HEADER_SIZE as usize + 7 * 8;
+    bytes[center] ^= 0x01;
+    assert!(read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), true).is_err());
+
+    // Maps of earlier versions only have a checksum of their data.
+    LittleEndian::write_u32(&mut bytes[4..], FULL_CHECKSUM_VERSION - 1);
+    let data = get_checksum(&bytes[VERSIONED_HEADER_SIZE as usize + LEGACY_HEADER_SIZE as usize..]);
+    LittleEndian::write_u32(&mut bytes[12..], data);
+    assert!(read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), true).is_ok());
+}
+
+#[test]
+fn test_maps_with_too_large_shapes_are_invalid() {
+    let mut bytes = Vec::new();
+    write_densmap_to_writer(&mut bytes, &get_test_densmap(), 10.0).unwrap();
+
+    let shape = VERSIONED_HEADER_SIZE as usize + 5 * 8;
+    LittleEndian::write_u64(&mut bytes[shape..], u64::MAX / 2);
+    LittleEndian::write_u64(&mut bytes[shape + 8..], 4);
+
+    let err = read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), false).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+    // A shape which fits but is larger than the data is truncated.
+    LittleEndian::write_u64(&mut bytes[shape..], 1 << 40);
+    LittleEndian::write_u64(&mut bytes[shape + 8..], 1);
+
+    let err = read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), false).unwrap_err();
+    assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+}
+
+#[test]
+fn test_metadata_is_read_along_with_the_map() {
+    let densmap = get_test_densmap();
+
+    let mut metadata = Metadata::new();
+    metadata.insert("simulation".to_string(), "spreading water".to_string());
+    metadata.insert("force_field".to_string(), "SPC/E".to_string());
+
+    let mut bytes = Vec::new();
+    write_densmap_with_metadata_to_writer(&mut bytes, &densmap, 10.0, &metadata).unwrap();
+
+    assert_eq!(
+        (densmap.clone(), 10.0, metadata),
+        read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), true).unwrap()
+    );
+    assert_eq!(
+        (densmap, 10.0),
+        read_densmap_from_reader(&mut bytes.as_slice(), true).unwrap()
+    );
+
+    // A length of the metadata beyond the end of the file is not allocated.
+    LittleEndian::write_u64(&mut bytes[VERSIONED_HEADER_SIZE as usize..], u64::MAX);
+    let err = read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), false).unwrap_err();
+    assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+}
+
+#[test]
+fn test_big_endian_maps_are_read() {
+    let densmap = get_test_densmap();
+
+    let mut bytes = Vec::new();
+    write_encoded::<BigEndian, _>(
+        &mut bytes,
+        &Encoded::from_2d(&densmap),
+        10.0,
+        &Metadata::new(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        (densmap.clone(), 10.0, Metadata::new()),
+        read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), true).unwrap()
+    );
+
+    // Maps of the original format do not record their byte order.
+    let legacy = &bytes[VERSIONED_HEADER_SIZE as usize..];
+    assert_eq!(Endianness::Big, detect_legacy_byte_order(legacy));
+    assert_eq!(
+        (densmap, 10.0, Metadata::new()),
+        read_densmap_with_metadata_from_reader(&mut &legacy[..], true).unwrap()
+    );
+
+    // The flag of the header must match the byte order.
+    BigEndian::write_u32(&mut bytes[8..], FLAG_CHECKSUM);
+    assert!(read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), true).is_err());
+}
+
+#[test]
+fn test_original_format_without_version_is_read() {
+    let densmap = get_test_densmap();
+
+    let mut bytes = Vec::new();
+    write_densmap_to_writer(&mut bytes, &densmap, 10.0).unwrap();
+    let legacy = &bytes[VERSIONED_HEADER_SIZE as usize..];
+
+    assert_eq!(
+        (densmap, 10.0, Metadata::new()),
+        read_densmap_with_metadata_from_reader(&mut &legacy[..], true).unwrap()
+    );
+}
+
+#[test]
+fn test_newer_format_versions_are_not_read() {
+    let mut bytes = Vec::new();
+    write_densmap_to_writer(&mut bytes, &get_test_densmap(), 10.0).unwrap();
+    LittleEndian::write_u32(&mut bytes[4..], FORMAT_VERSION + 1);
+
+    assert!(read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), true).is_err());
+}
+
 #[test]
 fn test_correct_index_from_coordinate_in_uniform_bin_sized_system() {
     let shape = [6, 9];
@@ -213,3 +1752,141 @@ fn test_correct_index_from_tuple() {
     assert_eq!(None, tuple2index(6, 0, shape));
     assert_eq!(None, tuple2index(0, 9, shape));
 }
+
+#[test]
+fn test_tuple2index_periodic_wraps_along_periodic_axes() {
+    let shape = [6, 9];
+
+    assert_eq!(Some(5), tuple2index_periodic(-1, 0, shape, [true, false]));
+    assert_eq!(Some(0), tuple2index_periodic(6, 0, shape, [true, false]));
+    assert_eq!(None, tuple2index_periodic(0, -1, shape, [true, false]));
+
+    assert_eq!(Some(48), tuple2index_periodic(0, -1, shape, [false, true]));
+    assert_eq!(None, tuple2index_periodic(-1, 0, shape, [false, true]));
+
+    assert_eq!(Some(53), tuple2index_periodic(-1, -1, shape, [true, true]));
+    assert_eq!(Some(7), tuple2index_periodic(13, 10, shape, [true, true]));
+}
+
+#[test]
+fn test_z_resolved_maps_are_written_with_their_layers_and_read_as_selected_2d_maps() {
+    let densmap = DensMap3D {
+        bin_size: [1.0, 1.0, 0.5],
+        origin: [0.0, 0.0, 1.0],
+        shape: [3, 1, 2],
+        center: [1.5, 0.5],
+        data: vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+    };
+    let metadata = Metadata::new();
+
+    let mut bytes = Vec::new();
+    write_densmap3d_to_writer(&mut bytes, &densmap, 10.0, &metadata).unwrap();
+
+    assert_eq!(
+        (densmap.clone(), 10.0, metadata.clone()),
+        read_densmap3d_from_reader(&mut bytes.as_slice(), true).unwrap()
+    );
+
+    // As a 2D map the density is integrated over all layers.
+    let (integrated, _, _) =
+        read_densmap_with_metadata_from_reader(&mut bytes.as_slice(), true).unwrap();
+    assert_eq!([3, 1], integrated.shape);
+    assert_eq!(vec![2.5, 3.5, 4.5], integrated.data);
+
+    let path = std::env::temp_dir().join("densmap_test_z_resolved_map.dat");
+    write_densmap3d(&path, &densmap, 10.0, &metadata).unwrap();
+
+    let read_with_z = |z| {
+        let grid = GridOptions {
+            z,
+            ..GridOptions::default()
+        };
+        read_densmap_with_grid(&path, &grid).map(|(densmap, _)| densmap.data)
+    };
+
+    let slice = read_with_z(ZSelection::Slice(1));
+    let range = read_with_z(ZSelection::Range(0.0, 1.5));
+    let outside = read_with_z(ZSelection::Slice(2));
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(vec![4.0, 5.0, 6.0], slice.unwrap());
+    assert_eq!(vec![0.5, 1.0, 1.5], range.unwrap());
+    assert_eq!(io::ErrorKind::InvalidInput, outside.unwrap_err().kind());
+}
+
+#[test]
+fn test_maps_with_several_fields_are_read_as_the_selected_field_or_their_sum() {
+    let get_field = |data| DensMap3D {
+        data,
+        ..DensMap3D::from_2d(get_test_densmap())
+    };
+
+    let maps = FieldMaps::new(vec![
+        ("water".to_string(), get_field(vec![1.0, 2.0, 3.0])),
+        ("surfactant".to_string(), get_field(vec![0.5, 0.5, 0.0])),
+    ])
+    .unwrap();
+    let metadata = Metadata::new();
+
+    let mut bytes = Vec::new();
+    write_field_maps_to_writer(&mut bytes, &maps, 10.0, &metadata).unwrap();
+
+    assert_eq!(
+        (maps.clone(), 10.0, metadata.clone()),
+        read_field_maps_from_reader(&mut bytes.as_slice(), true).unwrap()
+    );
+
+    // A length of the field names beyond the end of the file is not allocated.
+    let mut corrupted = bytes.clone();
+    let names = (VERSIONED_HEADER_SIZE + LEGACY_HEADER_SIZE) as usize;
+    LittleEndian::write_u64(&mut corrupted[names..], u64::MAX);
+    let err = read_field_maps_from_reader(&mut corrupted.as_slice(), false).unwrap_err();
+    assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+
+    // Without fields or layers a map is written in the plain format.
+    let mut single = Vec::new();
+    let densmap = get_test_densmap();
+    let single_maps = FieldMaps::from_single(DensMap3D::from_2d(densmap.clone()));
+    write_field_maps_to_writer(&mut single, &single_maps, 10.0, &metadata).unwrap();
+
+    let mut plain = Vec::new();
+    write_densmap_to_writer(&mut plain, &densmap, 10.0).unwrap();
+    assert_eq!(plain, single);
+
+    let path = std::env::temp_dir().join("densmap_test_field_maps.dat");
+    write_field_maps_with_level(&path, &maps, 10.0, &metadata, 1).unwrap();
+
+    let read_field = |field: Option<&str>| {
+        let grid = GridOptions {
+            field: field.map(|name| name.to_string()),
+            ..GridOptions::default()
+        };
+        read_densmap_with_grid(&path, &grid).map(|(densmap, _)| densmap.data)
+    };
+
+    let surfactant = read_field(Some("surfactant"));
+    let total = read_field(None);
+    let missing = read_field(Some("substrate"));
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(vec![0.5, 0.5, 0.0], surfactant.unwrap());
+    assert_eq!(vec![1.5, 2.5, 3.0], total.unwrap());
+    assert_eq!(io::ErrorKind::InvalidInput, missing.unwrap_err().kind());
+
+    // The gzip stream is complete when the writing returns.
+    let path = std::env::temp_dir().join("densmap_test_field_maps.dat.gz");
+    write_field_maps_with_level(&path, &maps, 10.0, &metadata, 6).unwrap();
+    let compressed = read_densmap_with_grid(&path, &GridOptions::default());
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(vec![1.5, 2.5, 3.0], compressed.unwrap().0.data);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_densmaps_are_serialized_and_deserialized_with_serde() {
+    let densmap = get_test_densmap();
+
+    let json = serde_json::to_string(&densmap).unwrap();
+    assert_eq!(densmap, serde_json::from_str::<DensMap>(&json).unwrap());
+}
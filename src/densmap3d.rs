@@ -0,0 +1,196 @@
+use crate::densmap::{DensMap, Vec2, Vec3};
+
+#[derive(Clone, Debug, PartialEq)]
+/// Density map which is resolved along z into layers of the bin size along z.
+///
+/// The 2D analyses work on a single layer or on the density integrated over a range
+/// of layers, which are selected with `ZSelection`.
+pub struct DensMap3D {
+    /// Bin size in all directions.
+    pub bin_size: Vec3,
+    /// Origin of system along x, y and z.
+    pub origin: Vec3,
+    /// Shape of system along x, y and z.
+    pub shape: [u64; 3],
+    /// Center of fitted spherical cap along x and y.
+    pub center: Vec2,
+    /// Density map data as a 1D vector, in order of x changing every index, then y
+    /// and last z.
+    pub data: Vec<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Selection of the layers of a z-resolved map which are used as a 2D map.
+pub enum ZSelection {
+    /// Integrate the density over all layers.
+    #[default]
+    All,
+    /// Use the single layer of this index, counted from the bottom of the map.
+    Slice(usize),
+    /// Integrate the density over the layers with centers within this range along z.
+    Range(f64, f64),
+}
+
+impl DensMap3D {
+    /// Create a map with a single layer from a 2D map.
+    pub fn from_2d(densmap: DensMap) -> DensMap3D {
+        let [nx, ny] = densmap.shape;
+        let [x0, y0] = densmap.origin;
+
+        DensMap3D {
+            bin_size: densmap.bin_size,
+            origin: [x0, y0, 0.0],
+            shape: [nx, ny, 1],
+            center: densmap.center,
+            data: densmap.data,
+        }
+    }
+
+    /// Get the layer of an index as a 2D map.
+    ///
+    /// # Error
+    /// Returns an error if the index is outside of the map.
+    pub fn slice(&self, iz: usize) -> Result<DensMap, String> {
+        let [nx, ny, nz] = self.shape;
+
+        if iz as u64 >= nz {
+            return Err(format!(
+                "cannot select layer {} of a map with {} layers along z",
+                iz, nz
+            ));
+        }
+
+        let layer_size = (nx * ny) as usize;
+        let data = self.data[iz * layer_size..(iz + 1) * layer_size].to_vec();
+
+        Ok(self.get_2d_map(data))
+    }
+
+    /// Integrate the density along z over the layers with centers within the range.
+    ///
+    /// The layer densities are multiplied by the bin size along z, or summed if it is
+    /// not set.
+    ///
+    /// # Error
+    /// Returns an error if no layer lies within the range.
+    pub fn integrate(&self, z_begin: f64, z_end: f64) -> Result<DensMap, String> {
+        let [nx, ny, nz] = self.shape;
+        let dz = self.bin_size[2];
+        let weight = if dz > 0.0 { dz } else { 1.0 };
+
+        let layers = (0..nz as usize)
+            .filter(|&iz| {
+                let z = self.origin[2] + dz * (iz as f64 + 0.5);
+                z >= z_begin && z <= z_end
+            })
+            .collect::<Vec<_>>();
+
+        if layers.is_empty() {
+            return Err(format!(
+                "no layers of the map lie within z = {} to {}",
+                z_begin, z_end
+            ));
+        }
+
+        let layer_size = (nx * ny) as usize;
+        let mut data = vec![0.0; layer_size];
+
+        for iz in layers {
+            let layer = &self.data[iz * layer_size..(iz + 1) * layer_size];
+
+            for (v, &rho) in data.iter_mut().zip(layer) {
+                *v += weight * rho;
+            }
+        }
+
+        Ok(self.get_2d_map(data))
+    }
+
+    /// Convert the map into a 2D map of the selected layers.
+    ///
+    /// A map with a single layer is used as it is, regardless of the selection.
+    ///
+    /// # Error
+    /// Returns an error if no layer is selected.
+    pub fn into_2d(self, selection: ZSelection) -> Result<DensMap, String> {
+        let [nx, ny, nz] = self.shape;
+        let [x0, y0, _] = self.origin;
+
+        if nz == 1 {
+            return Ok(DensMap {
+                bin_size: self.bin_size,
+                origin: [x0, y0],
+                shape: [nx, ny],
+                center: self.center,
+                data: self.data,
+            });
+        }
+
+        self.select(selection)
+    }
+
+    /// Get the 2D map of the selected layers.
+    ///
+    /// # Error
+    /// Returns an error if no layer is selected.
+    pub fn select(&self, selection: ZSelection) -> Result<DensMap, String> {
+        match selection {
+            ZSelection::All => self.integrate(f64::NEG_INFINITY, f64::INFINITY),
+            ZSelection::Slice(iz) => self.slice(iz),
+            ZSelection::Range(z_begin, z_end) => self.integrate(z_begin, z_end),
+        }
+    }
+
+    fn get_2d_map(&self, data: Vec<f64>) -> DensMap {
+        let [nx, ny, _] = self.shape;
+        let [x0, y0, _] = self.origin;
+
+        DensMap {
+            bin_size: self.bin_size,
+            origin: [x0, y0],
+            shape: [nx, ny],
+            center: self.center,
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_densmap3d() -> DensMap3D {
+        DensMap3D {
+            bin_size: [1.0, 1.0, 0.5],
+            origin: [0.0, 0.0, 1.0],
+            shape: [2, 1, 3],
+            center: [1.0, 0.5],
+            data: vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+        }
+    }
+
+    #[test]
+    fn test_slices_of_layers_are_2d_maps() {
+        let densmap = get_densmap3d();
+
+        assert_eq!(vec![3.0, 4.0], densmap.slice(1).unwrap().data);
+        assert_eq!([2, 1], densmap.slice(1).unwrap().shape);
+        assert!(densmap.slice(3).is_err());
+    }
+
+    #[test]
+    fn test_integrating_over_layers_sums_their_density_times_bin_size() {
+        let densmap = get_densmap3d();
+
+        // Layer centers are at z = 1.25, 1.75 and 2.25.
+        assert_eq!(
+            vec![2.0, 3.0],
+            densmap.select(ZSelection::Range(1.0, 2.0)).unwrap().data
+        );
+        assert_eq!(
+            vec![4.5, 6.0],
+            densmap.select(ZSelection::All).unwrap().data
+        );
+        assert!(densmap.integrate(3.0, 4.0).is_err());
+    }
+}
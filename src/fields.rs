@@ -0,0 +1,162 @@
+use crate::densmap3d::DensMap3D;
+
+/// Name of the field of a map which holds a single density.
+pub const DEFAULT_FIELD: &str = "density";
+
+#[derive(Clone, Debug, PartialEq)]
+/// Density map with several named scalar fields per bin, like the densities of the
+/// different species of a mixture.
+///
+/// All fields share the grid of the map. A map of a single density has a single field
+/// named `DEFAULT_FIELD`.
+pub struct FieldMaps {
+    /// Names of the fields.
+    pub names: Vec<String>,
+    /// Maps of the fields, in the order of their names.
+    pub fields: Vec<DensMap3D>,
+}
+
+impl FieldMaps {
+    /// Create a map with the single field of a density map.
+    pub fn from_single(densmap: DensMap3D) -> FieldMaps {
+        FieldMaps {
+            names: vec![DEFAULT_FIELD.to_string()],
+            fields: vec![densmap],
+        }
+    }
+
+    /// Create a map from named fields.
+    ///
+    /// # Error
+    /// Returns an error if there are no fields, if a name is repeated or if the fields
+    /// do not share their grid.
+    pub fn new(fields: Vec<(String, DensMap3D)>) -> Result<FieldMaps, String> {
+        let (names, fields): (Vec<_>, Vec<_>) = fields.into_iter().unzip();
+
+        let first = fields
+            .first()
+            .ok_or_else(|| "a map must have at least one field".to_string())?;
+
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                return Err(format!("field '{}' is given more than once", name));
+            }
+        }
+
+        for (name, densmap) in names.iter().zip(&fields) {
+            if densmap.bin_size != first.bin_size
+                || densmap.origin != first.origin
+                || densmap.shape != first.shape
+                || densmap.center != first.center
+            {
+                return Err(format!(
+                    "field '{}' does not have the grid of field '{}'",
+                    name, names[0]
+                ));
+            }
+        }
+
+        Ok(FieldMaps { names, fields })
+    }
+
+    /// Whether the map only holds a single density of the default name.
+    pub fn is_single(&self) -> bool {
+        self.names.len() == 1 && self.names[0] == DEFAULT_FIELD
+    }
+
+    /// Get the field of a name.
+    ///
+    /// # Error
+    /// Returns an error which lists the fields of the map if it has no field of the name.
+    pub fn get(&self, name: &str) -> Result<&DensMap3D, String> {
+        self.position(name).map(|i| &self.fields[i])
+    }
+
+    /// Convert the map into the field of a name, or into the sum of all fields if no
+    /// name is given.
+    ///
+    /// # Error
+    /// Returns an error if the map has no field of the name.
+    pub fn into_field(mut self, name: Option<&str>) -> Result<DensMap3D, String> {
+        match name {
+            Some(name) => {
+                let i = self.position(name)?;
+                Ok(self.fields.swap_remove(i))
+            }
+            None => {
+                let mut fields = self.fields.into_iter();
+                let mut total = fields.next().unwrap();
+
+                for densmap in fields {
+                    for (v, rho) in total.data.iter_mut().zip(densmap.data) {
+                        *v += rho;
+                    }
+                }
+
+                Ok(total)
+            }
+        }
+    }
+
+    fn position(&self, name: &str) -> Result<usize, String> {
+        self.names.iter().position(|n| n == name).ok_or_else(|| {
+            format!(
+                "map has no field '{}', its fields are: {}",
+                name,
+                self.names.join(", ")
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_densmap3d(data: Vec<f64>) -> DensMap3D {
+        DensMap3D {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0, 0.0],
+            shape: [2, 1, 1],
+            center: [1.0, 0.5],
+            data,
+        }
+    }
+
+    #[test]
+    fn test_fields_are_selected_by_name_or_summed() {
+        let maps = FieldMaps::new(vec![
+            ("water".to_string(), get_densmap3d(vec![1.0, 2.0])),
+            ("surfactant".to_string(), get_densmap3d(vec![0.5, 0.0])),
+        ])
+        .unwrap();
+
+        assert!(!maps.is_single());
+        assert_eq!(vec![0.5, 0.0], maps.get("surfactant").unwrap().data);
+        assert!(maps.get("substrate").is_err());
+
+        assert_eq!(
+            vec![1.0, 2.0],
+            maps.clone().into_field(Some("water")).unwrap().data
+        );
+        assert_eq!(vec![1.5, 2.0], maps.into_field(None).unwrap().data);
+    }
+
+    #[test]
+    fn test_fields_must_share_grid_and_have_unique_names() {
+        let mut other = get_densmap3d(vec![1.0, 2.0]);
+        other.origin = [1.0, 0.0, 0.0];
+
+        assert!(FieldMaps::new(vec![]).is_err());
+        assert!(FieldMaps::new(vec![
+            ("water".to_string(), get_densmap3d(vec![1.0, 2.0])),
+            ("water".to_string(), get_densmap3d(vec![1.0, 2.0])),
+        ])
+        .is_err());
+        assert!(FieldMaps::new(vec![
+            ("water".to_string(), get_densmap3d(vec![1.0, 2.0])),
+            ("surfactant".to_string(), other),
+        ])
+        .is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    densmap::{coord2index, read_field_maps_with_grid, GridOptions, Shape, Vec2, Vec3},
+    densmap3d::{DensMap3D, ZSelection},
+    fields::FieldMaps,
+};
+
+use std::{io, path::Path};
+
+/// Names of the fields which hold the velocity along x and y in a flow map.
+pub const FLOW_FIELDS: [&str; 2] = ["vx", "vy"];
+
+#[derive(Clone, Debug, PartialEq)]
+/// Map of the flow field of a system, with the mean velocity along x and y in every bin.
+///
+/// Flow maps are stored as density maps with the fields `vx` and `vy`, see `FieldMaps`.
+pub struct FlowMap {
+    /// Bin size in all directions.
+    pub bin_size: Vec3,
+    /// Origin of system along x and y.
+    pub origin: Vec2,
+    /// Shape of system along x and y.
+    pub shape: Shape,
+    /// Velocity along x of every bin, in the order of the density map data.
+    pub vx: Vec<f64>,
+    /// Velocity along y of every bin, in the order of the density map data.
+    pub vy: Vec<f64>,
+}
+
+impl FlowMap {
+    /// Get the flow map of a map with the velocity fields.
+    ///
+    /// Velocities cannot be integrated along z, so the layer of z-resolved maps must
+    /// be selected as a slice.
+    ///
+    /// # Error
+    /// Returns an error if the map does not have both velocity fields or if the layer
+    /// of a z-resolved map is not selected as a slice.
+    pub fn from_fields(maps: &FieldMaps, selection: ZSelection) -> Result<FlowMap, String> {
+        let get_layer = |name| -> Result<DensMap3D, String> {
+            let field = maps.get(name)?;
+
+            match selection {
+                ZSelection::Slice(iz) => Ok(DensMap3D::from_2d(field.slice(iz)?)),
+                _ if field.shape[2] == 1 => Ok(field.clone()),
+                _ => Err(
+                    "the layer of a z-resolved flow map must be selected as a slice".to_string(),
+                ),
+            }
+        };
+
+        let [name_x, name_y] = FLOW_FIELDS;
+        let vx = get_layer(name_x)?;
+        let vy = get_layer(name_y)?;
+
+        Ok(FlowMap {
+            bin_size: vx.bin_size,
+            origin: [vx.origin[0], vx.origin[1]],
+            shape: [vx.shape[0], vx.shape[1]],
+            vx: vx.data,
+            vy: vy.data,
+        })
+    }
+
+    /// Get the velocity of the bin at an absolute coordinate of the system.
+    ///
+    /// Returns `None` if the coordinate lies outside of the map.
+    pub fn velocity_at(&self, x: f64, y: f64) -> Option<Vec2> {
+        let [x0, y0] = self.origin;
+
+        coord2index(x - x0, y - y0, self.bin_size, self.shape).map(|i| [self.vx[i], self.vy[i]])
+    }
+
+    /// Scale the lengths of the map, its grid and velocities, by a factor to convert
+    /// them to another unit, like `DensMap::scale_lengths`.
+    pub fn scale_lengths(self, factor: f64) -> FlowMap {
+        let [dx, dy, dz] = self.bin_size;
+        let [x0, y0] = self.origin;
+
+        FlowMap {
+            bin_size: [factor * dx, factor * dy, factor * dz],
+            origin: [factor * x0, factor * y0],
+            vx: self.vx.into_iter().map(|v| factor * v).collect(),
+            vy: self.vy.into_iter().map(|v| factor * v).collect(),
+            ..self
+        }
+    }
+}
+
+/// Read a flow map, see `FlowMap`, from the input path, of the layers which are
+/// selected by the options.
+pub fn read_flow_map(path: &Path, grid: &GridOptions) -> Result<(FlowMap, f64), io::Error> {
+    let (maps, time, _) = read_field_maps_with_grid(path, grid)?;
+    let flow = FlowMap::from_fields(&maps, grid.z)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    Ok((flow, time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_field(data: Vec<f64>) -> DensMap3D {
+        DensMap3D {
+            bin_size: [1.0, 1.0, 1.0],
+            origin: [1.0, 0.0, 0.0],
+            shape: [2, 1, 2],
+            center: [2.0, 0.5],
+            data,
+        }
+    }
+
+    #[test]
+    fn test_flow_map_is_read_from_velocity_fields_with_a_slice_of_layers() {
+        let maps = FieldMaps::new(vec![
+            ("vx".to_string(), get_field(vec![1.0, 2.0, 3.0, 4.0])),
+            ("vy".to_string(), get_field(vec![-1.0, -2.0, -3.0, -4.0])),
+        ])
+        .unwrap();
+
+        let flow = FlowMap::from_fields(&maps, ZSelection::Slice(1)).unwrap();
+        assert_eq!([2, 1], flow.shape);
+        assert_eq!(Some([3.0, -3.0]), flow.velocity_at(1.5, 0.5));
+        assert_eq!(Some([4.0, -4.0]), flow.velocity_at(2.5, 0.5));
+        assert_eq!(None, flow.velocity_at(0.5, 0.5));
+
+        assert!(FlowMap::from_fields(&maps, ZSelection::All).is_err());
+
+        let density = FieldMaps::from_single(get_field(vec![0.0; 4]));
+        assert!(FlowMap::from_fields(&density, ZSelection::Slice(0)).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Index of the files which are found for a series of density maps, which is kept next
+//! to them to not walk and match large directories on every run.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// First line of index files, which is changed along with their format.
+const INDEX_HEADER: &str = "# densmap frame index 1";
+
+/// Directories which were modified more recently than this are not indexed, since
+/// their modification times may not change for files which are added within the
+/// resolution of the file system.
+pub const MTIME_MARGIN: Duration = Duration::from_secs(2);
+
+/// Get the path of the index of the series with a base file name in a directory.
+pub fn get_index_path(dir: &Path, base: &str) -> PathBuf {
+    dir.join(format!(".{}.densmap-index", base))
+}
+
+/// Get the modification time of a directory, since the Unix epoch.
+pub fn get_mtime(dir: &Path) -> Result<Duration, io::Error> {
+    fs::metadata(dir)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| io::Error::other(err.to_string()))
+}
+
+/// Get whether modification times are old enough to be indexed, see `MTIME_MARGIN`.
+pub fn is_settled(mtimes: &[Duration]) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    mtimes.iter().all(|&mtime| mtime + MTIME_MARGIN < now)
+}
+
+/// Read the indexed files of a series in a directory, with the times of their file names.
+///
+/// The key identifies how the files were found, like their regular expression. Returns
+/// `None` if the index cannot be read, has another key or if any of its directories
+/// has been modified since it was written, in which case the files are found again.
+pub fn read_index(path: &Path, key: &str, dir: &Path) -> Option<Vec<(f64, PathBuf)>> {
+    let mut lines = BufReader::new(File::open(path).ok()?).lines();
+
+    if lines.next()?.ok()? != INDEX_HEADER || lines.next()?.ok()? != format!("key\t{}", key) {
+        return None;
+    }
+
+    let mut files = Vec::new();
+
+    for line in lines {
+        let line = line.ok()?;
+        let mut fields = line.splitn(3, '\t');
+
+        match (fields.next()?, fields.next()?, fields.next()?) {
+            ("dir", mtime, name) => {
+                let (secs, nanos) = mtime.split_once('.')?;
+                let mtime = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+
+                if get_mtime(&dir.join(name)).ok()? != mtime {
+                    return None;
+                }
+            }
+            ("file", time, name) => files.push((time.parse().ok()?, dir.join(name))),
+            _ => return None,
+        }
+    }
+
+    Some(files)
+}
+
+/// Write the index of the files of a series which were found in directories.
+///
+/// The directories and files are relative to the directory of the series. Paths which
+/// are not valid UTF-8 or have line breaks are not indexed and return an error.
+pub fn write_index(
+    path: &Path,
+    key: &str,
+    dirs: &[(PathBuf, Duration)],
+    files: &[(f64, PathBuf)],
+) -> Result<(), io::Error> {
+    let get_name = |path: &Path| {
+        path.to_str()
+            .filter(|name| !name.contains('\n'))
+            .map(|name| name.to_string())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("cannot index the path '{}'", path.display()),
+                )
+            })
+    };
+
+    let mut lines = vec![INDEX_HEADER.to_string(), format!("key\t{}", key)];
+
+    for (dir, mtime) in dirs {
+        lines.push(format!(
+            "dir\t{}.{:09}\t{}",
+            mtime.as_secs(),
+            mtime.subsec_nanos(),
+            get_name(dir)?
+        ));
+    }
+
+    for (time, file) in files {
+        lines.push(format!("file\t{}\t{}", time, get_name(file)?));
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    for line in lines {
+        writeln!(writer, "{}", line)?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_is_read_back_for_same_key_and_unchanged_dirs() {
+        let dir = std::env::temp_dir().join("densmap_test_frame_index");
+        fs::create_dir_all(dir.join("part1")).unwrap();
+
+        // The index is created before the modification times are kept, like when
+        // indexing a series.
+        let path = get_index_path(&dir, "map");
+        File::create(&path).unwrap();
+
+        let dirs = [PathBuf::from(""), PathBuf::from("part1")]
+            .iter()
+            .map(|name| (name.clone(), get_mtime(&dir.join(name)).unwrap()))
+            .collect::<Vec<_>>();
+        let files = vec![
+            (0.1, PathBuf::from("map00000.100ps.dat")),
+            (10.0, PathBuf::from("part1/map00010.000ps.dat")),
+        ];
+
+        write_index(&path, "map(.*)ps", &dirs, &files).unwrap();
+
+        let indexed = read_index(&path, "map(.*)ps", &dir);
+        let other_key = read_index(&path, "map(.*)ns", &dir);
+
+        // Removing an indexed directory changes the directory of the series too.
+        fs::remove_dir(dir.join("part1")).unwrap();
+        let removed = read_index(&path, "map(.*)ps", &dir);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            Some(vec![
+                (0.1, dir.join("map00000.100ps.dat")),
+                (10.0, dir.join("part1/map00010.000ps.dat")),
+            ]),
+            indexed
+        );
+        assert_eq!(None, other_key);
+        assert_eq!(None, removed);
+
+        assert!(write_index(&path, "", &[], &[(0.0, PathBuf::from("a\nb"))]).is_err());
+    }
+}
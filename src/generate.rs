@@ -0,0 +1,412 @@
+use crate::densmap::{DensMap, Shape, Vec2, Vec3};
+
+use rand::{
+    distributions::{IndependentSample, Normal},
+    Rng, SeedableRng, StdRng,
+};
+
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Idealized droplet on a substrate, as seen in the density map of the layer closest
+/// to the substrate.
+///
+/// Only this first layer is modelled: it is a disc of the base radius R, around which
+/// the density falls to the background with the profile
+/// `rho_bg + (rho - rho_bg) (1 - tanh((r - R) / w)) / 2`, where w is the interface
+/// width. A width of zero gives a sharp interface. The height of the droplet above the
+/// layer is not modelled, so the map is not that of a spherical cap projected onto the
+/// substrate, which would fall off with the cap height `sqrt(Rs^2 - r^2) - (Rs - h0)`
+/// towards the contact line.
+pub struct DropletModel {
+    /// Base radius of the droplet.
+    pub radius: f64,
+    /// Width of the liquid-vapor interface.
+    pub interface_width: f64,
+    /// Density inside of the droplet.
+    pub density: f64,
+    /// Density of the vapor around the droplet.
+    pub background: f64,
+    /// Standard deviation of the Gaussian noise added to every bin.
+    pub noise: f64,
+}
+
+impl DropletModel {
+    /// Get the density without noise at a distance from the droplet center.
+    pub fn density_at(&self, r: f64) -> f64 {
+        let liquid_fraction = if self.interface_width > 0.0 {
+            0.5 * (1.0 - ((r - self.radius) / self.interface_width).tanh())
+        } else if r <= self.radius {
+            1.0
+        } else {
+            0.0
+        };
+
+        self.background + (self.density - self.background) * liquid_fraction
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Evolution of the droplet radius with time.
+pub enum RadiusSeries {
+    /// The radius does not change.
+    Constant,
+    /// The droplet spreads according to Tanner's law, R(t) = R0 (1 + t / tau)^(1/10).
+    Spreading { tau: f64 },
+    /// The radius oscillates around its initial value, R(t) = R0 (1 + A sin(2 pi t / T))
+    /// with the relative amplitude A and period T.
+    Oscillating { amplitude: f64, period: f64 },
+}
+
+impl RadiusSeries {
+    /// Get the radius at a time, for a droplet with the initial radius at time 0.
+    pub fn radius_at(&self, radius: f64, time: f64) -> f64 {
+        match *self {
+            RadiusSeries::Constant => radius,
+            RadiusSeries::Spreading { tau } => radius * (1.0 + time / tau).powf(0.1),
+            RadiusSeries::Oscillating { amplitude, period } => {
+                radius * (1.0 + amplitude * (2.0 * PI * time / period).sin())
+            }
+        }
+    }
+}
+
+/// Generate the density map of a droplet around the center on a grid.
+///
+/// The density of every bin is taken at its center. If the noise of the model is
+/// positive, Gaussian noise is added to the bins. The noise is not cut off at zero,
+/// which would bias the mean density of bins close to it upwards, so bins of a low
+/// density can be negative like after subtracting the background of a map. The center
+/// is stored as the center of the map.
+pub fn generate_droplet<R: Rng>(
+    model: &DropletModel,
+    bin_size: Vec3,
+    origin: Vec2,
+    shape: Shape,
+    center: Vec2,
+    rng: &mut R,
+) -> DensMap {
+    let [dx, dy, _] = bin_size;
+    let [x0, y0] = origin;
+    let [xc, yc] = center;
+    let [nx, ny] = shape;
+
+    let noise = if model.noise > 0.0 {
+        Some(Normal::new(0.0, model.noise))
+    } else {
+        None
+    };
+
+    let data = (0..ny)
+        .flat_map(|iy| (0..nx).map(move |ix| (ix, iy)))
+        .map(|(ix, iy)| {
+            let x = x0 + dx * (ix as f64 + 0.5);
+            let y = y0 + dy * (iy as f64 + 0.5);
+            let r = ((x - xc).powi(2) + (y - yc).powi(2)).sqrt();
+
+            let density = model.density_at(r);
+
+            match noise {
+                Some(normal) => density + normal.ind_sample(rng),
+                None => density,
+            }
+        })
+        .collect();
+
+    DensMap {
+        bin_size,
+        origin,
+        shape,
+        center,
+        data,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Builder of density maps on a grid, to construct maps programmatically.
+///
+/// The default grid is that of the maps of the `generate` command: 80 x 80 bins of size
+/// 0.1 from the origin, with the center in the middle of the map.
+pub struct DensMapBuilder {
+    bin_size: Vec3,
+    origin: Vec2,
+    shape: Shape,
+    center: Option<Vec2>,
+    seed: usize,
+}
+
+impl Default for DensMapBuilder {
+    fn default() -> Self {
+        DensMapBuilder {
+            bin_size: [0.1, 0.1, 0.0],
+            origin: [0.0, 0.0],
+            shape: [80, 80],
+            center: None,
+            seed: 0,
+        }
+    }
+}
+
+impl DensMapBuilder {
+    /// Create a builder with the default grid.
+    pub fn new() -> Self {
+        DensMapBuilder::default()
+    }
+
+    /// Set the bin size in all directions.
+    pub fn bin_size(mut self, bin_size: Vec3) -> Self {
+        self.bin_size = bin_size;
+        self
+    }
+
+    /// Set the origin along x and y.
+    pub fn origin(mut self, origin: Vec2) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Set the number of bins along x and y.
+    pub fn shape(mut self, shape: Shape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Set the center of the map, which is otherwise in the middle of the grid.
+    pub fn center(mut self, center: Vec2) -> Self {
+        self.center = Some(center);
+        self
+    }
+
+    /// Set the seed of the noise of generated droplets.
+    pub fn seed(mut self, seed: usize) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Build a map with the density of every bin given by a function of the absolute
+    /// coordinate of its center.
+    pub fn from_fn<F: FnMut(f64, f64) -> f64>(&self, mut f: F) -> DensMap {
+        let [dx, dy, _] = self.bin_size;
+        let [x0, y0] = self.origin;
+        let [nx, ny] = self.shape;
+
+        let data = (0..ny)
+            .flat_map(|iy| (0..nx).map(move |ix| (ix, iy)))
+            .map(|(ix, iy)| f(x0 + dx * (ix as f64 + 0.5), y0 + dy * (iy as f64 + 0.5)))
+            .collect();
+
+        self.with_data(data)
+    }
+
+    /// Build a map with the same density in every bin.
+    pub fn uniform(&self, density: f64) -> DensMap {
+        self.from_fn(|_, _| density)
+    }
+
+    /// Build a map with the given data, in order of x changing every index.
+    ///
+    /// # Error
+    /// Returns an error if the length of the data does not match the shape.
+    pub fn from_data(&self, data: Vec<f64>) -> Result<DensMap, String> {
+        let [nx, ny] = self.shape;
+
+        if data.len() as u64 != nx * ny {
+            return Err(format!(
+                "cannot build a map of shape {}x{} from {} values",
+                nx,
+                ny,
+                data.len()
+            ));
+        }
+
+        Ok(self.with_data(data))
+    }
+
+    /// Build the map of a droplet around the center, see `generate_droplet`.
+    ///
+    /// The noise of the model is seeded with the seed of the builder, so the same
+    /// builder always gives the same map.
+    pub fn droplet(&self, model: &DropletModel) -> DensMap {
+        let mut rng = StdRng::from_seed(&[self.seed][..]);
+
+        generate_droplet(
+            model,
+            self.bin_size,
+            self.origin,
+            self.shape,
+            self.get_center(),
+            &mut rng,
+        )
+    }
+
+    /// Build the map of a droplet of unit density in vacuum, which is a disc of the
+    /// radius in the layer closest to the substrate, see `DropletModel`.
+    pub fn disc(&self, radius: f64, interface_width: f64, noise: f64) -> DensMap {
+        self.droplet(&DropletModel {
+            radius,
+            interface_width,
+            density: 1.0,
+            background: 0.0,
+            noise,
+        })
+    }
+
+    fn get_center(&self) -> Vec2 {
+        let [dx, dy, _] = self.bin_size;
+        let [x0, y0] = self.origin;
+        let [nx, ny] = self.shape;
+
+        self.center
+            .unwrap_or([x0 + 0.5 * dx * nx as f64, y0 + 0.5 * dy * ny as f64])
+    }
+
+    fn with_data(&self, data: Vec<f64>) -> DensMap {
+        DensMap {
+            bin_size: self.bin_size,
+            origin: self.origin,
+            shape: self.shape,
+            center: self.get_center(),
+            data,
+        }
+    }
+}
+
+impl DensMap {
+    /// Create the map of a droplet of unit density in vacuum on the default grid of
+    /// `DensMapBuilder`, see `DensMapBuilder::disc`.
+    pub fn disc(radius: f64, interface_width: f64, noise: f64) -> DensMap {
+        DensMapBuilder::new().disc(radius, interface_width, noise)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_model(noise: f64) -> DropletModel {
+        DropletModel {
+            radius: 2.0,
+            interface_width: 0.5,
+            density: 10.0,
+            background: 1.0,
+            noise,
+        }
+    }
+
+    #[test]
+    fn test_droplet_density_is_halfway_to_background_at_the_radius() {
+        let model = get_model(0.0);
+
+        assert!((model.density_at(0.0) - 10.0).abs() < 1e-2);
+        assert_eq!(5.5, model.density_at(2.0));
+        assert!((model.density_at(10.0) - 1.0).abs() < 1e-6);
+
+        let sharp = DropletModel {
+            interface_width: 0.0,
+            ..model
+        };
+        assert_eq!(10.0, sharp.density_at(2.0));
+        assert_eq!(1.0, sharp.density_at(2.01));
+    }
+
+    #[test]
+    fn test_generated_droplet_is_centered_on_the_given_point() {
+        let mut rng = StdRng::from_seed(&[1][..]);
+        let densmap = generate_droplet(
+            &get_model(0.0),
+            [1.0, 1.0, 0.0],
+            [0.0, 0.0],
+            [10, 6],
+            [3.5, 2.5],
+            &mut rng,
+        );
+
+        assert_eq!(60, densmap.data.len());
+        assert_eq!([3.5, 2.5], densmap.center);
+
+        let max = densmap.data.iter().cloned().fold(0.0, f64::max);
+        assert_eq!(max, densmap.data[3 + 2 * 10]);
+        assert_eq!(densmap.data[2 + 2 * 10], densmap.data[4 + 2 * 10]);
+    }
+
+    #[test]
+    fn test_noise_is_reproducible_with_a_seed_and_unbiased() {
+        let model = DropletModel {
+            background: 0.0,
+            ..get_model(2.0)
+        };
+
+        let generate = |seed| {
+            let mut rng = StdRng::from_seed(&[seed][..]);
+            generate_droplet(
+                &model,
+                [1.0, 1.0, 0.0],
+                [0.0, 0.0],
+                [8, 8],
+                [4.0, 4.0],
+                &mut rng,
+            )
+        };
+
+        assert_eq!(generate(1), generate(1));
+        assert_ne!(generate(1), generate(2));
+
+        // The noise is not cut off at zero, so the mean density of the vapor in the
+        // corners of the maps stays close to zero.
+        let corners = (0..100)
+            .flat_map(|seed| {
+                let densmap = generate(seed);
+                vec![
+                    densmap.data[0],
+                    densmap.data[7],
+                    densmap.data[56],
+                    densmap.data[63],
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        assert!(corners.iter().any(|&v| v < 0.0));
+        let mean = corners.iter().sum::<f64>() / corners.len() as f64;
+        assert!(mean.abs() < 0.5, "{}", mean);
+    }
+
+    #[test]
+    fn test_builder_constructs_maps_on_its_grid() {
+        let builder = DensMapBuilder::new()
+            .bin_size([0.5, 1.0, 0.0])
+            .origin([1.0, 0.0])
+            .shape([4, 2]);
+
+        let densmap = builder.from_fn(|x, y| x + 10.0 * y);
+        assert_eq!([2.0, 1.0], densmap.center);
+        assert_eq!(
+            vec![6.25, 6.75, 7.25, 7.75, 16.25, 16.75, 17.25, 17.75],
+            densmap.data
+        );
+
+        assert_eq!(vec![2.0; 8], builder.uniform(2.0).data);
+        assert!(builder.from_data(vec![0.0; 8]).is_ok());
+        assert!(builder.from_data(vec![0.0; 7]).is_err());
+
+        let droplet = DensMap::disc(2.0, 0.2, 0.1);
+        assert_eq!([80, 80], droplet.shape);
+        assert_eq!([4.0, 4.0], droplet.center);
+        assert_eq!(droplet, DensMap::disc(2.0, 0.2, 0.1));
+    }
+
+    #[test]
+    fn test_radius_series() {
+        assert_eq!(2.0, RadiusSeries::Constant.radius_at(2.0, 100.0));
+
+        let spreading = RadiusSeries::Spreading { tau: 10.0 };
+        assert_eq!(2.0, spreading.radius_at(2.0, 0.0));
+        assert!(spreading.radius_at(2.0, 100.0) > spreading.radius_at(2.0, 10.0));
+
+        let oscillating = RadiusSeries::Oscillating {
+            amplitude: 0.1,
+            period: 40.0,
+        };
+        assert!((oscillating.radius_at(2.0, 10.0) - 2.2).abs() < 1e-9);
+        assert!((oscillating.radius_at(2.0, 40.0) - 2.0).abs() < 1e-9);
+    }
+}
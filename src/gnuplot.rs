@@ -0,0 +1,239 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// How the data is drawn in a plot.
+pub enum PlotStyle {
+    Lines,
+    Points,
+    /// Gridded data with the value in the third column, as written by `write_grid`.
+    Image,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Script which plots a data file with gnuplot.
+///
+/// The script is written next to the data file and renders the plot to a PNG image
+/// of the same name, so that running `gnuplot radius.gp` creates `radius.png`.
+pub struct GnuplotScript {
+    pub title: String,
+    pub xlabel: String,
+    pub ylabel: String,
+    /// Columns of the x and y values, counted from 1.
+    pub columns: [usize; 2],
+    pub style: PlotStyle,
+}
+
+impl GnuplotScript {
+    /// Create a script which plots the first two columns with lines.
+    pub fn new(title: &str, xlabel: &str, ylabel: &str) -> Self {
+        GnuplotScript {
+            title: title.to_string(),
+            xlabel: xlabel.to_string(),
+            ylabel: ylabel.to_string(),
+            columns: [1, 2],
+            style: PlotStyle::Lines,
+        }
+    }
+
+    pub fn with_columns(self, x: usize, y: usize) -> Self {
+        GnuplotScript {
+            columns: [x, y],
+            ..self
+        }
+    }
+
+    pub fn with_style(self, style: PlotStyle) -> Self {
+        GnuplotScript { style, ..self }
+    }
+
+    /// Write the script for a data file, returning the path of the script.
+    ///
+    /// The script is named as the data file with the extension `.gp`. The ranges
+    /// of the axes are set to those of the data in the file, which must have been
+    /// completely written. Files with the extension `.csv` are read as comma separated
+    /// with a header line and other files as white space separated, where lines
+    /// beginning with '#' or '@' are comments.
+    pub fn write(&self, data_path: &Path) -> Result<PathBuf, io::Error> {
+        let is_csv = data_path
+            .extension()
+            .map(|ext| ext == "csv")
+            .unwrap_or(false);
+        let ranges = get_column_ranges(data_path, self.columns, is_csv)?;
+
+        let script_path = data_path.with_extension("gp");
+        fs::write(&script_path, self.to_script(data_path, ranges, is_csv))?;
+
+        Ok(script_path)
+    }
+
+    fn to_script(&self, data_path: &Path, ranges: [Option<(f64, f64)>; 2], is_csv: bool) -> String {
+        let file_name = data_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let image_name = Path::new(&file_name).with_extension("png");
+
+        let mut script = String::new();
+
+        writeln!(script, "set terminal png size 800,600").unwrap();
+        writeln!(
+            script,
+            "set output '{}'",
+            quote(&image_name.to_string_lossy())
+        )
+        .unwrap();
+        writeln!(script, "set title '{}'", quote(&self.title)).unwrap();
+        writeln!(script, "set xlabel '{}'", quote(&self.xlabel)).unwrap();
+        writeln!(script, "set ylabel '{}'", quote(&self.ylabel)).unwrap();
+
+        if is_csv {
+            writeln!(script, "set datafile separator ','").unwrap();
+        }
+
+        for (axis, range) in ["x", "y"].iter().zip(ranges.iter()) {
+            if let Some((min, max)) = range {
+                // Lines and points are kept off the borders of the plot.
+                let pad = match self.style {
+                    PlotStyle::Image => 0.0,
+                    _ => 0.05 * (max - min),
+                };
+
+                writeln!(
+                    script,
+                    "set {}range [{}:{}]",
+                    axis,
+                    format_value(min - pad),
+                    format_value(max + pad)
+                )
+                .unwrap();
+            }
+        }
+
+        let [x, y] = self.columns;
+        let (using, style) = match self.style {
+            PlotStyle::Lines => (format!("{}:{}", x, y), "lines"),
+            PlotStyle::Points => (format!("{}:{}", x, y), "points pointtype 7 pointsize 0.5"),
+            PlotStyle::Image => (format!("{}:{}:3", x, y), "image"),
+        };
+
+        let header = if is_csv { "skip 1 " } else { "" };
+
+        writeln!(
+            script,
+            "plot '{}' {}using {} with {} notitle",
+            quote(&file_name),
+            header,
+            using,
+            style
+        )
+        .unwrap();
+
+        script
+    }
+}
+
+/// Format a value with a precision which is sufficient for the range of a plot.
+fn format_value(value: f64) -> String {
+    let formatted = format!("{:.6}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+
+    match trimmed {
+        "-0" => "0".to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+/// Escape a string for use within single quotes in gnuplot.
+fn quote(s: &str) -> String {
+    s.replace('\'', "''")
+}
+
+/// Get the ranges of the values in two columns of a data file, if it has any values.
+fn get_column_ranges(
+    path: &Path,
+    columns: [usize; 2],
+    is_csv: bool,
+) -> Result<[Option<(f64, f64)>; 2], io::Error> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut ranges = [None, None];
+
+    for line in reader.lines().skip(if is_csv { 1 } else { 0 }) {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with('@') {
+            continue;
+        }
+
+        let values = if is_csv {
+            line.split(',').collect::<Vec<_>>()
+        } else {
+            line.split_whitespace().collect::<Vec<_>>()
+        };
+
+        for (range, &column) in ranges.iter_mut().zip(columns.iter()) {
+            let value = values
+                .get(column.wrapping_sub(1))
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .filter(|value| value.is_finite());
+
+            if let Some(v) = value {
+                let (min, max) = range.unwrap_or((v, v));
+                *range = Some((min.min(v), max.max(v)));
+            }
+        }
+    }
+
+    // Empty ranges are left to gnuplot.
+    Ok([
+        ranges[0].filter(|(min, max)| max > min),
+        ranges[1].filter(|(min, max)| max > min),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_sets_labels_and_ranges_from_data() {
+        let dir = std::env::temp_dir().join("densmap_test_gnuplot");
+        fs::create_dir_all(&dir).unwrap();
+
+        let xvg = dir.join("radius.xvg");
+        fs::write(&xvg, "# time radius\n 0.0 1.0\n 10.0 3.0\n 20.0 2.0\n").unwrap();
+
+        let csv = dir.join("tidy.csv");
+        fs::write(&csv, "time,angle,radius\n0,0,2.0\n0,90,4.0\n10,0,2.5\n").unwrap();
+
+        let script_path = GnuplotScript::new("Radius", "t (ps)", "r (nm)")
+            .write(&xvg)
+            .unwrap();
+        let script = fs::read_to_string(&script_path).unwrap();
+
+        let csv_script = GnuplotScript::new("Tidy", "t (ps)", "r (nm)")
+            .with_columns(1, 3)
+            .with_style(PlotStyle::Points)
+            .write(&csv)
+            .and_then(fs::read_to_string);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(dir.join("radius.gp"), script_path);
+        assert!(script.contains("set output 'radius.png'"));
+        assert!(script.contains("set xlabel 't (ps)'"));
+        assert!(script.contains("set xrange [-1:21]"));
+        assert!(script.contains("set yrange [0.9:3.1]"));
+        assert!(script.contains("plot 'radius.xvg' using 1:2 with lines"));
+
+        let csv_script = csv_script.unwrap();
+        assert!(csv_script.contains("set datafile separator ','"));
+        assert!(csv_script.contains("set yrange [1.9:4.1]"));
+        assert!(csv_script.contains("plot 'tidy.csv' skip 1 using 1:3 with points"));
+    }
+}
@@ -1,16 +1,372 @@
 use std::{
-    fs::File,
-    io::{self, BufWriter, Write},
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
     path::Path,
 };
 
 /// Write input data as an .xvg formatted file.
+///
+/// Errors of the y values are written as a third column if the data has them.
 pub fn write_xvg<T: XYData>(path: &Path, data: &T) -> Result<(), io::Error> {
-    let fp = File::create(&path)?;
+    let fp = File::create(path)?;
+    write_xvg_rows(&mut BufWriter::new(fp), data)
+}
+
+/// Append data to an existing xvg file, or create it if it does not exist.
+pub fn append_xvg<T: XYData>(path: &Path, data: &T) -> Result<(), io::Error> {
+    let fp = OpenOptions::new().append(true).create(true).open(path)?;
+    write_xvg_rows(&mut BufWriter::new(fp), data)
+}
+
+/// Append data as a new data set of an xvg file, or create it if it does not exist.
+///
+/// Data sets are separated by lines with a single '&', which is how xmgrace reads
+/// several sets from one file. This lets the graphs of all frames be written to a single
+/// file, which xmgrace can animate.
+pub fn append_xvg_set<T: XYData>(path: &Path, data: &T) -> Result<(), io::Error> {
+    let fp = OpenOptions::new().append(true).create(true).open(path)?;
+    let has_data = fp.metadata()?.len() > 0;
+    let mut writer = BufWriter::new(fp);
+
+    if has_data {
+        writeln!(writer, "&")?;
+    }
+
+    write_xvg_rows(&mut writer, data)
+}
+
+/// Writer of an .xvg formatted file to which rows are added one at a time.
+///
+/// Every row is flushed to the file once it has been written, so that the rows of
+/// a long analysis are kept if it is stopped before finishing.
+pub struct XvgWriter {
+    writer: BufWriter<File>,
+}
+
+impl XvgWriter {
+    /// Create the file, truncating it if it exists.
+    pub fn create(path: &Path) -> Result<Self, io::Error> {
+        let fp = File::create(path)?;
+
+        Ok(XvgWriter {
+            writer: BufWriter::new(fp),
+        })
+    }
+
+    /// Open the file to append rows to it, or create it if it does not exist.
+    pub fn append(path: &Path) -> Result<Self, io::Error> {
+        let fp = OpenOptions::new().append(true).create(true).open(path)?;
+
+        Ok(XvgWriter {
+            writer: BufWriter::new(fp),
+        })
+    }
+
+    /// Write a row with an x value and the values of its columns.
+    pub fn write_row(&mut self, x: f64, values: &[f64]) -> Result<(), io::Error> {
+        let columns = values.iter().map(std::slice::from_ref).collect::<Vec<_>>();
+
+        write_xvg_column_rows(&mut self.writer, &[x], &columns)?;
+        self.writer.flush()
+    }
+}
+
+/// Read the first two columns of the first data set of an .xvg formatted file as a graph.
+///
+/// See `read_xvg_sets` for how the file is parsed.
+pub fn read_xvg(path: &Path) -> Result<Graph, io::Error> {
+    let data = read_xvg_columns(path)?;
+
+    let y = data
+        .columns
+        .into_iter()
+        .next()
+        .map(|(_, values)| values)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected at least two columns in '{}'", path.display()),
+            )
+        })?;
+
+    Ok(Graph::Carthesian {
+        x: data.x,
+        y,
+        yerr: None,
+    })
+}
+
+/// Read all columns of the first data set of an .xvg formatted file.
+///
+/// See `read_xvg_sets` for how the file is parsed.
+pub fn read_xvg_columns(path: &Path) -> Result<Columns, io::Error> {
+    read_xvg_sets(path)?.into_iter().next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no data in '{}'", path.display()),
+        )
+    })
+}
+
+/// Read all data sets of an .xvg formatted file, like those of `append_xvg_set`.
+///
+/// Data sets are separated by lines with a single '&'. Every row of a set must have
+/// the same number of columns, the first of which are the x values. Empty lines and
+/// lines beginning with '#' are skipped, as are those beginning with '@' except for
+/// the label of the x axis and the legends of the columns, as written by
+/// `write_xvg_columns`. Columns without a legend are named by their set in xmgrace,
+/// ie. 's0', 's1' and so on.
+pub fn read_xvg_sets(path: &Path) -> Result<Vec<Columns>, io::Error> {
+    let reader = BufReader::new(File::open(path)?);
+
+    let mut x_label = "x".to_string();
+    let mut legends = Vec::new();
+
+    let mut sets = Vec::new();
+    let mut rows: Vec<Vec<f64>> = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else if line == "&" {
+            sets.push(rows.split_off(0));
+        } else if let Some(directive) = line.strip_prefix('@') {
+            parse_xvg_directive(directive.trim(), &mut x_label, &mut legends);
+        } else {
+            let values = line
+                .split_whitespace()
+                .map(|value| value.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("could not parse line '{}': {}", line, err),
+                    )
+                })?;
+
+            if let Some(first) = rows.first() {
+                if first.len() != values.len() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "expected {} columns in line '{}' but found {}",
+                            first.len(),
+                            line,
+                            values.len()
+                        ),
+                    ));
+                }
+            }
+
+            rows.push(values);
+        }
+    }
+
+    if !rows.is_empty() {
+        sets.push(rows);
+    }
+
+    Ok(sets
+        .into_iter()
+        .map(|rows| {
+            let num_columns = rows.first().map(|row| row.len()).unwrap_or(1);
+
+            let mut data = Columns::new(&x_label, rows.iter().map(|row| row[0]).collect());
+
+            for i in 1..num_columns {
+                let name = legends
+                    .iter()
+                    .find(|(n, _)| *n == i - 1)
+                    .map(|(_, legend)| legend.clone())
+                    .unwrap_or_else(|| format!("s{}", i - 1));
+
+                data = data.with_column(&name, rows.iter().map(|row| row[i]).collect());
+            }
+
+            data
+        })
+        .collect())
+}
+
+/// Read the x axis label or a legend of a set from an xmgrace directive.
+fn parse_xvg_directive(directive: &str, x_label: &mut String, legends: &mut Vec<(usize, String)>) {
+    let get_quoted = |s: &str| {
+        let start = s.find('"')?;
+        let end = s.rfind('"')?;
+        if end > start {
+            Some(s[start + 1..end].to_string())
+        } else {
+            None
+        }
+    };
+
+    let mut words = directive.split_whitespace();
+
+    match (words.next(), words.next()) {
+        (Some("xaxis"), Some("label")) => {
+            if let Some(label) = get_quoted(directive) {
+                *x_label = label;
+            }
+        }
+        (Some(set), Some("legend")) => {
+            let index = set.strip_prefix('s').and_then(|i| i.parse::<usize>().ok());
+
+            if let (Some(index), Some(legend)) = (index, get_quoted(directive)) {
+                legends.push((index, legend));
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The errors of data which has them are written as a third column.
+fn write_xvg_rows<T: XYData, W: Write>(writer: &mut W, data: &T) -> Result<(), io::Error> {
+    match data.yerr() {
+        Some(yerr) => write_xvg_column_rows(writer, data.x(), &[data.y(), yerr]),
+        None => write_xvg_column_rows(writer, data.x(), &[data.y()]),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Data with values along x and any number of named columns of values for them.
+pub struct Columns {
+    pub x_label: String,
+    pub x: Vec<f64>,
+    pub columns: Vec<(String, Vec<f64>)>,
+}
+
+impl Columns {
+    pub fn new(x_label: &str, x: Vec<f64>) -> Self {
+        Columns {
+            x_label: x_label.to_string(),
+            x,
+            columns: Vec::new(),
+        }
+    }
+
+    /// Add a named column of values for the x values.
+    pub fn with_column(mut self, name: &str, values: Vec<f64>) -> Self {
+        self.columns.push((name.to_string(), values));
+        self
+    }
+}
+
+/// Write multi-column data as an .xvg formatted file.
+///
+/// The file begins with a header which labels the x axis and sets the name of every
+/// column as the legend of its data set, using the directives of xmgrace. Columns which
+/// are shorter than the x values truncate the rows.
+pub fn write_xvg_columns(path: &Path, data: &Columns) -> Result<(), io::Error> {
+    let fp = File::create(path)?;
+    let mut writer = BufWriter::new(fp);
+
+    writeln!(writer, "@    xaxis  label \"{}\"", data.x_label)?;
+    writeln!(writer, "@TYPE xy")?;
+    for (i, (name, _)) in data.columns.iter().enumerate() {
+        writeln!(writer, "@ s{} legend \"{}\"", i, name)?;
+    }
+
+    write_columns_rows(&mut writer, data)
+}
+
+/// Append multi-column data to an existing xvg file, or create it without a header
+/// if it does not exist.
+pub fn append_xvg_columns(path: &Path, data: &Columns) -> Result<(), io::Error> {
+    let fp = OpenOptions::new().append(true).create(true).open(path)?;
+    write_columns_rows(&mut BufWriter::new(fp), data)
+}
+
+fn write_columns_rows<W: Write>(writer: &mut W, data: &Columns) -> Result<(), io::Error> {
+    let columns = data
+        .columns
+        .iter()
+        .map(|(_, values)| values.as_slice())
+        .collect::<Vec<_>>();
+
+    write_xvg_column_rows(writer, &data.x, &columns)
+}
+
+fn write_xvg_column_rows<W: Write>(
+    writer: &mut W,
+    x: &[f64],
+    columns: &[&[f64]],
+) -> Result<(), io::Error> {
+    let num_rows = columns
+        .iter()
+        .map(|values| values.len())
+        .fold(x.len(), usize::min);
+
+    for (i, x) in x.iter().enumerate().take(num_rows) {
+        write!(writer, "{:12.5}", x)?;
+
+        for values in columns {
+            write!(writer, " {:12.5}", values[i])?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Write the contact lines of a full trajectory as a single long-format CSV file.
+///
+/// Every row holds the values for one angle of one frame, with columns for the time,
+/// the angle, the absolute radius, the radius relative to the droplet radius and the
+/// local velocity of the contact line. The contact lines should contain the relative
+/// radius at every angle and the velocities should be given for every angle of every frame.
+pub fn write_contact_line_csv<T: XYData>(
+    path: &Path,
+    times: &[f64],
+    droplet_radius: &[f64],
+    contact_lines: &[T],
+    velocities: &[Vec<f64>],
+) -> Result<(), io::Error> {
+    let fp = File::create(path)?;
     let mut writer = BufWriter::new(fp);
 
-    for (x, y) in data.x().iter().zip(data.y().iter()) {
-        write!(writer, "{:12.5} {:12.5}\n", x, y)?;
+    writeln!(writer, "time,angle,radius,delta_r,local_velocity")?;
+
+    for (((t, r0), contact_line), velocity) in times
+        .iter()
+        .zip(droplet_radius.iter())
+        .zip(contact_lines.iter())
+        .zip(velocities.iter())
+    {
+        for ((angle, dr), v) in contact_line
+            .x()
+            .iter()
+            .zip(contact_line.y().iter())
+            .zip(velocity.iter())
+        {
+            writeln!(writer, "{},{},{},{},{}", t, angle, r0 + dr, dr, v)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write gridded data z(x, y) as a text file with x, y and z columns.
+///
+/// The values are given for every y (outer) and x (inner). Blocks of constant y are
+/// separated by empty lines, which is the format used by eg. gnuplot for surface plots.
+pub fn write_grid(
+    path: &Path,
+    xs: &[f64],
+    ys: &[f64],
+    values: &[Vec<f64>],
+) -> Result<(), io::Error> {
+    let fp = File::create(path)?;
+    let mut writer = BufWriter::new(fp);
+
+    for (y, row) in ys.iter().zip(values.iter()) {
+        for (x, z) in xs.iter().zip(row.iter()) {
+            writeln!(writer, "{:12.5} {:12.5} {:12.5}", x, y, z)?;
+        }
+        writeln!(writer)?;
     }
 
     Ok(())
@@ -25,20 +381,28 @@ pub trait XYData: PartialEq {
     fn resample(&self, xs: &[f64]) -> Self;
     fn x(&self) -> &[f64];
     fn y(&self) -> &[f64];
+    /// Errors of the y values, if they are known.
+    fn yerr(&self) -> Option<&[f64]>;
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Histogram data with bin center points and values.
 pub struct Histogram {
     pub x: Vec<f64>,
     pub y: Vec<f64>,
+    /// Errors of the values, eg. the standard error of averaged values.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub yerr: Option<Vec<f64>>,
 }
 
+/// Errors are resampled by linear interpolation along with the values.
 impl XYData for Histogram {
     fn resample(&self, xs: &[f64]) -> Self {
         Histogram {
             x: xs.to_vec(),
-            y: interpolate_data(&self.x, &self.y, &xs),
+            y: interpolate_data(&self.x, &self.y, xs),
+            yerr: resample_errors(&self.x, self.yerr.as_deref(), xs),
         }
     }
 
@@ -49,57 +413,83 @@ impl XYData for Histogram {
     fn y(&self) -> &[f64] {
         &self.y
     }
+
+    fn yerr(&self) -> Option<&[f64]> {
+        self.yerr.as_deref()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Two dimensional graph types.
+///
+/// Both types have optional errors of their y values or radii.
 pub enum Graph {
     Carthesian {
         x: Vec<f64>,
         y: Vec<f64>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        yerr: Option<Vec<f64>>,
     },
     /// Polar coordinates have angles in degrees.
     Polar {
         angles: Vec<f64>,
         radius: Vec<f64>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        yerr: Option<Vec<f64>>,
     },
 }
 
 /// For carthesian coordinates, x and y values correspond directly to the variables.
 /// For polar coordinates the angles become the x values and the radius the y values.
+/// Errors are resampled by linear interpolation along with the values.
 impl XYData for Graph {
     fn resample(&self, xs: &[f64]) -> Self {
         match self {
-            Graph::Carthesian { x, y } => Graph::Carthesian {
+            Graph::Carthesian { x, y, yerr } => Graph::Carthesian {
                 x: xs.to_vec(),
-                y: interpolate_data(&x, &y, &xs),
+                y: interpolate_data(x, y, xs),
+                yerr: resample_errors(x, yerr.as_deref(), xs),
             },
-            Graph::Polar { angles, radius } => Graph::Polar {
+            Graph::Polar {
+                angles,
+                radius,
+                yerr,
+            } => Graph::Polar {
                 angles: xs.to_vec(),
-                radius: interpolate_data(&angles, &radius, &xs),
+                radius: interpolate_data(angles, radius, xs),
+                yerr: resample_errors(angles, yerr.as_deref(), xs),
             },
         }
     }
 
     fn x(&self) -> &[f64] {
         match self {
-            Graph::Carthesian { x, y: _ } => &x,
-            Graph::Polar { angles, radius: _ } => &angles,
+            Graph::Carthesian { x, .. } => x,
+            Graph::Polar { angles, .. } => angles,
         }
     }
 
     fn y(&self) -> &[f64] {
         match self {
-            Graph::Carthesian { x: _, y } => &y,
-            Graph::Polar { angles: _, radius } => &radius,
+            Graph::Carthesian { y, .. } => y,
+            Graph::Polar { radius, .. } => radius,
+        }
+    }
+
+    fn yerr(&self) -> Option<&[f64]> {
+        match self {
+            Graph::Carthesian { yerr, .. } | Graph::Polar { yerr, .. } => yerr.as_deref(),
         }
     }
 }
 
+/// Errors are not kept when converting between the coordinate types, since they
+/// do not apply to the new values.
 impl Graph {
     pub fn to_carthesian(&self) -> Self {
         match self {
-            Graph::Polar { angles, radius } => {
+            Graph::Polar { angles, radius, .. } => {
                 let mut x = Vec::with_capacity(angles.len());
                 let mut y = Vec::with_capacity(angles.len());
 
@@ -109,15 +499,15 @@ impl Graph {
                     y.push(r * dy);
                 });
 
-                Graph::Carthesian { x, y }
+                Graph::Carthesian { x, y, yerr: None }
             }
-            Graph::Carthesian { x: _, y: _ } => self.clone(),
+            Graph::Carthesian { .. } => self.clone(),
         }
     }
 
     pub fn to_polar(&self) -> Self {
         match self {
-            Graph::Carthesian { x: xs, y: ys } => {
+            Graph::Carthesian { x: xs, y: ys, .. } => {
                 let mut angles = Vec::with_capacity(xs.len());
                 let mut radius = Vec::with_capacity(xs.len());
 
@@ -126,16 +516,21 @@ impl Graph {
                     radius.push((x.powi(2) + y.powi(2)).sqrt());
                 });
 
-                Graph::Polar { angles, radius }
+                Graph::Polar {
+                    angles,
+                    radius,
+                    yerr: None,
+                }
             }
-            Graph::Polar {
-                angles: _,
-                radius: _,
-            } => self.clone(),
+            Graph::Polar { .. } => self.clone(),
         }
     }
 }
 
+fn resample_errors(from_xs: &[f64], yerr: Option<&[f64]>, onto_xs: &[f64]) -> Option<Vec<f64>> {
+    yerr.map(|yerr| interpolate_data(from_xs, yerr, onto_xs))
+}
+
 /// Resample data from a set of input x values onto another using linear interpolation.
 ///
 /// # Notes
@@ -188,3 +583,170 @@ fn test_interpolating_values_outside_of_initial_range_is_linear() {
 
     assert_eq!(vec![7.0, -3.0], interpolate_data(&from_xs, &ys, &onto_xs));
 }
+
+#[test]
+fn test_xvg_is_read_back_after_it_is_written_and_appended() {
+    let path = std::env::temp_dir().join("densmap_test_read_xvg.xvg");
+
+    let first = Graph::Carthesian {
+        x: vec![0.0, 1.0],
+        y: vec![2.5, 3.5],
+        yerr: None,
+    };
+    let second = Graph::Carthesian {
+        x: vec![2.0],
+        y: vec![-1.0],
+        yerr: None,
+    };
+
+    write_xvg(&path, &first).unwrap();
+    append_xvg(&path, &second).unwrap();
+    let graph = read_xvg(&path);
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        Graph::Carthesian {
+            x: vec![0.0, 1.0, 2.0],
+            y: vec![2.5, 3.5, -1.0],
+            yerr: None,
+        },
+        graph.unwrap()
+    );
+}
+
+#[test]
+fn test_xvg_writer_flushes_rows_as_they_are_written() {
+    let path = std::env::temp_dir().join("densmap_test_xvg_writer.xvg");
+
+    let mut writer = XvgWriter::create(&path).unwrap();
+    writer.write_row(0.0, &[2.5]).unwrap();
+    writer.write_row(1.0, &[3.5]).unwrap();
+
+    // The rows can be read before the writer is dropped.
+    let graph = read_xvg(&path);
+
+    let expected = Graph::Carthesian {
+        x: vec![0.0, 1.0],
+        y: vec![2.5, 3.5],
+        yerr: None,
+    };
+    write_xvg(&path.with_extension("ref"), &expected).unwrap();
+    let reference = std::fs::read(path.with_extension("ref"));
+    let written = std::fs::read(&path);
+
+    drop(writer);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(path.with_extension("ref")).unwrap();
+
+    assert_eq!(expected, graph.unwrap());
+    assert_eq!(reference.unwrap(), written.unwrap());
+}
+
+#[test]
+fn test_xvg_sets_are_separated_by_ampersands() {
+    let path = std::env::temp_dir().join("densmap_test_xvg_sets.xvg");
+    let _ = std::fs::remove_file(&path);
+
+    let graph = Graph::Carthesian {
+        x: vec![0.0],
+        y: vec![1.0],
+        yerr: None,
+    };
+
+    append_xvg_set(&path, &graph).unwrap();
+    append_xvg_set(&path, &graph).unwrap();
+    let contents = std::fs::read_to_string(&path);
+
+    std::fs::remove_file(&path).unwrap();
+
+    let row = format!("{:12.5} {:12.5}\n", 0.0, 1.0);
+    assert_eq!(format!("{}&\n{}", row, row), contents.unwrap());
+}
+
+#[test]
+fn test_xvg_columns_are_written_with_legends_and_truncated_rows() {
+    let path = std::env::temp_dir().join("densmap_test_xvg_columns.xvg");
+
+    let data = Columns::new("time", vec![0.0, 10.0, 20.0])
+        .with_column("radius", vec![1.0, 2.0, 3.0])
+        .with_column("area", vec![3.0, 12.0]);
+
+    write_xvg_columns(&path, &data).unwrap();
+    append_xvg_columns(&path, &data).unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let graph = read_xvg(&path);
+
+    std::fs::remove_file(&path).unwrap();
+
+    let lines = contents.lines().collect::<Vec<_>>();
+    assert_eq!("@    xaxis  label \"time\"", lines[0]);
+    assert_eq!("@ s0 legend \"radius\"", lines[2]);
+    assert_eq!("@ s1 legend \"area\"", lines[3]);
+    assert_eq!(
+        format!("{:12.5} {:12.5} {:12.5}", 10.0, 2.0, 12.0),
+        lines[5]
+    );
+    assert_eq!(8, lines.len());
+
+    // The first column of values is read as a graph.
+    assert_eq!(vec![1.0, 2.0, 1.0, 2.0], graph.unwrap().y().to_vec());
+}
+
+#[test]
+fn test_errors_are_resampled_and_written_as_third_column() {
+    let path = std::env::temp_dir().join("densmap_test_xvg_errors.xvg");
+
+    let histogram = Histogram {
+        x: vec![0.0, 1.0],
+        y: vec![1.0, 3.0],
+        yerr: Some(vec![0.2, 0.4]),
+    };
+
+    let resampled = histogram.resample(&[0.5]);
+    assert!((resampled.yerr().unwrap()[0] - 0.3).abs() < 1e-9);
+
+    write_xvg(&path, &resampled).unwrap();
+    let contents = std::fs::read_to_string(&path);
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        format!("{:12.5} {:12.5} {:12.5}\n", 0.5, 2.0, 0.3),
+        contents.unwrap()
+    );
+
+    // Errors of radii do not apply to Carthesian coordinates.
+    let polar = Graph::Polar {
+        angles: vec![0.0],
+        radius: vec![1.0],
+        yerr: Some(vec![0.1]),
+    };
+    assert_eq!(None, polar.to_carthesian().yerr());
+}
+
+#[test]
+fn test_xvg_sets_are_read_with_legends_and_columns() {
+    let path = std::env::temp_dir().join("densmap_test_read_xvg_sets.xvg");
+
+    let data = Columns::new("time", vec![0.0, 10.0])
+        .with_column("radius", vec![1.0, 2.0])
+        .with_column("area", vec![3.0, 12.0]);
+
+    write_xvg_columns(&path, &data).unwrap();
+    let mut fp = OpenOptions::new().append(true).open(&path).unwrap();
+    writeln!(fp, "&").unwrap();
+    append_xvg_columns(&path, &data).unwrap();
+
+    let sets = read_xvg_sets(&path).unwrap();
+    std::fs::write(&path, "# comment\n1.0 2.0\n2.0\n").unwrap();
+    let uneven = read_xvg_columns(&path);
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(2, sets.len());
+    assert_eq!(data, sets[0]);
+    assert_eq!(data.x, sets[1].x);
+    assert_eq!(data.columns[1].1, sets[1].columns[1].1);
+    assert_eq!(io::ErrorKind::InvalidData, uneven.unwrap_err().kind());
+}
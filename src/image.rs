@@ -0,0 +1,140 @@
+use crate::densmap::{get_checksum, DensMap};
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Write a density map as a binary grayscale PGM image.
+///
+/// Values are scaled linearly from black at `min` to white at `max` and clamped
+/// outside of that range. The image is oriented with y increasing upwards.
+pub fn write_pgm(path: &Path, densmap: &DensMap, min: f64, max: f64) -> Result<(), io::Error> {
+    let fp = File::create(path)?;
+    let mut writer = BufWriter::new(fp);
+
+    writer.write_all(&get_pgm_bytes(densmap, min, max))
+}
+
+fn get_pgm_bytes(densmap: &DensMap, min: f64, max: f64) -> Vec<u8> {
+    let [nx, ny] = densmap.shape;
+
+    let mut bytes = format!("P5\n{} {}\n255\n", nx, ny).into_bytes();
+    for row in get_pixel_rows(densmap, min, max) {
+        bytes.extend(row);
+    }
+
+    bytes
+}
+
+/// Get a density map as a grayscale PNG image, scaled as by `write_pgm`.
+///
+/// Scan lines are compressed without filtering, which is sufficient for the small
+/// images of maps.
+pub fn get_png_bytes(densmap: &DensMap, min: f64, max: f64) -> Result<Vec<u8>, io::Error> {
+    let [nx, ny] = densmap.shape;
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in get_pixel_rows(densmap, min, max) {
+        // Every scan line begins with its filter type, which is none.
+        encoder.write_all(&[0])?;
+        encoder.write_all(&row)?;
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(nx as u32).to_be_bytes());
+    header.extend_from_slice(&(ny as u32).to_be_bytes());
+    // Bit depth of 8 in grayscale, with default compression, filters and no interlace.
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_png_chunk(&mut bytes, b"IHDR", &header);
+    write_png_chunk(&mut bytes, b"IDAT", &encoder.finish()?);
+    write_png_chunk(&mut bytes, b"IEND", &[]);
+
+    Ok(bytes)
+}
+
+fn write_png_chunk(bytes: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    let mut chunk = chunk_type.to_vec();
+    chunk.extend_from_slice(data);
+
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&chunk);
+    bytes.extend_from_slice(&get_checksum(&chunk).to_be_bytes());
+}
+
+/// Get the rows of pixels of an image of a map from the top, with y increasing upwards.
+fn get_pixel_rows(densmap: &DensMap, min: f64, max: f64) -> Vec<Vec<u8>> {
+    let [nx, ny] = densmap.shape;
+    let (nx, ny) = (nx as usize, ny as usize);
+
+    let range = if max > min { max - min } else { 1.0 };
+
+    (0..ny)
+        .rev()
+        .map(|iy| {
+            densmap.data[iy * nx..(iy + 1) * nx]
+                .iter()
+                .map(|&v| {
+                    let scaled = ((v - min) / range).clamp(0.0, 1.0);
+                    (255.0 * scaled).round() as u8
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::DensMapBuilder;
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_pgm_image_is_scaled_and_flipped_along_y() {
+        let densmap = DensMapBuilder::new()
+            .bin_size([1.0, 1.0, 0.0])
+            .shape([2, 2])
+            .from_data(vec![0.0, 1.0, 2.0, 3.0])
+            .unwrap();
+
+        let bytes = get_pgm_bytes(&densmap, 0.0, 2.0);
+
+        assert_eq!(b"P5\n2 2\n255\n", &bytes[..11]);
+        assert_eq!(&[255, 255, 0, 128], &bytes[11..]);
+    }
+
+    #[test]
+    fn test_png_image_has_header_and_compressed_rows() {
+        let densmap = DensMapBuilder::new()
+            .bin_size([1.0, 1.0, 0.0])
+            .shape([2, 2])
+            .from_data(vec![0.0, 1.0, 2.0, 3.0])
+            .unwrap();
+
+        let bytes = get_png_bytes(&densmap, 0.0, 2.0).unwrap();
+
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &bytes[..8]);
+        assert_eq!(b"IHDR", &bytes[12..16]);
+        assert_eq!(&[0, 0, 0, 2, 0, 0, 0, 2, 8, 0], &bytes[16..26]);
+        assert_eq!(b"IEND", &bytes[bytes.len() - 8..bytes.len() - 4]);
+
+        // The data chunk follows the 13 bytes of the header and its checksum.
+        let idat = 8 + 12 + 13;
+        let mut len = [0; 4];
+        len.copy_from_slice(&bytes[idat..idat + 4]);
+        let len = u32::from_be_bytes(len) as usize;
+        assert_eq!(b"IDAT", &bytes[idat + 4..idat + 8]);
+
+        let mut rows = Vec::new();
+        ZlibDecoder::new(&bytes[idat + 8..idat + 8 + len])
+            .read_to_end(&mut rows)
+            .unwrap();
+        assert_eq!(vec![0, 255, 255, 0, 0, 128], rows);
+    }
+}
@@ -1,4 +1,24 @@
 pub mod analysis;
+pub mod arithmetic;
+pub mod arrow;
 pub mod average;
+pub mod container;
 pub mod densmap;
+pub mod densmap3d;
+pub mod fields;
+pub mod flow;
+mod frame_index;
+pub mod generate;
+pub mod gnuplot;
 pub mod graphdata;
+pub mod image;
+pub mod mask;
+pub mod netcdf;
+pub mod npy;
+mod parallel;
+pub mod plot;
+pub mod report;
+pub mod series;
+pub mod terminal;
+pub mod transform;
+pub mod vtk;
@@ -0,0 +1,225 @@
+use crate::densmap::{read_densmap, DensMap, Vec2};
+
+use std::{path::Path, str::FromStr};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Axes of the density map.
+pub enum Axis {
+    X,
+    Y,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Regions of a density map which can be masked out.
+///
+/// Coordinates are absolute system coordinates. A bin lies inside of a region
+/// if its center point does.
+pub enum MaskRegion {
+    /// Rectangle spanned by the minimum and maximum coordinates along x and y.
+    Rectangle { min: Vec2, max: Vec2 },
+    /// Half-plane below a coordinate along an axis.
+    Below { axis: Axis, value: f64 },
+    /// Half-plane above a coordinate along an axis.
+    Above { axis: Axis, value: f64 },
+    /// Explicit selection of bins, in the same order as the data of the density map.
+    Bins(Vec<bool>),
+}
+
+impl MaskRegion {
+    /// Create a mask of all bins in the input map which have non-zero values.
+    pub fn from_densmap(densmap: &DensMap) -> MaskRegion {
+        MaskRegion::Bins(densmap.data.iter().map(|&v| v != 0.0).collect())
+    }
+
+    fn contains(&self, i: usize, [x, y]: Vec2) -> bool {
+        let coord = |axis: &Axis| match axis {
+            Axis::X => x,
+            Axis::Y => y,
+        };
+
+        match self {
+            MaskRegion::Rectangle { min, max } => {
+                x >= min[0] && x <= max[0] && y >= min[1] && y <= max[1]
+            }
+            MaskRegion::Below { axis, value } => coord(axis) < *value,
+            MaskRegion::Above { axis, value } => coord(axis) > *value,
+            MaskRegion::Bins(bins) => bins[i],
+        }
+    }
+}
+
+/// Parse a mask region from a string.
+///
+/// The accepted forms are `rect:xmin,xmax,ymin,ymax`, `below-x:value`, `below-y:value`,
+/// `above-x:value`, `above-y:value` and `file:path`. For the last form the file is read
+/// as a density map, the non-zero bins of which are masked.
+impl FromStr for MaskRegion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = match s.find(':') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => return Err(format!("invalid mask region '{}': missing ':'", s)),
+        };
+
+        let parse_values = |value: &str| {
+            value
+                .split(',')
+                .map(|v| v.trim().parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("invalid mask region '{}': {}", s, err))
+        };
+
+        let parse_single = |value: &str| match parse_values(value)?.as_slice() {
+            &[v] => Ok(v),
+            _ => Err(format!(
+                "invalid mask region '{}': expected a single value",
+                s
+            )),
+        };
+
+        match kind {
+            "rect" => match parse_values(value)?.as_slice() {
+                &[xmin, xmax, ymin, ymax] => Ok(MaskRegion::Rectangle {
+                    min: [xmin, ymin],
+                    max: [xmax, ymax],
+                }),
+                _ => Err(format!(
+                    "invalid mask region '{}': expected xmin,xmax,ymin,ymax",
+                    s
+                )),
+            },
+            "below-x" => Ok(MaskRegion::Below {
+                axis: Axis::X,
+                value: parse_single(value)?,
+            }),
+            "below-y" => Ok(MaskRegion::Below {
+                axis: Axis::Y,
+                value: parse_single(value)?,
+            }),
+            "above-x" => Ok(MaskRegion::Above {
+                axis: Axis::X,
+                value: parse_single(value)?,
+            }),
+            "above-y" => Ok(MaskRegion::Above {
+                axis: Axis::Y,
+                value: parse_single(value)?,
+            }),
+            "file" => {
+                let (densmap, _) = read_densmap(Path::new(value))
+                    .map_err(|err| format!("could not read mask file '{}': {}", value, err))?;
+                Ok(MaskRegion::from_densmap(&densmap))
+            }
+            _ => Err(format!(
+                "invalid mask region '{}': unknown kind '{}'",
+                s, kind
+            )),
+        }
+    }
+}
+
+impl DensMap {
+    /// Set all bins which lie inside of any of the input regions to zero, which excludes
+    /// them from contributing to any analysis.
+    ///
+    /// # Error
+    /// Returns an error if a mask of explicit bins does not match the size of the map.
+    pub fn mask(&mut self, regions: &[MaskRegion]) -> Result<(), String> {
+        for region in regions {
+            if let MaskRegion::Bins(bins) = region {
+                if bins.len() != self.data.len() {
+                    return Err(format!(
+                        "mask has {} bins but the density map has {}",
+                        bins.len(),
+                        self.data.len()
+                    ));
+                }
+            }
+        }
+
+        for (i, (_, _, x, y, v)) in self.iter_bins_mut().enumerate() {
+            if regions.iter().any(|region| region.contains(i, [x, y])) {
+                *v = 0.0;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_filled_densmap() -> DensMap {
+        DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [10.0, 20.0],
+            shape: [4, 3],
+            center: [12.0, 21.5],
+            data: vec![1.0; 12],
+        }
+    }
+
+    #[test]
+    fn test_masking_rectangle_and_half_planes_zeroes_bins_inside() {
+        let mut densmap = get_filled_densmap();
+        densmap
+            .mask(&[
+                MaskRegion::Rectangle {
+                    min: [10.0, 20.0],
+                    max: [11.0, 21.0],
+                },
+                MaskRegion::Above {
+                    axis: Axis::Y,
+                    value: 22.0,
+                },
+            ])
+            .unwrap();
+
+        assert_eq!(
+            vec![0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0],
+            densmap.data
+        );
+
+        let mut densmap = get_filled_densmap();
+        densmap
+            .mask(&[MaskRegion::Below {
+                axis: Axis::X,
+                value: 12.0,
+            }])
+            .unwrap();
+
+        assert_eq!(
+            vec![0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0],
+            densmap.data
+        );
+    }
+
+    #[test]
+    fn test_masking_with_bins_of_wrong_size_returns_error() {
+        let mut densmap = get_filled_densmap();
+        assert!(densmap.mask(&[MaskRegion::Bins(vec![true; 3])]).is_err());
+    }
+
+    #[test]
+    fn test_parse_mask_regions() {
+        assert_eq!(
+            Ok(MaskRegion::Rectangle {
+                min: [0.0, 2.0],
+                max: [1.0, 3.0]
+            }),
+            "rect:0,1,2,3".parse()
+        );
+        assert_eq!(
+            Ok(MaskRegion::Below {
+                axis: Axis::Y,
+                value: 1.5
+            }),
+            "below-y:1.5".parse()
+        );
+        assert!("rect:0,1".parse::<MaskRegion>().is_err());
+        assert!("inside:1.0".parse::<MaskRegion>().is_err());
+        assert!("below-x".parse::<MaskRegion>().is_err());
+    }
+}
@@ -0,0 +1,386 @@
+use crate::{
+    densmap::{DensMap, LengthUnit, Shape},
+    series::TimeUnit,
+};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use std::{
+    f64::consts::PI,
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Tags and types of the NetCDF classic format.
+const NC_DIMENSION: u32 = 0x0a;
+const NC_VARIABLE: u32 = 0x0b;
+const NC_ATTRIBUTE: u32 = 0x0c;
+const NC_CHAR: u32 = 2;
+const NC_DOUBLE: u32 = 6;
+
+/// Indices of the dimensions in the header.
+const DIM_TIME: u32 = 0;
+const DIM_Y: u32 = 1;
+const DIM_X: u32 = 2;
+
+/// Variables which hold a single value per frame, with their descriptions.
+const SERIES_VARIABLES: [(&str, &str); 5] = [
+    ("time", "time"),
+    ("radius", "droplet base radius"),
+    ("area", "droplet base area"),
+    ("center_x", "droplet center along x"),
+    ("center_y", "droplet center along y"),
+];
+
+/// Writer of a trajectory of density maps to a NetCDF file.
+///
+/// The file uses the classic format, with time as the unlimited dimension. Every frame
+/// adds a record of the map as the variable `density(time, y, x)` along with the time
+/// series `time`, `radius`, `area`, `center_x` and `center_y`. The bin centers are
+/// written as the coordinate variables `x` and `y`. The times and lengths are written
+/// in the units of the analysis, which are set as the `units` attributes of their
+/// variables, while the densities are written in the units of the input maps.
+///
+/// All maps must have the shape of the first, which the header is written from.
+/// The number of frames is written to the header by `finish`, which must be called
+/// after the last frame.
+pub struct NetcdfWriter<W: Write + Seek> {
+    writer: W,
+    shape: Shape,
+    num_records: u32,
+}
+
+impl NetcdfWriter<BufWriter<File>> {
+    /// Create the file at the path and write its header from the grid of the map.
+    pub fn create(
+        path: &Path,
+        densmap: &DensMap,
+        time_unit: TimeUnit,
+        length_unit: LengthUnit,
+    ) -> Result<Self, io::Error> {
+        let fp = File::create(path)?;
+        NetcdfWriter::new(BufWriter::new(fp), densmap, time_unit, length_unit)
+    }
+}
+
+impl<W: Write + Seek> NetcdfWriter<W> {
+    /// Write the header from the grid of the map, with the units of the times and
+    /// lengths of all frames.
+    pub fn new(
+        mut writer: W,
+        densmap: &DensMap,
+        time_unit: TimeUnit,
+        length_unit: LengthUnit,
+    ) -> Result<Self, io::Error> {
+        let [nx, ny] = densmap.shape;
+        let [dx, dy, _] = densmap.bin_size;
+        let [x0, y0] = densmap.origin;
+
+        // The header size does not depend on the data offsets that it contains.
+        let header = |data_begin| get_header(densmap.shape, data_begin, time_unit, length_unit);
+        let header_len = header(0).len() as u32;
+        writer.write_all(&header(header_len))?;
+
+        for ix in 0..nx {
+            writer.write_f64::<BigEndian>(x0 + dx * (ix as f64 + 0.5))?;
+        }
+
+        for iy in 0..ny {
+            writer.write_f64::<BigEndian>(y0 + dy * (iy as f64 + 0.5))?;
+        }
+
+        Ok(NetcdfWriter {
+            writer,
+            shape: densmap.shape,
+            num_records: 0,
+        })
+    }
+
+    /// Write a frame as a record of its map, time and droplet base radius.
+    ///
+    /// The radius is `None` if it could not be determined for the frame, in which case
+    /// the radius and area are written as NaN.
+    pub fn write_frame(
+        &mut self,
+        densmap: &DensMap,
+        time: f64,
+        radius: Option<f64>,
+    ) -> Result<(), io::Error> {
+        if densmap.shape != self.shape {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "map of shape {:?} cannot be added to a NetCDF file of maps with shape {:?}",
+                    densmap.shape, self.shape
+                ),
+            ));
+        }
+
+        let radius = radius.unwrap_or(f64::NAN);
+        let [xc, yc] = densmap.center;
+
+        for &v in &[time, radius, PI * radius.powi(2), xc, yc] {
+            self.writer.write_f64::<BigEndian>(v)?;
+        }
+
+        for &v in &densmap.data {
+            self.writer.write_f64::<BigEndian>(v)?;
+        }
+
+        self.num_records += 1;
+
+        Ok(())
+    }
+
+    /// Write the number of frames to the header and flush the file.
+    pub fn finish(mut self) -> Result<W, io::Error> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_u32::<BigEndian>(self.num_records)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+/// Construct the header for maps of the given shape, with the data beginning at
+/// the given offset.
+fn get_header(
+    shape: Shape,
+    data_begin: u32,
+    time_unit: TimeUnit,
+    length_unit: LengthUnit,
+) -> Vec<u8> {
+    let [nx, ny] = shape;
+    let length = length_unit.name();
+    let area = format!("{}2", length);
+    let time = time_unit.to_string();
+    let series_units = [time.as_str(), length, area.as_str(), length, length];
+    let map_size = 8 * nx as u32 * ny as u32;
+
+    let mut header = b"CDF\x01".to_vec();
+    header.write_u32::<BigEndian>(0).unwrap();
+
+    // The time dimension has length 0 to mark it as the unlimited record dimension.
+    header.write_u32::<BigEndian>(NC_DIMENSION).unwrap();
+    header.write_u32::<BigEndian>(3).unwrap();
+    for &(name, len) in &[("time", 0), ("y", ny as u32), ("x", nx as u32)] {
+        write_name(&mut header, name);
+        header.write_u32::<BigEndian>(len).unwrap();
+    }
+
+    write_attributes(
+        &mut header,
+        &[
+            ("title", "density maps of droplet"),
+            ("source", "densmap-analysis"),
+        ],
+    );
+
+    header.write_u32::<BigEndian>(NC_VARIABLE).unwrap();
+    header
+        .write_u32::<BigEndian>(2 + SERIES_VARIABLES.len() as u32 + 1)
+        .unwrap();
+
+    // The coordinates are written first, followed by the records of all frames.
+    // Within a record the time series values are followed by the map.
+    let x_begin = data_begin;
+    let y_begin = x_begin + 8 * nx as u32;
+    let record_begin = y_begin + 8 * ny as u32;
+
+    write_variable(
+        &mut header,
+        "x",
+        &[DIM_X],
+        &[("units", length), ("long_name", "bin center along x")],
+        8 * nx as u32,
+        x_begin,
+    );
+    write_variable(
+        &mut header,
+        "y",
+        &[DIM_Y],
+        &[("units", length), ("long_name", "bin center along y")],
+        8 * ny as u32,
+        y_begin,
+    );
+
+    let series = SERIES_VARIABLES.iter().zip(&series_units);
+
+    for (i, (&(name, long_name), units)) in series.enumerate() {
+        write_variable(
+            &mut header,
+            name,
+            &[DIM_TIME],
+            &[("units", units), ("long_name", long_name)],
+            8,
+            record_begin + 8 * i as u32,
+        );
+    }
+
+    write_variable(
+        &mut header,
+        "density",
+        &[DIM_TIME, DIM_Y, DIM_X],
+        &[("long_name", "density")],
+        map_size,
+        record_begin + 8 * SERIES_VARIABLES.len() as u32,
+    );
+
+    header
+}
+
+/// Write a name as its length and characters, padded to a multiple of 4 bytes.
+fn write_name(header: &mut Vec<u8>, name: &str) {
+    header.write_u32::<BigEndian>(name.len() as u32).unwrap();
+    header.extend_from_slice(name.as_bytes());
+    header.resize(header.len() + (4 - name.len() % 4) % 4, 0);
+}
+
+fn write_attributes(header: &mut Vec<u8>, attributes: &[(&str, &str)]) {
+    header.write_u32::<BigEndian>(NC_ATTRIBUTE).unwrap();
+    header
+        .write_u32::<BigEndian>(attributes.len() as u32)
+        .unwrap();
+
+    for (name, value) in attributes {
+        write_name(header, name);
+        header.write_u32::<BigEndian>(NC_CHAR).unwrap();
+        write_name(header, value);
+    }
+}
+
+fn write_variable(
+    header: &mut Vec<u8>,
+    name: &str,
+    dims: &[u32],
+    attributes: &[(&str, &str)],
+    size: u32,
+    begin: u32,
+) {
+    write_name(header, name);
+
+    header.write_u32::<BigEndian>(dims.len() as u32).unwrap();
+    for &dim in dims {
+        header.write_u32::<BigEndian>(dim).unwrap();
+    }
+
+    write_attributes(header, attributes);
+
+    header.write_u32::<BigEndian>(NC_DOUBLE).unwrap();
+    header.write_u32::<BigEndian>(size).unwrap();
+    header.write_u32::<BigEndian>(begin).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn read_u32(bytes: &[u8], pos: usize) -> u32 {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(&bytes[pos..pos + 4]);
+        u32::from_be_bytes(buf)
+    }
+
+    fn read_f64(bytes: &[u8], pos: usize) -> f64 {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(&bytes[pos..pos + 8]);
+        f64::from_be_bytes(buf)
+    }
+
+    fn get_densmap() -> DensMap {
+        DensMap {
+            bin_size: [0.5, 0.25, 0.0],
+            origin: [1.0, 2.0],
+            shape: [3, 2],
+            center: [1.75, 2.25],
+            data: (0..6).map(|i| i as f64).collect(),
+        }
+    }
+
+    #[test]
+    fn test_header_names_are_padded_to_multiples_of_four_bytes() {
+        let mut header = Vec::new();
+        write_name(&mut header, "radius");
+
+        assert_eq!(
+            vec![0, 0, 0, 6, b'r', b'a', b'd', b'i', b'u', b's', 0, 0],
+            header
+        );
+    }
+
+    #[test]
+    fn test_netcdf_file_has_coordinates_followed_by_one_record_per_frame() {
+        let densmap = get_densmap();
+
+        let mut writer = NetcdfWriter::new(
+            Cursor::new(Vec::new()),
+            &densmap,
+            TimeUnit::Ps,
+            LengthUnit::Nm,
+        )
+        .unwrap();
+        writer.write_frame(&densmap, 10.0, Some(1.0)).unwrap();
+        writer.write_frame(&densmap, 20.0, None).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let header_len = get_header(densmap.shape, 0, TimeUnit::Ps, LengthUnit::Nm).len();
+        let record_len = 8 * (SERIES_VARIABLES.len() + 6);
+
+        assert_eq!(b"CDF\x01", &bytes[..4]);
+        assert_eq!(2, read_u32(&bytes, 4));
+        assert_eq!(header_len + 8 * 5 + 2 * record_len, bytes.len());
+
+        // Bin centers along x and y.
+        assert_eq!(1.25, read_f64(&bytes, header_len));
+        assert_eq!(2.125, read_f64(&bytes, header_len + 8 * 3));
+
+        let first_record = header_len + 8 * 5;
+        assert_eq!(10.0, read_f64(&bytes, first_record));
+        assert_eq!(1.0, read_f64(&bytes, first_record + 8));
+        assert_eq!(PI, read_f64(&bytes, first_record + 16));
+        assert_eq!(5.0, read_f64(&bytes, first_record + record_len - 8));
+
+        let second_record = first_record + record_len;
+        assert_eq!(20.0, read_f64(&bytes, second_record));
+        assert!(read_f64(&bytes, second_record + 8).is_nan());
+    }
+
+    #[test]
+    fn test_units_of_the_analysis_are_written_to_the_header() {
+        let header = get_header([3, 2], 0, TimeUnit::Ns, LengthUnit::Angstrom);
+
+        let contains = |value: &str| {
+            let mut name = Vec::new();
+            write_name(&mut name, value);
+            header.windows(name.len()).any(|w| w == name.as_slice())
+        };
+
+        assert!(contains("ns"));
+        assert!(contains("angstrom"));
+        assert!(contains("angstrom2"));
+        assert!(!contains("nm"));
+        assert!(!contains("ps"));
+    }
+
+    #[test]
+    fn test_maps_of_another_shape_are_not_written() {
+        let densmap = get_densmap();
+        let mut writer = NetcdfWriter::new(
+            Cursor::new(Vec::new()),
+            &densmap,
+            TimeUnit::Ps,
+            LengthUnit::Nm,
+        )
+        .unwrap();
+
+        let other = DensMap {
+            shape: [2, 3],
+            ..densmap
+        };
+
+        assert!(writer.write_frame(&other, 0.0, None).is_err());
+    }
+}
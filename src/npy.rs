@@ -0,0 +1,784 @@
+use crate::densmap::{DensMap, GridOptions, Vec2, Vec3};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
+use flate2::read::DeflateDecoder;
+use regex::Regex;
+use serde_json::{json, Value};
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// Magic string which begins every .npy file.
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Signatures of the zip records which are read from .npz archives.
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4b50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP_END_OF_CENTRAL_DIR: u32 = 0x0605_4b50;
+const ZIP64_END_OF_CENTRAL_DIR: u32 = 0x0606_4b50;
+const ZIP64_END_OF_CENTRAL_DIR_LOCATOR: u32 = 0x0706_4b50;
+
+/// Values in the zip records which mark that the real value is stored as a zip64 field.
+const ZIP64_MARKER_U16: u16 = 0xffff;
+const ZIP64_MARKER_U32: u32 = 0xffff_ffff;
+
+/// Grid and time of a density map as read along with a NumPy array.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Metadata {
+    grid: GridOptions,
+    time: Option<f64>,
+}
+
+/// A NumPy array as its shape and values, in row-major order.
+type Array = (Vec<u64>, Vec<f64>);
+
+/// Read a density map from a 2D NumPy array in the .npy format.
+///
+/// The array must have the shape (ny, nx), as written by `write_npy`. Its values may
+/// be of any floating point or integer type. The grid and time of the map is read
+/// from a JSON file at the same path with the extension '.json' if it exists, as
+/// written by `write_npy_metadata`. Values which are set in the grid options take
+/// precedence over it, see `GridOptions::construct_densmap`.
+///
+/// # Errors
+/// Returns an error if the array is not 2D or if the bin size is not given by either
+/// the metadata or the grid options.
+pub fn read_npy(path: &Path, grid: &GridOptions) -> Result<(DensMap, f64), io::Error> {
+    let fp = File::open(path)?;
+    let len = fp.metadata()?.len();
+    let array = read_npy_array(&mut BufReader::new(fp), len)?;
+
+    let metadata_path = path.with_extension("json");
+    let metadata = if metadata_path.exists() {
+        let fp = File::open(&metadata_path)?;
+        let value: Value = serde_json::from_reader(BufReader::new(fp))?;
+        get_metadata_from_json(&value)
+    } else {
+        Metadata::default()
+    };
+
+    construct_densmap(array, metadata, grid)
+}
+
+/// Read a density map from a NumPy archive in the .npz format.
+///
+/// The map is read from the array named `data`, or from the only array if the archive
+/// holds a single one, with the same requirements as for `read_npy`. Its grid and
+/// time is read from the (optional) arrays `bin_size`, `origin`, `center` and `time`,
+/// for example as written by `numpy.savez(path, data=..., bin_size=[dx, dy], ...)`.
+/// Values which are set in the grid options take precedence over them.
+///
+/// Both stored and compressed (`numpy.savez_compressed`) archives can be read.
+pub fn read_npz(path: &Path, grid: &GridOptions) -> Result<(DensMap, f64), io::Error> {
+    let bytes = fs::read(path)?;
+    let arrays = read_zip_entries(&bytes)?
+        .into_iter()
+        .map(|(name, contents)| {
+            let name = name.trim_end_matches(".npy").to_string();
+            read_npy_array(&mut contents.as_slice(), contents.len() as u64)
+                .map(|array| (name, array))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let get_values = |name: &str| {
+        arrays
+            .iter()
+            .find(|(array_name, _)| array_name == name)
+            .map(|(_, (_, values))| values.as_slice())
+    };
+
+    let metadata = Metadata {
+        grid: GridOptions {
+            bin_size: get_values("bin_size").and_then(get_bin_size),
+            origin: get_values("origin").and_then(get_point),
+            center: get_values("center").and_then(get_point),
+            ..GridOptions::default()
+        },
+        time: get_values("time").and_then(|values| values.first().cloned()),
+    };
+
+    let array = match arrays.iter().position(|(name, _)| name == "data") {
+        Some(i) => arrays[i].1.clone(),
+        None if arrays.len() == 1 => arrays[0].1.clone(),
+        None => {
+            return Err(invalid_data(
+                "archive has no array named 'data' to read the density map from",
+            ));
+        }
+    };
+
+    construct_densmap(array, metadata, grid)
+}
+
+/// Construct the density map from an array of shape (ny, nx) and its grid.
+fn construct_densmap(
+    (shape, data): Array,
+    metadata: Metadata,
+    grid: &GridOptions,
+) -> Result<(DensMap, f64), io::Error> {
+    let (nx, ny) = match *shape.as_slice() {
+        [ny, nx] => (nx, ny),
+        _ => {
+            return Err(invalid_data(&format!(
+                "array must be 2-dimensional with shape (ny, nx), but has shape {:?}",
+                shape
+            )));
+        }
+    };
+
+    let densmap = grid
+        .construct_densmap([nx, ny], data, &metadata.grid)
+        .map_err(|msg| invalid_data(&msg))?;
+
+    Ok((densmap, metadata.time.unwrap_or(0.0)))
+}
+
+fn get_metadata_from_json(value: &Value) -> Metadata {
+    let get_values = |key: &str| -> Option<Vec<f64>> {
+        value[key]
+            .as_array()?
+            .iter()
+            .map(|v| v.as_f64())
+            .collect::<Option<Vec<_>>>()
+    };
+
+    Metadata {
+        grid: GridOptions {
+            bin_size: get_values("bin_size")
+                .as_ref()
+                .and_then(|v| get_bin_size(v)),
+            origin: get_values("origin").as_ref().and_then(|v| get_point(v)),
+            center: get_values("center").as_ref().and_then(|v| get_point(v)),
+            ..GridOptions::default()
+        },
+        time: value["time"].as_f64(),
+    }
+}
+
+/// Get a bin size from 2 or 3 values, with the bin size along z set to 0 if not given.
+fn get_bin_size(values: &[f64]) -> Option<Vec3> {
+    match *values {
+        [dx, dy] => Some([dx, dy, 0.0]),
+        [dx, dy, dz] => Some([dx, dy, dz]),
+        _ => None,
+    }
+}
+
+fn get_point(values: &[f64]) -> Option<Vec2> {
+    match *values {
+        [x, y] => Some([x, y]),
+        _ => None,
+    }
+}
+
+/// Read a NumPy array of any dimension from a reader of a .npy file of a length in bytes.
+///
+/// Arrays in Fortran (column-major) order are transposed to row-major order.
+///
+/// # Errors
+/// Returns an error if the shape of the array needs more data than what remains of
+/// the file after its header, which is checked before the data is allocated.
+fn read_npy_array<R: Read>(reader: &mut R, len: u64) -> Result<Array, io::Error> {
+    let mut magic = [0; 6];
+    reader.read_exact(&mut magic)?;
+
+    if magic != NPY_MAGIC {
+        return Err(invalid_data(
+            "not a NumPy array: the file has no .npy magic string",
+        ));
+    }
+
+    let mut version = [0; 2];
+    reader.read_exact(&mut version)?;
+
+    let (header_len, prefix_len) = match version[0] {
+        1 => {
+            let mut buf = [0; 2];
+            reader.read_exact(&mut buf)?;
+            (u64::from(LittleEndian::read_u16(&buf)), 10)
+        }
+        2 | 3 => {
+            let mut buf = [0; 4];
+            reader.read_exact(&mut buf)?;
+            (u64::from(LittleEndian::read_u32(&buf)), 12)
+        }
+        _ => {
+            return Err(invalid_data(&format!(
+                "unsupported .npy format version {}.{}",
+                version[0], version[1]
+            )));
+        }
+    };
+
+    let remaining = len.checked_sub(prefix_len + header_len).ok_or_else(|| {
+        invalid_data(&format!(
+            "the .npy header of {} bytes is longer than the file",
+            header_len
+        ))
+    })?;
+
+    let mut header = vec![0; header_len as usize];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8_lossy(&header);
+
+    let (descr, fortran_order, shape) = parse_npy_header(&header)?;
+    let num_values = shape
+        .iter()
+        .try_fold(1u64, |acc, &n| acc.checked_mul(n))
+        .ok_or_else(|| invalid_data(&format!("the .npy shape {:?} is too large", shape)))?;
+
+    let values = read_npy_values(reader, &descr, &shape, num_values, remaining)?;
+
+    let values = if fortran_order && shape.len() > 1 {
+        transpose_to_row_major(&values, &shape)
+    } else {
+        values
+    };
+
+    Ok((shape, values))
+}
+
+/// Parse the data type, order and shape from the dictionary in a .npy header.
+fn parse_npy_header(header: &str) -> Result<(String, bool, Vec<u64>), io::Error> {
+    let descr_re = Regex::new(r"'descr'\s*:\s*'([^']*)'").unwrap();
+    let order_re = Regex::new(r"'fortran_order'\s*:\s*(True|False)").unwrap();
+    let shape_re = Regex::new(r"'shape'\s*:\s*\(([^)]*)\)").unwrap();
+
+    let err = || invalid_data(&format!("could not parse .npy header '{}'", header.trim()));
+
+    let descr = descr_re.captures(header).ok_or_else(err)?[1].to_string();
+    let fortran_order = &order_re.captures(header).ok_or_else(err)?[1] == "True";
+    let shape = shape_re.captures(header).ok_or_else(err)?[1]
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| err())?;
+
+    Ok((descr, fortran_order, shape))
+}
+
+/// Read the values of an array with the given data type, converted to double precision,
+/// from the remaining bytes of the file.
+fn read_npy_values<R: Read>(
+    reader: &mut R,
+    descr: &str,
+    shape: &[u64],
+    num_values: u64,
+    remaining: u64,
+) -> Result<Vec<f64>, io::Error> {
+    let err = || invalid_data(&format!("unsupported .npy data type '{}'", descr));
+
+    let mut chars = descr.chars();
+    let byte_order = chars.next().ok_or_else(err)?;
+    let kind = chars.next().ok_or_else(err)?;
+    let size = chars.as_str().parse::<usize>().map_err(|_| err())?;
+
+    let num_bytes = num_values
+        .checked_mul(size as u64)
+        .filter(|&num_bytes| num_bytes <= remaining)
+        .ok_or_else(|| {
+            invalid_data(&format!(
+                "the .npy array of shape {:?} and type '{}' needs more than the {} bytes \
+                 which remain of the file",
+                shape, descr, remaining
+            ))
+        })?;
+
+    let mut bytes = vec![0; num_bytes as usize];
+    reader.read_exact(&mut bytes)?;
+
+    let values = match byte_order {
+        '<' | '=' | '|' => decode_values::<LittleEndian>(&bytes, kind, size),
+        '>' => decode_values::<BigEndian>(&bytes, kind, size),
+        _ => None,
+    };
+
+    values.ok_or_else(err)
+}
+
+fn decode_values<B: ByteOrder>(bytes: &[u8], kind: char, size: usize) -> Option<Vec<f64>> {
+    let decode: fn(&[u8]) -> f64 = match (kind, size) {
+        ('f', 4) => |b| f64::from(B::read_f32(b)),
+        ('f', 8) => |b| B::read_f64(b),
+        ('i', 1) => |b| f64::from(b[0] as i8),
+        ('i', 2) => |b| f64::from(B::read_i16(b)),
+        ('i', 4) => |b| f64::from(B::read_i32(b)),
+        ('i', 8) => |b| B::read_i64(b) as f64,
+        ('u', 1) | ('b', 1) => |b| f64::from(b[0]),
+        ('u', 2) => |b| f64::from(B::read_u16(b)),
+        ('u', 4) => |b| f64::from(B::read_u32(b)),
+        ('u', 8) => |b| B::read_u64(b) as f64,
+        _ => return None,
+    };
+
+    Some(bytes.chunks(size).map(decode).collect())
+}
+
+/// Transpose the values of an array in column-major order to row-major order.
+fn transpose_to_row_major(values: &[f64], shape: &[u64]) -> Vec<f64> {
+    let shape = shape.iter().map(|&n| n as usize).collect::<Vec<_>>();
+    let row_strides = (0..shape.len())
+        .map(|axis| shape[axis + 1..].iter().product())
+        .collect::<Vec<usize>>();
+
+    let mut transposed = vec![0.0; values.len()];
+
+    for (i, &v) in values.iter().enumerate() {
+        // Decompose the column-major index with the first axis changing fastest.
+        let mut rem = i;
+        let index = shape
+            .iter()
+            .zip(row_strides.iter())
+            .map(|(&n, &stride)| {
+                let coord = rem % n;
+                rem /= n;
+                coord * stride
+            })
+            .sum::<usize>();
+
+        transposed[index] = v;
+    }
+
+    transposed
+}
+
+/// Read the names and decompressed contents of all files in a zip archive.
+///
+/// Only the subset of the format which is written by NumPy is supported: files which
+/// are stored or compressed with deflate, in archives which may use zip64 records.
+fn read_zip_entries(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, io::Error> {
+    let eocd = (0..=bytes.len().saturating_sub(22))
+        .rev()
+        .take(u16::MAX as usize + 1)
+        .find(|&i| read_u32_at(bytes, i).ok() == Some(ZIP_END_OF_CENTRAL_DIR))
+        .ok_or_else(|| invalid_data("not a zip archive: no end of central directory found"))?;
+
+    let mut num_entries = u64::from(read_u16_at(bytes, eocd + 10)?);
+    let mut offset = u64::from(read_u32_at(bytes, eocd + 16)?);
+
+    if num_entries == u64::from(ZIP64_MARKER_U16) || offset == u64::from(ZIP64_MARKER_U32) {
+        let locator = eocd
+            .checked_sub(20)
+            .filter(|&i| read_u32_at(bytes, i).ok() == Some(ZIP64_END_OF_CENTRAL_DIR_LOCATOR))
+            .ok_or_else(|| invalid_data("zip archive is missing its zip64 record"))?;
+
+        let zip64_eocd = read_u64_at(bytes, locator + 8)? as usize;
+        check_signature(bytes, zip64_eocd, ZIP64_END_OF_CENTRAL_DIR)?;
+
+        num_entries = read_u64_at(bytes, zip64_eocd + 32)?;
+        offset = read_u64_at(bytes, zip64_eocd + 48)?;
+    }
+
+    let mut pos = offset as usize;
+    let mut entries = Vec::new();
+
+    for _ in 0..num_entries {
+        check_signature(bytes, pos, ZIP_CENTRAL_HEADER)?;
+
+        let method = read_u16_at(bytes, pos + 10)?;
+        let mut compressed_size = u64::from(read_u32_at(bytes, pos + 20)?);
+        let mut uncompressed_size = u64::from(read_u32_at(bytes, pos + 24)?);
+        let name_len = read_u16_at(bytes, pos + 28)? as usize;
+        let extra_len = read_u16_at(bytes, pos + 30)? as usize;
+        let comment_len = read_u16_at(bytes, pos + 32)? as usize;
+        let mut local_offset = u64::from(read_u32_at(bytes, pos + 42)?);
+
+        let name = String::from_utf8_lossy(get_bytes(bytes, pos + 46, name_len)?).to_string();
+
+        // Values which do not fit in the record are stored in order in the zip64 field.
+        let extra = get_bytes(bytes, pos + 46 + name_len, extra_len)?;
+        let mut i = 0;
+
+        while i + 4 <= extra.len() {
+            let id = read_u16_at(extra, i)?;
+            let size = read_u16_at(extra, i + 2)? as usize;
+
+            if id == 0x0001 {
+                let mut field = i + 4;
+
+                for value in [
+                    &mut uncompressed_size,
+                    &mut compressed_size,
+                    &mut local_offset,
+                ] {
+                    if *value == u64::from(ZIP64_MARKER_U32) {
+                        *value = read_u64_at(extra, field)?;
+                        field += 8;
+                    }
+                }
+            }
+
+            i += 4 + size;
+        }
+
+        check_signature(bytes, local_offset as usize, ZIP_LOCAL_HEADER)?;
+        let local_name_len = read_u16_at(bytes, local_offset as usize + 26)? as usize;
+        let local_extra_len = read_u16_at(bytes, local_offset as usize + 28)? as usize;
+        let start = local_offset as usize + 30 + local_name_len + local_extra_len;
+        let compressed = get_bytes(bytes, start, compressed_size as usize)?;
+
+        let contents = match method {
+            0 => compressed.to_vec(),
+            8 => {
+                // Deflate cannot compress by more than a factor of 1032, which bounds
+                // the allocation for archives with a corrupt size.
+                let capacity = uncompressed_size.min(compressed_size.saturating_mul(1032));
+                let mut contents = Vec::with_capacity(capacity as usize);
+                DeflateDecoder::new(compressed)
+                    .take(uncompressed_size)
+                    .read_to_end(&mut contents)?;
+                contents
+            }
+            _ => {
+                return Err(invalid_data(&format!(
+                    "unsupported compression method {} for '{}' in zip archive",
+                    method, name
+                )));
+            }
+        };
+
+        entries.push((name, contents));
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+
+    Ok(entries)
+}
+
+fn get_bytes(bytes: &[u8], pos: usize, len: usize) -> Result<&[u8], io::Error> {
+    pos.checked_add(len)
+        .and_then(|end| bytes.get(pos..end))
+        .ok_or_else(|| invalid_data("unexpected end of zip archive"))
+}
+
+fn read_u16_at(bytes: &[u8], pos: usize) -> Result<u16, io::Error> {
+    get_bytes(bytes, pos, 2).map(LittleEndian::read_u16)
+}
+
+fn read_u32_at(bytes: &[u8], pos: usize) -> Result<u32, io::Error> {
+    get_bytes(bytes, pos, 4).map(LittleEndian::read_u32)
+}
+
+fn read_u64_at(bytes: &[u8], pos: usize) -> Result<u64, io::Error> {
+    get_bytes(bytes, pos, 8).map(LittleEndian::read_u64)
+}
+
+fn check_signature(bytes: &[u8], pos: usize, signature: u32) -> Result<(), io::Error> {
+    if read_u32_at(bytes, pos)? == signature {
+        Ok(())
+    } else {
+        Err(invalid_data("invalid record in zip archive"))
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Write the data of a density map as a 2D NumPy array in the .npy format.
+///
+/// The array has the shape (ny, nx) of double precision values, such that element
+/// `[iy, ix]` holds the value of bin (ix, iy). The grid of the map is not included,
+/// see `write_npy_metadata` for writing it to a sidecar file.
+pub fn write_npy(path: &Path, densmap: &DensMap) -> Result<(), io::Error> {
+    let fp = File::create(path)?;
+    write_npy_data(&mut BufWriter::new(fp), densmap)
+}
+
+fn write_npy_data<W: Write>(writer: &mut W, densmap: &DensMap) -> Result<(), io::Error> {
+    let [nx, ny] = densmap.shape;
+    let header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+        ny, nx
+    );
+
+    // The magic string, version, header length and header are padded with spaces
+    // to a multiple of 64 bytes, including the final newline.
+    let unpadded_len = NPY_MAGIC.len() + 2 + 2 + header.len() + 1;
+    let padding = (64 - unpadded_len % 64) % 64;
+    let header_len = header.len() + padding + 1;
+
+    writer.write_all(NPY_MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_u16::<LittleEndian>(header_len as u16)?;
+    writer.write_all(header.as_bytes())?;
+    writer.write_all(&vec![b' '; padding])?;
+    writer.write_all(b"\n")?;
+
+    for &v in &densmap.data {
+        writer.write_f64::<LittleEndian>(v)?;
+    }
+
+    Ok(())
+}
+
+/// Write the grid of a density map along with its time as a JSON file, to accompany
+/// the data written by `write_npy`.
+pub fn write_npy_metadata(path: &Path, densmap: &DensMap, time: f64) -> Result<(), io::Error> {
+    let fp = File::create(path)?;
+    let mut writer = BufWriter::new(fp);
+
+    serde_json::to_writer_pretty(&mut writer, &get_npy_metadata(densmap, time))?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+fn get_npy_metadata(densmap: &DensMap, time: f64) -> serde_json::Value {
+    json!({
+        "shape": densmap.shape,
+        "bin_size": densmap.bin_size,
+        "origin": densmap.origin,
+        "center": densmap.center,
+        "time": time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_npy_header_is_padded_and_data_is_written_in_row_order() {
+        let densmap = DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [3, 2],
+            center: [1.5, 1.0],
+            data: (0..6).map(|i| i as f64).collect(),
+        };
+
+        let mut bytes = Vec::new();
+        write_npy_data(&mut bytes, &densmap).unwrap();
+
+        assert_eq!(NPY_MAGIC, &bytes[..6]);
+        assert_eq!(&[1, 0], &bytes[6..8]);
+
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let data_start = 10 + header_len;
+        assert_eq!(0, data_start % 64);
+        assert_eq!(b'\n', bytes[data_start - 1]);
+
+        let header = String::from_utf8(bytes[10..data_start].to_vec()).unwrap();
+        assert!(header.contains("'shape': (2, 3)"));
+
+        assert_eq!(data_start + 6 * 8, bytes.len());
+        let last = &bytes[bytes.len() - 8..];
+        let mut buf = [0; 8];
+        buf.copy_from_slice(last);
+        assert_eq!(5.0, f64::from_le_bytes(buf));
+    }
+
+    #[test]
+    fn test_npy_metadata_holds_grid_and_time() {
+        let densmap = DensMap {
+            bin_size: [0.5, 0.25, 1.0],
+            origin: [1.0, 2.0],
+            shape: [3, 2],
+            center: [1.5, 1.0],
+            data: vec![0.0; 6],
+        };
+
+        let metadata = get_npy_metadata(&densmap, 10.0);
+
+        assert_eq!(json!([3, 2]), metadata["shape"]);
+        assert_eq!(json!([0.5, 0.25, 1.0]), metadata["bin_size"]);
+        assert_eq!(json!([1.0, 2.0]), metadata["origin"]);
+        assert_eq!(json!([1.5, 1.0]), metadata["center"]);
+        assert_eq!(json!(10.0), metadata["time"]);
+    }
+
+    /// Get a path in the temporary directory which is unique to the test process, so that
+    /// several runs of the tests do not write to the same file.
+    fn get_temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}_{}", std::process::id(), name))
+    }
+
+    fn get_npy_bytes(descr: &str, fortran_order: bool, shape: &str, data: &[u8]) -> Vec<u8> {
+        let header = format!(
+            "{{'descr': '{}', 'fortran_order': {}, 'shape': {}, }}\n",
+            descr,
+            if fortran_order { "True" } else { "False" },
+            shape
+        );
+
+        let mut bytes = NPY_MAGIC.to_vec();
+        bytes.extend_from_slice(&[1, 0]);
+        bytes
+            .write_u16::<LittleEndian>(header.len() as u16)
+            .unwrap();
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(data);
+
+        bytes
+    }
+
+    /// Construct a zip archive with all files stored without compression.
+    fn get_stored_zip_bytes(files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut central_dir = Vec::new();
+
+        for (name, contents) in files {
+            let offset = bytes.len() as u32;
+
+            bytes.write_u32::<LittleEndian>(ZIP_LOCAL_HEADER).unwrap();
+            bytes.extend_from_slice(&[0; 22]);
+            bytes.write_u16::<LittleEndian>(name.len() as u16).unwrap();
+            bytes.write_u16::<LittleEndian>(0).unwrap();
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(contents);
+
+            central_dir
+                .write_u32::<LittleEndian>(ZIP_CENTRAL_HEADER)
+                .unwrap();
+            central_dir.extend_from_slice(&[0; 16]);
+            central_dir
+                .write_u32::<LittleEndian>(contents.len() as u32)
+                .unwrap();
+            central_dir
+                .write_u32::<LittleEndian>(contents.len() as u32)
+                .unwrap();
+            central_dir
+                .write_u16::<LittleEndian>(name.len() as u16)
+                .unwrap();
+            central_dir.extend_from_slice(&[0; 12]);
+            central_dir.write_u32::<LittleEndian>(offset).unwrap();
+            central_dir.extend_from_slice(name.as_bytes());
+        }
+
+        let offset = bytes.len() as u32;
+        bytes.extend_from_slice(&central_dir);
+
+        bytes
+            .write_u32::<LittleEndian>(ZIP_END_OF_CENTRAL_DIR)
+            .unwrap();
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.write_u16::<LittleEndian>(files.len() as u16).unwrap();
+        bytes.write_u16::<LittleEndian>(files.len() as u16).unwrap();
+        bytes
+            .write_u32::<LittleEndian>(central_dir.len() as u32)
+            .unwrap();
+        bytes.write_u32::<LittleEndian>(offset).unwrap();
+        bytes.write_u16::<LittleEndian>(0).unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn test_written_npy_array_is_read_back() {
+        let densmap = DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [3, 2],
+            center: [1.5, 1.0],
+            data: (0..6).map(|i| i as f64).collect(),
+        };
+
+        let mut bytes = Vec::new();
+        write_npy_data(&mut bytes, &densmap).unwrap();
+
+        let (shape, data) = read_npy_array(&mut bytes.as_slice(), bytes.len() as u64).unwrap();
+        assert_eq!(vec![2, 3], shape);
+        assert_eq!(densmap.data, data);
+    }
+
+    #[test]
+    fn test_npy_arrays_are_converted_from_other_types_and_orders() {
+        // 2x3 array [[0, 1, 2], [3, 4, 5]] as big endian 32 bit integers in column order
+        let mut data = Vec::new();
+        for v in &[0, 3, 1, 4, 2, 5] {
+            data.write_i32::<BigEndian>(*v).unwrap();
+        }
+
+        let bytes = get_npy_bytes(">i4", true, "(2, 3)", &data);
+        let (shape, data) = read_npy_array(&mut bytes.as_slice(), bytes.len() as u64).unwrap();
+
+        assert_eq!(vec![2, 3], shape);
+        assert_eq!(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0], data);
+
+        let bytes = get_npy_bytes("<c16", false, "(2, 3)", &[0; 96]);
+        assert!(read_npy_array(&mut bytes.as_slice(), bytes.len() as u64).is_err());
+    }
+
+    #[test]
+    fn test_npy_arrays_larger_than_the_file_are_invalid() {
+        let read = |shape: &str, data: &[u8]| {
+            let bytes = get_npy_bytes("<f8", false, shape, data);
+            read_npy_array(&mut bytes.as_slice(), bytes.len() as u64)
+                .unwrap_err()
+                .kind()
+        };
+
+        // The shape overflows, or needs more data than the file has.
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            read("(4294967296, 4294967296, 2)", &[0; 8])
+        );
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            read("(1000000000000,)", &[0; 8])
+        );
+        assert_eq!(io::ErrorKind::InvalidData, read("(2, 3)", &[0; 47]));
+
+        let path = get_temp_path("densmap_test_npy_truncated.npy");
+        fs::write(&path, get_npy_bytes("<f8", false, "(2, 3)", &[0; 40])).unwrap();
+
+        let result = read_npy(&path, &GridOptions::default());
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(io::ErrorKind::InvalidData, result.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_npz_archive_is_read_with_its_grid_arrays() {
+        let mut data = Vec::new();
+        for i in 0..6 {
+            data.write_f64::<LittleEndian>(i as f64).unwrap();
+        }
+
+        let mut bin_size = Vec::new();
+        bin_size.write_f64::<LittleEndian>(0.5).unwrap();
+        bin_size.write_f64::<LittleEndian>(0.25).unwrap();
+
+        let mut time = Vec::new();
+        time.write_f64::<LittleEndian>(10.0).unwrap();
+
+        let bytes = get_stored_zip_bytes(&[
+            ("data.npy", get_npy_bytes("<f8", false, "(2, 3)", &data)),
+            (
+                "bin_size.npy",
+                get_npy_bytes("<f8", false, "(2,)", &bin_size),
+            ),
+            ("time.npy", get_npy_bytes("<f8", false, "()", &time)),
+        ]);
+
+        let path = get_temp_path("densmap_test_npz_archive.npz");
+        fs::write(&path, &bytes).unwrap();
+
+        let grid = GridOptions {
+            origin: Some([1.0, 1.0]),
+            ..GridOptions::default()
+        };
+
+        let result = read_npz(&path, &grid);
+        fs::remove_file(&path).unwrap();
+
+        let (densmap, t) = result.unwrap();
+        assert_eq!([0.5, 0.25, 0.0], densmap.bin_size);
+        assert_eq!([3, 2], densmap.shape);
+        assert_eq!([1.0, 1.0], densmap.origin);
+        assert_eq!([1.75, 1.25], densmap.center);
+        assert_eq!((0..6).map(|i| i as f64).collect::<Vec<_>>(), densmap.data);
+        assert_eq!(10.0, t);
+    }
+
+    #[test]
+    fn test_npy_array_without_bin_size_cannot_be_read() {
+        let array = (vec![2, 3], vec![0.0; 6]);
+        assert!(construct_densmap(array, Metadata::default(), &GridOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! Parallel iteration with rayon, which falls back to sequential iteration when the
+//! crate is built without the `parallel` feature, eg. for WebAssembly.
+//!
+//! Only the adaptors which are used by the crate are provided by the fallback, with
+//! the same names as those of rayon so the call sites are identical.
+
+#[cfg(feature = "parallel")]
+pub use rayon::prelude::*;
+
+#[cfg(not(feature = "parallel"))]
+pub use self::sequential::*;
+
+#[cfg(not(feature = "parallel"))]
+mod sequential {
+    use std::slice::{Chunks, Iter};
+
+    pub trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {}
+
+    pub trait ParallelSlice<T> {
+        fn par_iter(&self) -> Iter<'_, T>;
+        fn par_chunks(&self, chunk_size: usize) -> Chunks<'_, T>;
+    }
+
+    impl<T> ParallelSlice<T> for [T] {
+        fn par_iter(&self) -> Iter<'_, T> {
+            self.iter()
+        }
+
+        fn par_chunks(&self, chunk_size: usize) -> Chunks<'_, T> {
+            self.chunks(chunk_size)
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use crate::graphdata::XYData;
+
+use std::{fs, io, path::Path};
+
+/// Size of the plots in pixels.
+const PLOT_WIDTH: f64 = 480.0;
+const PLOT_HEIGHT: f64 = 300.0;
+/// Margin around the axes of plots, which holds the labels.
+const PLOT_MARGIN: f64 = 50.0;
+
+/// Get a line plot of data as an SVG image, with the range of the data at the axes.
+///
+/// Values which are not finite are left out of the plot.
+pub fn get_svg_plot<T: XYData>(data: &T, xlabel: &str, ylabel: &str) -> String {
+    let range = |values: &[f64]| {
+        let (min, max) = values
+            .iter()
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| {
+                (min.min(v), max.max(v))
+            });
+
+        match (min.is_finite(), max > min) {
+            (true, true) => (min, max),
+            (true, false) => (min - 0.5, min + 0.5),
+            (false, _) => (0.0, 1.0),
+        }
+    };
+
+    let (xmin, xmax) = range(data.x());
+    let (ymin, ymax) = range(data.y());
+
+    let (x0, y0) = (PLOT_MARGIN, PLOT_HEIGHT - PLOT_MARGIN);
+    let (width, height) = (
+        PLOT_WIDTH - 2.0 * PLOT_MARGIN,
+        PLOT_HEIGHT - 2.0 * PLOT_MARGIN,
+    );
+
+    let points = data
+        .x()
+        .iter()
+        .zip(data.y())
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .map(|(x, y)| {
+            let px = x0 + width * (x - xmin) / (xmax - xmin);
+            let py = y0 - height * (y - ymin) / (ymax - ymin);
+            format!("{:.1},{:.1}", px, py)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\">\
+         <rect x=\"{x0}\" y=\"{top}\" width=\"{width}\" height=\"{height}\" fill=\"none\" stroke=\"black\"/>\
+         <polyline points=\"{points}\" fill=\"none\" stroke=\"steelblue\" stroke-width=\"1.5\"/>\
+         <text x=\"{x0}\" y=\"{below}\" text-anchor=\"start\">{xmin}</text>\
+         <text x=\"{right}\" y=\"{below}\" text-anchor=\"end\">{xmax}</text>\
+         <text x=\"{left}\" y=\"{y0}\" text-anchor=\"end\">{ymin}</text>\
+         <text x=\"{left}\" y=\"{topline}\" text-anchor=\"end\">{ymax}</text>\
+         <text x=\"{xmid}\" y=\"{xlabel_y}\" text-anchor=\"middle\">{xlabel}</text>\
+         <text x=\"12\" y=\"{ymid}\" text-anchor=\"middle\" transform=\"rotate(-90 12 {ymid})\">{ylabel}</text>\
+         </svg>",
+        w = PLOT_WIDTH,
+        h = PLOT_HEIGHT,
+        x0 = x0,
+        y0 = y0,
+        top = PLOT_MARGIN,
+        width = width,
+        height = height,
+        points = points,
+        below = y0 + 15.0,
+        right = x0 + width,
+        left = x0 - 5.0,
+        topline = PLOT_MARGIN + 10.0,
+        xmin = format_value(xmin),
+        xmax = format_value(xmax),
+        ymin = format_value(ymin),
+        ymax = format_value(ymax),
+        xmid = x0 + width / 2.0,
+        xlabel_y = PLOT_HEIGHT - 10.0,
+        xlabel = escape_xml(xlabel),
+        ymid = PLOT_MARGIN + height / 2.0,
+        ylabel = escape_xml(ylabel),
+    )
+}
+
+/// Write a line plot of data as an SVG image, see `get_svg_plot`.
+pub fn write_svg_plot<T: XYData>(
+    path: &Path,
+    data: &T,
+    xlabel: &str,
+    ylabel: &str,
+) -> Result<(), io::Error> {
+    fs::write(path, get_svg_plot(data, xlabel, ylabel))
+}
+
+fn format_value(value: f64) -> String {
+    format!("{:.4}", value)
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphdata::Graph;
+
+    #[test]
+    fn test_svg_plot_scales_data_onto_axes() {
+        let graph = Graph::Carthesian {
+            x: vec![0.0, 5.0, 10.0, 20.0],
+            y: vec![1.0, 2.0, f64::NAN, 3.0],
+            yerr: None,
+        };
+
+        let svg = get_svg_plot(&graph, "t (ps)", "r <nm>");
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>"));
+        assert!(svg.contains("points=\"50.0,250.0 145.0,150.0 430.0,50.0\""));
+        assert!(svg.contains(">20</text>"));
+        assert!(svg.contains(">r &lt;nm&gt;</text>"));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{
+    analysis::pipeline::{
+        Analysis, ContactLineAutocorrelation, Frame, Output, RadiusAnalysis, AUTOCORRELATION,
+        RADIUS,
+    },
+    densmap::{DensMap, LengthUnit},
+    graphdata::{Histogram, XYData},
+    image::get_png_bytes,
+    plot::get_svg_plot,
+    series::TimeUnit,
+};
+
+use std::{fmt::Write as _, fs, io, path::Path};
+
+/// Self-contained HTML report of the results of an analysis.
+///
+/// The report collects the droplet radius, contact line autocorrelation and averaged
+/// radial density profile of the frames which are added to it, along with snapshots
+/// of a few maps which are evenly spread over the trajectory. Plots are written
+/// as inline SVG and snapshots as inline PNG images, so the report is a single file
+/// which can be shared and opened in any browser.
+pub struct Report {
+    title: String,
+    snapshot_frames: Vec<usize>,
+    num_frames: usize,
+    radius: RadiusAnalysis,
+    autocorrelation: ContactLineAutocorrelation,
+    radial_density: Option<Histogram>,
+    num_radial_density: usize,
+    snapshots: Vec<(f64, DensMap)>,
+    time_unit: TimeUnit,
+    length_unit: LengthUnit,
+}
+
+impl Report {
+    /// Create a report of a trajectory with the number of frames, of which a number
+    /// of snapshots are included.
+    pub fn new(title: &str, num_frames: usize, num_snapshots: usize) -> Self {
+        let mut snapshot_frames = match num_snapshots {
+            0 => Vec::new(),
+            1 => vec![0],
+            n => (0..n)
+                .map(|i| i * num_frames.saturating_sub(1) / (n - 1))
+                .collect(),
+        };
+        snapshot_frames.dedup();
+
+        Report {
+            title: title.to_string(),
+            snapshot_frames,
+            num_frames: 0,
+            radius: RadiusAnalysis::default(),
+            autocorrelation: ContactLineAutocorrelation::default(),
+            radial_density: None,
+            num_radial_density: 0,
+            snapshots: Vec::new(),
+            time_unit: TimeUnit::Ps,
+            length_unit: LengthUnit::Nm,
+        }
+    }
+
+    /// Set the unit of the times of the frames, which is ps by default.
+    pub fn with_time_unit(self, time_unit: TimeUnit) -> Self {
+        Report { time_unit, ..self }
+    }
+
+    /// Set the unit of the lengths of the frames, which is nm by default.
+    pub fn with_length_unit(self, length_unit: LengthUnit) -> Self {
+        Report {
+            length_unit,
+            ..self
+        }
+    }
+
+    /// Add the results of the next frame of the trajectory to the report.
+    ///
+    /// The radial density profiles are averaged over all frames. Profiles with other
+    /// bins than that of the first frame are resampled onto its bins.
+    pub fn add_frame(&mut self, frame: &Frame) -> Result<(), String> {
+        self.radius.process_frame(frame)?;
+        self.autocorrelation.process_frame(frame)?;
+
+        match self.radial_density.as_mut() {
+            Some(sum) => {
+                let profile = if frame.radial_density.x == sum.x {
+                    frame.radial_density.clone()
+                } else {
+                    frame.radial_density.resample(&sum.x)
+                };
+
+                sum.y.iter_mut().zip(profile.y).for_each(|(v, w)| *v += w);
+            }
+            None => self.radial_density = Some(frame.radial_density.clone()),
+        }
+        self.num_radial_density += 1;
+
+        if self.snapshot_frames.contains(&self.num_frames) {
+            self.snapshots.push((frame.time, frame.densmap.clone()));
+        }
+        self.num_frames += 1;
+
+        Ok(())
+    }
+
+    /// Get the report as an HTML document.
+    pub fn to_html(&mut self) -> Result<String, io::Error> {
+        let mut body = String::new();
+
+        writeln!(
+            body,
+            "<p>Analyzed {} frames, with snapshots of {}.</p>",
+            self.num_frames,
+            self.snapshots.len()
+        )
+        .unwrap();
+
+        let results = self
+            .radius
+            .finish()
+            .into_iter()
+            .chain(self.autocorrelation.finish());
+
+        let xlabel = format!("t ({})", self.time_unit);
+        let radius_label = format!("r ({})", self.length_unit);
+
+        for (name, output) in results {
+            let (heading, ylabel) = match name.as_str() {
+                RADIUS => ("Droplet radius", radius_label.as_str()),
+                AUTOCORRELATION => ("Contact line autocorrelation", "C(t)"),
+                _ => continue,
+            };
+
+            let svg = match &output {
+                Output::Graph(graph) => get_svg_plot(graph, &xlabel, ylabel),
+                Output::Histogram(histogram) => get_svg_plot(histogram, &xlabel, ylabel),
+            };
+
+            writeln!(body, "<h2>{}</h2>\n{}", heading, svg).unwrap();
+        }
+
+        if let Some(sum) = &self.radial_density {
+            let average = Histogram {
+                x: sum.x.clone(),
+                y: sum
+                    .y
+                    .iter()
+                    .map(|v| v / self.num_radial_density as f64)
+                    .collect(),
+                yerr: None,
+            };
+
+            writeln!(
+                body,
+                "<h2>Average radial density</h2>\n{}",
+                get_svg_plot(&average, &radius_label, "density")
+            )
+            .unwrap();
+        }
+
+        if !self.snapshots.is_empty() {
+            writeln!(body, "<h2>Snapshots</h2>\n<div class=\"snapshots\">").unwrap();
+
+            for (time, densmap) in &self.snapshots {
+                let max = densmap.data.iter().cloned().fold(0.0, f64::max);
+                let png = get_png_bytes(densmap, 0.0, max)?;
+
+                writeln!(
+                    body,
+                    "<figure><img src=\"data:image/png;base64,{}\" alt=\"Map at {} {unit}\">\
+                     <figcaption>t = {} {unit}</figcaption></figure>",
+                    encode_base64(&png),
+                    time,
+                    time,
+                    unit = self.time_unit
+                )
+                .unwrap();
+            }
+
+            writeln!(body, "</div>").unwrap();
+        }
+
+        Ok(format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             <style>\n{style}</style>\n</head>\n<body>\n<h1>{title}</h1>\n{body}</body>\n</html>\n",
+            title = escape_html(&self.title),
+            style = STYLE,
+            body = body
+        ))
+    }
+
+    /// Write the report to a file.
+    pub fn write(&mut self, path: &Path) -> Result<(), io::Error> {
+        fs::write(path, self.to_html()?)
+    }
+}
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: auto; }
+svg { font-size: 12px; }
+.snapshots { display: flex; flex-wrap: wrap; }
+.snapshots img { width: 200px; image-rendering: pixelated; }
+";
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Encode bytes as base64 for data URIs.
+fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(4 * bytes.len().div_ceil(3));
+
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::{
+            radial_density::get_radial_density_distribution, sample_interface::sample_interface,
+        },
+        generate::DensMapBuilder,
+    };
+
+    #[test]
+    fn test_base64_is_padded() {
+        assert_eq!("", encode_base64(b""));
+        assert_eq!("Zg==", encode_base64(b"f"));
+        assert_eq!("Zm8=", encode_base64(b"fo"));
+        assert_eq!("Zm9v", encode_base64(b"foo"));
+        assert_eq!("Zm9vYmFy", encode_base64(b"foobar"));
+    }
+
+    #[test]
+    fn test_report_includes_plots_and_snapshots_of_frames() {
+        let mut report = Report::new("Spreading <test>", 3, 2);
+
+        for (i, &radius) in [1.5, 2.0, 2.5].iter().enumerate() {
+            let densmap = DensMapBuilder::new().disc(radius, 0.1, 0.0);
+            let radial_density = get_radial_density_distribution(&densmap);
+            let contact_line = sample_interface(&densmap, radius, LengthUnit::Nm);
+
+            report
+                .add_frame(&Frame {
+                    densmap: &densmap,
+                    time: 10.0 * i as f64,
+                    radial_density: &radial_density,
+                    radius: Some(radius),
+                    contact_line: Some(&contact_line),
+                })
+                .unwrap();
+        }
+
+        let html = report.to_html().unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Spreading &lt;test&gt;</title>"));
+        assert!(html.contains("<h2>Droplet radius</h2>"));
+        assert!(html.contains("<h2>Contact line autocorrelation</h2>"));
+        assert!(html.contains("<h2>Average radial density</h2>"));
+        assert_eq!(3, html.matches("<svg").count());
+
+        // Snapshots are taken of the first and last frame.
+        assert_eq!(2, html.matches("data:image/png;base64,").count());
+        assert!(html.contains("t = 20 ps"));
+        assert!(!html.contains("t = 10 ps"));
+    }
+}
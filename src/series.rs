@@ -0,0 +1,1019 @@
+use crate::{
+    densmap::{is_stdio, read_densmap_time, read_densmap_with_grid, DensMap, GridOptions},
+    frame_index::{get_index_path, get_mtime, is_settled, read_index, write_index},
+};
+
+use regex::Regex;
+use walkdir::WalkDir;
+
+use std::{
+    env::current_dir,
+    ffi::OsStr,
+    fmt, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Default regular expression of the time signature in file names of density maps,
+/// which captures the time of a map like '00010.000ps'.
+pub const DEFAULT_TIME_REGEX: &str = r"([0-9]{5}\.[0-9]{3})ps";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Unit of the times of a trajectory.
+pub enum TimeUnit {
+    #[default]
+    Ps,
+    Ns,
+}
+
+impl TimeUnit {
+    /// Number of picoseconds in the unit.
+    pub fn in_ps(&self) -> f64 {
+        match self {
+            TimeUnit::Ps => 1.0,
+            TimeUnit::Ns => 1000.0,
+        }
+    }
+}
+
+impl fmt::Display for TimeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeUnit::Ps => write!(f, "ps"),
+            TimeUnit::Ns => write!(f, "ns"),
+        }
+    }
+}
+
+impl FromStr for TimeUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ps" => Ok(TimeUnit::Ps),
+            "ns" => Ok(TimeUnit::Ns),
+            _ => Err(format!("invalid time unit '{}': expected 'ps' or 'ns'", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Conversion of the times of density maps, which are in ps in their file names and
+/// headers, to the unit of the analysis.
+///
+/// Times are converted to the unit, after which they are scaled and offset as
+/// `t = scale * t_unit + offset`. The scale can correct the times of maps which were
+/// written by tools that use another unit than ps.
+pub struct TimeConversion {
+    pub unit: TimeUnit,
+    pub scale: f64,
+    /// Offset in the unit of the conversion.
+    pub offset: f64,
+}
+
+impl TimeConversion {
+    /// Convert a time of a file name or header.
+    pub fn apply(&self, time: f64) -> f64 {
+        self.scale * time / self.unit.in_ps() + self.offset
+    }
+}
+
+impl Default for TimeConversion {
+    fn default() -> Self {
+        TimeConversion {
+            unit: TimeUnit::Ps,
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Source of the times of density maps.
+pub enum TimeSource {
+    /// The time signature of the file name, or the header of maps without one.
+    #[default]
+    Filename,
+    /// The header of the map, which has to be read to get its time.
+    Header,
+}
+
+impl FromStr for TimeSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "filename" => Ok(TimeSource::Filename),
+            "header" => Ok(TimeSource::Header),
+            _ => Err(format!(
+                "invalid time source '{}': expected 'filename' or 'header'",
+                s
+            )),
+        }
+    }
+}
+
+/// Tolerance within which the times of the file name and header of a map agree, which
+/// allows for the rounding of time signatures like '00010.000ps' to three decimals.
+pub const TIME_AGREEMENT_TOLERANCE: f64 = 1e-3;
+
+/// Whether the times of the file name and header of a map agree, within the tolerance
+/// or within `DT_TOLERANCE` relative to the time for the limited precision of large
+/// times in headers.
+pub fn times_agree(file_time: f64, header_time: f64) -> bool {
+    (file_time - header_time).abs() <= TIME_AGREEMENT_TOLERANCE.max(DT_TOLERANCE * file_time.abs())
+}
+
+/// Get the time of the time signature in the file name of a path, which is the first
+/// capture group of the regular expression.
+pub fn get_file_name_time(path: &Path, re: &Regex) -> Option<f64> {
+    let file_name = path.file_name()?.to_string_lossy();
+
+    re.captures(&file_name)
+        .and_then(|captures| captures.get(1))
+        .and_then(|time| time.as_str().parse::<f64>().ok())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Filter of the times of the frames of a trajectory.
+///
+/// The times of file names and headers are converted before they are filtered, so
+/// the limits are in the unit of the conversion.
+pub struct TimeFilter {
+    /// Only include times for which t >= begin.
+    pub begin: Option<f64>,
+    /// Only include times for which t <= end.
+    pub end: Option<f64>,
+    /// Only include times which are multiples of dt, within `DT_TOLERANCE`.
+    pub dt: Option<f64>,
+    /// Conversion of the times before they are filtered.
+    pub conversion: TimeConversion,
+    /// Source of the times which are filtered.
+    pub source: TimeSource,
+}
+
+/// Tolerance relative to dt within which a time is a multiple of it, which allows
+/// for rounding errors in the times of frames like 200.00000001 ps.
+pub const DT_TOLERANCE: f64 = 1e-6;
+
+impl TimeFilter {
+    /// Whether a time passes the filter.
+    pub fn contains(&self, time: f64) -> bool {
+        self.begin.map(|b| time >= b).unwrap_or(true)
+            && self.end.map(|e| time <= e).unwrap_or(true)
+            && self.dt.map(|d| is_multiple_of(time, d)).unwrap_or(true)
+    }
+}
+
+/// Whether a time is a multiple of dt, within the tolerance.
+fn is_multiple_of(time: f64, dt: f64) -> bool {
+    let remainder = time.rem_euclid(dt.abs());
+    let distance = remainder.min(dt.abs() - remainder);
+
+    distance <= DT_TOLERANCE * dt.abs()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Selection of frames by their position in a series, independent of their times.
+pub struct FrameRange {
+    /// Index of the first selected frame.
+    pub start: usize,
+    /// Index after the last selected frame, or all remaining frames if not set.
+    pub stop: Option<usize>,
+    /// Number of frames between selected frames.
+    pub step: usize,
+}
+
+impl FrameRange {
+    /// Whether the frame at an index is selected.
+    pub fn contains(&self, index: usize) -> bool {
+        index >= self.start
+            && self.stop.map(|stop| index < stop).unwrap_or(true)
+            && (index - self.start).is_multiple_of(self.step)
+    }
+}
+
+/// Parse a range of frames from a string of the form `start:stop:step`.
+///
+/// Like slices in Python, the stop index is excluded and all parts can be left out,
+/// eg. `100:` selects all frames from index 100 and `::10` every tenth frame. The step
+/// can be left out along with its colon.
+impl FromStr for FrameRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(':').map(|v| v.trim()).collect::<Vec<_>>();
+
+        if parts.len() < 2 || parts.len() > 3 {
+            return Err(format!(
+                "invalid frame range '{}': expected 'start:stop:step'",
+                s
+            ));
+        }
+
+        let parse_part = |part: &str| match part {
+            "" => Ok(None),
+            value => value
+                .parse::<usize>()
+                .map(Some)
+                .map_err(|err| format!("invalid frame range '{}': {}", s, err)),
+        };
+
+        let range = FrameRange {
+            start: parse_part(parts[0])?.unwrap_or(0),
+            stop: parse_part(parts[1])?,
+            step: parts
+                .get(2)
+                .cloned()
+                .map(parse_part)
+                .transpose()?
+                .flatten()
+                .unwrap_or(1),
+        };
+
+        if range.step == 0 {
+            return Err(format!(
+                "invalid frame range '{}': step must be positive",
+                s
+            ));
+        }
+
+        Ok(range)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Irregularity of the times of the frames of a trajectory.
+pub enum TimeStepIssue {
+    /// Several frames have the same time.
+    Duplicate { time: f64 },
+    /// The time step between two frames is larger than the typical time step, which
+    /// indicates that frames are missing.
+    Gap { begin: f64, end: f64 },
+}
+
+impl fmt::Display for TimeStepIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeStepIssue::Duplicate { time } => write!(f, "several frames have time {}", time),
+            TimeStepIssue::Gap { begin, end } => {
+                write!(
+                    f,
+                    "frames may be missing between times {} and {}",
+                    begin, end
+                )
+            }
+        }
+    }
+}
+
+/// Factor of the median time step above which a time step is a gap.
+const GAP_FACTOR: f64 = 1.5;
+
+/// Find duplicate times and gaps in sorted times of frames.
+///
+/// A gap is a time step which is larger than `GAP_FACTOR` times the median of the
+/// positive time steps. Times which are equal within `DT_TOLERANCE` of the median
+/// time step are duplicates.
+pub fn check_time_steps(times: &[f64]) -> Vec<TimeStepIssue> {
+    let mut steps = times
+        .windows(2)
+        .map(|t| t[1] - t[0])
+        .filter(|&dt| dt > 0.0)
+        .collect::<Vec<_>>();
+    steps.sort_by(|a, b| a.total_cmp(b));
+
+    let median = match steps.get(steps.len().saturating_sub(1) / 2) {
+        Some(&median) => median,
+        None => return Vec::new(),
+    };
+
+    let mut issues = times
+        .windows(2)
+        .filter_map(|t| {
+            let dt = t[1] - t[0];
+
+            if dt.abs() <= DT_TOLERANCE * median {
+                Some(TimeStepIssue::Duplicate { time: t[0] })
+            } else if dt > GAP_FACTOR * median {
+                Some(TimeStepIssue::Gap {
+                    begin: t[0],
+                    end: t[1],
+                })
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Report every duplicated time once.
+    issues.dedup();
+
+    issues
+}
+
+/// Find the files in a directory and its subdirectories down to a maximum depth which
+/// have a time signature, sorted by name, along with the directories which were walked.
+fn find_files(dir: &Path, re: &Regex, max_depth: usize) -> (Vec<(f64, PathBuf)>, Vec<PathBuf>) {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    let entries = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(max_depth)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_map(|entry| entry.ok());
+
+    for entry in entries {
+        if entry.file_type().is_dir() && entry.depth() < max_depth {
+            dirs.push(entry.into_path());
+        } else if entry.file_type().is_file() {
+            if let Some(time) = get_file_name_time(entry.path(), re) {
+                files.push((time, entry.into_path()));
+            }
+        }
+    }
+
+    (files, dirs)
+}
+
+/// Write the index of the files which were found in a directory, if its directories
+/// were not modified too recently to tell later changes apart. Errors are ignored,
+/// since the files are found again if the index cannot be read.
+fn write_found_index(
+    path: &Path,
+    key: &str,
+    dir: &Path,
+    files: &[(f64, PathBuf)],
+    dirs: &[PathBuf],
+) {
+    let get_mtimes = || {
+        dirs.iter()
+            .map(|path| {
+                let relative = path.strip_prefix(dir).unwrap_or(path).to_path_buf();
+                get_mtime(path).map(|mtime| (relative, mtime))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    match get_mtimes() {
+        Ok(dirs) if is_settled(&dirs.iter().map(|&(_, mtime)| mtime).collect::<Vec<_>>()) => (),
+        _ => return,
+    }
+
+    // Creating the index modifies its directory, so it is created before the
+    // modification times are kept.
+    if std::fs::File::create(path).is_err() {
+        return;
+    }
+
+    let files = files
+        .iter()
+        .map(|(time, file)| (*time, file.strip_prefix(dir).unwrap_or(file).to_path_buf()))
+        .collect::<Vec<_>>();
+
+    let written = get_mtimes().and_then(|dirs| write_index(path, key, &dirs, &files));
+
+    if written.is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Convert a glob pattern of a file name to a regular expression which matches it fully.
+fn glob_to_regex(pattern: &str) -> String {
+    let mut regex = String::from("^");
+    let mut in_class = false;
+
+    for (i, c) in pattern.char_indices() {
+        match c {
+            '*' if !in_class => regex.push_str(".*"),
+            '?' if !in_class => regex.push('.'),
+            '[' if !in_class => {
+                in_class = true;
+                regex.push('[');
+            }
+            '!' if in_class && pattern[..i].ends_with('[') => regex.push('^'),
+            ']' if in_class => {
+                in_class = false;
+                regex.push(']');
+            }
+            '\\' | '^' if in_class => regex.push_str(&regex::escape(&c.to_string())),
+            _ if in_class => regex.push(c),
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Series of density maps in separate files, like the frames of a trajectory.
+///
+/// The maps are read lazily, one at a time as the series is iterated over.
+pub struct DensMapSeries {
+    paths: Vec<PathBuf>,
+    times: Option<Vec<f64>>,
+    grid: GridOptions,
+}
+
+impl DensMapSeries {
+    /// Create a series of the maps of the given files, in their order.
+    pub fn from_paths(paths: Vec<PathBuf>) -> Self {
+        DensMapSeries {
+            paths,
+            times: None,
+            grid: GridOptions::default(),
+        }
+    }
+
+    /// Create a series of the maps of the given files, where file names with the glob
+    /// wildcards '*', '?' or '[...]' are expanded to the files in their directory which
+    /// match them. This avoids the limit on the length of the command line of shells
+    /// when expanding a large number of files.
+    ///
+    /// Other paths are kept as they are, in their order. The files which match a pattern
+    /// are sorted by the times of their time signatures, or by name if any of them has
+    /// no time signature. Wildcards in directory names are not expanded.
+    ///
+    /// # Errors
+    /// Returns an error if the regular expression of the time signature is invalid,
+    /// if the directory of a pattern cannot be read or if no files match a pattern.
+    pub fn from_patterns(patterns: &[PathBuf], time_regex: &str) -> Result<Self, io::Error> {
+        let time_re = Regex::new(time_regex).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid time signature '{}': {}", time_regex, err),
+            )
+        })?;
+
+        let mut paths = Vec::with_capacity(patterns.len());
+
+        for pattern in patterns {
+            let file_name = pattern
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if pattern.exists() || !file_name.contains(&['*', '?', '['][..]) {
+                paths.push(pattern.clone());
+                continue;
+            }
+
+            let re = Regex::new(&glob_to_regex(&file_name)).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid pattern '{}': {}", pattern.display(), err),
+                )
+            })?;
+
+            let dir = match pattern.parent() {
+                Some(dir) if dir != Path::new("") => dir,
+                _ => Path::new("."),
+            };
+
+            let mut entries = WalkDir::new(dir)
+                .min_depth(1)
+                .max_depth(1)
+                .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .filter(|entry| re.is_match(&entry.file_name().to_string_lossy()))
+                .map(|entry| {
+                    let time = get_file_name_time(entry.path(), &time_re);
+
+                    // Keep the pattern's directory as given rather than the walked path.
+                    (time, dir.join(entry.file_name()))
+                })
+                .collect::<Vec<_>>();
+
+            if entries.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no files match '{}'", pattern.display()),
+                ));
+            }
+
+            if entries.iter().all(|(time, _)| time.is_some()) {
+                entries.sort_by(|(t0, _), (t1, _)| t0.unwrap().total_cmp(&t1.unwrap()));
+            }
+
+            paths.extend(entries.into_iter().map(|(_, path)| path));
+        }
+
+        Ok(DensMapSeries::from_paths(paths))
+    }
+
+    /// Find the files of a series, which are named by a base path followed by a time
+    /// signature and the extension, eg. 'map00010.000ps.dat'.
+    ///
+    /// The time signature is a regular expression which must include a capture group
+    /// around the time value. The files are sorted by their times, not by name since
+    /// time signatures may vary in width, and those with times which do not pass the
+    /// filter are left out. Files with equal times are sorted by name.
+    ///
+    /// If the filter takes the times from the headers of the maps, only their headers
+    /// are read. Files of which the header cannot be read are kept with the time of
+    /// their file name, to fail when they are read like other unreadable maps.
+    ///
+    /// # Errors
+    /// Returns an error if the regular expression of the time signature is invalid
+    /// or if the directory of the base path cannot be read.
+    pub fn find(
+        base_path: &Path,
+        time_regex: &str,
+        ext: &OsStr,
+        filter: TimeFilter,
+    ) -> Result<Self, io::Error> {
+        DensMapSeries::find_recursive(base_path, time_regex, ext, filter, 1, false)
+    }
+
+    /// Find the files of a series like `find`, also in the subdirectories of the
+    /// directory of the base path down to a maximum depth, where a depth of 1 only
+    /// searches the directory itself.
+    ///
+    /// This finds trajectories which are split into directories, like 'part001/' and
+    /// 'part002/', in a single series which is sorted by the times of all files.
+    ///
+    /// With `use_index` the files which are found are kept in an index file next to
+    /// them, which is read instead of walking the directories again as long as none
+    /// of them have been modified. Indexes which cannot be written, like in read-only
+    /// directories, are skipped.
+    pub fn find_recursive(
+        base_path: &Path,
+        time_regex: &str,
+        ext: &OsStr,
+        filter: TimeFilter,
+        max_depth: usize,
+        use_index: bool,
+    ) -> Result<Self, io::Error> {
+        let dir = base_path.parent().unwrap_or_else(|| Path::new("./"));
+        let abs_dir = if dir.is_absolute() {
+            dir.to_path_buf()
+        } else {
+            current_dir()?.join(dir)
+        };
+
+        let base = base_path
+            .file_name()
+            .unwrap_or_else(|| OsStr::new(""))
+            .to_string_lossy();
+
+        // The base and extension are matched as they are, even if they contain
+        // characters with a meaning in regular expressions.
+        let regex_string = format!(
+            r"^{}{}\.{}$",
+            regex::escape(&base),
+            time_regex,
+            regex::escape(&ext.to_string_lossy())
+        );
+        let re = Regex::new(&regex_string).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid time signature '{}': {}", time_regex, err),
+            )
+        })?;
+
+        // The index is kept for valid UTF-8 base names, which it is named by.
+        let index_path = base
+            .chars()
+            .all(|c| c != char::REPLACEMENT_CHARACTER)
+            .then(|| get_index_path(&abs_dir, &base))
+            .filter(|_| use_index);
+        let index_key = format!("{}\t{}", regex_string, max_depth);
+
+        let indexed = index_path
+            .as_ref()
+            .and_then(|path| read_index(path, &index_key, &abs_dir));
+
+        let found = match indexed {
+            Some(found) => found,
+            None => {
+                let (found, dirs) = find_files(&abs_dir, &re, max_depth);
+
+                if let Some(path) = &index_path {
+                    write_found_index(path, &index_key, &abs_dir, &found, &dirs);
+                }
+
+                found
+            }
+        };
+
+        let mut entries = Vec::with_capacity(found.len());
+
+        // Files of which the header cannot be read are kept with the time of their
+        // file name, so that the error is reported (or skipped) when they are read.
+        for (time, path) in found {
+            let time = match filter.source {
+                TimeSource::Filename => time,
+                TimeSource::Header => {
+                    read_densmap_time(&path, &GridOptions::default()).unwrap_or(time)
+                }
+            };
+            let time = filter.conversion.apply(time);
+
+            if filter.contains(time) {
+                entries.push((time, path));
+            }
+        }
+
+        // The sort is stable, which keeps files with equal times sorted by name.
+        entries.sort_by(|(t0, _), (t1, _)| t0.total_cmp(t1));
+
+        let (times, paths) = entries.into_iter().unzip();
+
+        Ok(DensMapSeries {
+            paths,
+            times: Some(times),
+            grid: GridOptions::default(),
+        })
+    }
+
+    /// Only keep the maps with times which pass the filter, like for the files found
+    /// by `find`.
+    ///
+    /// The time of a map is read from the time signature of its file name. Maps with
+    /// file names without a time signature are read to get the time of their header,
+    /// except for maps which are read from stdin which are always kept.
+    ///
+    /// # Errors
+    /// Returns an error if the regular expression of the time signature is invalid
+    /// or if a map has to be read but cannot be.
+    pub fn filter(self, time_regex: &str, filter: TimeFilter) -> Result<Self, io::Error> {
+        if filter == TimeFilter::default() {
+            return Ok(self);
+        }
+
+        let re = Regex::new(time_regex).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid time signature '{}': {}", time_regex, err),
+            )
+        })?;
+
+        let mut paths = Vec::with_capacity(self.paths.len());
+        let mut times = Vec::with_capacity(self.paths.len());
+
+        for path in self.paths {
+            let file_time = match filter.source {
+                TimeSource::Filename => get_file_name_time(&path, &re),
+                TimeSource::Header => None,
+            };
+
+            let time = match file_time {
+                Some(time) => Some(time),
+                None if is_stdio(&path) => None,
+                None => Some(read_densmap_with_grid(&path, &self.grid)?.1),
+            };
+
+            let time = time.map(|t| filter.conversion.apply(t));
+
+            if time.map(|t| filter.contains(t)).unwrap_or(true) {
+                paths.push(path);
+                times.push(time);
+            }
+        }
+
+        Ok(DensMapSeries {
+            paths,
+            times: times.into_iter().collect(),
+            ..self
+        })
+    }
+
+    /// Set the grid options with which the maps are read.
+    pub fn with_grid(self, grid: GridOptions) -> Self {
+        DensMapSeries { grid, ..self }
+    }
+
+    /// Get the times of the maps in the series, if they are known from their file names
+    /// or headers without reading them.
+    pub fn times(&self) -> Option<&[f64]> {
+        self.times.as_deref()
+    }
+
+    /// Get the paths of the maps in the series.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Iterate over the maps of the series and their times, reading every map as
+    /// it is reached.
+    pub fn iter(&self) -> impl Iterator<Item = Result<(DensMap, f64), io::Error>> + '_ {
+        self.paths
+            .iter()
+            .map(move |path| read_densmap_with_grid(path, &self.grid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{densmap::write_densmap, generate::DensMapBuilder};
+
+    #[test]
+    fn test_time_filter_dt_allows_for_rounding_errors_of_times() {
+        let filter = TimeFilter {
+            dt: Some(10.0),
+            ..TimeFilter::default()
+        };
+
+        assert!(filter.contains(200.0));
+        assert!(filter.contains(200.00000001));
+        assert!(filter.contains(199.99999999));
+        assert!(filter.contains(-10.0));
+        assert!(!filter.contains(205.0));
+        assert!(!filter.contains(200.1));
+
+        // Times like 0.3 are not exact multiples of 0.1 in floating point.
+        let filter = TimeFilter {
+            dt: Some(0.1),
+            ..TimeFilter::default()
+        };
+        assert!(filter.contains(0.3));
+        assert!(!filter.contains(0.35));
+    }
+
+    #[test]
+    fn test_time_conversion_is_applied_in_the_unit() {
+        let conversion = TimeConversion {
+            unit: TimeUnit::Ns,
+            scale: 2.0,
+            offset: -1.0,
+        };
+
+        assert_eq!(3.0, conversion.apply(2000.0));
+        assert_eq!(10.0, TimeConversion::default().apply(10.0));
+
+        assert_eq!(Ok(TimeUnit::Ns), "NS".parse());
+        assert!("s".parse::<TimeUnit>().is_err());
+        assert_eq!("ns", TimeUnit::Ns.to_string());
+    }
+
+    #[test]
+    fn test_times_of_file_names_and_headers_agree_within_rounding() {
+        let re = Regex::new(DEFAULT_TIME_REGEX).unwrap();
+
+        let time = get_file_name_time(Path::new("dir/map00010.000ps.dat"), &re).unwrap();
+        assert_eq!(10.0, time);
+        assert_eq!(None, get_file_name_time(Path::new("map.dat"), &re));
+
+        assert!(times_agree(time, 10.0004));
+        assert!(!times_agree(time, 10.01));
+        assert!(times_agree(1e6, 1e6 + 0.5));
+        assert!(!times_agree(1e6, 1e6 + 5.0));
+
+        assert_eq!(Ok(TimeSource::Header), "header".parse());
+        assert!("name".parse::<TimeSource>().is_err());
+    }
+
+    #[test]
+    fn test_time_steps_are_checked_for_duplicates_and_gaps() {
+        let times = [0.0, 10.0, 20.0, 20.0, 20.0, 30.0, 60.0, 70.0];
+
+        assert_eq!(
+            vec![
+                TimeStepIssue::Duplicate { time: 20.0 },
+                TimeStepIssue::Gap {
+                    begin: 30.0,
+                    end: 60.0
+                },
+            ],
+            check_time_steps(&times)
+        );
+
+        assert!(check_time_steps(&[0.0, 10.0, 20.0, 30.0]).is_empty());
+        assert!(check_time_steps(&[0.0]).is_empty());
+    }
+
+    #[test]
+    fn test_glob_patterns_match_file_names_fully() {
+        let re = Regex::new(&glob_to_regex("densmap_*.dat.gz")).unwrap();
+        assert!(re.is_match("densmap_00010.000ps.dat.gz"));
+        assert!(!re.is_match("densmap_00010.000ps.dat"));
+        assert!(!re.is_match("densmap_00010.000ps.datxgz"));
+
+        let re = Regex::new(&glob_to_regex("map?[0-9][!a].dat")).unwrap();
+        assert!(re.is_match("mapx12.dat"));
+        assert!(!re.is_match("mapx1a.dat"));
+        assert!(!re.is_match("mapxy2.dat"));
+    }
+
+    #[test]
+    fn test_frame_range_selects_indices_like_slices() {
+        let range = "2:9:3".parse::<FrameRange>().unwrap();
+        let selected = (0..12).filter(|&i| range.contains(i)).collect::<Vec<_>>();
+        assert_eq!(vec![2, 5, 8], selected);
+
+        let open = "10:".parse::<FrameRange>().unwrap();
+        assert!(!open.contains(9));
+        assert!(open.contains(1000));
+
+        let every = "::4".parse::<FrameRange>().unwrap();
+        assert!(every.contains(0) && every.contains(8) && !every.contains(6));
+
+        assert!("5".parse::<FrameRange>().is_err());
+        assert!("0:10:0".parse::<FrameRange>().is_err());
+        assert!("a:10".parse::<FrameRange>().is_err());
+    }
+
+    #[test]
+    fn test_series_finds_files_by_time_signature_and_filter() {
+        let dir = std::env::temp_dir().join("densmap_test_series");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let densmap = DensMapBuilder::new()
+            .bin_size([1.0, 1.0, 0.0])
+            .shape([2, 1])
+            .from_data(vec![1.0, 2.0])
+            .unwrap();
+
+        for &time in &[0.0, 10.0, 20.0, 30.0] {
+            let path = dir.join(format!("map{:09.3}ps.dat", time));
+            write_densmap(&path, &densmap, time).unwrap();
+        }
+
+        // Times beyond the width of the signature are sorted after all others.
+        write_densmap(&dir.join("map1000.0ps.dat"), &densmap, 1000.0).unwrap();
+        write_densmap(&dir.join("map40.0ps.dat"), &densmap, 40.0).unwrap();
+        std::fs::write(dir.join("other00010.000ps.dat"), b"").unwrap();
+
+        let filter = TimeFilter {
+            begin: Some(5.0),
+            dt: Some(20.0),
+            ..TimeFilter::default()
+        };
+        let series = DensMapSeries::find(
+            &dir.join("map"),
+            DEFAULT_TIME_REGEX,
+            OsStr::new("dat"),
+            filter,
+        );
+        let sorted = DensMapSeries::find(
+            &dir.join("map"),
+            r"([0-9]+\.[0-9]+)ps",
+            OsStr::new("dat"),
+            TimeFilter::default(),
+        );
+        let invalid = DensMapSeries::find(
+            &dir.join("map"),
+            "(",
+            OsStr::new("dat"),
+            TimeFilter::default(),
+        );
+
+        // Maps without a time signature are filtered by the time of their header.
+        write_densmap(&dir.join("renamed.dat"), &densmap, 40.0).unwrap();
+
+        let times = series
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|result| result.map(|(_, time)| time))
+            .collect::<Result<Vec<_>, _>>();
+
+        let globbed = DensMapSeries::from_patterns(
+            &[dir.join("map00030.000ps.dat"), dir.join("map?0*.0ps.dat")],
+            r"([0-9]+\.[0-9]+)ps",
+        );
+        let nested = dir.join("part002");
+        std::fs::create_dir_all(&nested).unwrap();
+        write_densmap(&nested.join("map00050.000ps.dat"), &densmap, 50.0).unwrap();
+
+        let recursive = DensMapSeries::find_recursive(
+            &dir.join("map"),
+            DEFAULT_TIME_REGEX,
+            OsStr::new("dat"),
+            TimeFilter {
+                begin: Some(25.0),
+                ..TimeFilter::default()
+            },
+            2,
+            false,
+        );
+
+        let unmatched = DensMapSeries::from_patterns(&[dir.join("none*.dat")], DEFAULT_TIME_REGEX);
+
+        let listed = DensMapSeries::from_paths(vec![
+            dir.join("map00010.000ps.dat"),
+            dir.join("map00020.000ps.dat"),
+            dir.join("renamed.dat"),
+        ])
+        .filter(DEFAULT_TIME_REGEX, filter)
+        .map(|series| series.paths().len());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(vec![20.0], times.unwrap());
+        assert_eq!(2, listed.unwrap());
+        assert_eq!(
+            Some(&[0.0, 10.0, 20.0, 30.0, 40.0, 1000.0][..]),
+            sorted.as_ref().unwrap().times()
+        );
+        assert_eq!(
+            vec![
+                dir.join("map00030.000ps.dat"),
+                dir.join("map40.0ps.dat"),
+                dir.join("map1000.0ps.dat"),
+            ],
+            globbed.unwrap().paths()
+        );
+        assert_eq!(
+            vec![
+                dir.join("map00030.000ps.dat"),
+                nested.join("map00050.000ps.dat")
+            ],
+            recursive.unwrap().paths()
+        );
+        assert_eq!(io::ErrorKind::NotFound, unmatched.unwrap_err().kind());
+        assert_eq!(io::ErrorKind::InvalidInput, invalid.unwrap_err().kind());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_series_finds_files_with_odd_and_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = std::env::temp_dir().join("densmap_test_series_odd_names");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let densmap = DensMapBuilder::new()
+            .bin_size([1.0, 1.0, 0.0])
+            .shape([2, 1])
+            .from_data(vec![1.0, 2.0])
+            .unwrap();
+
+        // The base has characters of regular expressions and bytes which are not UTF-8.
+        let base = OsStr::from_bytes(b"m\xffp+(v2).");
+        let mut name = base.to_os_string();
+        name.push("00010.000ps.dat");
+        write_densmap(&dir.join(&name), &densmap, 10.0).unwrap();
+        std::fs::write(dir.join("mp+(v2).00020.000ps.dat"), b"").unwrap();
+
+        let series = DensMapSeries::find(
+            &dir.join(base),
+            DEFAULT_TIME_REGEX,
+            OsStr::new("dat"),
+            TimeFilter::default(),
+        )
+        .unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(Some(&[10.0][..]), series.times());
+        assert_eq!(&[dir.join(&name)], series.paths());
+    }
+
+    #[test]
+    fn test_series_finds_files_by_time_of_header_and_keeps_unreadable_files() {
+        let dir =
+            std::env::temp_dir().join(format!("{}_densmap_test_series_header", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let densmap = DensMapBuilder::new()
+            .bin_size([1.0, 1.0, 0.0])
+            .shape([2, 1])
+            .from_data(vec![1.0, 2.0])
+            .unwrap();
+
+        // The header times are in the reverse order of the file names.
+        write_densmap(&dir.join("map00010.000ps.dat"), &densmap, 20.0).unwrap();
+        write_densmap(&dir.join("map00020.000ps.dat"), &densmap, 10.0).unwrap();
+        std::fs::write(dir.join("map00030.000ps.dat"), b"DMAP").unwrap();
+        std::fs::write(dir.join("map00050.000ps.dat"), b"DMAP").unwrap();
+
+        let series = DensMapSeries::find(
+            &dir.join("map"),
+            DEFAULT_TIME_REGEX,
+            OsStr::new("dat"),
+            TimeFilter {
+                end: Some(40.0),
+                source: TimeSource::Header,
+                ..TimeFilter::default()
+            },
+        )
+        .unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(Some(&[10.0, 20.0, 30.0][..]), series.times());
+        assert_eq!(
+            &[
+                dir.join("map00020.000ps.dat"),
+                dir.join("map00010.000ps.dat"),
+                dir.join("map00030.000ps.dat"),
+            ],
+            series.paths()
+        );
+    }
+}
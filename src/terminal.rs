@@ -0,0 +1,206 @@
+use crate::{
+    densmap::DensMap,
+    graphdata::{Graph, XYData},
+};
+
+#[cfg(test)]
+use crate::generate::DensMapBuilder;
+
+/// Characters for the density of cells in text maps, from empty to dense.
+const SHADES: &[char] = &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+/// Character for cells which the contact line passes through in text maps.
+const CONTACT_LINE_MARK: char = 'o';
+
+/// Render a density map as rows of text for a terminal, with `columns` characters in
+/// each of `rows` rows from the top and y increasing upwards.
+///
+/// Every character shows the mean density of the bins of its cell, shaded from empty
+/// to `max`. Cells which the points of a contact line with absolute radii around
+/// the center of the map lie in are marked, to overlay the contact line on the map.
+pub fn render_text_map(
+    densmap: &DensMap,
+    [columns, rows]: [usize; 2],
+    max: f64,
+    contact_line: Option<&Graph>,
+) -> Vec<String> {
+    let mut cells = (0..rows)
+        .map(|row| {
+            (0..columns)
+                .map(|column| {
+                    let density = get_cell_density(densmap, [columns, rows], column, row);
+                    let scaled = if max > 0.0 { density / max } else { 0.0 };
+                    let i = (scaled * (SHADES.len() - 1) as f64).round();
+
+                    SHADES[(i.max(0.0) as usize).min(SHADES.len() - 1)]
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(contact_line) = contact_line {
+        let carthesian = contact_line.to_carthesian();
+
+        for (x, y) in carthesian.x().iter().zip(carthesian.y()) {
+            let position = [densmap.center[0] + x, densmap.center[1] + y];
+
+            if let Some((column, row)) = get_cell(densmap, [columns, rows], position) {
+                cells[row][column] = CONTACT_LINE_MARK;
+            }
+        }
+    }
+
+    cells
+        .into_iter()
+        .map(|row| row.into_iter().collect())
+        .collect()
+}
+
+/// Render the bins of a density map which are at or above a cutoff as rows of braille
+/// characters, oriented as by `render_text_map`.
+///
+/// Every character has 2 by 4 dots, which gives a finer view of the droplet shape
+/// at the cutoff than the shaded text map.
+pub fn render_braille_map(
+    densmap: &DensMap,
+    [columns, rows]: [usize; 2],
+    cutoff: f64,
+) -> Vec<String> {
+    // Bits of the dots in a braille character by column and row within the character.
+    const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+    let dots = [2 * columns, 4 * rows];
+
+    (0..rows)
+        .map(|row| {
+            (0..columns)
+                .map(|column| {
+                    let mut bits = 0;
+
+                    for (i, column_bits) in DOTS.iter().enumerate() {
+                        for (j, bit) in column_bits.iter().enumerate() {
+                            let density =
+                                get_cell_density(densmap, dots, 2 * column + i, 4 * row + j);
+
+                            if density >= cutoff {
+                                bits |= bit;
+                            }
+                        }
+                    }
+
+                    std::char::from_u32(0x2800 + bits).unwrap_or(' ')
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Get the mean of the finite densities of the bins in a cell of a grid of cells over
+/// the map, with row 0 at the top.
+///
+/// Cells which are smaller than a bin have the density of the bin which they begin in.
+fn get_cell_density(
+    densmap: &DensMap,
+    [columns, rows]: [usize; 2],
+    column: usize,
+    row: usize,
+) -> f64 {
+    let [nx, ny] = densmap.shape;
+    let (nx, ny) = (nx as usize, ny as usize);
+
+    // Bins of the cell along x and y, with y counted from the bottom.
+    let get_range = |i: usize, n: usize, num_cells: usize| {
+        let begin = i * n / num_cells;
+        let end = ((i + 1) * n / num_cells).max(begin + 1).min(n);
+
+        begin.min(n.saturating_sub(1))..end
+    };
+
+    let xs = get_range(column, nx, columns);
+    let ys = get_range(rows - 1 - row, ny, rows);
+
+    let (sum, count) = ys
+        .flat_map(|iy| xs.clone().map(move |ix| iy * nx + ix))
+        .filter_map(|i| densmap.data.get(i))
+        .filter(|v| v.is_finite())
+        .fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+
+    if count > 0 {
+        sum / count as f64
+    } else {
+        0.0
+    }
+}
+
+/// Get the cell of a grid of cells over the map which a position is in.
+fn get_cell(
+    densmap: &DensMap,
+    [columns, rows]: [usize; 2],
+    [x, y]: [f64; 2],
+) -> Option<(usize, usize)> {
+    let [nx, ny] = densmap.shape;
+    let [dx, dy, _] = densmap.bin_size;
+
+    let fx = (x - densmap.origin[0]) / (nx as f64 * dx);
+    let fy = (y - densmap.origin[1]) / (ny as f64 * dy);
+
+    if (0.0..1.0).contains(&fx) && (0.0..1.0).contains(&fy) {
+        let column = (fx * columns as f64) as usize;
+        let row = rows - 1 - (fy * rows as f64) as usize;
+
+        Some((column, row))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+fn get_test_map() -> DensMap {
+    // A 4 by 4 map with values only in its lower left quarter.
+    DensMapBuilder::new()
+        .bin_size([1.0, 1.0, 0.0])
+        .shape([4, 4])
+        .from_fn(|x, y| if x < 2.0 && y < 2.0 { 2.0 } else { 0.0 })
+}
+
+#[test]
+fn test_text_map_is_shaded_from_the_top_with_contact_line() {
+    let densmap = get_test_map();
+
+    assert_eq!(
+        vec!["    ", "    ", "@@  ", "@@  "],
+        render_text_map(&densmap, [4, 4], 2.0, None)
+    );
+
+    // Half of the maximum is shaded in the middle of the shades.
+    assert_eq!(
+        vec!["  ", "+ "],
+        render_text_map(&densmap, [2, 2], 4.0, None)
+    );
+
+    // A point just above and right of the center lies in the upper right quarter.
+    let contact_line = Graph::Carthesian {
+        x: vec![0.5],
+        y: vec![0.5],
+        yerr: None,
+    };
+
+    assert_eq!(
+        vec!["    ", "  o ", "@@  ", "@@  "],
+        render_text_map(&densmap, [4, 4], 2.0, Some(&contact_line))
+    );
+}
+
+#[test]
+fn test_braille_map_has_dots_at_or_above_cutoff() {
+    let densmap = get_test_map();
+
+    // Every character covers 2 by 4 bins, of which the lower left 2 by 2 are set.
+    let rows = render_braille_map(&densmap, [2, 1], 2.0);
+    assert_eq!(vec![format!("{}\u{2800}", '\u{28e4}')], rows);
+
+    assert_eq!(
+        vec!["\u{2800}\u{2800}"],
+        render_braille_map(&densmap, [2, 1], 3.0)
+    );
+}
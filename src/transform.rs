@@ -0,0 +1,513 @@
+use crate::{densmap::DensMap, mask::Axis};
+
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// How the values of combined bins are reduced into a single value.
+pub enum RebinMode {
+    /// Sum the values, which conserves the total mass of the map.
+    Sum,
+    /// Average the values, which conserves the density scale of the map.
+    Mean,
+}
+
+impl FromStr for RebinMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sum" => Ok(RebinMode::Sum),
+            "mean" => Ok(RebinMode::Mean),
+            _ => Err(format!(
+                "invalid rebin mode '{}': must be 'sum' or 'mean'",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Geometric transforms which can be applied to a density map.
+pub enum Transform {
+    /// Rotate counter-clockwise by a number of quarter turns.
+    Rotate90(u32),
+    /// Rotate counter-clockwise by an angle in degrees around the droplet center.
+    Rotate(f64),
+    /// Mirror the map along an axis.
+    Mirror(Axis),
+}
+
+impl FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rot90" => Ok(Transform::Rotate90(1)),
+            "rot180" => Ok(Transform::Rotate90(2)),
+            "rot270" => Ok(Transform::Rotate90(3)),
+            "mirror-x" => Ok(Transform::Mirror(Axis::X)),
+            "mirror-y" => Ok(Transform::Mirror(Axis::Y)),
+            _ if s.starts_with("rotate:") => s["rotate:".len()..]
+                .trim()
+                .parse::<f64>()
+                .map(Transform::Rotate)
+                .map_err(|err| format!("could not parse rotation angle in '{}': {}", s, err)),
+            _ => Err(format!(
+                "invalid transform '{}': must be one of 'rot90', 'rot180', 'rot270', \
+                 'mirror-x', 'mirror-y' or 'rotate:<degrees>'",
+                s
+            )),
+        }
+    }
+}
+
+impl DensMap {
+    /// Apply a geometric transform to the map.
+    pub fn transform(&self, transform: Transform) -> DensMap {
+        match transform {
+            Transform::Rotate90(quarter_turns) => self.rotate90(quarter_turns),
+            Transform::Rotate(angle) => self.rotate(angle),
+            Transform::Mirror(axis) => self.mirror(axis),
+        }
+    }
+
+    /// Rotate the map counter-clockwise by a number of quarter turns.
+    ///
+    /// The rotated map covers the same origin, with the shape and bin size along
+    /// x and y swapped for odd numbers of turns. The droplet center is rotated
+    /// along with the data.
+    pub fn rotate90(&self, quarter_turns: u32) -> DensMap {
+        (0..quarter_turns % 4).fold(self.clone(), |densmap, _| densmap.rotate_quarter_turn())
+    }
+
+    fn rotate_quarter_turn(&self) -> DensMap {
+        let [nx, ny] = self.shape;
+        let [dx, dy, dz] = self.bin_size;
+        let [x0, y0] = self.origin;
+        let [cx, cy] = self.center;
+
+        // The new bin (jx, jy) holds the old bin (jy, ny - 1 - jx).
+        let data = (0..nx)
+            .flat_map(|jy| (0..ny).map(move |jx| (jx, jy)))
+            .map(|(jx, jy)| self.data[((ny - 1 - jx) * nx + jy) as usize])
+            .collect();
+
+        DensMap {
+            bin_size: [dy, dx, dz],
+            origin: self.origin,
+            shape: [ny, nx],
+            center: [x0 + dy * ny as f64 - (cy - y0), y0 + (cx - x0)],
+            data,
+        }
+    }
+
+    /// Mirror the map along an axis, such that coordinates along the axis are reversed
+    /// within the map. The droplet center is mirrored along with the data.
+    pub fn mirror(&self, axis: Axis) -> DensMap {
+        let [nx, ny] = self.shape;
+        let [dx, dy, _] = self.bin_size;
+        let [x0, y0] = self.origin;
+        let [cx, cy] = self.center;
+
+        let data = (0..ny)
+            .flat_map(|iy| (0..nx).map(move |ix| (ix, iy)))
+            .map(|(ix, iy)| match axis {
+                Axis::X => self.data[(iy * nx + nx - 1 - ix) as usize],
+                Axis::Y => self.data[((ny - 1 - iy) * nx + ix) as usize],
+            })
+            .collect();
+
+        let center = match axis {
+            Axis::X => [2.0 * x0 + dx * nx as f64 - cx, cy],
+            Axis::Y => [cx, 2.0 * y0 + dy * ny as f64 - cy],
+        };
+
+        DensMap {
+            center,
+            data,
+            ..self.clone()
+        }
+    }
+
+    /// Rotate the map counter-clockwise by an angle in degrees around the droplet center,
+    /// keeping the grid. Values are bilinearly interpolated between bin centers, and
+    /// bins which are rotated in from outside of the map are set to zero.
+    pub fn rotate(&self, angle: f64) -> DensMap {
+        let [nx, ny] = self.shape;
+        let [dx, dy, _] = self.bin_size;
+        let [x0, y0] = self.origin;
+        let [cx, cy] = self.center;
+        let (sin, cos) = angle.to_radians().sin_cos();
+
+        // Fractional index of a coordinate along an axis, relative to the bin centers.
+        // Returns None if it lies outside of the map.
+        let get_weights = |u: f64, n: u64| {
+            if u < -0.5 || u > n as f64 - 0.5 {
+                None
+            } else {
+                let u = u.max(0.0).min((n - 1) as f64);
+                let i0 = u.floor() as u64;
+                let i1 = (i0 + 1).min(n - 1);
+                Some((i0, i1, u - i0 as f64))
+            }
+        };
+
+        let value = |ix: u64, iy: u64| self.data[(iy * nx + ix) as usize];
+
+        let data = (0..ny)
+            .flat_map(|iy| (0..nx).map(move |ix| (ix, iy)))
+            .map(|(ix, iy)| {
+                // Rotate the bin center back to find where its value comes from.
+                let x = x0 + (ix as f64 + 0.5) * dx - cx;
+                let y = y0 + (iy as f64 + 0.5) * dy - cy;
+                let xs = cx + cos * x + sin * y;
+                let ys = cy - sin * x + cos * y;
+
+                let weights_x = get_weights((xs - x0) / dx - 0.5, nx);
+                let weights_y = get_weights((ys - y0) / dy - 0.5, ny);
+
+                match (weights_x, weights_y) {
+                    (Some((ix0, ix1, wx)), Some((iy0, iy1, wy))) => {
+                        (1.0 - wy) * ((1.0 - wx) * value(ix0, iy0) + wx * value(ix1, iy0))
+                            + wy * ((1.0 - wx) * value(ix0, iy1) + wx * value(ix1, iy1))
+                    }
+                    _ => 0.0,
+                }
+            })
+            .collect();
+
+        DensMap {
+            data,
+            ..self.clone()
+        }
+    }
+
+    /// Crop the map to the bins whose centers lie within the given region, in absolute
+    /// system coordinates. The origin and shape of the returned map are adjusted, while
+    /// the center is kept as is.
+    ///
+    /// # Error
+    /// Returns an error if no bins lie inside of the region.
+    pub fn crop(&self, xmin: f64, xmax: f64, ymin: f64, ymax: f64) -> Result<DensMap, String> {
+        let [dx, dy, _] = self.bin_size;
+        let [x0, y0] = self.origin;
+        let [nx, ny] = self.shape;
+
+        // Bin ix has its center at x0 + (ix + 0.5) * dx.
+        let get_range = |min: f64, max: f64, origin: f64, d: f64, n: u64| {
+            let i0 = ((min - origin) / d - 0.5).ceil().max(0.0) as u64;
+            let i1 = (((max - origin) / d - 0.5).floor() + 1.0)
+                .max(0.0)
+                .min(n as f64) as u64;
+            (i0, i1.max(i0))
+        };
+
+        let (ix0, ix1) = get_range(xmin, xmax, x0, dx, nx);
+        let (iy0, iy1) = get_range(ymin, ymax, y0, dy, ny);
+
+        if ix0 >= ix1 || iy0 >= iy1 {
+            return Err(format!(
+                "no bins lie within the region x = [{}, {}], y = [{}, {}]",
+                xmin, xmax, ymin, ymax
+            ));
+        }
+
+        let data = (iy0..iy1)
+            .flat_map(|iy| (ix0..ix1).map(move |ix| (iy * nx + ix) as usize))
+            .map(|i| self.data[i])
+            .collect();
+
+        Ok(DensMap {
+            bin_size: self.bin_size,
+            origin: [x0 + dx * ix0 as f64, y0 + dy * iy0 as f64],
+            shape: [ix1 - ix0, iy1 - iy0],
+            center: self.center,
+            data,
+        })
+    }
+
+    /// Combine blocks of `factor_x` by `factor_y` bins into single bins, producing a coarser map.
+    ///
+    /// Bins which remain at the upper edges of the map along x or y, if the shape is not
+    /// divisible by the factor, are dropped. The origin and center are kept as is.
+    ///
+    /// # Error
+    /// Returns an error if a factor is zero or larger than the shape of the map.
+    pub fn rebin(&self, factor_x: u64, factor_y: u64, mode: RebinMode) -> Result<DensMap, String> {
+        let [nx, ny] = self.shape;
+
+        if factor_x == 0 || factor_y == 0 || factor_x > nx || factor_y > ny {
+            return Err(format!(
+                "cannot rebin map of shape {}x{} by factors {}x{}",
+                nx, ny, factor_x, factor_y
+            ));
+        }
+
+        let nx_new = nx / factor_x;
+        let ny_new = ny / factor_y;
+        let block_size = (factor_x * factor_y) as f64;
+
+        let data = (0..ny_new)
+            .flat_map(|iy| (0..nx_new).map(move |ix| (ix, iy)))
+            .map(|(ix, iy)| {
+                let sum = (0..factor_y)
+                    .flat_map(|jy| (0..factor_x).map(move |jx| (jx, jy)))
+                    .map(|(jx, jy)| {
+                        let i = (iy * factor_y + jy) * nx + ix * factor_x + jx;
+                        self.data[i as usize]
+                    })
+                    .sum::<f64>();
+
+                match mode {
+                    RebinMode::Sum => sum,
+                    RebinMode::Mean => sum / block_size,
+                }
+            })
+            .collect();
+
+        let [dx, dy, dz] = self.bin_size;
+
+        Ok(DensMap {
+            bin_size: [dx * factor_x as f64, dy * factor_y as f64, dz],
+            origin: self.origin,
+            shape: [nx_new, ny_new],
+            center: self.center,
+            data,
+        })
+    }
+
+    /// Interpolate the map onto a grid which is finer by `factor_x` by `factor_y`, using
+    /// bilinear interpolation between bin centers. Values outside of the outermost bin
+    /// centers are taken from the closest center.
+    ///
+    /// If `conserve_mass` is set the values are rescaled such that the total mass
+    /// of the map is conserved. Otherwise the density scale of the map is conserved.
+    ///
+    /// # Error
+    /// Returns an error if a factor is zero.
+    pub fn upsample(
+        &self,
+        factor_x: u64,
+        factor_y: u64,
+        conserve_mass: bool,
+    ) -> Result<DensMap, String> {
+        if factor_x == 0 || factor_y == 0 {
+            return Err(format!(
+                "cannot upsample map by factors {}x{}",
+                factor_x, factor_y
+            ));
+        }
+
+        let [nx, ny] = self.shape;
+        let nx_new = nx * factor_x;
+        let ny_new = ny * factor_y;
+
+        // Get the (fractional) index in the original map of a bin center in the new map,
+        // along with the two bins to interpolate between.
+        let get_weights = |j: u64, factor: u64, n: u64| {
+            let u = ((j as f64 + 0.5) / factor as f64 - 0.5)
+                .max(0.0)
+                .min((n - 1) as f64);
+            let i0 = u.floor() as u64;
+            let i1 = (i0 + 1).min(n - 1);
+            (i0, i1, u - i0 as f64)
+        };
+
+        let mut data = (0..ny_new)
+            .flat_map(|jy| (0..nx_new).map(move |jx| (jx, jy)))
+            .map(|(jx, jy)| {
+                let (ix0, ix1, wx) = get_weights(jx, factor_x, nx);
+                let (iy0, iy1, wy) = get_weights(jy, factor_y, ny);
+
+                let value = |ix: u64, iy: u64| self.data[(iy * nx + ix) as usize];
+
+                (1.0 - wy) * ((1.0 - wx) * value(ix0, iy0) + wx * value(ix1, iy0))
+                    + wy * ((1.0 - wx) * value(ix0, iy1) + wx * value(ix1, iy1))
+            })
+            .collect::<Vec<_>>();
+
+        if conserve_mass {
+            let mass = self.data.iter().sum::<f64>();
+            let mass_new = data.iter().sum::<f64>();
+
+            if mass_new != 0.0 {
+                let scale = mass / mass_new;
+                data.iter_mut().for_each(|v| *v *= scale);
+            }
+        }
+
+        let [dx, dy, dz] = self.bin_size;
+
+        Ok(DensMap {
+            bin_size: [dx / factor_x as f64, dy / factor_y as f64, dz],
+            origin: self.origin,
+            shape: [nx_new, ny_new],
+            center: self.center,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_indexed_densmap() -> DensMap {
+        DensMap {
+            bin_size: [0.5, 1.0, 0.0],
+            origin: [1.0, 2.0],
+            shape: [4, 3],
+            center: [2.0, 3.5],
+            data: (0..12).map(|i| i as f64).collect(),
+        }
+    }
+
+    #[test]
+    fn test_rotate90_moves_bins_and_center_counter_clockwise() {
+        let densmap = get_indexed_densmap();
+
+        let rotated = densmap.rotate90(1);
+        assert_eq!([3, 4], rotated.shape);
+        assert_eq!([1.0, 0.5, 0.0], rotated.bin_size);
+        assert_eq!(densmap.origin, rotated.origin);
+        assert_eq!([2.5, 3.0], rotated.center);
+
+        // The first column of the old map, from the top, becomes the first row
+        assert_eq!(&[8.0, 4.0, 0.0], &rotated.data[..3]);
+
+        assert_eq!(densmap, densmap.rotate90(4));
+        assert_eq!(densmap, densmap.rotate90(3).rotate90(1));
+        assert_eq!(densmap.rotate90(2), densmap.rotate90(1).rotate90(1));
+    }
+
+    #[test]
+    fn test_mirror_reverses_bins_and_center_along_axis() {
+        let densmap = get_indexed_densmap();
+
+        let mirrored = densmap.mirror(Axis::X);
+        assert_eq!(&[3.0, 2.0, 1.0, 0.0], &mirrored.data[..4]);
+        assert_eq!([2.0, 3.5], mirrored.center);
+
+        let mirrored = densmap.mirror(Axis::Y);
+        assert_eq!(&[8.0, 9.0, 10.0, 11.0], &mirrored.data[..4]);
+        assert_eq!([2.0, 3.5], mirrored.center);
+
+        assert_eq!(densmap, densmap.mirror(Axis::X).mirror(Axis::X));
+    }
+
+    #[test]
+    fn test_rotate_by_angle_around_center_matches_quarter_turn() {
+        let densmap = DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [3, 3],
+            center: [1.5, 1.5],
+            data: (0..9).map(|i| i as f64).collect(),
+        };
+
+        let rotated = densmap.rotate(90.0);
+        let expected = densmap.rotate90(1);
+
+        for (a, b) in rotated.data.iter().zip(expected.data.iter()) {
+            assert!((a - b).abs() < 1e-9);
+        }
+
+        // Bins which are rotated in from outside of the map are empty
+        let densmap = DensMap {
+            shape: [5, 5],
+            center: [2.5, 2.5],
+            data: (0..25).map(|i| i as f64).collect(),
+            ..densmap
+        };
+
+        let rotated = densmap.rotate(45.0);
+        assert_eq!(0.0, rotated.data[0]);
+        assert!((rotated.data[12] - 12.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_transform() {
+        assert_eq!(Ok(Transform::Rotate90(3)), "rot270".parse());
+        assert_eq!(Ok(Transform::Mirror(Axis::Y)), "mirror-y".parse());
+        assert_eq!(Ok(Transform::Rotate(-12.5)), "rotate:-12.5".parse());
+        assert!("rotate:x".parse::<Transform>().is_err());
+        assert!("flip".parse::<Transform>().is_err());
+    }
+
+    #[test]
+    fn test_crop_keeps_bins_with_centers_inside_region() {
+        let densmap = get_indexed_densmap();
+
+        // Bin centers along x are at 1.25, 1.75, 2.25, 2.75 and along y at 2.5, 3.5, 4.5.
+        let cropped = densmap.crop(1.5, 2.5, 3.0, 10.0).unwrap();
+
+        assert_eq!([2, 2], cropped.shape);
+        assert_eq!([1.5, 3.0], cropped.origin);
+        assert_eq!(densmap.center, cropped.center);
+        assert_eq!(vec![5.0, 6.0, 9.0, 10.0], cropped.data);
+    }
+
+    #[test]
+    fn test_crop_to_region_outside_of_map_returns_error() {
+        let densmap = get_indexed_densmap();
+
+        assert!(densmap.crop(10.0, 20.0, 0.0, 10.0).is_err());
+        assert!(densmap.crop(1.3, 1.6, 0.0, 10.0).is_err());
+    }
+
+    #[test]
+    fn test_rebin_sums_or_averages_blocks_and_drops_remainder() {
+        let densmap = get_indexed_densmap();
+
+        let rebinned = densmap.rebin(2, 2, RebinMode::Sum).unwrap();
+        assert_eq!([2, 1], rebinned.shape);
+        assert_eq!([1.0, 2.0, 0.0], rebinned.bin_size);
+        assert_eq!(densmap.origin, rebinned.origin);
+        assert_eq!(vec![10.0, 18.0], rebinned.data);
+
+        let rebinned = densmap.rebin(2, 1, RebinMode::Mean).unwrap();
+        assert_eq!([2, 3], rebinned.shape);
+        assert_eq!(vec![0.5, 2.5, 4.5, 6.5, 8.5, 10.5], rebinned.data);
+    }
+
+    #[test]
+    fn test_rebin_with_invalid_factors_returns_error() {
+        let densmap = get_indexed_densmap();
+
+        assert!(densmap.rebin(0, 1, RebinMode::Sum).is_err());
+        assert!(densmap.rebin(1, 4, RebinMode::Sum).is_err());
+    }
+
+    #[test]
+    fn test_upsample_interpolates_linearly_between_bin_centers() {
+        let densmap = DensMap {
+            bin_size: [1.0, 1.0, 0.0],
+            origin: [0.0, 0.0],
+            shape: [2, 1],
+            center: [1.0, 0.5],
+            data: vec![0.0, 4.0],
+        };
+
+        let upsampled = densmap.upsample(2, 1, false).unwrap();
+        assert_eq!([4, 1], upsampled.shape);
+        assert_eq!([0.5, 1.0, 0.0], upsampled.bin_size);
+        assert_eq!(vec![0.0, 1.0, 3.0, 4.0], upsampled.data);
+
+        let upsampled = densmap.upsample(2, 2, true).unwrap();
+        assert_eq!([4, 2], upsampled.shape);
+        assert_eq!(4.0, upsampled.data.iter().sum::<f64>());
+    }
+
+    #[test]
+    fn test_upsample_and_rebin_of_constant_map_are_inverse() {
+        let densmap = DensMap {
+            data: vec![2.0; 12],
+            ..get_indexed_densmap()
+        };
+
+        let upsampled = densmap.upsample(3, 2, false).unwrap();
+        let rebinned = upsampled.rebin(3, 2, RebinMode::Mean).unwrap();
+
+        assert_eq!(densmap, rebinned);
+    }
+}
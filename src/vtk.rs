@@ -0,0 +1,312 @@
+use crate::densmap::{DensMap, Vec2};
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// VTK file formats which can be read by ParaView.
+pub enum VtkFormat {
+    /// The legacy plain text format, with the extension '.vtk'.
+    Legacy,
+    /// The XML format, with the extension '.vti' for maps and '.vtp' for lines.
+    Xml,
+}
+
+impl VtkFormat {
+    /// Get the format from the extension of a path, defaulting to the legacy format.
+    pub fn from_path(path: &Path) -> VtkFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("vti") | Some("vtp") => VtkFormat::Xml,
+            _ => VtkFormat::Legacy,
+        }
+    }
+}
+
+/// Write a density map as a VTK image of the given format.
+///
+/// The bins of the map are written as the cells of a flat grid in the xy plane, with
+/// their densities as the cell scalars `density`. The time is written as field data
+/// which ParaView uses to order the frames of a series of files.
+pub fn write_vtk(
+    path: &Path,
+    densmap: &DensMap,
+    time: f64,
+    format: VtkFormat,
+) -> Result<(), io::Error> {
+    let fp = File::create(path)?;
+    let mut writer = BufWriter::new(fp);
+
+    match format {
+        VtkFormat::Legacy => write_legacy_image(&mut writer, densmap, time),
+        VtkFormat::Xml => write_xml_image(&mut writer, densmap, time),
+    }
+}
+
+/// Write a contact line as a closed VTK polyline of the given format.
+///
+/// The points are given in absolute coordinates and placed in the xy plane.
+pub fn write_vtk_contact_line(
+    path: &Path,
+    points: &[Vec2],
+    time: f64,
+    format: VtkFormat,
+) -> Result<(), io::Error> {
+    let fp = File::create(path)?;
+    let mut writer = BufWriter::new(fp);
+
+    match format {
+        VtkFormat::Legacy => write_legacy_polyline(&mut writer, points, time),
+        VtkFormat::Xml => write_xml_polyline(&mut writer, points, time),
+    }
+}
+
+/// Get the spacing of the grid along z, which must be positive even for a flat grid.
+fn get_z_spacing(densmap: &DensMap) -> f64 {
+    if densmap.bin_size[2] > 0.0 {
+        densmap.bin_size[2]
+    } else {
+        1.0
+    }
+}
+
+fn write_legacy_image<W: Write>(
+    writer: &mut W,
+    densmap: &DensMap,
+    time: f64,
+) -> Result<(), io::Error> {
+    let [dx, dy, _] = densmap.bin_size;
+    let [x0, y0] = densmap.origin;
+    let [nx, ny] = densmap.shape;
+
+    writeln!(writer, "# vtk DataFile Version 3.0")?;
+    writeln!(writer, "density map")?;
+    writeln!(writer, "ASCII")?;
+    writeln!(writer, "DATASET STRUCTURED_POINTS")?;
+    writeln!(writer, "DIMENSIONS {} {} 1", nx + 1, ny + 1)?;
+    writeln!(writer, "ORIGIN {} {} 0", x0, y0)?;
+    writeln!(writer, "SPACING {} {} {}", dx, dy, get_z_spacing(densmap))?;
+    write_legacy_time(writer, time)?;
+
+    writeln!(writer, "CELL_DATA {}", densmap.data.len())?;
+    writeln!(writer, "SCALARS density double 1")?;
+    writeln!(writer, "LOOKUP_TABLE default")?;
+
+    for v in &densmap.data {
+        writeln!(writer, "{}", v)?;
+    }
+
+    Ok(())
+}
+
+fn write_legacy_polyline<W: Write>(
+    writer: &mut W,
+    points: &[Vec2],
+    time: f64,
+) -> Result<(), io::Error> {
+    let n = points.len();
+
+    writeln!(writer, "# vtk DataFile Version 3.0")?;
+    writeln!(writer, "contact line")?;
+    writeln!(writer, "ASCII")?;
+    writeln!(writer, "DATASET POLYDATA")?;
+    write_legacy_time(writer, time)?;
+
+    writeln!(writer, "POINTS {} double", n)?;
+    for [x, y] in points {
+        writeln!(writer, "{} {} 0", x, y)?;
+    }
+
+    // The line is closed by returning to the first point.
+    writeln!(writer, "LINES 1 {}", n + 2)?;
+    write!(writer, "{}", n + 1)?;
+    for i in (0..n).chain(0..1) {
+        write!(writer, " {}", i)?;
+    }
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+fn write_legacy_time<W: Write>(writer: &mut W, time: f64) -> Result<(), io::Error> {
+    writeln!(writer, "FIELD FieldData 1")?;
+    writeln!(writer, "TIME 1 1 double")?;
+    writeln!(writer, "{}", time)
+}
+
+fn write_xml_image<W: Write>(
+    writer: &mut W,
+    densmap: &DensMap,
+    time: f64,
+) -> Result<(), io::Error> {
+    let [dx, dy, _] = densmap.bin_size;
+    let [x0, y0] = densmap.origin;
+    let [nx, ny] = densmap.shape;
+    let extent = format!("0 {} 0 {} 0 0", nx, ny);
+
+    writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+    writeln!(
+        writer,
+        r#"<VTKFile type="ImageData" version="0.1" byte_order="LittleEndian">"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <ImageData WholeExtent="{}" Origin="{} {} 0" Spacing="{} {} {}">"#,
+        extent,
+        x0,
+        y0,
+        dx,
+        dy,
+        get_z_spacing(densmap)
+    )?;
+    write_xml_time(writer, time)?;
+    writeln!(writer, r#"    <Piece Extent="{}">"#, extent)?;
+    writeln!(writer, r#"      <CellData Scalars="density">"#)?;
+    writeln!(
+        writer,
+        r#"        <DataArray type="Float64" Name="density" format="ascii">"#
+    )?;
+
+    for v in &densmap.data {
+        writeln!(writer, "{}", v)?;
+    }
+
+    writeln!(writer, "        </DataArray>")?;
+    writeln!(writer, "      </CellData>")?;
+    writeln!(writer, "    </Piece>")?;
+    writeln!(writer, "  </ImageData>")?;
+    writeln!(writer, "</VTKFile>")?;
+
+    Ok(())
+}
+
+fn write_xml_polyline<W: Write>(
+    writer: &mut W,
+    points: &[Vec2],
+    time: f64,
+) -> Result<(), io::Error> {
+    let n = points.len();
+
+    writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+    writeln!(
+        writer,
+        r#"<VTKFile type="PolyData" version="0.1" byte_order="LittleEndian">"#
+    )?;
+    writeln!(writer, "  <PolyData>")?;
+    write_xml_time(writer, time)?;
+    writeln!(
+        writer,
+        r#"    <Piece NumberOfPoints="{}" NumberOfLines="1">"#,
+        n
+    )?;
+
+    writeln!(writer, "      <Points>")?;
+    writeln!(
+        writer,
+        r#"        <DataArray type="Float64" NumberOfComponents="3" format="ascii">"#
+    )?;
+    for [x, y] in points {
+        writeln!(writer, "{} {} 0", x, y)?;
+    }
+    writeln!(writer, "        </DataArray>")?;
+    writeln!(writer, "      </Points>")?;
+
+    // The line is closed by returning to the first point.
+    let connectivity = (0..n)
+        .chain(0..1)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    writeln!(writer, "      <Lines>")?;
+    writeln!(
+        writer,
+        r#"        <DataArray type="Int64" Name="connectivity" format="ascii">{}</DataArray>"#,
+        connectivity
+    )?;
+    writeln!(
+        writer,
+        r#"        <DataArray type="Int64" Name="offsets" format="ascii">{}</DataArray>"#,
+        n + 1
+    )?;
+    writeln!(writer, "      </Lines>")?;
+    writeln!(writer, "    </Piece>")?;
+    writeln!(writer, "  </PolyData>")?;
+    writeln!(writer, "</VTKFile>")?;
+
+    Ok(())
+}
+
+fn write_xml_time<W: Write>(writer: &mut W, time: f64) -> Result<(), io::Error> {
+    writeln!(writer, "    <FieldData>")?;
+    writeln!(
+        writer,
+        r#"      <DataArray type="Float64" Name="TimeValue" NumberOfTuples="1" format="ascii">{}</DataArray>"#,
+        time
+    )?;
+    writeln!(writer, "    </FieldData>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_densmap() -> DensMap {
+        DensMap {
+            bin_size: [0.5, 0.25, 0.0],
+            origin: [1.0, 2.0],
+            shape: [3, 2],
+            center: [1.75, 2.25],
+            data: (0..6).map(|i| i as f64).collect(),
+        }
+    }
+
+    #[test]
+    fn test_legacy_image_has_the_bins_as_cells() {
+        let mut bytes = Vec::new();
+        write_legacy_image(&mut bytes, &get_densmap(), 10.0).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+
+        assert!(text.contains("DIMENSIONS 4 3 1\n"));
+        assert!(text.contains("ORIGIN 1 2 0\n"));
+        assert!(text.contains("SPACING 0.5 0.25 1\n"));
+        assert!(text.contains("TIME 1 1 double\n10\n"));
+        assert!(text.contains("CELL_DATA 6\n"));
+        assert!(text.ends_with("LOOKUP_TABLE default\n0\n1\n2\n3\n4\n5\n"));
+    }
+
+    #[test]
+    fn test_xml_image_has_the_extent_of_the_map() {
+        let mut bytes = Vec::new();
+        write_xml_image(&mut bytes, &get_densmap(), 10.0).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+
+        assert!(text.contains(r#"WholeExtent="0 3 0 2 0 0" Origin="1 2 0""#));
+        assert!(text.contains(r#"<Piece Extent="0 3 0 2 0 0">"#));
+        assert_eq!(
+            1,
+            text.matches("format=\"ascii\">\n0\n1\n2\n3\n4\n5\n")
+                .count()
+        );
+        assert!(text.trim_end().ends_with("</VTKFile>"));
+    }
+
+    #[test]
+    fn test_contact_line_polylines_are_closed() {
+        let points = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+
+        let mut bytes = Vec::new();
+        write_legacy_polyline(&mut bytes, &points, 0.0).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.contains("POINTS 3 double\n0 0 0\n1 0 0\n1 1 0\n"));
+        assert!(text.ends_with("LINES 1 5\n4 0 1 2 0\n"));
+
+        let mut bytes = Vec::new();
+        write_xml_polyline(&mut bytes, &points, 0.0).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.contains(r#"Name="connectivity" format="ascii">0 1 2 0<"#));
+        assert!(text.contains(r#"Name="offsets" format="ascii">4<"#));
+    }
+}